@@ -0,0 +1,373 @@
+//! Contains the structs and traits that define an `access control` backend.
+//!
+//! This is the WebDAV Access Control Protocol ([RFC3744]). If an access
+//! control backend is configured with [`DavConfig::acl`], every request
+//! is checked against the privileges that the backend grants the
+//! [principal][crate::DavConfig::principal] on the request path, and the
+//! `DAV:acl`, `DAV:current-user-privilege-set`, `DAV:owner` and
+//! `DAV:principal-collection-set` properties become available.
+//!
+//! Principals are identified by their URL (href), so the principal that is
+//! passed to the handler should be a URL like `/principals/users/alice/`.
+//!
+//! [RFC3744]: https://tools.ietf.org/html/rfc3744
+//! [`DavConfig::acl`]: crate::DavConfig::acl
+use std::fmt::Debug;
+use std::future::{self, Future};
+use std::pin::Pin;
+
+use dyn_clone::{DynClone, clone_trait_object};
+
+use crate::davpath::DavPath;
+
+/// A privilege, as defined in RFC3744 section 3.
+///
+/// `Write` and `All` are aggregates of other privileges.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[repr(u32)]
+pub enum DavPrivilege {
+    Read = 0x0001,
+    WriteProperties = 0x0002,
+    WriteContent = 0x0004,
+    Bind = 0x0008,
+    Unbind = 0x0010,
+    Unlock = 0x0020,
+    ReadAcl = 0x0040,
+    ReadCurrentUserPrivilegeSet = 0x0080,
+    WriteAcl = 0x0100,
+    Write = 0x001e,
+    All = 0x01ff,
+}
+
+impl DavPrivilege {
+    /// All privileges, aggregates first.
+    pub const LIST: &'static [DavPrivilege] = &[
+        DavPrivilege::All,
+        DavPrivilege::Read,
+        DavPrivilege::Write,
+        DavPrivilege::WriteProperties,
+        DavPrivilege::WriteContent,
+        DavPrivilege::Bind,
+        DavPrivilege::Unbind,
+        DavPrivilege::Unlock,
+        DavPrivilege::ReadAcl,
+        DavPrivilege::ReadCurrentUserPrivilegeSet,
+        DavPrivilege::WriteAcl,
+    ];
+
+    /// Name of the privilege in the `DAV:` namespace.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DavPrivilege::Read => "read",
+            DavPrivilege::WriteProperties => "write-properties",
+            DavPrivilege::WriteContent => "write-content",
+            DavPrivilege::Bind => "bind",
+            DavPrivilege::Unbind => "unbind",
+            DavPrivilege::Unlock => "unlock",
+            DavPrivilege::ReadAcl => "read-acl",
+            DavPrivilege::ReadCurrentUserPrivilegeSet => "read-current-user-privilege-set",
+            DavPrivilege::WriteAcl => "write-acl",
+            DavPrivilege::Write => "write",
+            DavPrivilege::All => "all",
+        }
+    }
+
+    /// Privilege from its name in the `DAV:` namespace.
+    pub fn from_name(name: &str) -> Option<DavPrivilege> {
+        DavPrivilege::LIST
+            .iter()
+            .find(|p| p.name() == name)
+            .copied()
+    }
+}
+
+/// A set of [`DavPrivilege`]s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DavPrivilegeSet(u32);
+
+impl DavPrivilegeSet {
+    /// New set, all privileges.
+    pub fn all() -> DavPrivilegeSet {
+        DavPrivilegeSet(DavPrivilege::All as u32)
+    }
+
+    /// New empty set.
+    pub fn none() -> DavPrivilegeSet {
+        DavPrivilegeSet(0)
+    }
+
+    /// Add a privilege.
+    pub fn add(&mut self, p: DavPrivilege) -> &Self {
+        self.0 |= p as u32;
+        self
+    }
+
+    /// Remove a privilege.
+    pub fn remove(&mut self, p: DavPrivilege) -> &Self {
+        self.0 &= !(p as u32);
+        self
+    }
+
+    /// Check if a privilege is in the set. For an aggregate privilege,
+    /// all the privileges it contains must be in the set.
+    pub fn contains(&self, p: DavPrivilege) -> bool {
+        self.0 & (p as u32) == p as u32
+    }
+
+    /// Is the set empty.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The privileges in this set, aggregates first.
+    pub fn iter(&self) -> impl Iterator<Item = DavPrivilege> + '_ {
+        DavPrivilege::LIST
+            .iter()
+            .copied()
+            .filter(|p| self.contains(*p))
+    }
+
+    /// The smallest list of privileges that covers this set.
+    pub fn to_vec(&self) -> Vec<DavPrivilege> {
+        let mut left = self.0;
+        let mut v = Vec::new();
+        for p in self.iter() {
+            if left & (p as u32) == p as u32 {
+                left &= !(p as u32);
+                v.push(p);
+            }
+        }
+        v
+    }
+}
+
+impl From<DavPrivilege> for DavPrivilegeSet {
+    fn from(p: DavPrivilege) -> Self {
+        DavPrivilegeSet(p as u32)
+    }
+}
+
+impl std::ops::BitOr for DavPrivilegeSet {
+    type Output = DavPrivilegeSet;
+
+    fn bitor(self, rhs: DavPrivilegeSet) -> DavPrivilegeSet {
+        DavPrivilegeSet(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::Sub for DavPrivilegeSet {
+    type Output = DavPrivilegeSet;
+
+    fn sub(self, rhs: DavPrivilegeSet) -> DavPrivilegeSet {
+        DavPrivilegeSet(self.0 & !rhs.0)
+    }
+}
+
+/// The principal an ACE applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DavAcePrincipal {
+    /// A principal URL.
+    Href(String),
+    /// `DAV:all`, every user.
+    All,
+    /// `DAV:authenticated`, every user that has a principal.
+    Authenticated,
+    /// `DAV:unauthenticated`, every user without a principal.
+    Unauthenticated,
+    /// `DAV:self`, the principal that is the resource itself.
+    SelfPrincipal,
+    /// `DAV:property` with `DAV:owner`, the owner of the resource.
+    Owner,
+}
+
+impl DavAcePrincipal {
+    /// Does this ACE principal match `principal` for a resource
+    /// at `href` that is owned by `owner`.
+    pub fn matches(&self, principal: Option<&str>, href: &str, owner: Option<&str>) -> bool {
+        match self {
+            DavAcePrincipal::All => true,
+            DavAcePrincipal::Authenticated => principal.is_some(),
+            DavAcePrincipal::Unauthenticated => principal.is_none(),
+            DavAcePrincipal::Href(h) => principal.is_some_and(|p| same_href(p, h)),
+            DavAcePrincipal::SelfPrincipal => principal.is_some_and(|p| same_href(p, href)),
+            DavAcePrincipal::Owner => match (principal, owner) {
+                (Some(p), Some(o)) => same_href(p, o),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Access control entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DavAce {
+    /// Who this entry applies to.
+    pub principal: DavAcePrincipal,
+    /// Deny instead of grant.
+    pub deny: bool,
+    /// The privileges granted or denied.
+    pub privileges: DavPrivilegeSet,
+    /// Protected entries cannot be changed with the ACL method.
+    pub protected: bool,
+    /// If inherited, the URL of the resource it was inherited from.
+    pub inherited: Option<String>,
+}
+
+impl DavAce {
+    /// An ACE that grants `privileges` to `principal`.
+    pub fn grant(principal: DavAcePrincipal, privileges: impl Into<DavPrivilegeSet>) -> DavAce {
+        DavAce {
+            principal,
+            deny: false,
+            privileges: privileges.into(),
+            protected: false,
+            inherited: None,
+        }
+    }
+
+    /// An ACE that denies `privileges` to `principal`.
+    pub fn deny(principal: DavAcePrincipal, privileges: impl Into<DavPrivilegeSet>) -> DavAce {
+        DavAce {
+            deny: true,
+            ..DavAce::grant(principal, privileges)
+        }
+    }
+}
+
+/// Evaluate a list of ACEs, in order, for `principal` on the resource at `href`.
+///
+/// For every privilege, the first matching ACE that mentions it decides
+/// whether it is granted or denied. Privileges that no ACE mentions
+/// are not granted.
+pub fn evaluate_acl(
+    aces: &[DavAce],
+    principal: Option<&str>,
+    href: &str,
+    owner: Option<&str>,
+) -> DavPrivilegeSet {
+    let mut granted = DavPrivilegeSet::none();
+    let mut decided = DavPrivilegeSet::none();
+    for ace in aces {
+        if !ace.principal.matches(principal, href, owner) {
+            continue;
+        }
+        let privs = ace.privileges - decided;
+        if !ace.deny {
+            granted = granted | privs;
+        }
+        decided = decided | privs;
+    }
+    granted
+}
+
+/// Preconditions of the ACL method (RFC3744 section 8.1.1) that
+/// an access control backend can report as failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DavAclError {
+    /// The ACL of this resource cannot be changed.
+    NotSupported,
+    NoAceConflict,
+    NoProtectedAceConflict,
+    NoInheritedAceConflict,
+    LimitedNumberOfAces,
+    DenyBeforeGrant,
+    GrantOnly,
+    NoInvert,
+    NoAbstract,
+    NotSupportedPrivilege,
+    MissingRequiredPrincipal,
+    RecognizedPrincipal,
+    AllowedPrincipal,
+}
+
+impl DavAclError {
+    /// Name of the precondition element in the `DAV:` namespace.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self {
+            DavAclError::NotSupported => return None,
+            DavAclError::NoAceConflict => "no-ace-conflict",
+            DavAclError::NoProtectedAceConflict => "no-protected-ace-conflict",
+            DavAclError::NoInheritedAceConflict => "no-inherited-ace-conflict",
+            DavAclError::LimitedNumberOfAces => "limited-number-of-aces",
+            DavAclError::DenyBeforeGrant => "deny-before-grant",
+            DavAclError::GrantOnly => "grant-only",
+            DavAclError::NoInvert => "no-invert",
+            DavAclError::NoAbstract => "no-abstract",
+            DavAclError::NotSupportedPrivilege => "not-supported-privilege",
+            DavAclError::MissingRequiredPrincipal => "missing-required-principal",
+            DavAclError::RecognizedPrincipal => "recognized-principal",
+            DavAclError::AllowedPrincipal => "allowed-principal",
+        };
+        Some(name)
+    }
+}
+
+pub type AclFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The trait that defines an access control backend.
+///
+/// Only `privileges` is required. The other methods have defaults
+/// for a backend that computes privileges but has no ACLs to show.
+pub trait DavAccessControl: Debug + Send + Sync + DynClone {
+    /// The privileges that `principal` has on `path`.
+    /// A principal of `None` means an unauthenticated user.
+    fn privileges<'a>(
+        &'a self,
+        principal: Option<&'a str>,
+        path: &'a DavPath,
+    ) -> AclFuture<'a, DavPrivilegeSet>;
+
+    /// The ACL of `path`, including inherited and protected entries.
+    #[allow(unused_variables)]
+    fn acl<'a>(&'a self, path: &'a DavPath) -> AclFuture<'a, Vec<DavAce>> {
+        Box::pin(future::ready(Vec::new()))
+    }
+
+    /// Replace the ACL of `path` (the ACL method). `aces` never contains
+    /// protected or inherited entries; those must be kept as they are.
+    #[allow(unused_variables)]
+    fn set_acl<'a>(
+        &'a self,
+        path: &'a DavPath,
+        aces: Vec<DavAce>,
+    ) -> AclFuture<'a, Result<(), DavAclError>> {
+        Box::pin(future::ready(Err(DavAclError::NotSupported)))
+    }
+
+    /// The principal URL of the owner of `path`.
+    #[allow(unused_variables)]
+    fn owner<'a>(&'a self, path: &'a DavPath) -> AclFuture<'a, Option<String>> {
+        Box::pin(future::ready(None))
+    }
+
+    /// The URLs of the collections that contain principals.
+    fn principal_collections(&'_ self) -> AclFuture<'_, Vec<String>> {
+        Box::pin(future::ready(Vec::new()))
+    }
+
+    /// The URLs of all principals.
+    fn principals(&'_ self) -> AclFuture<'_, Vec<String>> {
+        Box::pin(future::ready(Vec::new()))
+    }
+
+    /// Forget the ACL of `path` and below (after DELETE).
+    #[allow(unused_variables)]
+    fn delete<'a>(&'a self, path: &'a DavPath) -> AclFuture<'a, ()> {
+        Box::pin(future::ready(()))
+    }
+
+    /// Move the ACL of `from` and below to `to` (after MOVE).
+    #[allow(unused_variables)]
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> AclFuture<'a, ()> {
+        Box::pin(future::ready(()))
+    }
+}
+
+clone_trait_object! {DavAccessControl}
+
+// compare two URLs, ignoring a trailing slash.
+pub(crate) fn same_href(a: &str, b: &str) -> bool {
+    let a = a.strip_suffix('/').unwrap_or(a);
+    let b = b.strip_suffix('/').unwrap_or(b);
+    a == b
+}
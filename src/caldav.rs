@@ -39,6 +39,9 @@ pub const DEFAULT_CALDAV_NAME: &str = "calendars";
 pub const DEFAULT_CALDAV_DIRECTORY: &str = "/calendars";
 pub const DEFAULT_CALDAV_DIRECTORY_ENDSLASH: &str = "/calendars/";
 
+/// Default maximum resource size for calendar object resources (1MB)
+pub const DEFAULT_MAX_CALENDAR_RESOURCE_SIZE: u64 = 1024 * 1024;
+
 /// CalDAV resource types
 #[derive(Debug, Clone, PartialEq)]
 pub enum CalDavResourceType {
@@ -56,6 +59,7 @@ pub enum CalendarComponentType {
     VTodo,
     VJournal,
     VFreeBusy,
+    VAvailability,
     VTimezone,
     VAlarm,
 }
@@ -67,10 +71,24 @@ impl CalendarComponentType {
             CalendarComponentType::VTodo => "VTODO",
             CalendarComponentType::VJournal => "VJOURNAL",
             CalendarComponentType::VFreeBusy => "VFREEBUSY",
+            CalendarComponentType::VAvailability => "VAVAILABILITY",
             CalendarComponentType::VTimezone => "VTIMEZONE",
             CalendarComponentType::VAlarm => "VALARM",
         }
     }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "VEVENT" => Some(CalendarComponentType::VEvent),
+            "VTODO" => Some(CalendarComponentType::VTodo),
+            "VJOURNAL" => Some(CalendarComponentType::VJournal),
+            "VFREEBUSY" => Some(CalendarComponentType::VFreeBusy),
+            "VAVAILABILITY" => Some(CalendarComponentType::VAvailability),
+            "VTIMEZONE" => Some(CalendarComponentType::VTimezone),
+            "VALARM" => Some(CalendarComponentType::VAlarm),
+            _ => None,
+        }
+    }
 }
 
 /// CalDAV calendar collection properties
@@ -94,8 +112,9 @@ impl Default for CalendarProperties {
                 CalendarComponentType::VTodo,
                 CalendarComponentType::VJournal,
                 CalendarComponentType::VFreeBusy,
+                CalendarComponentType::VAvailability,
             ],
-            max_resource_size: Some(1024 * 1024), // 1MB default
+            max_resource_size: Some(DEFAULT_MAX_CALENDAR_RESOURCE_SIZE),
             color: None,
             display_name: None,
         }
@@ -108,6 +127,38 @@ pub struct CalendarQuery {
     pub comp_filter: Option<ComponentFilter>,
     pub time_range: Option<TimeRange>,
     pub properties: Vec<String>,
+    /// VTIMEZONE for floating date-times (CALDAV:timezone)
+    pub timezone: Option<String>,
+    /// Which parts of the calendar data to return (CALDAV:calendar-data)
+    pub calendar_data: Option<CalendarDataRequest>,
+}
+
+/// The CALDAV:calendar-data element in the properties of a REPORT request:
+/// which parts of the calendar data to return (RFC 4791, section 9.6).
+#[derive(Debug, Clone, Default)]
+pub struct CalendarDataRequest {
+    /// Only these components and properties (CALDAV:comp)
+    pub comp: Option<CompSelection>,
+    /// Expand recurring components into their instances (CALDAV:expand)
+    pub expand: Option<TimeRange>,
+    /// Only the overridden instances in this range (CALDAV:limit-recurrence-set)
+    pub limit_recurrence_set: Option<TimeRange>,
+    /// Only the FREEBUSY periods in this range (CALDAV:limit-freebusy-set)
+    pub limit_freebusy_set: Option<TimeRange>,
+}
+
+/// A CALDAV:comp element: a component, and which of its properties
+/// and subcomponents to return.
+#[derive(Debug, Clone, Default)]
+pub struct CompSelection {
+    pub name: String,
+    /// All properties (CALDAV:allprop)
+    pub all_props: bool,
+    /// Property names, and if their value is left out (novalue="yes")
+    pub props: Vec<(String, bool)>,
+    /// All subcomponents (CALDAV:allcomp)
+    pub all_comps: bool,
+    pub comps: Vec<CompSelection>,
 }
 
 #[derive(Debug, Clone)]
@@ -144,8 +195,13 @@ pub struct TimeRange {
 #[derive(Debug, Clone)]
 pub enum CalDavReportType {
     CalendarQuery(CalendarQuery),
-    CalendarMultiget { hrefs: Vec<String> },
-    FreeBusyQuery { time_range: TimeRange },
+    CalendarMultiget {
+        hrefs: Vec<String>,
+        calendar_data: Option<CalendarDataRequest>,
+    },
+    FreeBusyQuery {
+        time_range: TimeRange,
+    },
 }
 
 /// Helper functions for CalDAV XML generation
@@ -223,7 +279,9 @@ pub fn is_calendar_data(content: &[u8]) -> bool {
 ///
 /// This function validates that the content is a well-formed iCalendar object.
 /// Use this function in your application layer to validate calendar data
-/// before or after writing to the filesystem.
+/// before or after writing to the filesystem. The data is checked by the
+/// same parser that a PUT into a calendar collection and the CalDAV reports
+/// use, and is then returned as an `icalendar::Calendar`.
 ///
 /// # Example
 ///
@@ -238,6 +296,9 @@ pub fn is_calendar_data(content: &[u8]) -> bool {
 /// ```
 #[cfg(feature = "caldav")]
 pub fn validate_calendar_data(content: &str) -> Result<Calendar, String> {
+    if crate::ical::parse_calendar(content).is_none() {
+        return Err("Invalid iCalendar data: not a VCALENDAR object".to_string());
+    }
     content
         .parse::<Calendar>()
         .map_err(|e| format!("Invalid iCalendar data: {}", e))
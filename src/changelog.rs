@@ -0,0 +1,130 @@
+//
+// Change log, used by the filesystem implementations to hand out
+// RFC 6578 sync tokens and to find out what changed since a token.
+//
+use std::collections::{BTreeSet, VecDeque};
+#[cfg(feature = "localfs")]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::fs::{FsError, FsResult};
+
+// How many changes we remember. Tokens older than that are invalid.
+const MAX_ENTRIES: usize = 10000;
+
+const TOKEN_PREFIX: &str = "urn:dav-server:sync:";
+
+#[derive(Debug)]
+pub(crate) struct ChangeLog {
+    // tokens from another instance (or from before a restart) are invalid.
+    instance: String,
+    seq: u64,
+    // sequence number of the last entry that was dropped from the log.
+    dropped: u64,
+    entries: VecDeque<(u64, Vec<u8>)>,
+}
+
+// A parsed sync token.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SyncToken {
+    pub seq: u64,
+    // when the token was handed out (LocalFs also looks at mtimes).
+    #[cfg(feature = "localfs")]
+    pub time: SystemTime,
+}
+
+impl ChangeLog {
+    pub(crate) fn new() -> ChangeLog {
+        ChangeLog {
+            instance: uuid::Uuid::new_v4().simple().to_string(),
+            seq: 0,
+            dropped: 0,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Remember that the resource at `path` was created, changed or removed.
+    pub(crate) fn record(&mut self, path: &[u8]) {
+        self.seq += 1;
+        self.entries.push_back((self.seq, normalize(path).to_vec()));
+        if self.entries.len() > MAX_ENTRIES
+            && let Some((seq, _)) = self.entries.pop_front()
+        {
+            self.dropped = seq;
+        }
+    }
+
+    /// The current token.
+    pub(crate) fn token(&self) -> String {
+        let t = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        format!(
+            "{}{}-{}-{:x}",
+            TOKEN_PREFIX,
+            self.instance,
+            self.seq,
+            t.as_micros()
+        )
+    }
+
+    /// Parse a token that was handed out by `token()`.
+    pub(crate) fn parse_token(&self, token: &str) -> FsResult<SyncToken> {
+        let mut parts = token
+            .strip_prefix(TOKEN_PREFIX)
+            .ok_or(FsError::Forbidden)?
+            .split('-');
+        let (Some(instance), Some(seq), Some(time), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(FsError::Forbidden);
+        };
+        let seq = seq.parse::<u64>().map_err(|_| FsError::Forbidden)?;
+        #[cfg_attr(not(feature = "localfs"), allow(unused_variables))]
+        let time = u64::from_str_radix(time, 16).map_err(|_| FsError::Forbidden)?;
+        if instance != self.instance || seq > self.seq || seq < self.dropped {
+            return Err(FsError::Forbidden);
+        }
+        Ok(SyncToken {
+            seq,
+            #[cfg(feature = "localfs")]
+            time: UNIX_EPOCH + Duration::from_micros(time),
+        })
+    }
+
+    /// Members of collection `coll` that changed after `token`,
+    /// relative to the collection.
+    pub(crate) fn changes(&self, coll: &[u8], token: &SyncToken, deep: bool) -> BTreeSet<Vec<u8>> {
+        self.entries
+            .iter()
+            .filter(|(seq, _)| *seq > token.seq)
+            .filter_map(|(_, path)| member_of(coll, path, deep))
+            .collect()
+    }
+}
+
+/// If `path` is a member of collection `coll`, return the path
+/// relative to the collection. If `deep` is false, only direct
+/// members count.
+pub(crate) fn member_of(coll: &[u8], path: &[u8], deep: bool) -> Option<Vec<u8>> {
+    let coll = normalize(coll);
+    let path = normalize(path);
+    let rel = if coll == b"/" {
+        path.strip_prefix(b"/")?
+    } else {
+        path.strip_prefix(coll)?.strip_prefix(b"/")?
+    };
+    if rel.is_empty() || (!deep && rel.contains(&b'/')) {
+        return None;
+    }
+    Some(rel.to_vec())
+}
+
+// strip the trailing slash.
+fn normalize(path: &[u8]) -> &[u8] {
+    if path.len() > 1 && path.ends_with(b"/") {
+        &path[..path.len() - 1]
+    } else {
+        path
+    }
+}
@@ -0,0 +1,261 @@
+//
+// Content checksums, as used by the ownCloud / Nextcloud clients
+// in the OC-Checksum header ("SHA1:<hex>"), and by the
+// Content-Digest / Repr-Digest headers of RFC 9530.
+//
+// Checksums are passed around as (type, lowercase hex) pairs. A list of
+// them is stored as "SHA1:<hex> MD5:<hex>", the format of the
+// oc:checksums property.
+//
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use http::HeaderMap;
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha512};
+
+/// The checksum algorithms we know about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChecksumType {
+    Sha1,
+    Sha256,
+    Sha512,
+    Md5,
+    Adler32,
+}
+
+impl ChecksumType {
+    // The name in an OC-Checksum header.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ChecksumType::Sha1 => "SHA1",
+            ChecksumType::Sha256 => "SHA256",
+            ChecksumType::Sha512 => "SHA512",
+            ChecksumType::Md5 => "MD5",
+            ChecksumType::Adler32 => "ADLER32",
+        }
+    }
+
+    // The name in a Content-Digest or Repr-Digest header.
+    fn digest_name(&self) -> &'static str {
+        match self {
+            ChecksumType::Sha1 => "sha",
+            ChecksumType::Sha256 => "sha-256",
+            ChecksumType::Sha512 => "sha-512",
+            ChecksumType::Md5 => "md5",
+            ChecksumType::Adler32 => "adler",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ChecksumType> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA1" => Some(ChecksumType::Sha1),
+            "SHA256" => Some(ChecksumType::Sha256),
+            "SHA512" => Some(ChecksumType::Sha512),
+            "MD5" => Some(ChecksumType::Md5),
+            "ADLER32" => Some(ChecksumType::Adler32),
+            _ => None,
+        }
+    }
+}
+
+/// An OC-Checksum header value: `<type>:<hex digest>`.
+/// Unknown types are ignored.
+pub(crate) fn parse_oc_checksum(value: &str) -> Option<(ChecksumType, String)> {
+    let (name, digest) = value.trim().split_once(':')?;
+    let ctype = ChecksumType::from_name(name)?;
+    Some((ctype, digest.trim().to_ascii_lowercase()))
+}
+
+/// A list of checksums, as stored: `SHA1:<hex> MD5:<hex>`.
+fn parse_checksums(value: &str) -> Vec<(ChecksumType, String)> {
+    value
+        .split_whitespace()
+        .filter_map(parse_oc_checksum)
+        .collect()
+}
+
+pub(crate) fn format_checksums(checksums: &[(ChecksumType, String)]) -> String {
+    let sums: Vec<_> = checksums
+        .iter()
+        .map(|(t, digest)| format!("{}:{digest}", t.name()))
+        .collect();
+    sums.join(" ")
+}
+
+/// A Content-Digest or Repr-Digest header value (RFC 9530), a dictionary
+/// like `sha-256=:<base64>:, sha-512=:<base64>:`. Unknown algorithms
+/// and malformed members are ignored.
+pub(crate) fn parse_digest_fields(value: &str) -> Vec<(ChecksumType, String)> {
+    let mut sums = Vec::new();
+    for member in value.split(',') {
+        let Some((name, digest)) = member.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().to_ascii_lowercase();
+        let Some(ctype) = CHECKSUM_TYPES.iter().find(|t| t.digest_name() == name) else {
+            continue;
+        };
+        let digest = digest.trim();
+        let Some(digest) = digest.strip_prefix(':').and_then(|d| d.strip_suffix(':')) else {
+            continue;
+        };
+        if let Ok(bytes) = BASE64.decode(digest) {
+            sums.push((*ctype, to_hex(&bytes)));
+        }
+    }
+    sums
+}
+
+const CHECKSUM_TYPES: &[ChecksumType] = &[
+    ChecksumType::Sha1,
+    ChecksumType::Sha256,
+    ChecksumType::Sha512,
+    ChecksumType::Md5,
+    ChecksumType::Adler32,
+];
+
+/// Add the stored checksums of a file to the response headers: the
+/// `Repr-Digest` header of RFC 9530 for the current algorithms, and the
+/// older `Digest` header of RFC 3230.
+pub(crate) fn insert_digest_headers(headers: &mut HeaderMap, checksums: &str) {
+    let mut repr_digest = Vec::new();
+    let mut digest = Vec::new();
+    for (ctype, hex) in parse_checksums(checksums) {
+        let Some(bytes) = from_hex(&hex) else {
+            continue;
+        };
+        let b64 = BASE64.encode(bytes);
+        if matches!(ctype, ChecksumType::Sha256 | ChecksumType::Sha512) {
+            repr_digest.push(format!("{}=:{b64}:", ctype.digest_name()));
+        }
+        // RFC 3230 has no byte encoding for adler32.
+        if ctype != ChecksumType::Adler32 {
+            digest.push(format!(
+                "{}={b64}",
+                ctype.digest_name().to_ascii_uppercase()
+            ));
+        }
+    }
+    if !repr_digest.is_empty() {
+        headers.insert("Repr-Digest", repr_digest.join(", ").parse().unwrap());
+    }
+    if !digest.is_empty() {
+        headers.insert("Digest", digest.join(", ").parse().unwrap());
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Incremental hashing of the content.
+pub(crate) enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Md5(Md5),
+    Adler32(u32, u32),
+}
+
+impl Hasher {
+    pub(crate) fn new(ctype: ChecksumType) -> Hasher {
+        match ctype {
+            ChecksumType::Sha1 => Hasher::Sha1(Sha1::new()),
+            ChecksumType::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumType::Sha512 => Hasher::Sha512(Sha512::new()),
+            ChecksumType::Md5 => Hasher::Md5(Md5::new()),
+            ChecksumType::Adler32 => Hasher::Adler32(1, 0),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Adler32(a, b) => {
+                // 5552 is the largest n for which the sums cannot overflow.
+                for chunk in data.chunks(5552) {
+                    for &byte in chunk {
+                        *a += byte as u32;
+                        *b += *a;
+                    }
+                    *a %= 65521;
+                    *b %= 65521;
+                }
+            }
+        }
+    }
+
+    /// The digest as lowercase hex.
+    pub(crate) fn finish(self) -> String {
+        let digest = match self {
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Adler32(a, b) => ((b << 16) | a).to_be_bytes().to_vec(),
+        };
+        to_hex(&digest)
+    }
+
+    pub(crate) fn ctype(&self) -> ChecksumType {
+        match self {
+            Hasher::Sha1(_) => ChecksumType::Sha1,
+            Hasher::Sha256(_) => ChecksumType::Sha256,
+            Hasher::Sha512(_) => ChecksumType::Sha512,
+            Hasher::Md5(_) => ChecksumType::Md5,
+            Hasher::Adler32(..) => ChecksumType::Adler32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(ctype: ChecksumType, data: &[u8]) -> String {
+        let mut h = Hasher::new(ctype);
+        h.update(data);
+        h.finish()
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(
+            digest(ChecksumType::Sha1, b"abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            digest(ChecksumType::Md5, b"abc"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(digest(ChecksumType::Adler32, b"Wikipedia"), "11e60398");
+        assert_eq!(
+            parse_oc_checksum("sha1:A9993E36"),
+            Some((ChecksumType::Sha1, "a9993e36".to_string()))
+        );
+        assert_eq!(parse_oc_checksum("CRC32:1234"), None);
+        assert_eq!(
+            parse_digest_fields(
+                "sha-256=:ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=:, foo=:YQ==:"
+            ),
+            vec![(
+                ChecksumType::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()
+            )]
+        );
+    }
+}
@@ -4,8 +4,10 @@
 //
 use std::error::Error as StdError;
 use std::io;
+use std::path::PathBuf;
 use std::pin::pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::{self, buf::Buf};
 use derive_where::derive_where;
@@ -21,9 +23,13 @@ use crate::davpath::DavPath;
 use crate::util::{DavMethod, DavMethodSet, dav_method};
 
 use crate::DavResult;
+use crate::acl::DavAccessControl;
 use crate::errors::DavError;
 use crate::fs::*;
+use crate::handle_version::version_query;
 use crate::ls::*;
+#[cfg(feature = "caldav")]
+use crate::schedule::{CalendarUser, ItipDelivery};
 use crate::voidfs::{VoidFs, is_voidfs};
 
 #[derive(Clone, Copy, PartialEq)]
@@ -77,6 +83,28 @@ pub struct DavConfig<C = ()> {
     pub(crate) read_buf_size: Option<usize>,
     // Does GET on a file return 302 redirect.
     pub(crate) redirect: Option<bool>,
+    // Access control backend.
+    pub(crate) acl: Option<Box<dyn DavAccessControl>>,
+    // Put files under version control when they are written. Default: `false`.
+    pub(crate) autoversion: Option<bool>,
+    // Treat symbolic links as redirect references (RFC4437). Default: `false`.
+    pub(crate) redirectrefs: Option<bool>,
+    // Byte limits per subtree (RFC4331).
+    pub(crate) quota_limits: Option<Vec<(String, u64)>>,
+    // Directory where resumable uploads are staged.
+    pub(crate) upload_dir: Option<PathBuf>,
+    // How long an unfinished resumable upload is kept. Default: 24 hours.
+    pub(crate) upload_expiry: Option<Duration>,
+    // Replace files atomically on PUT. Default: `false`.
+    pub(crate) atomic_put: Option<bool>,
+    // Remove lock-null resources that were not written to. Default: `false`.
+    pub(crate) lock_null: Option<bool>,
+    // Calendar users, for CalDAV scheduling (RFC6638).
+    #[cfg(feature = "caldav")]
+    pub(crate) calendar_users: Option<Vec<CalendarUser>>,
+    // Delivery of scheduling messages to other servers.
+    #[cfg(feature = "caldav")]
+    pub(crate) itip_delivery: Option<Box<dyn ItipDelivery>>,
 }
 
 impl<C> DavConfig<C> {
@@ -176,6 +204,120 @@ impl<C> DavConfig<C> {
         this
     }
 
+    /// Set the access control backend to use (RFC3744). The privileges
+    /// of the [`principal`](Self::principal) are checked on every request.
+    pub fn acl(self, acl: Box<dyn DavAccessControl>) -> Self {
+        let mut this = self;
+        this.acl = Some(acl);
+        this
+    }
+
+    /// Put files under version control automatically when they are written
+    /// with PUT (RFC3253 auto-versioning), so that the old content is kept.
+    /// Files that are already under version control get a new version on
+    /// every PUT anyway, unless they are checked out.
+    pub fn autoversion(self, autoversion: bool) -> Self {
+        let mut this = self;
+        this.autoversion = Some(autoversion);
+        this
+    }
+
+    /// Treat symbolic links as redirect references (RFC4437), and enable
+    /// the MKREDIRECTREF and UPDATEREDIRECTREF methods. A request for a
+    /// redirect reference (or for a path below it) is answered with a
+    /// redirect to its target, unless it is sent with `Apply-To-Redirect-Ref: T`.
+    ///
+    /// This takes precedence over [`hide_symlinks`](Self::hide_symlinks).
+    pub fn redirectrefs(self, redirectrefs: bool) -> Self {
+        let mut this = self;
+        this.redirectrefs = Some(redirectrefs);
+        this
+    }
+
+    /// Limit the number of bytes that can be stored below `subtree`, a path
+    /// relative to the [`strip_prefix`](Self::strip_prefix) (e.g. `/home/alice/`).
+    /// When the limit is reached, PUT, COPY and MKCOL fail with
+    /// `507 Insufficient Storage` and the `DAV:quota-not-exceeded` precondition (RFC4331).
+    ///
+    /// Can be called more than once, to limit several subtrees.
+    pub fn quota_limit(self, subtree: impl Into<String>, limit: u64) -> Self {
+        let mut this = self;
+        this.quota_limits
+            .get_or_insert_with(Vec::new)
+            .push((subtree.into(), limit));
+        this
+    }
+
+    /// Enable resumable uploads (draft-ietf-httpbis-resumable-upload).
+    ///
+    /// A PUT with an `Upload-Complete` header creates an upload resource
+    /// that can be resumed with PATCH after the connection was lost. The
+    /// data is staged in `dir`, a directory on the local disk, and only
+    /// written to the filesystem when the upload is complete.
+    pub fn resumable_uploads(self, dir: impl Into<PathBuf>) -> Self {
+        let mut this = self;
+        this.upload_dir = Some(dir.into());
+        this
+    }
+
+    /// How long an unfinished resumable upload is kept after it was
+    /// last written to (default is 24 hours).
+    pub fn upload_expiry(self, expiry: Duration) -> Self {
+        let mut this = self;
+        this.upload_expiry = Some(expiry);
+        this
+    }
+
+    /// Replace files atomically on PUT. The new content is written to a
+    /// temporary file, which only replaces the old file when the whole
+    /// body has been received. A client that disconnects halfway leaves
+    /// the old file untouched, and readers never see partial content.
+    ///
+    /// The filesystem must support this (`LocalFs` and `MemFs` do),
+    /// otherwise it is ignored.
+    pub fn atomic_put(self, atomic: bool) -> Self {
+        let mut this = self;
+        this.atomic_put = Some(atomic);
+        this
+    }
+
+    /// Treat the empty resources that a LOCK of an unmapped URL creates as
+    /// lock-null resources (RFC2518). If such a resource was not written
+    /// to, it is removed again when it is unlocked, or when its lock has
+    /// expired, before the next request is handled.
+    ///
+    /// Without this, the empty resource stays, as RFC4918 prescribes.
+    /// The locksystem must be able to track lock-null resources
+    /// (`MemLs`, `FileLs` and `KvLs` can), otherwise it is ignored.
+    pub fn lock_null(self, lock_null: bool) -> Self {
+        let mut this = self;
+        this.lock_null = Some(lock_null);
+        this
+    }
+
+    /// Add a calendar user, for CalDAV scheduling (RFC6638).
+    ///
+    /// When a calendar user who is the current [`principal`](Self::principal)
+    /// stores an event with attendees in their calendar home, the attendees
+    /// are sent scheduling messages. See the [`schedule`](crate::schedule) module.
+    ///
+    /// Can be called more than once, to add several calendar users.
+    #[cfg(feature = "caldav")]
+    pub fn calendar_user(self, user: CalendarUser) -> Self {
+        let mut this = self;
+        this.calendar_users.get_or_insert_with(Vec::new).push(user);
+        this
+    }
+
+    /// Set the delivery of scheduling messages to calendar users
+    /// that are not on this server. Without it, those are dropped.
+    #[cfg(feature = "caldav")]
+    pub fn itip_delivery(self, delivery: Box<dyn ItipDelivery>) -> Self {
+        let mut this = self;
+        this.itip_delivery = Some(delivery);
+        this
+    }
+
     fn merge(&self, new: Self) -> Self {
         Self {
             prefix: new.prefix.or_else(|| self.prefix.clone()),
@@ -190,6 +332,18 @@ impl<C> DavConfig<C> {
             indexfile: new.indexfile.or_else(|| self.indexfile.clone()),
             read_buf_size: new.read_buf_size.or(self.read_buf_size),
             redirect: new.redirect.or(self.redirect),
+            acl: new.acl.or_else(|| self.acl.clone()),
+            autoversion: new.autoversion.or(self.autoversion),
+            redirectrefs: new.redirectrefs.or(self.redirectrefs),
+            quota_limits: new.quota_limits.or_else(|| self.quota_limits.clone()),
+            upload_dir: new.upload_dir.or_else(|| self.upload_dir.clone()),
+            upload_expiry: new.upload_expiry.or(self.upload_expiry),
+            atomic_put: new.atomic_put.or(self.atomic_put),
+            lock_null: new.lock_null.or(self.lock_null),
+            #[cfg(feature = "caldav")]
+            calendar_users: new.calendar_users.or_else(|| self.calendar_users.clone()),
+            #[cfg(feature = "caldav")]
+            itip_delivery: new.itip_delivery.or_else(|| self.itip_delivery.clone()),
         }
     }
 }
@@ -211,6 +365,18 @@ pub(crate) struct DavInner<C> {
     pub indexfile: Option<String>,
     pub read_buf_size: Option<usize>,
     pub redirect: Option<bool>,
+    pub acl: Option<Box<dyn DavAccessControl>>,
+    pub autoversion: bool,
+    pub redirectrefs: bool,
+    pub quota_limits: Vec<(DavPath, u64)>,
+    pub upload_dir: Option<PathBuf>,
+    pub upload_expiry: Duration,
+    pub atomic_put: bool,
+    pub lock_null: bool,
+    #[cfg(feature = "caldav")]
+    pub calendar_users: Vec<CalendarUser>,
+    #[cfg(feature = "caldav")]
+    pub itip_delivery: Option<Box<dyn ItipDelivery>>,
     pub credentials: C,
 }
 
@@ -343,6 +509,18 @@ where
             indexfile,
             read_buf_size,
             redirect,
+            acl,
+            autoversion,
+            redirectrefs,
+            quota_limits,
+            upload_dir,
+            upload_expiry,
+            atomic_put,
+            lock_null,
+            #[cfg(feature = "caldav")]
+            calendar_users,
+            #[cfg(feature = "caldav")]
+            itip_delivery,
         } = cfg;
         Self {
             prefix: prefix.unwrap_or_default(),
@@ -357,6 +535,22 @@ where
             indexfile,
             read_buf_size,
             redirect,
+            acl,
+            autoversion: autoversion.unwrap_or(false),
+            redirectrefs: redirectrefs.unwrap_or(false),
+            quota_limits: quota_limits
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(p, limit)| DavPath::new(&p).ok().map(|p| (p, limit)))
+                .collect(),
+            upload_dir,
+            upload_expiry: upload_expiry.unwrap_or(Duration::from_secs(24 * 3600)),
+            atomic_put: atomic_put.unwrap_or(false),
+            lock_null: lock_null.unwrap_or(false),
+            #[cfg(feature = "caldav")]
+            calendar_users: calendar_users.unwrap_or_default(),
+            #[cfg(feature = "caldav")]
+            itip_delivery,
             credentials,
         }
     }
@@ -549,6 +743,16 @@ where
         // make sure the request path is valid.
         let path = DavPath::from_uri_and_prefix(req.uri(), &self.prefix)?;
 
+        // versions are read-only.
+        if !matches!(
+            method,
+            DavMethod::Get | DavMethod::Head | DavMethod::Options
+        ) && let Some(version) = version_query(&req)
+            && self.is_version(&path, &version).await
+        {
+            return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+        }
+
         // PUT is the only handler that reads the body itself. All the
         // other handlers either expected no body, or a pre-read Vec<u8>.
         let (body_strm, body_data) = match method {
@@ -562,10 +766,23 @@ where
             | DavMethod::Patch
             | DavMethod::PropFind
             | DavMethod::PropPatch
+            | DavMethod::MkCol
             | DavMethod::Lock
             | DavMethod::Report
             | DavMethod::MkCalendar
-            | DavMethod::MkAddressbook => {}
+            | DavMethod::MkAddressbook
+            | DavMethod::Acl
+            | DavMethod::VersionControl
+            | DavMethod::Checkout
+            | DavMethod::Checkin
+            | DavMethod::Bind
+            | DavMethod::Unbind
+            | DavMethod::Rebind
+            | DavMethod::MkRedirectRef
+            | DavMethod::UpdateRedirectRef
+            | DavMethod::Search
+            | DavMethod::OrderPatch
+            | DavMethod::Post => {}
             _ => {
                 if !body_data.is_empty() {
                     return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE.into());
@@ -575,6 +792,26 @@ where
 
         debug!("== START REQUEST {method:?} {path}");
 
+        // check the privileges of the principal.
+        if let Some(res) = self.check_privileges(&req, method, &path).await {
+            return Ok(res);
+        }
+
+        // requests for (or below) a redirect reference are redirected.
+        if let Some(res) = self.redirect_ref(&req, method, &path).await? {
+            return Ok(res);
+        }
+
+        // lock-null resources whose lock expired are gone before anyone
+        // looks for them. Not on every request, that costs a lookup in
+        // the lock system each time.
+        if matches!(
+            method,
+            DavMethod::Lock | DavMethod::Unlock | DavMethod::PropFind
+        ) {
+            self.remove_stale_lock_nulls().await;
+        }
+
         match method {
             DavMethod::Options => self.handle_options(&req).await,
             DavMethod::PropFind => self.handle_propfind(&req, &body_data).await,
@@ -582,25 +819,53 @@ where
             DavMethod::PropPatch => self.handle_proppatch(&req, &body_data).await,
             #[cfg(not(feature = "proppatch"))]
             DavMethod::PropPatch => Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED)),
-            DavMethod::MkCol => self.handle_mkcol(&req).await,
+            DavMethod::MkCol => self.handle_mkcol(&req, &body_data).await,
+            // resumable uploads, see handle_upload.rs.
+            DavMethod::Put | DavMethod::Patch if self.is_upload_request(&req) => {
+                self.handle_upload(&req, body_strm.unwrap()).await
+            }
+            DavMethod::Head | DavMethod::Get | DavMethod::Delete
+                if self.is_upload_request(&req) =>
+            {
+                self.handle_upload_resource(&req).await
+            }
             DavMethod::Delete => self.handle_delete(&req).await,
             DavMethod::Lock => self.handle_lock(&req, &body_data).await,
             DavMethod::Unlock => self.handle_unlock(&req).await,
             DavMethod::Head | DavMethod::Get => self.handle_get(&req).await,
             DavMethod::Copy | DavMethod::Move => self.handle_copymove(&req, method).await,
+            #[cfg(feature = "caldav")]
+            DavMethod::Put | DavMethod::Patch => {
+                self.handle_calendar_put(&req, body_strm.unwrap()).await
+            }
+            #[cfg(not(feature = "caldav"))]
             DavMethod::Put | DavMethod::Patch => self.handle_put(&req, body_strm.unwrap()).await,
-            #[cfg(any(feature = "caldav", feature = "carddav"))]
             DavMethod::Report => self.handle_report(&req, &body_data).await,
+            DavMethod::Search => self.handle_search(&req, &body_data).await,
+            DavMethod::OrderPatch => self.handle_orderpatch(&req, &body_data).await,
+            DavMethod::Acl => self.handle_acl(&req, &body_data).await,
+            DavMethod::VersionControl
+            | DavMethod::Checkout
+            | DavMethod::Checkin
+            | DavMethod::Uncheckout => self.handle_version(&req, method, &body_data).await,
+            DavMethod::Bind | DavMethod::Unbind | DavMethod::Rebind => {
+                self.handle_bind(&req, method, &body_data).await
+            }
+            DavMethod::MkRedirectRef | DavMethod::UpdateRedirectRef => {
+                self.handle_redirectref(&req, method, &body_data).await
+            }
             #[cfg(feature = "caldav")]
             DavMethod::MkCalendar => self.handle_mkcalendar(&req, &body_data).await,
             #[cfg(feature = "carddav")]
             DavMethod::MkAddressbook => self.handle_mkaddressbook(&req, &body_data).await,
-            #[cfg(not(any(feature = "caldav", feature = "carddav")))]
-            DavMethod::Report => Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED)),
+            #[cfg(feature = "caldav")]
+            DavMethod::Post => self.handle_post(&req, &body_data).await,
             #[cfg(not(feature = "caldav"))]
             DavMethod::MkCalendar => Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED)),
             #[cfg(not(feature = "carddav"))]
             DavMethod::MkAddressbook => Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED)),
+            #[cfg(not(feature = "caldav"))]
+            DavMethod::Post => Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED)),
         }
     }
 }
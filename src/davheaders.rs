@@ -19,6 +19,12 @@ pub static IF_NONE_MATCH: HeaderName = HeaderName::from_static("if-none-match");
 pub static X_UPDATE_RANGE: HeaderName = HeaderName::from_static("x-update-range");
 pub static IF: HeaderName = HeaderName::from_static("if");
 pub static CONTENT_LANGUAGE: HeaderName = HeaderName::from_static("content-language");
+pub static APPLY_TO_REDIRECT_REF: HeaderName = HeaderName::from_static("apply-to-redirect-ref");
+pub static POSITION: HeaderName = HeaderName::from_static("position");
+pub static PREFER: HeaderName = HeaderName::from_static("prefer");
+pub static UPLOAD_COMPLETE: HeaderName = HeaderName::from_static("upload-complete");
+pub static UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+pub static UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
 
 // helper.
 fn one<'i, I>(values: &mut I) -> Result<&'i HeaderValue, headers::Error>
@@ -80,6 +86,9 @@ header!(ContentType, CONTENT_TYPE, "content-type");
 header!(ContentLocation, CONTENT_LOCATION, "content-location");
 header!(LockToken, LOCK_TOKEN, "lock-token");
 header!(XLitmus, X_LITMUS, "x-litmus");
+header!(RedirectRef, REDIRECT_REF, "redirect-ref");
+header!(OrderingType, ORDERING_TYPE, "ordering-type");
+header!(PreferenceApplied, PREFERENCE_APPLIED, "preference-applied");
 
 /// - "Depth" header for PROPFIND requests. See the items for its response behaviour
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -296,6 +305,147 @@ impl Header for Overwrite {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApplyToRedirectRef(pub bool);
+
+impl Header for ApplyToRedirectRef {
+    fn name() -> &'static HeaderName {
+        &APPLY_TO_REDIRECT_REF
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let line = one(values)?;
+        match line.as_bytes() {
+            b"F" => Ok(ApplyToRedirectRef(false)),
+            b"T" => Ok(ApplyToRedirectRef(true)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let value = match self.0 {
+            true => "T",
+            false => "F",
+        };
+        values.extend(std::iter::once(HeaderValue::from_static(value)));
+    }
+}
+
+/// Position header (RFC 3648), where to put a new member of an
+/// ordered collection. The segments are percent-decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Position {
+    First,
+    Last,
+    Before(Vec<u8>),
+    After(Vec<u8>),
+}
+
+impl Header for Position {
+    fn name() -> &'static HeaderName {
+        &POSITION
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let line = one(values)?.to_str().map_err(map_invalid)?.trim();
+        let segment = |s: &str| {
+            let s = s.trim();
+            if s.is_empty() || s.contains('/') {
+                return Err(invalid());
+            }
+            Ok(percent_encoding::percent_decode_str(s).collect::<Vec<u8>>())
+        };
+        match line.split_once(' ') {
+            None if line == "first" => Ok(Position::First),
+            None if line == "last" => Ok(Position::Last),
+            Some(("before", s)) => Ok(Position::Before(segment(s)?)),
+            Some(("after", s)) => Ok(Position::After(segment(s)?)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let enc = |s: &[u8]| {
+            percent_encoding::percent_encode(s, percent_encoding::NON_ALPHANUMERIC).to_string()
+        };
+        let value = match self {
+            Position::First => "first".to_string(),
+            Position::Last => "last".to_string(),
+            Position::Before(s) => format!("before {}", enc(s)),
+            Position::After(s) => format!("after {}", enc(s)),
+        };
+        values.extend(std::iter::once(HeaderValue::from_str(&value).unwrap()));
+    }
+}
+
+/// Prefer header (RFC 7240), with the preferences from RFC 8144
+/// that we know about. Everything else is ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prefer {
+    /// return=minimal: leave out what the client did not need.
+    pub return_minimal: bool,
+    /// depth-noroot: leave out the target collection in Depth 1 listings.
+    pub depth_noroot: bool,
+}
+
+impl Header for Prefer {
+    fn name() -> &'static HeaderName {
+        &PREFER
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        let mut prefer = Prefer::default();
+        for value in values {
+            let value = value.to_str().map_err(map_invalid)?;
+            for pref in value.split(',') {
+                // parameters after the ';' are not used by any of these.
+                let pref = pref.split(';').next().unwrap_or_default();
+                let (name, val) = match pref.split_once('=') {
+                    Some((n, v)) => (n.trim(), v.trim().trim_matches('"')),
+                    None => (pref.trim(), ""),
+                };
+                match name.to_ascii_lowercase().as_str() {
+                    "return" if val.eq_ignore_ascii_case("minimal") => prefer.return_minimal = true,
+                    "depth-noroot" => prefer.depth_noroot = true,
+                    _ => {}
+                }
+            }
+        }
+        Ok(prefer)
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let mut v = Vec::new();
+        if self.return_minimal {
+            v.push("return=minimal");
+        }
+        if self.depth_noroot {
+            v.push("depth-noroot");
+        }
+        values.extend(std::iter::once(
+            HeaderValue::from_str(&v.join(", ")).unwrap(),
+        ));
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ETag {
     tag: String,
@@ -599,6 +749,70 @@ impl Header for XUpdateRange {
     }
 }
 
+/// Upload-Complete header (resumable uploads), a structured field boolean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UploadComplete(pub bool);
+
+impl Header for UploadComplete {
+    fn name() -> &'static HeaderName {
+        &UPLOAD_COMPLETE
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        match one(values)?.as_bytes() {
+            b"?1" => Ok(UploadComplete(true)),
+            b"?0" => Ok(UploadComplete(false)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let value = if self.0 { "?1" } else { "?0" };
+        values.extend(std::iter::once(HeaderValue::from_static(value)));
+    }
+}
+
+macro_rules! u64_header {
+    ($tname:ident, $hname:ident) => {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct $tname(pub u64);
+
+        impl Header for $tname {
+            fn name() -> &'static HeaderName {
+                &$hname
+            }
+
+            fn decode<'i, I>(values: &mut I) -> Result<Self, headers::Error>
+            where
+                I: Iterator<Item = &'i HeaderValue>,
+            {
+                one(values)?
+                    .to_str()
+                    .map_err(map_invalid)?
+                    .parse::<u64>()
+                    .map(|n| $tname(n))
+                    .map_err(map_invalid)
+            }
+
+            fn encode<E>(&self, values: &mut E)
+            where
+                E: Extend<HeaderValue>,
+            {
+                values.extend(std::iter::once(HeaderValue::from(self.0)))
+            }
+        }
+    };
+}
+
+u64_header!(UploadOffset, UPLOAD_OFFSET);
+u64_header!(UploadLength, UPLOAD_LENGTH);
+
 // The "If" header contains IfLists, of which the results are ORed.
 #[derive(Debug, Clone, PartialEq)]
 pub struct If(pub Vec<IfList>);
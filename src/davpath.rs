@@ -242,6 +242,16 @@ impl DavPath {
         }
     }
 
+    // add a prefix to a path that has none, e.g. one that came
+    // from the filesystem.
+    pub(crate) fn add_prefix(&mut self, prefix: &str) {
+        let prefix = prefix.trim_end_matches('/').as_bytes();
+        let mut fullpath = prefix.to_vec();
+        fullpath.extend_from_slice(self.get_path());
+        self.fullpath = fullpath;
+        self.pfxlen = Some(prefix.len());
+    }
+
     // add a slash
     pub(crate) fn add_slash_if(&mut self, b: bool) {
         if b && !self.is_collection() {
@@ -292,9 +302,13 @@ impl DavPath {
             segs.push(b"");
         }
         segs.insert(0, b"");
+        let mut fullpath = segs.join(&b'/').to_vec();
+        if fullpath.is_empty() {
+            fullpath.push(b'/');
+        }
         DavPath {
             pfxlen: self.pfxlen,
-            fullpath: segs.join(&b'/').to_vec(),
+            fullpath,
         }
     }
 }
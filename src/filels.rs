@@ -0,0 +1,228 @@
+//! Persistent locksystem, kept in a local file.
+//!
+//! `FileLs` works just like [`MemLs`](crate::memls::MemLs), but every
+//! change is written to a file, so that the locks survive a restart of
+//! the server. Locks that have expired while the server was down are
+//! dropped when the file is loaded.
+//!
+//! The file is replaced atomically (written to a temporary file next
+//! to it, synced and renamed), so it is never left half written.
+//!
+//! Like `MemLs`, create the instance once with `FileLs::new`, and clone()
+//! it every time you pass it to the DavHandler. Do not use the same file
+//! from more than one `FileLs`.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use xmltree::Element;
+
+use crate::davpath::DavPath;
+use crate::ls::*;
+use crate::memls::LockTree;
+
+/// Persistent LockSystem.
+#[derive(Debug, Clone)]
+pub struct FileLs(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    // the locks, and the number of changes made to them.
+    locks: Mutex<(LockTree, u64)>,
+    file: PathBuf,
+    // the last change that was written to the file. It is held while
+    // writing, so that the lock checks do not wait for the disk.
+    saved: Mutex<u64>,
+}
+
+impl FileLs {
+    /// Create a new "filels" locksystem, that keeps its locks in `file`.
+    ///
+    /// The locks that are already in the file are loaded. The file
+    /// does not need to exist, but its directory does.
+    pub fn new(file: impl AsRef<Path>) -> io::Result<Box<FileLs>> {
+        let file = file.as_ref().to_path_buf();
+        let tree = match std::fs::read(&file) {
+            Ok(data) => LockTree::load(&data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => LockTree::new(),
+            Err(e) => return Err(e),
+        };
+        let inner = Inner {
+            locks: Mutex::new((tree, 0)),
+            file,
+            saved: Mutex::new(0),
+        };
+        Ok(Box::new(FileLs(Arc::new(inner))))
+    }
+
+    // Change the locks, and save them if `f` says they were changed.
+    async fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut LockTree) -> (R, bool),
+    {
+        let (res, change) = {
+            let (tree, changes) = &mut *self.0.locks.lock().unwrap();
+            let (res, changed) = f(tree);
+            if changed {
+                *changes += 1;
+            }
+            (res, changed.then(|| (*changes, tree.save())))
+        };
+        if let Some((change, data)) = change {
+            self.save(change, data).await;
+        }
+        res
+    }
+
+    // Write the locks as they were after `change`. This does blocking
+    // I/O, so it runs on a thread where that is allowed. If a later
+    // change was written in the meantime, there is nothing to do.
+    async fn save(&self, change: u64, data: Vec<u8>) {
+        let inner = self.0.clone();
+        tokio::task::spawn_blocking(move || {
+            let saved = &mut *inner.saved.lock().unwrap();
+            if *saved >= change {
+                return;
+            }
+            match write_locks(&inner.file, &data) {
+                Ok(()) => *saved = change,
+                Err(e) => error!("filels: cannot save locks to {:?}: {e}", inner.file),
+            }
+        })
+        .map(|res| res.unwrap())
+        .await
+    }
+}
+
+impl DavLockSystem for FileLs {
+    fn lock(
+        &'_ self,
+        path: &DavPath,
+        principal: Option<&str>,
+        owner: Option<&Element>,
+        timeout: Option<Duration>,
+        shared: bool,
+        deep: bool,
+    ) -> LsFuture<'_, Result<DavLock, DavLock>> {
+        let path = path.clone();
+        let principal = principal.map(|p| p.to_string());
+        let owner = owner.cloned();
+        self.update(move |tree| {
+            let res = tree.lock(
+                &path,
+                principal.as_deref(),
+                owner.as_ref(),
+                timeout,
+                shared,
+                deep,
+            );
+            let changed = res.is_ok();
+            (res, changed)
+        })
+        .boxed()
+    }
+
+    fn unlock(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, Result<(), ()>> {
+        let path = path.clone();
+        let token = token.to_string();
+        self.update(move |tree| {
+            let res = tree.unlock(&path, &token);
+            let changed = res.is_ok();
+            (res, changed)
+        })
+        .boxed()
+    }
+
+    fn refresh(
+        &'_ self,
+        path: &DavPath,
+        token: &str,
+        timeout: Option<Duration>,
+    ) -> LsFuture<'_, Result<DavLock, ()>> {
+        let path = path.clone();
+        let token = token.to_string();
+        self.update(move |tree| {
+            let res = tree.refresh(&path, &token, timeout);
+            let changed = res.is_ok();
+            (res, changed)
+        })
+        .boxed()
+    }
+
+    fn check(
+        &'_ self,
+        path: &DavPath,
+        principal: Option<&str>,
+        ignore_principal: bool,
+        deep: bool,
+        submitted_tokens: &[String],
+    ) -> LsFuture<'_, Result<(), DavLock>> {
+        let (tree, _) = &*self.0.locks.lock().unwrap();
+        let res = tree.check(path, principal, ignore_principal, deep, submitted_tokens);
+        std::future::ready(res).boxed()
+    }
+
+    fn discover(&'_ self, path: &DavPath) -> LsFuture<'_, Vec<DavLock>> {
+        let (tree, _) = &*self.0.locks.lock().unwrap();
+        std::future::ready(tree.discover(path)).boxed()
+    }
+
+    fn delete(&'_ self, path: &DavPath) -> LsFuture<'_, Result<(), ()>> {
+        let path = path.clone();
+        self.update(move |tree| {
+            let changed = tree.delete(&path);
+            (Ok(()), changed)
+        })
+        .boxed()
+    }
+
+    fn add_lock_null(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, ()> {
+        let path = path.clone();
+        let token = token.to_string();
+        self.update(move |tree| ((), tree.add_lock_null(&path, &token)))
+            .boxed()
+    }
+
+    fn remove_lock_null(&'_ self, path: &DavPath) -> LsFuture<'_, ()> {
+        let path = path.clone();
+        self.update(move |tree| ((), tree.remove_lock_null(&path)))
+            .boxed()
+    }
+
+    fn stale_lock_nulls(&'_ self) -> LsFuture<'_, Vec<DavPath>> {
+        self.update(|tree| {
+            let stale = tree.stale_lock_nulls();
+            let changed = !stale.is_empty();
+            (stale, changed)
+        })
+        .boxed()
+    }
+}
+
+// Write to a temporary file first, so that the file is always complete.
+fn write_locks(file: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = file.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut f = File::create(&tmp)?;
+    f.write_all(data)
+        .and_then(|_| f.sync_all())
+        .and_then(|_| std::fs::rename(&tmp, file))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp);
+        })?;
+    // and make sure the rename is on disk as well.
+    #[cfg(unix)]
+    if let Some(dir) = file.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
@@ -132,6 +132,46 @@ impl DavProp {
     }
 }
 
+/// Result of [`sync_changes`][DavFileSystem::sync_changes].
+#[derive(Debug, Clone, Default)]
+pub struct DavSyncChanges {
+    /// The new sync token.
+    pub token: String,
+    /// Members that were added, changed or removed since the old token,
+    /// as paths relative to the collection (e.g. `b"dir/file.txt"`).
+    pub members: Vec<Vec<u8>>,
+}
+
+/// One stored version of a resource (RFC 3253).
+#[derive(Debug, Clone)]
+pub struct DavVersion {
+    /// Name of the version, unique within the history of the resource (e.g. `"3"`).
+    pub name: String,
+    /// Time the version was created.
+    pub created: SystemTime,
+    /// Size of the content.
+    pub size: u64,
+}
+
+/// Result of [`version_history`][DavFileSystem::version_history].
+#[derive(Debug, Clone, Default)]
+pub struct DavVersionHistory {
+    /// All versions, oldest first.
+    pub versions: Vec<DavVersion>,
+    /// Is the resource checked out (i.e. may it be changed without
+    /// creating a new version).
+    pub checked_out: bool,
+}
+
+/// A redirect reference (RFC 4437), like a symbolic link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DavRedirectRef {
+    /// The target, an absolute URL or an absolute path (including the prefix).
+    pub target: String,
+    /// `DAV:permanent` (the default is `DAV:temporary`).
+    pub permanent: bool,
+}
+
 /// Future returned by almost all of the DavFileSystem methods.
 pub type FsFuture<'a, T> = Pin<Box<dyn Future<Output = FsResult<T>> + Send + 'a>>;
 /// Convenience alias for a boxed Stream.
@@ -249,6 +289,31 @@ pub trait DavFileSystem {
         notimplemented_fut!("set_modified")
     }
 
+    /// Set the creation time of a file / directory.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[doc(hidden)]
+    #[allow(unused_variables)]
+    fn set_created<'a>(&'a self, path: &'a DavPath, tm: SystemTime) -> FsFuture<'a, ()> {
+        notimplemented_fut!("set_created")
+    }
+
+    /// Make a file read-only, or writable again.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn set_readonly<'a>(&'a self, path: &'a DavPath, readonly: bool) -> FsFuture<'a, ()> {
+        notimplemented_fut!("set_readonly")
+    }
+
+    /// Make a file executable, or not (unix: the "x" mode bits).
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn set_executable<'a>(&'a self, path: &'a DavPath, executable: bool) -> FsFuture<'a, ()> {
+        notimplemented_fut!("set_executable")
+    }
+
     /// Indicator that tells if this filesystem driver supports DAV properties.
     ///
     /// The default implementation returns `false`.
@@ -300,6 +365,184 @@ pub trait DavFileSystem {
     fn get_quota(&'_ self) -> FsFuture<'_, (u64, Option<u64>)> {
         notimplemented_fut!("get_quota")
     }
+
+    /// Get the current sync token of a collection (RFC 6578).
+    ///
+    /// The token is an opaque URI that the client hands back to
+    /// [`sync_changes`][Self::sync_changes] later on.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn sync_token<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        notimplemented_fut!("sync_token")
+    }
+
+    /// List the members of a collection that changed since `token`.
+    ///
+    /// If `deep` is false, only the direct members of the collection are
+    /// returned, otherwise all of its descendants. Members that were
+    /// removed must be listed as well. Return [`FsError::Forbidden`]
+    /// if the token is invalid or has expired, the client will then
+    /// start over with a full sync.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn sync_changes<'a>(
+        &'a self,
+        path: &'a DavPath,
+        token: &'a str,
+        deep: bool,
+    ) -> FsFuture<'a, DavSyncChanges> {
+        notimplemented_fut!("sync_changes")
+    }
+
+    /// Put a file under version control (RFC 3253). The current content
+    /// becomes the first version. If the file already is under version
+    /// control, nothing happens.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn version_control<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        notimplemented_fut!("version_control")
+    }
+
+    /// Get the version history of a file, or `None` if the file
+    /// is not under version control.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn version_history<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Option<DavVersionHistory>> {
+        notimplemented_fut!("version_history")
+    }
+
+    /// Check out a file that is under version control.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn checkout<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        notimplemented_fut!("checkout")
+    }
+
+    /// Store the current content of a file as a new version. If `keep_checked_out`
+    /// is false, the file is checked in, otherwise it stays checked out.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn checkin<'a>(
+        &'a self,
+        path: &'a DavPath,
+        keep_checked_out: bool,
+    ) -> FsFuture<'a, DavVersion> {
+        notimplemented_fut!("checkin")
+    }
+
+    /// Cancel a checkout: restore the content of the latest version
+    /// and check the file in.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn uncheckout<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        notimplemented_fut!("uncheckout")
+    }
+
+    /// Open a version of a file for reading.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn open_version<'a>(
+        &'a self,
+        path: &'a DavPath,
+        version: &'a str,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        notimplemented_fut!("open_version")
+    }
+
+    /// Add a new binding (RFC 5842) `to` for the resource at `from`, like
+    /// a hard link. `to` must not exist yet. Return [`FsError::Forbidden`]
+    /// if this kind of resource cannot be bound.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn bind<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        notimplemented_fut!("bind")
+    }
+
+    /// Remove the binding `path`. Unlike `remove_dir`, this also works for
+    /// a non-empty collection. The resource itself is only destroyed
+    /// when its last binding is removed.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn unbind<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        notimplemented_fut!("unbind")
+    }
+
+    /// A unique, stable identifier of the resource, independent of the
+    /// binding it is reached through. Must be an URI (e.g. `urn:uuid:...`).
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn resource_id<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        notimplemented_fut!("resource_id")
+    }
+
+    /// The checksums of a file, that were stored when it was written
+    /// with [`OpenOptions::checksum`] set. The format is a space separated
+    /// list of `TYPE:hexdigest` values, e.g. `SHA1:<hex> MD5:<hex>`.
+    ///
+    /// Returns [`FsError::NotFound`] if there are none, or if the
+    /// file was changed since.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn checksums<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        notimplemented_fut!("checksums")
+    }
+
+    /// All bindings of the resource at `path`, including `path` itself.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn bindings<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Vec<DavPath>> {
+        notimplemented_fut!("bindings")
+    }
+
+    /// Create a redirect reference (RFC 4437) at `path`. Fails with
+    /// [`FsError::Exists`] if `path` exists, and with [`FsError::NotImplemented`]
+    /// if the filesystem cannot store this kind of redirect.
+    ///
+    /// A redirect reference must show up as a symbolic link in
+    /// [`symlink_metadata`][DavFileSystem::symlink_metadata].
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn create_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("create_redirectref")
+    }
+
+    /// Change the target and/or lifetime of an existing redirect reference.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn update_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("update_redirectref")
+    }
+
+    /// Read a redirect reference.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn redirectref<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, DavRedirectRef> {
+        notimplemented_fut!("redirectref")
+    }
 }
 
 /// File system with access control. Type parameter `C` (credentials) represents
@@ -459,6 +702,46 @@ where
         notimplemented_fut!("set_mofified")
     }
 
+    /// Set the creation time of a file / directory.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[doc(hidden)]
+    #[allow(unused_variables)]
+    fn set_created<'a>(
+        &'a self,
+        path: &'a DavPath,
+        tm: SystemTime,
+        credentials: &'a C,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("set_created")
+    }
+
+    /// Make a file read-only, or writable again.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn set_readonly<'a>(
+        &'a self,
+        path: &'a DavPath,
+        readonly: bool,
+        credentials: &'a C,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("set_readonly")
+    }
+
+    /// Make a file executable, or not (unix: the "x" mode bits).
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn set_executable<'a>(
+        &'a self,
+        path: &'a DavPath,
+        executable: bool,
+        credentials: &'a C,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("set_executable")
+    }
+
     /// Indicator that tells if this filesystem driver supports DAV properties.
     ///
     /// The default implementation returns `false`.
@@ -522,6 +805,202 @@ where
     fn get_quota<'a>(&'a self, credentials: &'a C) -> FsFuture<'a, (u64, Option<u64>)> {
         notimplemented_fut!("get_quota")
     }
+
+    /// Get the current sync token of a collection (RFC 6578).
+    ///
+    /// The token is an opaque URI that the client hands back to
+    /// [`sync_changes`][Self::sync_changes] later on.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn sync_token<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, String> {
+        notimplemented_fut!("sync_token")
+    }
+
+    /// List the members of a collection that changed since `token`.
+    ///
+    /// If `deep` is false, only the direct members of the collection are
+    /// returned, otherwise all of its descendants. Members that were
+    /// removed must be listed as well. Return [`FsError::Forbidden`]
+    /// if the token is invalid or has expired, the client will then
+    /// start over with a full sync.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn sync_changes<'a>(
+        &'a self,
+        path: &'a DavPath,
+        token: &'a str,
+        deep: bool,
+        credentials: &'a C,
+    ) -> FsFuture<'a, DavSyncChanges> {
+        notimplemented_fut!("sync_changes")
+    }
+
+    /// Put a file under version control (RFC 3253). The current content
+    /// becomes the first version. If the file already is under version
+    /// control, nothing happens.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn version_control<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, ()> {
+        notimplemented_fut!("version_control")
+    }
+
+    /// Get the version history of a file, or `None` if the file
+    /// is not under version control.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn version_history<'a>(
+        &'a self,
+        path: &'a DavPath,
+        credentials: &'a C,
+    ) -> FsFuture<'a, Option<DavVersionHistory>> {
+        notimplemented_fut!("version_history")
+    }
+
+    /// Check out a file that is under version control.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn checkout<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, ()> {
+        notimplemented_fut!("checkout")
+    }
+
+    /// Store the current content of a file as a new version. If `keep_checked_out`
+    /// is false, the file is checked in, otherwise it stays checked out.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn checkin<'a>(
+        &'a self,
+        path: &'a DavPath,
+        keep_checked_out: bool,
+        credentials: &'a C,
+    ) -> FsFuture<'a, DavVersion> {
+        notimplemented_fut!("checkin")
+    }
+
+    /// Cancel a checkout: restore the content of the latest version
+    /// and check the file in.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn uncheckout<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, ()> {
+        notimplemented_fut!("uncheckout")
+    }
+
+    /// Open a version of a file for reading.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn open_version<'a>(
+        &'a self,
+        path: &'a DavPath,
+        version: &'a str,
+        credentials: &'a C,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        notimplemented_fut!("open_version")
+    }
+
+    /// Add a new binding (RFC 5842) `to` for the resource at `from`, like
+    /// a hard link. `to` must not exist yet. Return [`FsError::Forbidden`]
+    /// if this kind of resource cannot be bound.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn bind<'a>(
+        &'a self,
+        from: &'a DavPath,
+        to: &'a DavPath,
+        credentials: &'a C,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("bind")
+    }
+
+    /// Remove the binding `path`. Unlike `remove_dir`, this also works for
+    /// a non-empty collection. The resource itself is only destroyed
+    /// when its last binding is removed.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn unbind<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, ()> {
+        notimplemented_fut!("unbind")
+    }
+
+    /// A unique, stable identifier of the resource, independent of the
+    /// binding it is reached through. Must be an URI (e.g. `urn:uuid:...`).
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn resource_id<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, String> {
+        notimplemented_fut!("resource_id")
+    }
+
+    /// The checksums of a file, that were stored when it was written
+    /// with [`OpenOptions::checksum`] set. The format is a space separated
+    /// list of `TYPE:hexdigest` values, e.g. `SHA1:<hex> MD5:<hex>`.
+    ///
+    /// Returns [`FsError::NotFound`] if there are none, or if the
+    /// file was changed since.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn checksums<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, String> {
+        notimplemented_fut!("checksums")
+    }
+
+    /// All bindings of the resource at `path`, including `path` itself.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn bindings<'a>(&'a self, path: &'a DavPath, credentials: &'a C) -> FsFuture<'a, Vec<DavPath>> {
+        notimplemented_fut!("bindings")
+    }
+
+    /// Create a redirect reference (RFC 4437) at `path`. Fails with
+    /// [`FsError::Exists`] if `path` exists, and with [`FsError::NotImplemented`]
+    /// if the filesystem cannot store this kind of redirect.
+    ///
+    /// A redirect reference must show up as a symbolic link in
+    /// [`symlink_metadata`][GuardedFileSystem::symlink_metadata].
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn create_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+        credentials: &'a C,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("create_redirectref")
+    }
+
+    /// Change the target and/or lifetime of an existing redirect reference.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn update_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+        credentials: &'a C,
+    ) -> FsFuture<'a, ()> {
+        notimplemented_fut!("update_redirectref")
+    }
+
+    /// Read a redirect reference.
+    ///
+    /// The default implementation returns [`FsError::NotImplemented`].
+    #[allow(unused_variables)]
+    fn redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        credentials: &'a C,
+    ) -> FsFuture<'a, DavRedirectRef> {
+        notimplemented_fut!("redirectref")
+    }
 }
 
 clone_trait_object! {<C> GuardedFileSystem<C>}
@@ -609,6 +1088,33 @@ impl<Fs: DavFileSystem + Clone + Send + Sync> GuardedFileSystem<()> for Fs {
         DavFileSystem::set_modified(self, path, tm)
     }
 
+    fn set_created<'a>(
+        &'a self,
+        path: &'a DavPath,
+        tm: SystemTime,
+        _credentials: &(),
+    ) -> FsFuture<'a, ()> {
+        DavFileSystem::set_created(self, path, tm)
+    }
+
+    fn set_readonly<'a>(
+        &'a self,
+        path: &'a DavPath,
+        readonly: bool,
+        _credentials: &(),
+    ) -> FsFuture<'a, ()> {
+        DavFileSystem::set_readonly(self, path, readonly)
+    }
+
+    fn set_executable<'a>(
+        &'a self,
+        path: &'a DavPath,
+        executable: bool,
+        _credentials: &(),
+    ) -> FsFuture<'a, ()> {
+        DavFileSystem::set_executable(self, path, executable)
+    }
+
     fn have_props<'a>(
         &'a self,
         path: &'a DavPath,
@@ -648,6 +1154,109 @@ impl<Fs: DavFileSystem + Clone + Send + Sync> GuardedFileSystem<()> for Fs {
     fn get_quota(&'_ self, _credentials: &()) -> FsFuture<'_, (u64, Option<u64>)> {
         DavFileSystem::get_quota(self)
     }
+
+    fn sync_token<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, String> {
+        DavFileSystem::sync_token(self, path)
+    }
+
+    fn sync_changes<'a>(
+        &'a self,
+        path: &'a DavPath,
+        token: &'a str,
+        deep: bool,
+        _credentials: &(),
+    ) -> FsFuture<'a, DavSyncChanges> {
+        DavFileSystem::sync_changes(self, path, token, deep)
+    }
+
+    fn version_control<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, ()> {
+        DavFileSystem::version_control(self, path)
+    }
+
+    fn version_history<'a>(
+        &'a self,
+        path: &'a DavPath,
+        _credentials: &(),
+    ) -> FsFuture<'a, Option<DavVersionHistory>> {
+        DavFileSystem::version_history(self, path)
+    }
+
+    fn checkout<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, ()> {
+        DavFileSystem::checkout(self, path)
+    }
+
+    fn checkin<'a>(
+        &'a self,
+        path: &'a DavPath,
+        keep_checked_out: bool,
+        _credentials: &(),
+    ) -> FsFuture<'a, DavVersion> {
+        DavFileSystem::checkin(self, path, keep_checked_out)
+    }
+
+    fn uncheckout<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, ()> {
+        DavFileSystem::uncheckout(self, path)
+    }
+
+    fn open_version<'a>(
+        &'a self,
+        path: &'a DavPath,
+        version: &'a str,
+        _credentials: &(),
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        DavFileSystem::open_version(self, path, version)
+    }
+
+    fn bind<'a>(
+        &'a self,
+        from: &'a DavPath,
+        to: &'a DavPath,
+        _credentials: &(),
+    ) -> FsFuture<'a, ()> {
+        DavFileSystem::bind(self, from, to)
+    }
+
+    fn unbind<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, ()> {
+        DavFileSystem::unbind(self, path)
+    }
+
+    fn resource_id<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, String> {
+        DavFileSystem::resource_id(self, path)
+    }
+
+    fn checksums<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, String> {
+        DavFileSystem::checksums(self, path)
+    }
+
+    fn bindings<'a>(&'a self, path: &'a DavPath, _credentials: &()) -> FsFuture<'a, Vec<DavPath>> {
+        DavFileSystem::bindings(self, path)
+    }
+
+    fn create_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+        _credentials: &(),
+    ) -> FsFuture<'a, ()> {
+        DavFileSystem::create_redirectref(self, path, redirect)
+    }
+
+    fn update_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+        _credentials: &(),
+    ) -> FsFuture<'a, ()> {
+        DavFileSystem::update_redirectref(self, path, redirect)
+    }
+
+    fn redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        _credentials: &(),
+    ) -> FsFuture<'a, DavRedirectRef> {
+        DavFileSystem::redirectref(self, path)
+    }
 }
 
 /// One directory entry (or child node).
@@ -761,6 +1370,17 @@ pub trait DavMetaData: Debug + Send + Sync + DynClone {
         notimplemented!("executable")
     }
 
+    /// Is file read-only (unix: the owner has no "w" mode bit). Default: `FsError::NotImplemented`.
+    fn readonly(&self) -> FsResult<bool> {
+        notimplemented!("readonly")
+    }
+
+    /// Is file hidden (unix: the name starts with a "."). Default: `FsError::NotImplemented`.
+    #[allow(unused_variables)]
+    fn hidden(&self, path: &DavPath) -> FsResult<bool> {
+        notimplemented!("hidden")
+    }
+
     // Is empty file
     fn is_empty(&self) -> bool {
         self.len() == 0
@@ -786,8 +1406,17 @@ pub struct OpenOptions {
     pub create_new: bool,
     /// write file total size
     pub size: Option<u64>,
-    /// checksum, owncloud extension
+    /// checksum, owncloud extension: the OC-Checksum header of the
+    /// request, as sent by the client.
     pub checksum: Option<String>,
+    /// checksums of the content, in the format of [`DavFileSystem::checksums`].
+    /// The file is only flushed if the content matches, and they are stored
+    /// with the file at that point. Writing without checksums clears them.
+    pub checksums: Option<String>,
+    /// replace the file atomically: the written data only becomes
+    /// visible when `flush()` is called. If the file is dropped before
+    /// that, the old content is kept.
+    pub atomic: bool,
 }
 
 impl OpenOptions {
@@ -802,6 +1431,8 @@ impl OpenOptions {
             create_new: false,
             size: None,
             checksum: None,
+            checksums: None,
+            atomic: false,
         }
     }
 
@@ -815,6 +1446,8 @@ impl OpenOptions {
             create_new: false,
             size: None,
             checksum: None,
+            checksums: None,
+            atomic: false,
         }
     }
 
@@ -828,6 +1461,8 @@ impl OpenOptions {
             create_new: false,
             size: None,
             checksum: None,
+            checksums: None,
+            atomic: false,
         }
     }
 }
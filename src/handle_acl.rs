@@ -0,0 +1,430 @@
+//
+// RFC 3744, WebDAV Access Control Protocol.
+//
+// The privilege checks for all methods, the ACL method, and the
+// principal resources that only exist in the access control backend.
+//
+use std::io::Cursor;
+use std::time::SystemTime;
+
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::acl::*;
+use crate::body::Body;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::util::{DavMethod, dav_xml_error_response};
+use crate::xmltree_ext::*;
+use crate::{DavError, DavInner, DavResult};
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // Does the principal have privilege `p` on `path`. Always true
+    // if there is no access control backend.
+    pub(crate) async fn has_privilege(&self, path: &DavPath, p: DavPrivilege) -> bool {
+        match self.acl {
+            Some(ref acl) => acl
+                .privileges(self.principal.as_deref(), path)
+                .await
+                .contains(p),
+            None => true,
+        }
+    }
+
+    // Check the privileges that `method` needs (RFC 3744 appendix B).
+    // Returns a 403 response with a DAV:need-privileges body if any are missing.
+    pub(crate) async fn check_privileges(
+        &self,
+        req: &Request<()>,
+        method: DavMethod,
+        path: &DavPath,
+    ) -> Option<Response<Body>> {
+        self.acl.as_ref()?;
+
+        let exists = |p: &DavPath| {
+            let p = p.clone();
+            async move { self.fs.metadata(&p, &self.credentials).await.is_ok() }
+        };
+        let dest = || {
+            req.headers()
+                .typed_get::<davheaders::Destination>()
+                .and_then(|d| DavPath::from_str_and_prefix(&d.0, &self.prefix).ok())
+        };
+
+        // an upload resource needs the privileges of the PUT that created it.
+        let method = match self.is_upload_request(req) {
+            true => DavMethod::Put,
+            false => method,
+        };
+
+        let mut needed = Vec::new();
+        match method {
+            DavMethod::Options => {}
+            DavMethod::Head
+            | DavMethod::Get
+            | DavMethod::PropFind
+            | DavMethod::Report
+            | DavMethod::Search => needed.push((path.clone(), DavPrivilege::Read)),
+            DavMethod::Put | DavMethod::Patch | DavMethod::Lock => {
+                if exists(path).await {
+                    needed.push((path.clone(), DavPrivilege::WriteContent));
+                } else {
+                    needed.push((path.parent(), DavPrivilege::Bind));
+                }
+            }
+            DavMethod::PropPatch | DavMethod::OrderPatch => {
+                needed.push((path.clone(), DavPrivilege::WriteProperties))
+            }
+            DavMethod::MkCol
+            | DavMethod::MkCalendar
+            | DavMethod::MkAddressbook
+            | DavMethod::MkRedirectRef => needed.push((path.parent(), DavPrivilege::Bind)),
+            DavMethod::Delete => needed.push((path.parent(), DavPrivilege::Unbind)),
+            DavMethod::Unlock => needed.push((path.clone(), DavPrivilege::Unlock)),
+            DavMethod::Acl => needed.push((path.clone(), DavPrivilege::WriteAcl)),
+            DavMethod::VersionControl
+            | DavMethod::Checkout
+            | DavMethod::Checkin
+            | DavMethod::Uncheckout
+            | DavMethod::UpdateRedirectRef => {
+                needed.push((path.clone(), DavPrivilege::WriteContent))
+            }
+            // a POST to a scheduling outbox sends scheduling messages.
+            DavMethod::Post => needed.push((path.clone(), DavPrivilege::WriteContent)),
+            DavMethod::Bind => needed.push((path.clone(), DavPrivilege::Bind)),
+            DavMethod::Unbind => needed.push((path.clone(), DavPrivilege::Unbind)),
+            // the source of a REBIND is in the body, checked in handle_bind.
+            DavMethod::Rebind => needed.push((path.clone(), DavPrivilege::Bind)),
+            DavMethod::Copy | DavMethod::Move => {
+                if method == DavMethod::Copy {
+                    needed.push((path.clone(), DavPrivilege::Read));
+                } else {
+                    needed.push((path.parent(), DavPrivilege::Unbind));
+                }
+                if let Some(dest) = dest() {
+                    if exists(&dest).await {
+                        needed.push((dest.parent(), DavPrivilege::Unbind));
+                    }
+                    needed.push((dest.parent(), DavPrivilege::Bind));
+                }
+            }
+        }
+
+        let mut missing = Vec::new();
+        for (p, privilege) in needed {
+            if !self.has_privilege(&p, privilege).await {
+                missing.push(Element::new3(
+                    "D",
+                    "resource",
+                    vec![
+                        Element::new2("D:href").text(p.with_prefix().as_url_string()),
+                        Element::new3(
+                            "D",
+                            "privilege",
+                            vec![Element::new3("D", privilege.name(), vec![])],
+                        ),
+                    ],
+                ));
+            }
+        }
+        if missing.is_empty() {
+            return None;
+        }
+        debug!(
+            "{method:?} {path}: principal {:?} lacks privileges",
+            self.principal
+        );
+        let body = element_to_string(Element::new3("D", "need-privileges", missing));
+        Some(dav_xml_error_response(StatusCode::FORBIDDEN, &body))
+    }
+
+    pub(crate) async fn handle_acl(
+        &self,
+        req: &Request<()>,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
+        let Some(ref acl) = self.acl else {
+            return Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED));
+        };
+
+        // resource must exist.
+        let path = self.path(req);
+        let meta = self.fs.metadata(&path, &self.credentials).await?;
+
+        // check the If and If-* headers.
+        let tokens = match if_match_get_tokens(
+            req,
+            Some(meta.as_ref()),
+            self.fs.as_ref(),
+            &self.ls,
+            &path,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(s.into()),
+        };
+
+        // if locked check if we hold that lock.
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            if let Err(_l) = locksystem
+                .check(&path, principal, false, false, &tokens)
+                .await
+            {
+                return Err(StatusCode::LOCKED.into());
+            }
+        }
+
+        let tree = Element::parse2(Cursor::new(xmldata))?;
+        if tree.name != "acl" || tree.namespace.as_deref() != Some("DAV:") {
+            return Err(DavError::XmlParseError);
+        }
+        let mut aces = Vec::new();
+        for elem in tree.child_elems_iter().filter(|e| e.name == "ace") {
+            match parse_ace(elem) {
+                Ok(ace) => aces.push(ace),
+                Err(e) => return Ok(acl_error_response(e)),
+            }
+        }
+
+        match acl.set_acl(&path, aces).await {
+            Ok(()) => {
+                let mut res = Response::new(Body::empty());
+                res.headers_mut().typed_insert(headers::ContentLength(0));
+                Ok(res)
+            }
+            Err(e) => Ok(acl_error_response(e)),
+        }
+    }
+
+    // A principal or principal collection that is not in the filesystem,
+    // but is known to the access control backend.
+    pub(crate) async fn principal_metadata(&self, path: &DavPath) -> Option<Box<dyn DavMetaData>> {
+        let href = path.with_prefix().as_url_string();
+        // calendar users are principals as well.
+        #[cfg(feature = "caldav")]
+        if self
+            .calendar_users
+            .iter()
+            .any(|u| same_href(&u.principal, &href))
+        {
+            return Some(Box::new(PrincipalMetaData));
+        }
+        let acl = self.acl.as_ref()?;
+        let mut hrefs = acl.principal_collections().await;
+        hrefs.extend(acl.principals().await);
+        if hrefs.iter().any(|h| same_href(h, &href)) {
+            Some(Box::new(PrincipalMetaData))
+        } else {
+            None
+        }
+    }
+
+    // The principals in the principal collection at `path`.
+    pub(crate) async fn principal_members(&self, path: &DavPath) -> Vec<DavPath> {
+        let Some(ref acl) = self.acl else {
+            return Vec::new();
+        };
+        let href = path.with_prefix().as_url_string();
+        let mut members = Vec::new();
+        for p in acl.principals().await {
+            let parent = match p.strip_suffix('/').unwrap_or(&p).rfind('/') {
+                Some(n) => &p[..=n],
+                None => continue,
+            };
+            if same_href(parent, &href)
+                && let Ok(mut mpath) = DavPath::from_str_and_prefix(&p, &self.prefix)
+            {
+                mpath.add_slash();
+                members.push(mpath);
+            }
+        }
+        members
+    }
+}
+
+// Metadata of a principal resource that is not in the filesystem.
+#[derive(Debug, Clone)]
+pub(crate) struct PrincipalMetaData;
+
+impl DavMetaData for PrincipalMetaData {
+    fn len(&self) -> u64 {
+        0
+    }
+    fn modified(&self) -> FsResult<SystemTime> {
+        Err(FsError::NotImplemented)
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "caldav")]
+    fn is_calendar(&self, _path: &DavPath) -> bool {
+        false
+    }
+    #[cfg(feature = "carddav")]
+    fn is_addressbook(&self, _path: &DavPath) -> bool {
+        false
+    }
+    fn is_symlink(&self) -> bool {
+        false
+    }
+}
+
+fn acl_error_response(e: DavAclError) -> Response<Body> {
+    match e.name() {
+        Some(name) => dav_xml_error_response(StatusCode::FORBIDDEN, &format!("<D:{name}/>")),
+        None => {
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::FORBIDDEN;
+            res
+        }
+    }
+}
+
+// parse an ACE from the body of the ACL method.
+fn parse_ace(elem: &Element) -> Result<DavAce, DavAclError> {
+    let mut principal = None;
+    let mut ace = DavAce::grant(DavAcePrincipal::All, DavPrivilegeSet::none());
+    for e in elem.child_elems_iter() {
+        match e.name.as_str() {
+            "principal" => principal = Some(parse_principal(e)?),
+            "invert" => return Err(DavAclError::NoInvert),
+            "protected" => return Err(DavAclError::NoProtectedAceConflict),
+            "inherited" => return Err(DavAclError::NoInheritedAceConflict),
+            "grant" | "deny" => {
+                ace.deny = e.name == "deny";
+                for p in e.child_elems_iter().filter(|p| p.name == "privilege") {
+                    for name in p.child_elems_iter() {
+                        match DavPrivilege::from_name(&name.name) {
+                            Some(p) if name.namespace.as_deref() == Some("DAV:") => {
+                                ace.privileges = ace.privileges | p.into();
+                            }
+                            _ => return Err(DavAclError::NotSupportedPrivilege),
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    ace.principal = principal.ok_or(DavAclError::RecognizedPrincipal)?;
+    Ok(ace)
+}
+
+fn parse_principal(elem: &Element) -> Result<DavAcePrincipal, DavAclError> {
+    let e = elem
+        .child_elems_iter()
+        .next()
+        .ok_or(DavAclError::RecognizedPrincipal)?;
+    let principal = match e.name.as_str() {
+        "href" => {
+            let href = e.get_text().unwrap_or_default();
+            DavAcePrincipal::Href(href.trim().to_string())
+        }
+        "all" => DavAcePrincipal::All,
+        "authenticated" => DavAcePrincipal::Authenticated,
+        "unauthenticated" => DavAcePrincipal::Unauthenticated,
+        "self" => DavAcePrincipal::SelfPrincipal,
+        "property" if e.get_child("owner").is_some() => DavAcePrincipal::Owner,
+        _ => return Err(DavAclError::RecognizedPrincipal),
+    };
+    Ok(principal)
+}
+
+// The value of the DAV:acl property.
+pub(crate) fn acl_element(aces: &[DavAce]) -> Element {
+    let aces = aces
+        .iter()
+        .map(|ace| {
+            let principal = match ace.principal {
+                DavAcePrincipal::Href(ref h) => Element::new2("D:href").text(h.clone()),
+                DavAcePrincipal::All => Element::new2("D:all"),
+                DavAcePrincipal::Authenticated => Element::new2("D:authenticated"),
+                DavAcePrincipal::Unauthenticated => Element::new2("D:unauthenticated"),
+                DavAcePrincipal::SelfPrincipal => Element::new2("D:self"),
+                DavAcePrincipal::Owner => {
+                    Element::new3("D", "property", vec![Element::new2("D:owner")])
+                }
+            };
+            let privileges = ace
+                .privileges
+                .to_vec()
+                .into_iter()
+                .map(|p| {
+                    Element::new3("D", "privilege", vec![Element::new3("D", p.name(), vec![])])
+                })
+                .collect();
+            let action = if ace.deny { "deny" } else { "grant" };
+            let mut children = vec![
+                Element::new3("D", "principal", vec![principal]),
+                Element::new3("D", action, privileges),
+            ];
+            if ace.protected {
+                children.push(Element::new2("D:protected"));
+            }
+            if let Some(ref href) = ace.inherited {
+                let href = Element::new2("D:href").text(href.clone());
+                children.push(Element::new3("D", "inherited", vec![href]));
+            }
+            Element::new3("D", "ace", children)
+        })
+        .collect();
+    Element::new3("D", "acl", aces)
+}
+
+// The value of the DAV:current-user-privilege-set property.
+pub(crate) fn privilege_set_element(privileges: DavPrivilegeSet) -> Element {
+    let privileges = privileges
+        .iter()
+        .map(|p| Element::new3("D", "privilege", vec![Element::new3("D", p.name(), vec![])]))
+        .collect();
+    Element::new3("D", "current-user-privilege-set", privileges)
+}
+
+// The value of the DAV:supported-privilege-set property.
+pub(crate) fn supported_privilege_set_element() -> Element {
+    fn supported(p: DavPrivilege, children: Vec<Element>) -> Element {
+        let mut v = vec![
+            Element::new3("D", "privilege", vec![Element::new3("D", p.name(), vec![])]),
+            Element::new2("D:description").text(p.name().replace('-', " ")),
+        ];
+        v.extend(children);
+        Element::new3("D", "supported-privilege", v)
+    }
+    let write = supported(
+        DavPrivilege::Write,
+        vec![
+            supported(DavPrivilege::WriteProperties, vec![]),
+            supported(DavPrivilege::WriteContent, vec![]),
+            supported(DavPrivilege::Bind, vec![]),
+            supported(DavPrivilege::Unbind, vec![]),
+        ],
+    );
+    let all = supported(
+        DavPrivilege::All,
+        vec![
+            supported(DavPrivilege::Read, vec![]),
+            write,
+            supported(DavPrivilege::Unlock, vec![]),
+            supported(DavPrivilege::ReadAcl, vec![]),
+            supported(DavPrivilege::ReadCurrentUserPrivilegeSet, vec![]),
+            supported(DavPrivilege::WriteAcl, vec![]),
+        ],
+    );
+    Element::new3("D", "supported-privilege-set", vec![all])
+}
+
+// serialize an element without the xml declaration.
+fn element_to_string(elem: Element) -> String {
+    let mut buf = Vec::new();
+    let mut emitter = xml::EmitterConfig::new()
+        .write_document_declaration(false)
+        .create_writer(&mut buf);
+    let _ = elem.write_ev(&mut emitter);
+    String::from_utf8(buf).unwrap_or_default()
+}
@@ -0,0 +1,261 @@
+//
+// RFC 5842, Binding Extensions to WebDAV.
+//
+// BIND, UNBIND and REBIND are all sent to a collection. The body names
+// the binding in that collection (DAV:segment) and, for BIND and REBIND,
+// the resource it should refer to (DAV:href).
+//
+use std::io::Cursor;
+
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::acl::DavPrivilege;
+use crate::body::Body;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::util::{DavMethod, dav_xml_error_response};
+use crate::xmltree_ext::*;
+use crate::{DavError, DavInner, DavResult};
+
+// The DAV:segment and DAV:href from the request body.
+fn parse_body(method: DavMethod, xmldata: &[u8]) -> DavResult<(String, Option<String>)> {
+    let root = Element::parse2(Cursor::new(xmldata))?;
+    let name = match method {
+        DavMethod::Bind => "bind",
+        DavMethod::Unbind => "unbind",
+        _ => "rebind",
+    };
+    if root.name != name || root.namespace.as_deref() != Some("DAV:") {
+        return Err(DavError::XmlParseError);
+    }
+    let text = |name: &str| {
+        root.child_elems_iter()
+            .find(|e| e.name == name)
+            .map(|e| e.get_text().unwrap_or_default().trim().to_string())
+    };
+    let segment = text("segment").ok_or(DavError::XmlParseError)?;
+    let href = text("href");
+    if method != DavMethod::Unbind && href.is_none() {
+        return Err(DavError::XmlParseError);
+    }
+    Ok((segment, href))
+}
+
+// The path of binding `segment` in collection `coll`.
+fn segment_path(coll: &DavPath, segment: &str) -> Option<DavPath> {
+    if segment.contains('/') {
+        return None;
+    }
+    // decode it, like the last segment of an URL path.
+    let seg = DavPath::new(&format!("/{segment}")).ok()?;
+    let name = seg.file_name_bytes();
+    if name.is_empty() || name == b"." || name == b".." {
+        return None;
+    }
+    let mut path = coll.clone();
+    path.push_segment(name);
+    Some(path)
+}
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // a DAV:href is either an absolute URL, or an absolute path.
+    fn href_to_path(&self, href: &str) -> Option<DavPath> {
+        let path = match href.starts_with('/') {
+            true => href.to_string(),
+            false => url::Url::parse(href).ok()?.path().to_string(),
+        };
+        DavPath::from_str_and_prefix(&path, &self.prefix).ok()
+    }
+
+    // a binding is gone, so are its locks and acl.
+    async fn binding_removed(&self, path: &DavPath) {
+        if let Some(ref locksystem) = self.ls {
+            locksystem.delete(path).await.ok();
+        }
+        if let Some(ref acl) = self.acl {
+            acl.delete(path).await;
+        }
+    }
+
+    pub(crate) async fn handle_bind(
+        &self,
+        req: &Request<()>,
+        method: DavMethod,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
+        // the request-uri must be a collection.
+        let mut path = self.path(req);
+        let meta = self.visible_metadata(&path).await?;
+        let (segment, href) = parse_body(method, xmldata)?;
+        if !meta.is_dir() {
+            let cond = match method {
+                DavMethod::Bind => "<D:bind-into-collection/>",
+                DavMethod::Unbind => "<D:unbind-from-collection/>",
+                _ => "<D:rebind-into-collection/>",
+            };
+            return Ok(dav_xml_error_response(StatusCode::FORBIDDEN, cond));
+        }
+        path.add_slash();
+
+        let Some(mut target) = segment_path(&path, &segment) else {
+            return Ok(dav_xml_error_response(
+                StatusCode::FORBIDDEN,
+                "<D:name-allowed/>",
+            ));
+        };
+
+        // BIND and REBIND: the resource to bind must exist.
+        let mut source = None;
+        if let Some(href) = href {
+            let src = self.href_to_path(&href);
+            let smeta = match src {
+                Some(ref src) => self.fs.metadata(src, &self.credentials).await.ok(),
+                None => None,
+            };
+            let (Some(mut src), Some(smeta)) = (src, smeta) else {
+                let cond = match method {
+                    DavMethod::Bind => "<D:bind-source-exists/>",
+                    _ => "<D:rebind-source-exists/>",
+                };
+                return Ok(dav_xml_error_response(StatusCode::CONFLICT, cond));
+            };
+            src.add_slash_if(smeta.is_dir());
+            target.add_slash_if(smeta.is_dir());
+            if method == DavMethod::Rebind && src.as_bytes() == b"/" {
+                return Ok(dav_xml_error_response(
+                    StatusCode::FORBIDDEN,
+                    "<D:protected-source-url-deletion-allowed/>",
+                ));
+            }
+            if src == target {
+                return Err(StatusCode::FORBIDDEN.into());
+            }
+            source = Some(src);
+        }
+
+        // the binding that is removed (UNBIND) or might be replaced.
+        let tmeta = self.fs.symlink_metadata(&target, &self.credentials).await;
+        if method == DavMethod::Unbind {
+            match tmeta {
+                Ok(ref m) => target.add_slash_if(m.is_dir()),
+                Err(_) => {
+                    return Ok(dav_xml_error_response(
+                        StatusCode::CONFLICT,
+                        "<D:unbind-source-exists/>",
+                    ));
+                }
+            }
+        }
+        let exists = method != DavMethod::Unbind && tmeta.is_ok();
+        let overwrite = req
+            .headers()
+            .typed_get::<davheaders::Overwrite>()
+            .is_none_or(|o| o.0);
+        if exists && !overwrite {
+            return Ok(dav_xml_error_response(
+                StatusCode::PRECONDITION_FAILED,
+                "<D:can-overwrite/>",
+            ));
+        }
+
+        // REBIND also removes the binding of the source.
+        if let Some(ref src) = source
+            && method == DavMethod::Rebind
+            && !self
+                .has_privilege(&src.parent(), DavPrivilege::Unbind)
+                .await
+        {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+
+        // check the If and If-* headers.
+        let tokens = match if_match_get_tokens(
+            req,
+            Some(meta.as_ref()),
+            self.fs.as_ref(),
+            &self.ls,
+            &path,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(s.into()),
+        };
+
+        // if locked check if we hold that lock.
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            let mut check = vec![&target];
+            if method == DavMethod::Rebind {
+                check.extend(source.as_ref());
+            }
+            for p in check {
+                if let Err(_l) = locksystem.check(p, principal, false, true, &tokens).await {
+                    return Err(StatusCode::LOCKED.into());
+                }
+            }
+        }
+
+        let mut res = Response::new(Body::empty());
+        res.headers_mut().typed_insert(headers::ContentLength(0));
+
+        if method == DavMethod::Unbind {
+            self.fs.unbind(&target, &self.credentials).await?;
+            self.binding_removed(&target).await;
+            return Ok(res);
+        }
+
+        // a binding that replaces another one is made under a temporary
+        // name first: the old one is only removed once that worked.
+        let source = source.unwrap();
+        let mut dest = target.clone();
+        if exists {
+            dest = path.clone();
+            dest.push_segment(format!(".bind-{}", uuid::Uuid::new_v4()).as_bytes());
+            dest.add_slash_if(target.is_collection());
+        }
+        let result = match method {
+            DavMethod::Bind => self.fs.bind(&source, &dest, &self.credentials).await,
+            _ => self.fs.rename(&source, &dest, &self.credentials).await,
+        };
+        match result {
+            Ok(()) => {}
+            Err(FsError::Forbidden) | Err(FsError::NotImplemented) => {
+                return Ok(dav_xml_error_response(
+                    StatusCode::FORBIDDEN,
+                    "<D:binding-allowed/>",
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        if exists {
+            if let Err(e) = self.fs.unbind(&target, &self.credentials).await {
+                let _ = match method {
+                    DavMethod::Bind => self.fs.unbind(&dest, &self.credentials).await,
+                    _ => self.fs.rename(&dest, &source, &self.credentials).await,
+                };
+                return Err(e.into());
+            }
+            self.binding_removed(&target).await;
+            self.fs.rename(&dest, &target, &self.credentials).await?;
+        }
+        if method == DavMethod::Rebind {
+            if let Some(ref locksystem) = self.ls {
+                locksystem.delete(&source).await.ok();
+            }
+            if let Some(ref acl) = self.acl {
+                acl.rename(&source, &target).await;
+            }
+        }
+
+        if !exists {
+            *res.status_mut() = StatusCode::CREATED;
+        }
+        Ok(res)
+    }
+}
@@ -1,8 +1,13 @@
-use chrono::Utc;
+use bytes::{Buf, Bytes};
+use chrono::{DateTime, Duration, Utc};
 use futures_util::StreamExt;
 use headers::HeaderMapExt;
 use http::{Request, Response, StatusCode};
+use http_body::Body as HttpBody;
+use http_body_util::Full;
+use std::error::Error as StdError;
 use std::io::Cursor;
+use std::ops::ControlFlow;
 use xml::reader::{EventReader, XmlEvent};
 use xmltree::{Element, XMLNode};
 
@@ -11,17 +16,22 @@ use crate::errors::*;
 use crate::fs::*;
 use crate::{DavInner, DavResult};
 
+use crate::acl::DavPrivilege;
 use crate::async_stream::AsyncStream;
 use crate::caldav::*;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
 use crate::davpath::DavPath;
-use crate::handle_props::PropWriter;
+use crate::handle_props::{PropWriter, davprop_to_element};
+use crate::ical::{self, Component, DateTimeValue, Instance, Property, Timezones};
+use crate::util::dav_xml_error_response;
 
 impl<C: Clone + Send + Sync + 'static> DavInner<C> {
     /// Handle REPORT method for CalDAV and CardDAV
     ///
     /// This method detects the namespace of the request body and routes
     /// to the appropriate CalDAV or CardDAV handler.
-    pub(crate) async fn handle_report(
+    pub(crate) async fn handle_caldav_report(
         &self,
         req: &Request<()>,
         body: &[u8],
@@ -41,8 +51,12 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             CalDavReportType::CalendarQuery(query) => {
                 self.handle_calendar_query(&path, query).await
             }
-            CalDavReportType::CalendarMultiget { hrefs } => {
-                self.handle_calendar_multiget(hrefs).await
+            CalDavReportType::CalendarMultiget {
+                hrefs,
+                calendar_data,
+            } => {
+                self.handle_calendar_multiget(hrefs, calendar_data.as_ref())
+                    .await
             }
             CalDavReportType::FreeBusyQuery { time_range } => {
                 self.handle_freebusy_query(&path, time_range).await
@@ -116,6 +130,124 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         Ok(resp)
     }
 
+    /// Handle PUT of a calendar object resource
+    ///
+    /// A PUT into a calendar collection is checked against the
+    /// preconditions of RFC 4791, section 5.3.2.1 before it is stored.
+    /// Other PUTs go straight to `handle_put`.
+    pub(crate) async fn handle_calendar_put<ReqBody, ReqData, ReqError>(
+        self,
+        req: &Request<()>,
+        body: ReqBody,
+    ) -> DavResult<Response<Body>>
+    where
+        ReqBody: HttpBody<Data = ReqData, Error = ReqError>,
+        ReqData: Buf + Send + 'static,
+        ReqError: StdError + Send + Sync + 'static,
+    {
+        let path = self.path(req);
+        let Some(collection) = self.calendar_collection(&path).await else {
+            return self.handle_put(req, body).await;
+        };
+        // check the If and If-* headers, and the locks, before the data,
+        // so that a 412 or 423 is not hidden by a failed precondition.
+        let meta = self.fs.metadata(&path, &self.credentials).await;
+        let tokens = if_match_get_tokens(
+            req,
+            meta.as_ref().map(|v| v.as_ref()).ok(),
+            self.fs.as_ref(),
+            &self.ls,
+            &path,
+            &self.credentials,
+        );
+        let tokens = match tokens.await {
+            Ok(t) => t,
+            Err(s) => return Err(DavError::StatusClose(s)),
+        };
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            if let Err(_l) = locksystem
+                .check(&path, principal, false, false, &tokens)
+                .await
+            {
+                return Err(DavError::StatusClose(StatusCode::LOCKED));
+            }
+        }
+
+        if self.is_schedule_box(&collection) {
+            return Ok(calendar_put_failed("calendar-collection-location-ok", None));
+        }
+
+        // the whole object is needed to check it.
+        let max_size = DEFAULT_MAX_CALENDAR_RESOURCE_SIZE;
+        if req
+            .headers()
+            .typed_get::<headers::ContentLength>()
+            .is_some_and(|n| n.0 > max_size)
+        {
+            return Ok(calendar_put_failed("max-resource-size", None));
+        }
+        let data = match self.read_request(body, max_size as usize).await {
+            Ok(data) => data,
+            Err(DavError::Status(StatusCode::PAYLOAD_TOO_LARGE)) => {
+                return Ok(calendar_put_failed("max-resource-size", None));
+            }
+            Err(e) => return Err(e),
+        };
+
+        // a partial update (PATCH, or PUT with Content-Range) is checked
+        // by the object that it results in.
+        let partial = req.method() == http::Method::PATCH
+            || req.headers().contains_key(http::header::CONTENT_RANGE);
+        let mut updated = None;
+        if partial {
+            let old = self.read_file(&path).await.unwrap_or_default();
+            updated = updated_object(req, old.into_bytes(), &data);
+            if updated.is_none() {
+                // handle_put rejects the range.
+                return self.handle_put(req, Full::new(Bytes::from(data))).await;
+            }
+        }
+        let object = updated.as_deref().unwrap_or(&data);
+        if object.len() as u64 > max_size {
+            return Ok(calendar_put_failed("max-resource-size", None));
+        }
+
+        // the parser that the reports use decides what is valid.
+        let calendar = std::str::from_utf8(object)
+            .ok()
+            .and_then(ical::parse_calendar);
+        let Some(calendar) = calendar else {
+            return Ok(calendar_put_failed("valid-calendar-data", None));
+        };
+        let supported = calendar_components(self.fs.as_ref(), &collection, &self.credentials).await;
+        let uid = match calendar_object_uid(&calendar, &supported) {
+            Ok(uid) => uid,
+            Err(condition) => return Ok(calendar_put_failed(condition, None)),
+        };
+
+        // a UID is used by one object in the collection, and an
+        // object keeps its UID when it is replaced.
+        for (item_path, _, content) in self.read_calendar_objects(&collection).await? {
+            let same_uid = extract_calendar_uid(&content).is_some_and(|u| u == uid);
+            if same_uid != (item_path == path) {
+                return Ok(calendar_put_failed("no-uid-conflict", Some(&item_path)));
+            }
+        }
+
+        self.handle_put(req, Full::new(Bytes::from(data))).await
+    }
+
+    // The calendar collection that `path` would be a member of.
+    async fn calendar_collection(&self, path: &DavPath) -> Option<DavPath> {
+        if path.is_collection() {
+            return None;
+        }
+        let parent = path.parent();
+        let meta = self.fs.metadata(&parent, &self.credentials).await.ok()?;
+        (meta.is_dir() && meta.is_calendar(&parent)).then_some(parent)
+    }
+
     fn parse_report_request(&self, body: &[u8]) -> DavResult<CalDavReportType> {
         if body.is_empty() {
             return Err(DavError::StatusClose(StatusCode::BAD_REQUEST));
@@ -178,7 +310,11 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 }
                 "calendar-multiget" => {
                     let hrefs = self.parse_calendar_multiget(root)?;
-                    Ok(CalDavReportType::CalendarMultiget { hrefs })
+                    let calendar_data = self.parse_report_calendar_data(root)?;
+                    Ok(CalDavReportType::CalendarMultiget {
+                        hrefs,
+                        calendar_data,
+                    })
                 }
                 "free-busy-query" => {
                     let time_range = self.parse_freebusy_query(root)?;
@@ -196,6 +332,8 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             comp_filter: None,
             time_range: None,
             properties: Vec::new(),
+            timezone: None,
+            calendar_data: self.parse_report_calendar_data(root)?,
         };
 
         for child in &root.children {
@@ -211,6 +349,9 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                             }
                         }
                     }
+                    "timezone" => {
+                        query.timezone = elem.get_text().map(|t| t.into_owned());
+                    }
                     "prop" => {
                         // Parse requested properties
                         for prop_child in &elem.children {
@@ -296,6 +437,37 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     "text-match" => {
                         filter.text_match = Some(self.parse_text_match(child_elem)?);
                     }
+                    "param-filter" => {
+                        filter
+                            .param_filters
+                            .push(self.parse_param_filter(child_elem)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_param_filter(&self, elem: &Element) -> DavResult<ParameterFilter> {
+        let name = elem
+            .attributes
+            .get("name")
+            .ok_or(DavError::StatusClose(StatusCode::BAD_REQUEST))?
+            .clone();
+
+        let mut filter = ParameterFilter::new(name);
+
+        for child in &elem.children {
+            if let XMLNode::Element(child_elem) = child {
+                match child_elem.name.as_str() {
+                    "is-not-defined" => {
+                        filter.is_not_defined = true;
+                    }
+                    "text-match" => {
+                        filter.text_match = Some(self.parse_text_match(child_elem)?);
+                    }
                     _ => {}
                 }
             }
@@ -336,6 +508,94 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         })
     }
 
+    // The CALDAV:calendar-data element in the requested properties.
+    fn parse_report_calendar_data(&self, root: &Element) -> DavResult<Option<CalendarDataRequest>> {
+        let Some(elem) = root
+            .get_child("prop")
+            .and_then(|prop| prop.get_child("calendar-data"))
+        else {
+            return Ok(None);
+        };
+
+        let mut request = CalendarDataRequest::default();
+        for child in &elem.children {
+            if let XMLNode::Element(child_elem) = child {
+                match child_elem.name.as_str() {
+                    "comp" => {
+                        request.comp = Some(self.parse_comp_selection(child_elem)?);
+                    }
+                    "expand" => {
+                        request.expand = Some(self.parse_limit_range(child_elem)?);
+                    }
+                    "limit-recurrence-set" => {
+                        request.limit_recurrence_set = Some(self.parse_limit_range(child_elem)?);
+                    }
+                    "limit-freebusy-set" => {
+                        request.limit_freebusy_set = Some(self.parse_limit_range(child_elem)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Some(request))
+    }
+
+    fn parse_comp_selection(&self, elem: &Element) -> DavResult<CompSelection> {
+        let name = elem
+            .attributes
+            .get("name")
+            .ok_or(DavError::StatusClose(StatusCode::BAD_REQUEST))?
+            .clone();
+
+        let mut selection = CompSelection {
+            name,
+            ..CompSelection::default()
+        };
+
+        for child in &elem.children {
+            if let XMLNode::Element(child_elem) = child {
+                match child_elem.name.as_str() {
+                    "allprop" => {
+                        selection.all_props = true;
+                    }
+                    "prop" => {
+                        let name = child_elem
+                            .attributes
+                            .get("name")
+                            .ok_or(DavError::StatusClose(StatusCode::BAD_REQUEST))?;
+                        let novalue = child_elem
+                            .attributes
+                            .get("novalue")
+                            .is_some_and(|v| v == "yes");
+                        selection.props.push((name.clone(), novalue));
+                    }
+                    "allcomp" => {
+                        selection.all_comps = true;
+                    }
+                    "comp" => {
+                        selection.comps.push(self.parse_comp_selection(child_elem)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(selection)
+    }
+
+    // expand, limit-recurrence-set and limit-freebusy-set need both a start and an end.
+    fn parse_limit_range(&self, elem: &Element) -> DavResult<TimeRange> {
+        let time_range = self.parse_time_range(elem)?;
+        match Range::new(&time_range) {
+            Some(Range {
+                start: Some(_),
+                end: Some(_),
+            }) => Ok(time_range),
+            _ => Err(DavError::StatusClose(StatusCode::BAD_REQUEST)),
+        }
+    }
+
     fn parse_calendar_multiget(&self, root: &Element) -> DavResult<Vec<String>> {
         let mut hrefs = Vec::new();
 
@@ -371,6 +631,44 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         path: &DavPath,
         query: CalendarQuery,
     ) -> DavResult<Response<Body>> {
+        if let Some(ref filter) = query.comp_filter {
+            let res = match filter.name.eq_ignore_ascii_case("VCALENDAR") {
+                true => check_comp_filter(filter),
+                false => Err("valid-filter"),
+            };
+            if let Err(condition) = res {
+                return Ok(dav_xml_error_response(
+                    StatusCode::FORBIDDEN,
+                    &format!(r#"<C:{condition} xmlns:C="{NS_CALDAV_URI}"/>"#),
+                ));
+            }
+        }
+
+        let mut results = Vec::new();
+        for (item_path, etag, content) in self.read_calendar_objects(path).await? {
+            // members we cannot read are left out.
+            if !self.has_privilege(&item_path, DavPrivilege::Read).await {
+                continue;
+            }
+            if self.matches_query(&content, &query) {
+                let content = match query.calendar_data {
+                    Some(ref request) => calendar_data(&content, request),
+                    None => content,
+                };
+                results.push((item_path, etag, content));
+            }
+        }
+
+        // Generate multistatus response
+        self.generate_calendar_multiget_response(results, Vec::new())
+            .await
+    }
+
+    // The calendar objects in a collection: their path, etag and content.
+    pub(crate) async fn read_calendar_objects(
+        &self,
+        path: &DavPath,
+    ) -> DavResult<Vec<(DavPath, String, String)>> {
         // Get directory listing
         let stream = self
             .fs
@@ -398,11 +696,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                             && is_calendar_data(&data)
                         {
                             let content = String::from_utf8_lossy(&data);
-
-                            if self.matches_query(&content, &query) {
-                                results.push((item_path.clone(), etag, content.to_string()));
-                                continue;
-                            }
+                            results.push((item_path, etag, content.to_string()));
                         }
                     }
                 }
@@ -410,17 +704,27 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         }
 
-        // Generate multistatus response
-        self.generate_calendar_multiget_response(results, Vec::new())
-            .await
+        Ok(results)
     }
 
-    async fn handle_calendar_multiget(&self, hrefs: Vec<String>) -> DavResult<Response<Body>> {
+    async fn handle_calendar_multiget(
+        &self,
+        hrefs: Vec<String>,
+        calendar_data_request: Option<&CalendarDataRequest>,
+    ) -> DavResult<Response<Body>> {
         let mut results = Vec::new();
-        let mut missing_hrefs: Vec<String> = Vec::new();
+        let mut missing_hrefs: Vec<(String, StatusCode)> = Vec::new();
 
         for href in &hrefs {
-            if let Ok(item_path) = DavPath::from_str_and_prefix(href, &self.prefix)
+            let item_path = DavPath::from_str_and_prefix(href, &self.prefix);
+            // resources we cannot read are reported, but not returned.
+            if let Ok(ref item_path) = item_path
+                && !self.has_privilege(item_path, DavPrivilege::Read).await
+            {
+                missing_hrefs.push((href.clone(), StatusCode::FORBIDDEN));
+                continue;
+            }
+            if let Ok(item_path) = item_path
                 && let Ok(mut file) = self
                     .fs
                     .open(&item_path, OpenOptions::read(), &self.credentials)
@@ -431,11 +735,15 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             {
                 let etag = metadata.etag().unwrap_or_default().to_string();
                 let content = String::from_utf8_lossy(&data);
-                results.push((item_path, etag, content.to_string()));
+                let content = match calendar_data_request {
+                    Some(request) => calendar_data(&content, request),
+                    None => content.to_string(),
+                };
+                results.push((item_path, etag, content));
                 continue;
             }
 
-            missing_hrefs.push(href.clone());
+            missing_hrefs.push((href.clone(), StatusCode::NOT_FOUND));
         }
 
         self.generate_calendar_multiget_response(results, missing_hrefs)
@@ -444,23 +752,41 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
 
     async fn handle_freebusy_query(
         &self,
-        _: &DavPath,
+        path: &DavPath,
         time_range: TimeRange,
     ) -> DavResult<Response<Body>> {
-        //TODO: freebusy implementation
-        // For now, return an empty freebusy response
-        // A full implementation would analyze calendar events and generate freebusy information
+        let Some(Range {
+            start: Some(start),
+            end: Some(end),
+        }) = Range::new(&time_range)
+        else {
+            return Err(DavError::StatusClose(StatusCode::BAD_REQUEST));
+        };
+
+        let mut periods = Vec::new();
+        for (_, _, content) in self.read_calendar_objects(path).await? {
+            if let Some(calendar) = ical::parse_calendar(&content) {
+                busy_periods(&calendar, (start, end), &mut periods);
+            }
+        }
 
-        let freebusy_data = format!(
+        let mut freebusy_data = format!(
             "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//DAV-SERVER//CalDAV//EN\r\n\
-             BEGIN:VFREEBUSY\r\nUID:{}\r\nDTSTAMP:{}Z\r\n\
-             DTSTART:{}\r\nDTEND:{}\r\n\
-             END:VFREEBUSY\r\nEND:VCALENDAR\r\n",
+             BEGIN:VFREEBUSY\r\nUID:{}\r\nDTSTAMP:{}\r\n\
+             DTSTART:{}\r\nDTEND:{}\r\n",
             uuid::Uuid::new_v4(),
-            Utc::now().format("%Y%m%dT%H%M%S"),
-            time_range.start.as_deref().unwrap_or("20000101T000000Z"),
-            time_range.end.as_deref().unwrap_or("20991231T235959Z")
+            ical::format_utc(Utc::now()),
+            ical::format_utc(start),
+            ical::format_utc(end),
         );
+        for (fbtype, (start, end)) in merge_periods(periods) {
+            freebusy_data.push_str(&format!(
+                "FREEBUSY;FBTYPE={fbtype}:{}/{}\r\n",
+                ical::format_utc(start),
+                ical::format_utc(end)
+            ));
+        }
+        freebusy_data.push_str("END:VFREEBUSY\r\nEND:VCALENDAR\r\n");
 
         let mut resp = Response::new(Body::from(freebusy_data));
         resp.headers_mut().insert(
@@ -471,24 +797,24 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
     }
 
     fn matches_query(&self, content: &str, query: &CalendarQuery) -> bool {
-        // Simple implementation - a full implementation would parse the iCalendar
-        // and apply all the filters properly
-
-        if let Some(ref comp_filter) = query.comp_filter
-            && !comp_filter.name.is_empty()
-            && !content.contains(&format!("BEGIN:{}", comp_filter.name))
-        {
-            false
-        } else {
-            true
+        let Some(ref filter) = query.comp_filter else {
+            return true;
+        };
+        let Some(calendar) = ical::parse_calendar(content) else {
+            return false;
+        };
+        let mut tzs = Timezones::new(&calendar);
+        if let Some(ref timezone) = query.timezone {
+            tzs.set_floating(timezone);
         }
+        comp_filter_matches(std::slice::from_ref(&calendar), None, filter, &tzs)
     }
 
     #[cfg(feature = "caldav")]
     async fn generate_calendar_multiget_response(
         &self,
         results: Vec<(DavPath, String, String)>,
-        missing_hrefs: Vec<String>,
+        missing_hrefs: Vec<(String, StatusCode)>,
     ) -> DavResult<Response<Body>> {
         let mut resp = Response::new(Body::empty());
 
@@ -508,6 +834,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             Vec::new(),
             self.fs.clone(),
             self.ls.as_ref(),
+            self.acl.as_ref(),
             self.principal.clone(),
             self.credentials.clone(),
             &empty_path,
@@ -520,8 +847,8 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 pw.write_calendar_data_response(&href, &etag, &calendar_data)?;
             }
 
-            for missing_href in missing_hrefs {
-                pw.write_calendar_not_found_response(&missing_href)?;
+            for (missing_href, status) in missing_hrefs {
+                pw.write_calendar_status_response(&missing_href, status)?;
             }
 
             pw.close().await?;
@@ -532,3 +859,829 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         Ok(resp)
     }
 }
+
+// A failed precondition of a PUT. The body might not have been read.
+fn calendar_put_failed(condition: &str, href: Option<&DavPath>) -> Response<Body> {
+    let body = match href {
+        Some(href) => format!(
+            r#"<C:{condition} xmlns:C="{NS_CALDAV_URI}"><D:href>{}</D:href></C:{condition}>"#,
+            href.with_prefix().as_url_string()
+        ),
+        None => format!(r#"<C:{condition} xmlns:C="{NS_CALDAV_URI}"/>"#),
+    };
+    let mut res = dav_xml_error_response(StatusCode::FORBIDDEN, &body);
+    res.headers_mut().typed_insert(headers::Connection::close());
+    res
+}
+
+// The UID of a calendar object resource (RFC 4791, section 4.1): it has
+// components of one supported type, all with the same UID, and no METHOD.
+fn calendar_object_uid(
+    calendar: &Component,
+    supported: &[CalendarComponentType],
+) -> Result<String, &'static str> {
+    let comps: Vec<_> = calendar
+        .components
+        .iter()
+        .filter(|c| c.name != "VTIMEZONE")
+        .collect();
+    let Some(first) = comps.first() else {
+        return Err("valid-calendar-object-resource");
+    };
+    if calendar.property("METHOD").is_some() || comps.iter().any(|c| c.name != first.name) {
+        return Err("valid-calendar-object-resource");
+    }
+    if !supported.iter().any(|t| t.as_str() == first.name) {
+        return Err("supported-calendar-component");
+    }
+    let uid = |c: &Component| c.property("UID").map(|p| p.value.clone());
+    match uid(first) {
+        Some(u) if comps.iter().all(|c| uid(c).as_ref() == Some(&u)) => Ok(u),
+        _ => Err("valid-calendar-object-resource"),
+    }
+}
+
+// What a calendar object looks like after a partial update: `data` written
+// at the start of the X-Update-Range (PATCH) or Content-Range (PUT). None
+// if there is no valid range.
+fn updated_object(req: &Request<()>, mut object: Vec<u8>, data: &[u8]) -> Option<Vec<u8>> {
+    let start = match req.method() == http::Method::PATCH {
+        true => match req.headers().typed_get::<davheaders::XUpdateRange>()? {
+            davheaders::XUpdateRange::FromTo(b, _) | davheaders::XUpdateRange::AllFrom(b) => b,
+            davheaders::XUpdateRange::Last(n) => (object.len() as u64).saturating_sub(n),
+            davheaders::XUpdateRange::Append => object.len() as u64,
+        },
+        false => {
+            req.headers()
+                .typed_get::<headers::ContentRange>()?
+                .bytes_range()?
+                .0
+        }
+    };
+    let start = usize::try_from(start).ok()?;
+    let end = start.checked_add(data.len())?;
+    if object.len() < end {
+        object.resize(end, 0);
+    }
+    object[start..end].copy_from_slice(data);
+    Some(object)
+}
+
+// The component types that a calendar collection takes: the ones in the
+// CALDAV:supported-calendar-component-set it was created with, or else
+// all that are supported.
+pub(crate) async fn calendar_components<C: Clone + Send + Sync + 'static>(
+    fs: &dyn GuardedFileSystem<C>,
+    collection: &DavPath,
+    credentials: &C,
+) -> Vec<CalendarComponentType> {
+    let props = fs.get_props(collection, true, credentials).await;
+    let set = props.unwrap_or_default().into_iter().find(|p| {
+        p.namespace.as_deref() == Some(NS_CALDAV_URI)
+            && p.name == "supported-calendar-component-set"
+    });
+    let components: Vec<_> = set
+        .map(davprop_to_element)
+        .iter()
+        .flat_map(|set| set.children.iter())
+        .filter_map(|node| node.as_element())
+        .filter(|comp| comp.name == "comp")
+        .filter_map(|comp| comp.attributes.get("name"))
+        .filter_map(|name| CalendarComponentType::from_name(name))
+        .collect();
+    match components.is_empty() {
+        true => CalendarProperties::default().supported_components,
+        false => components,
+    }
+}
+
+// The CALDAV:supported-collation and CALDAV:valid-filter preconditions.
+fn check_comp_filter(filter: &ComponentFilter) -> Result<(), &'static str> {
+    if filter
+        .time_range
+        .as_ref()
+        .is_some_and(|tr| Range::new(tr).is_none())
+    {
+        return Err("valid-filter");
+    }
+    for pf in &filter.prop_filters {
+        if pf
+            .time_range
+            .as_ref()
+            .is_some_and(|tr| Range::new(tr).is_none())
+        {
+            return Err("valid-filter");
+        }
+        let param_matches = pf
+            .param_filters
+            .iter()
+            .filter_map(|p| p.text_match.as_ref());
+        for tm in pf.text_match.iter().chain(param_matches) {
+            if !matches!(
+                tm.collation.as_deref(),
+                None | Some("i;ascii-casemap") | Some("i;octet")
+            ) {
+                return Err("supported-collation");
+            }
+        }
+    }
+    filter.comp_filters.iter().try_for_each(check_comp_filter)
+}
+
+// A CALDAV:time-range. A missing start or end is unbounded.
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+impl Range {
+    fn new(tr: &TimeRange) -> Option<Range> {
+        let parse = |t: &Option<String>| match t {
+            Some(t) => ical::parse_utc(t).map(Some),
+            None => Some(None),
+        };
+        let range = Range {
+            start: parse(&tr.start)?,
+            end: parse(&tr.end)?,
+        };
+        (range.start.is_some() || range.end.is_some()).then_some(range)
+    }
+
+    fn starts_before(&self, t: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|s| s < t)
+    }
+
+    fn starts_at_or_before(&self, t: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|s| s <= t)
+    }
+
+    fn ends_after(&self, t: DateTime<Utc>) -> bool {
+        self.end.is_none_or(|e| e > t)
+    }
+
+    fn ends_at_or_after(&self, t: DateTime<Utc>) -> bool {
+        self.end.is_none_or(|e| e >= t)
+    }
+}
+
+// A comp-filter matches if any of the components with its name does
+// (RFC 4791, section 9.7.1).
+fn comp_filter_matches(
+    comps: &[Component],
+    parent: Option<&Component>,
+    filter: &ComponentFilter,
+    tzs: &Timezones,
+) -> bool {
+    let mut named = comps
+        .iter()
+        .filter(|c| c.name.eq_ignore_ascii_case(&filter.name))
+        .peekable();
+    if filter.is_not_defined {
+        return named.peek().is_none();
+    }
+    named.any(|comp| {
+        filter
+            .time_range
+            .as_ref()
+            .and_then(Range::new)
+            .is_none_or(|range| comp_in_range(comp, comps, parent, range, tzs))
+            && filter
+                .prop_filters
+                .iter()
+                .all(|pf| prop_filter_matches(comp, pf, tzs))
+            && filter
+                .comp_filters
+                .iter()
+                .all(|cf| comp_filter_matches(&comp.components, Some(comp), cf, tzs))
+    })
+}
+
+// RFC 4791, section 9.7.2.
+fn prop_filter_matches(comp: &Component, filter: &PropertyFilter, tzs: &Timezones) -> bool {
+    let name = filter.name.to_ascii_uppercase();
+    let mut props = comp.properties_named(&name).peekable();
+    if filter.is_not_defined {
+        return props.peek().is_none();
+    }
+    props.any(|prop| {
+        filter
+            .time_range
+            .as_ref()
+            .and_then(Range::new)
+            .is_none_or(|range| prop_in_range(prop, range, tzs))
+            && filter
+                .text_match
+                .as_ref()
+                .is_none_or(|tm| text_matches(&prop.text(), tm))
+            && filter
+                .param_filters
+                .iter()
+                .all(|pf| param_filter_matches(prop, pf))
+    })
+}
+
+// RFC 4791, section 9.7.3.
+fn param_filter_matches(prop: &Property, filter: &ParameterFilter) -> bool {
+    let name = filter.name.to_ascii_uppercase();
+    let mut params = prop.params_named(&name).peekable();
+    if filter.is_not_defined {
+        return params.peek().is_none();
+    }
+    params.any(|value| {
+        filter
+            .text_match
+            .as_ref()
+            .is_none_or(|tm| text_matches(value, tm))
+    })
+}
+
+// RFC 4791, section 9.7.5. The collation has been checked already.
+fn text_matches(value: &str, tm: &TextMatch) -> bool {
+    let (value, text) = match tm.collation.as_deref() {
+        Some("i;octet") => (value.to_string(), tm.text.clone()),
+        _ => (value.to_ascii_lowercase(), tm.text.to_ascii_lowercase()),
+    };
+    let found = match tm.match_type.as_deref() {
+        Some("equals") => value == text,
+        Some("starts-with") => value.starts_with(&text),
+        Some("ends-with") => value.ends_with(&text),
+        _ => value.contains(&text),
+    };
+    found != tm.negate_condition
+}
+
+// A property value that is a DATE or DATE-TIME (RFC 4791, section 9.9).
+fn prop_in_range(prop: &Property, range: Range, tzs: &Timezones) -> bool {
+    prop.value.split(',').any(|value| {
+        let Some(t) = DateTimeValue::parse(value, prop.param("TZID")) else {
+            return false;
+        };
+        let start = tzs.to_utc(&t);
+        match t.date {
+            true => range.starts_before(start + Duration::days(1)) && range.ends_after(start),
+            false => range.starts_at_or_before(start) && range.ends_after(start),
+        }
+    })
+}
+
+// Does any instance of a component overlap with the time range
+// (RFC 4791, section 9.9).
+fn comp_in_range(
+    comp: &Component,
+    siblings: &[Component],
+    parent: Option<&Component>,
+    range: Range,
+    tzs: &Timezones,
+) -> bool {
+    match comp.name.as_str() {
+        "VEVENT" | "VTODO" | "VJOURNAL" => {
+            if comp.property("DTSTART").is_none() {
+                return comp.name == "VTODO" && todo_overlaps(comp, None, range, tzs);
+            }
+            let overridden = overridden_instances(comp, siblings, tzs);
+            let from = instances_from(comp, range.start, Duration::zero(), tzs);
+            any_instance(comp, &overridden, (from, range.end), tzs, |inst| {
+                instance_overlaps(comp, &inst, range, tzs)
+            })
+        }
+        "VFREEBUSY" => freebusy_overlaps(comp, range, tzs),
+        "VALARM" => parent.is_some_and(|parent| alarm_overlaps(comp, parent, range, tzs)),
+        _ => false,
+    }
+}
+
+// An instance of a VEVENT, VTODO or VJOURNAL.
+fn instance_overlaps(comp: &Component, inst: &Instance, range: Range, tzs: &Timezones) -> bool {
+    match comp.name.as_str() {
+        "VEVENT" => event_overlaps(comp, inst, range, tzs),
+        "VTODO" => todo_overlaps(comp, Some(inst), range, tzs),
+        _ => journal_overlaps(inst, range),
+    }
+}
+
+// The instances of a recurring component that are replaced by another
+// component with the same UID and a RECURRENCE-ID.
+fn overridden_instances(
+    comp: &Component,
+    siblings: &[Component],
+    tzs: &Timezones,
+) -> Vec<DateTime<Utc>> {
+    if comp.property("RECURRENCE-ID").is_some() {
+        return Vec::new();
+    }
+    let uid = comp.property("UID").map(|p| p.value.as_str());
+    siblings
+        .iter()
+        .filter(|c| c.name == comp.name && c.property("UID").map(|p| p.value.as_str()) == uid)
+        .filter_map(|c| c.recurrence_id(tzs))
+        .collect()
+}
+
+// Where the instances of a component that can reach into a range that
+// starts at `start` begin: an instance that starts earlier has ended by
+// then. `reach` is how much further than its end an instance can reach;
+// the extra day covers all-day instances and changes of the UTC offset.
+fn instances_from(
+    comp: &Component,
+    start: Option<DateTime<Utc>>,
+    reach: Duration,
+    tzs: &Timezones,
+) -> Option<DateTime<Utc>> {
+    let dtstart = instance_time(comp, "DTSTART", None, tzs)?;
+    let length = ["DTEND", "DUE"]
+        .into_iter()
+        .filter_map(|name| Some(instance_time(comp, name, None, tzs)? - dtstart))
+        .chain(comp.duration())
+        .fold(Duration::zero(), Duration::max);
+    let lead = length
+        .checked_add(&reach)?
+        .checked_add(&Duration::days(1))?;
+    start?.checked_sub_signed(lead)
+}
+
+fn any_instance(
+    comp: &Component,
+    overridden: &[DateTime<Utc>],
+    window: (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    tzs: &Timezones,
+    mut f: impl FnMut(Instance) -> bool,
+) -> bool {
+    let mut found = false;
+    comp.for_each_instance(tzs, overridden, window, |inst| {
+        found = f(inst);
+        match found {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    });
+    found
+}
+
+// A date-time property of an instance: moved along with DTSTART.
+fn instance_time(
+    comp: &Component,
+    name: &str,
+    inst: Option<&Instance>,
+    tzs: &Timezones,
+) -> Option<DateTime<Utc>> {
+    let t = tzs.to_utc(&comp.date_time(name)?);
+    match (inst, comp.date_time("DTSTART")) {
+        (Some(inst), Some(start)) => Some(inst.start + (t - tzs.to_utc(&start))),
+        _ => Some(t),
+    }
+}
+
+// When an instance of a VEVENT (or VAVAILABILITY/AVAILABLE) ends.
+fn event_end(comp: &Component, inst: &Instance, tzs: &Timezones) -> DateTime<Utc> {
+    if let Some(end) = instance_time(comp, "DTEND", Some(inst), tzs) {
+        return end;
+    }
+    match comp.duration() {
+        Some(duration) => inst.start + duration,
+        None if inst.date => inst.start + Duration::days(1),
+        None => inst.start,
+    }
+}
+
+fn event_overlaps(comp: &Component, inst: &Instance, range: Range, tzs: &Timezones) -> bool {
+    let (start, end) = (inst.start, event_end(comp, inst, tzs));
+    if end > start || comp.property("DTEND").is_some() {
+        range.starts_before(end) && range.ends_after(start)
+    } else {
+        range.starts_at_or_before(start) && range.ends_after(start)
+    }
+}
+
+fn todo_overlaps(comp: &Component, inst: Option<&Instance>, range: Range, tzs: &Timezones) -> bool {
+    let start = inst.map(|inst| inst.start);
+    let due = instance_time(comp, "DUE", inst, tzs);
+    match (start, comp.duration(), due) {
+        (Some(start), Some(duration), _) => {
+            let end = start + duration;
+            range.starts_at_or_before(end)
+                && (range.ends_after(start) || range.ends_at_or_after(end))
+        }
+        (Some(start), None, Some(due)) => {
+            (range.starts_before(due) || range.starts_at_or_before(start))
+                && (range.ends_after(start) || range.ends_at_or_after(due))
+        }
+        (Some(start), None, None) => range.starts_at_or_before(start) && range.ends_after(start),
+        (None, _, Some(due)) => range.starts_before(due) && range.ends_at_or_after(due),
+        (None, _, None) => {
+            let completed = instance_time(comp, "COMPLETED", None, tzs);
+            let created = instance_time(comp, "CREATED", None, tzs);
+            match (completed, created) {
+                (Some(completed), Some(created)) => {
+                    (range.starts_at_or_before(created) || range.starts_at_or_before(completed))
+                        && (range.ends_at_or_after(created) || range.ends_at_or_after(completed))
+                }
+                (Some(completed), None) => {
+                    range.starts_at_or_before(completed) && range.ends_at_or_after(completed)
+                }
+                (None, Some(created)) => range.ends_after(created),
+                (None, None) => true,
+            }
+        }
+    }
+}
+
+fn journal_overlaps(inst: &Instance, range: Range) -> bool {
+    let start = inst.start;
+    match inst.date {
+        true => range.starts_before(start + Duration::days(1)) && range.ends_after(start),
+        false => range.starts_at_or_before(start) && range.ends_after(start),
+    }
+}
+
+fn freebusy_overlaps(comp: &Component, range: Range, tzs: &Timezones) -> bool {
+    let mut periods = comp
+        .properties_named("FREEBUSY")
+        .flat_map(|p| p.value.split(','))
+        .filter_map(|value| tzs.period(value, None))
+        .peekable();
+    if periods.peek().is_some() {
+        return periods.any(|(start, end)| range.starts_before(end) && range.ends_after(start));
+    }
+    match (
+        instance_time(comp, "DTSTART", None, tzs),
+        instance_time(comp, "DTEND", None, tzs),
+    ) {
+        (Some(start), Some(end)) => range.starts_at_or_before(end) && range.ends_after(start),
+        _ => false,
+    }
+}
+
+// An alarm is in the time range if it goes off in it, for any
+// instance of the component it belongs to.
+fn alarm_overlaps(alarm: &Component, parent: &Component, range: Range, tzs: &Timezones) -> bool {
+    let Some(trigger) = alarm.property("TRIGGER") else {
+        return false;
+    };
+    let repeat: i32 = alarm
+        .property("REPEAT")
+        .and_then(|p| p.value.trim().parse().ok())
+        .unwrap_or(0);
+    let interval = alarm.duration().unwrap_or_default();
+    let goes_off = |t: DateTime<Utc>| {
+        (0..=repeat.clamp(0, 1000)).any(|n| {
+            let at = t + interval * n;
+            range.starts_at_or_before(at) && range.ends_after(at)
+        })
+    };
+
+    if trigger
+        .param("VALUE")
+        .is_some_and(|v| v.eq_ignore_ascii_case("DATE-TIME"))
+    {
+        return DateTimeValue::from_property(trigger).is_some_and(|t| goes_off(tzs.to_utc(&t)));
+    }
+    let Some(offset) = ical::parse_duration(&trigger.value) else {
+        return false;
+    };
+    let related_end = trigger
+        .param("RELATED")
+        .is_some_and(|v| v.eq_ignore_ascii_case("END"));
+    let end_of = |inst: Option<&Instance>| {
+        instance_time(parent, "DTEND", inst, tzs)
+            .or_else(|| instance_time(parent, "DUE", inst, tzs))
+            .or_else(|| Some(inst?.start + parent.duration()?))
+            .or(inst.map(|inst| inst.start))
+    };
+
+    if parent.property("DTSTART").is_none() {
+        return end_of(None).is_some_and(|end| goes_off(end + offset));
+    }
+    // instances that start after this cannot have an alarm in the range.
+    let until = range.end.map(|end| end - offset);
+    let reach = offset.abs() + interval.abs() * repeat.clamp(0, 1000);
+    let from = instances_from(parent, range.start, reach, tzs);
+    any_instance(parent, &[], (from, until), tzs, |inst| {
+        let base = match related_end {
+            true => end_of(Some(&inst)).unwrap_or(inst.start),
+            false => inst.start,
+        };
+        goes_off(base + offset)
+    })
+}
+
+// A period of time, [start, end).
+pub(crate) type Period = (DateTime<Utc>, DateTime<Utc>);
+
+// The busy time in a calendar object, for the free-busy-query report
+// (RFC 4791, section 7.10), clipped to `range`.
+pub(crate) fn busy_periods(
+    calendar: &Component,
+    range: Period,
+    out: &mut Vec<(&'static str, Period)>,
+) {
+    let tzs = Timezones::new(calendar);
+    let has_value = |comp: &Component, name: &str, value: &str| {
+        comp.property(name)
+            .is_some_and(|p| p.value.trim().eq_ignore_ascii_case(value))
+    };
+    let mut push = |fbtype, (start, end): Period| {
+        let period = (start.max(range.0), end.min(range.1));
+        if period.0 < period.1 {
+            out.push((fbtype, period));
+        }
+    };
+
+    for comp in calendar.components_named("VEVENT") {
+        if has_value(comp, "TRANSP", "TRANSPARENT") || has_value(comp, "STATUS", "CANCELLED") {
+            continue;
+        }
+        let fbtype = match has_value(comp, "STATUS", "TENTATIVE") {
+            true => "BUSY-TENTATIVE",
+            false => "BUSY",
+        };
+        let overridden = overridden_instances(comp, &calendar.components, &tzs);
+        let from = instances_from(comp, Some(range.0), Duration::zero(), &tzs);
+        comp.for_each_instance(&tzs, &overridden, (from, Some(range.1)), |inst| {
+            push(fbtype, (inst.start, event_end(comp, &inst, &tzs)));
+            ControlFlow::Continue(())
+        });
+    }
+
+    // busy time that has been published already.
+    for comp in calendar.components_named("VFREEBUSY") {
+        for prop in comp.properties_named("FREEBUSY") {
+            let fbtype = match prop.param("FBTYPE").map(|t| t.to_ascii_uppercase()) {
+                Some(t) if t == "FREE" => continue,
+                Some(t) if t == "BUSY-TENTATIVE" => "BUSY-TENTATIVE",
+                Some(t) if t == "BUSY-UNAVAILABLE" => "BUSY-UNAVAILABLE",
+                _ => "BUSY",
+            };
+            for value in prop.value.split(',') {
+                if let Some(period) = tzs.period(value, None) {
+                    push(fbtype, period);
+                }
+            }
+        }
+    }
+
+    for (fbtype, period) in unavailable_periods(calendar, range, &tzs) {
+        push(fbtype, period);
+    }
+}
+
+// The time that VAVAILABILITY components (RFC 7953) mark as busy: the
+// parts of their period that are not covered by an AVAILABLE component.
+// Where they overlap, the one with the highest priority wins.
+fn unavailable_periods(
+    calendar: &Component,
+    range: Period,
+    tzs: &Timezones,
+) -> Vec<(&'static str, Period)> {
+    let mut availabilities: Vec<_> = calendar.components_named("VAVAILABILITY").collect();
+    // 1 is the highest priority, 0 (undefined) the lowest.
+    availabilities.sort_by_key(|comp| {
+        match comp
+            .property("PRIORITY")
+            .and_then(|p| p.value.trim().parse::<u32>().ok())
+        {
+            None | Some(0) => 10,
+            Some(p) => p,
+        }
+    });
+
+    let mut unavailable = Vec::new();
+    let mut claimed: Vec<Period> = Vec::new();
+    for comp in availabilities {
+        let start = instance_time(comp, "DTSTART", None, tzs);
+        let end =
+            instance_time(comp, "DTEND", None, tzs).or_else(|| Some(start? + comp.duration()?));
+        let period = (
+            start.map_or(range.0, |s| s.max(range.0)),
+            end.map_or(range.1, |e| e.min(range.1)),
+        );
+        if period.0 >= period.1 {
+            continue;
+        }
+        let region = subtract_periods(&[period], &claimed);
+        claimed.push(period);
+
+        let mut available = Vec::new();
+        for avail in comp.components_named("AVAILABLE") {
+            let overridden = overridden_instances(avail, &comp.components, tzs);
+            let from = instances_from(avail, Some(period.0), Duration::zero(), tzs);
+            avail.for_each_instance(tzs, &overridden, (from, Some(period.1)), |inst| {
+                available.push((inst.start, event_end(avail, &inst, tzs)));
+                ControlFlow::Continue(())
+            });
+        }
+        let busytype = match comp
+            .property("BUSYTYPE")
+            .map(|p| p.value.to_ascii_uppercase())
+        {
+            Some(t) if t == "BUSY" => "BUSY",
+            Some(t) if t == "BUSY-TENTATIVE" => "BUSY-TENTATIVE",
+            _ => "BUSY-UNAVAILABLE",
+        };
+        for period in subtract_periods(&region, &available) {
+            unavailable.push((busytype, period));
+        }
+    }
+    unavailable
+}
+
+// The parts of `periods` that are not in `minus`.
+fn subtract_periods(periods: &[Period], minus: &[Period]) -> Vec<Period> {
+    let mut result = periods.to_vec();
+    for &(m_start, m_end) in minus {
+        result = result
+            .into_iter()
+            .flat_map(|(start, end)| {
+                if m_end <= start || m_start >= end {
+                    return vec![(start, end)];
+                }
+                let mut parts = Vec::new();
+                if start < m_start {
+                    parts.push((start, m_start));
+                }
+                if m_end < end {
+                    parts.push((m_end, end));
+                }
+                parts
+            })
+            .collect();
+    }
+    result
+}
+
+// Merge overlapping periods of the same FBTYPE, and order them by time.
+pub(crate) fn merge_periods(
+    mut periods: Vec<(&'static str, Period)>,
+) -> Vec<(&'static str, Period)> {
+    periods.sort();
+    let mut merged: Vec<(&'static str, Period)> = Vec::new();
+    for (fbtype, (start, end)) in periods {
+        match merged.last_mut() {
+            Some((t, (_, last_end))) if *t == fbtype && start <= *last_end => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => merged.push((fbtype, (start, end))),
+        }
+    }
+    merged.sort_by_key(|(_, period)| *period);
+    merged
+}
+
+// The calendar data as asked for with CALDAV:calendar-data
+// (RFC 4791, section 9.6).
+fn calendar_data(content: &str, request: &CalendarDataRequest) -> String {
+    let whole = request.comp.is_none()
+        && request.expand.is_none()
+        && request.limit_recurrence_set.is_none()
+        && request.limit_freebusy_set.is_none();
+    let calendar = (!whole).then(|| ical::parse_calendar(content)).flatten();
+    let Some(mut calendar) = calendar else {
+        return content.to_string();
+    };
+    let tzs = Timezones::new(&calendar);
+    let range = |tr: &Option<TimeRange>| tr.as_ref().and_then(Range::new);
+
+    if let Some(range) = range(&request.expand) {
+        expand_calendar(&mut calendar, range, &tzs);
+    } else if let Some(range) = range(&request.limit_recurrence_set) {
+        limit_recurrence_set(&mut calendar, range, &tzs);
+    }
+    if let Some(range) = range(&request.limit_freebusy_set) {
+        limit_freebusy_set(&mut calendar, range, &tzs);
+    }
+    if let Some(ref selection) = request.comp
+        && selection.name.eq_ignore_ascii_case(&calendar.name)
+    {
+        select_parts(&mut calendar, selection);
+    }
+    calendar.to_ics()
+}
+
+// Replace recurring components by their instances in the range, each with
+// a RECURRENCE-ID. All date-times are converted to UTC, so the VTIMEZONE
+// components are left out.
+fn expand_calendar(calendar: &mut Component, range: Range, tzs: &Timezones) {
+    let components = std::mem::take(&mut calendar.components);
+    for comp in &components {
+        match comp.name.as_str() {
+            "VTIMEZONE" => {}
+            "VEVENT" | "VTODO" | "VJOURNAL" if comp.property("DTSTART").is_some() => {
+                let recurring =
+                    comp.property("RRULE").is_some() || comp.property("RDATE").is_some();
+                let overridden = overridden_instances(comp, &components, tzs);
+                let from = instances_from(comp, range.start, Duration::zero(), tzs);
+                comp.for_each_instance(tzs, &overridden, (from, range.end), |inst| {
+                    if instance_overlaps(comp, &inst, range, tzs) {
+                        let mut instance = comp.clone();
+                        if recurring {
+                            set_instance_times(&mut instance, comp, &inst, tzs);
+                        }
+                        instance.convert_to_utc(tzs);
+                        calendar.components.push(instance);
+                    }
+                    ControlFlow::Continue(())
+                });
+            }
+            _ => {
+                if comp.name != "VTODO" || todo_overlaps(comp, None, range, tzs) {
+                    let mut comp = comp.clone();
+                    comp.convert_to_utc(tzs);
+                    calendar.components.push(comp);
+                }
+            }
+        }
+    }
+}
+
+// Turn a copy of a recurring component into one of its instances.
+fn set_instance_times(
+    instance: &mut Component,
+    comp: &Component,
+    inst: &Instance,
+    tzs: &Timezones,
+) {
+    instance
+        .properties
+        .retain(|p| !matches!(p.name.as_str(), "RRULE" | "RDATE" | "EXDATE" | "EXRULE"));
+    instance.set_date_time("DTSTART", inst.start, inst.date);
+    instance.set_date_time("RECURRENCE-ID", inst.start, inst.date);
+    for name in ["DTEND", "DUE"] {
+        if let Some(t) = instance_time(comp, name, Some(inst), tzs) {
+            let date = comp.date_time(name).is_some_and(|t| t.date);
+            instance.set_date_time(name, t, date);
+        }
+    }
+}
+
+// Leave out the overridden instances that are not in the range.
+fn limit_recurrence_set(calendar: &mut Component, range: Range, tzs: &Timezones) {
+    calendar.components.retain(|comp| {
+        let Some(recurrence_id) = comp.recurrence_id(tzs) else {
+            return true;
+        };
+        (range.starts_at_or_before(recurrence_id) && range.ends_after(recurrence_id))
+            || comp_in_range(comp, &[], None, range, tzs)
+    });
+}
+
+// Leave out the FREEBUSY periods that are not in the range.
+fn limit_freebusy_set(calendar: &mut Component, range: Range, tzs: &Timezones) {
+    for comp in &mut calendar.components {
+        if comp.name != "VFREEBUSY" {
+            continue;
+        }
+        for prop in &mut comp.properties {
+            if prop.name != "FREEBUSY" {
+                continue;
+            }
+            let periods: Vec<_> = prop
+                .value
+                .split(',')
+                .filter(|value| {
+                    tzs.period(value, None).is_some_and(|(start, end)| {
+                        range.starts_before(end) && range.ends_after(start)
+                    })
+                })
+                .collect();
+            prop.value = periods.join(",");
+        }
+        comp.properties
+            .retain(|p| p.name != "FREEBUSY" || !p.value.is_empty());
+    }
+}
+
+// Keep only the properties and subcomponents in the CALDAV:comp selection.
+fn select_parts(comp: &mut Component, selection: &CompSelection) {
+    if !selection.all_props {
+        comp.properties.retain_mut(|prop| {
+            let found = selection
+                .props
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&prop.name));
+            match found {
+                Some((_, novalue)) => {
+                    if *novalue {
+                        prop.value.clear();
+                    }
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+    if !selection.all_comps {
+        comp.components.retain_mut(|sub| {
+            let found = selection
+                .comps
+                .iter()
+                .find(|s| s.name.eq_ignore_ascii_case(&sub.name));
+            match found {
+                Some(sub_selection) => {
+                    select_parts(sub, sub_selection);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
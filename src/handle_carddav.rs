@@ -10,25 +10,13 @@ use crate::errors::*;
 use crate::fs::*;
 use crate::{DavInner, DavResult};
 
+use crate::acl::DavPrivilege;
 use crate::async_stream::AsyncStream;
 use crate::carddav::*;
 use crate::davpath::DavPath;
 use crate::handle_props::PropWriter;
 
 impl<C: Clone + Send + Sync + 'static> DavInner<C> {
-    /// Handle REPORT method when only CardDAV is enabled (not CalDAV)
-    ///
-    /// When CalDAV is also enabled, the unified handle_report in handle_caldav.rs
-    /// is used instead, which delegates to handle_carddav_report for CardDAV requests.
-    #[cfg(not(feature = "caldav"))]
-    pub(crate) async fn handle_report(
-        &self,
-        req: &Request<()>,
-        body: &[u8],
-    ) -> DavResult<Response<Body>> {
-        self.handle_carddav_report(req, body).await
-    }
-
     /// Handle CardDAV REPORT method for addressbook-query and addressbook-multiget
     pub(crate) async fn handle_carddav_report(
         &self,
@@ -334,6 +322,11 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     let mut item_path = path.clone();
                     item_path.push_segment(&dirent.name());
 
+                    // members we cannot read are left out.
+                    if !self.has_privilege(&item_path, DavPrivilege::Read).await {
+                        continue;
+                    }
+
                     // Check if this is a vCard resource, and append content to result
                     if let Ok(mut file) = self
                         .fs
@@ -367,10 +360,18 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
 
     async fn handle_addressbook_multiget(&self, hrefs: Vec<String>) -> DavResult<Response<Body>> {
         let mut results = Vec::new();
-        let mut missing_hrefs: Vec<String> = Vec::new();
+        let mut missing_hrefs: Vec<(String, StatusCode)> = Vec::new();
 
         for href in &hrefs {
-            if let Ok(item_path) = DavPath::from_str_and_prefix(href, &self.prefix)
+            let item_path = DavPath::from_str_and_prefix(href, &self.prefix);
+            // resources we cannot read are reported, but not returned.
+            if let Ok(ref item_path) = item_path
+                && !self.has_privilege(item_path, DavPrivilege::Read).await
+            {
+                missing_hrefs.push((href.clone(), StatusCode::FORBIDDEN));
+                continue;
+            }
+            if let Ok(item_path) = item_path
                 && let Ok(mut file) = self
                     .fs
                     .open(&item_path, OpenOptions::read(), &self.credentials)
@@ -385,7 +386,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 continue;
             }
 
-            missing_hrefs.push(href.clone());
+            missing_hrefs.push((href.clone(), StatusCode::NOT_FOUND));
         }
 
         self.generate_addressbook_multiget_response(results, missing_hrefs)
@@ -459,7 +460,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
     async fn generate_addressbook_multiget_response(
         &self,
         results: Vec<(DavPath, String, String)>,
-        missing_hrefs: Vec<String>,
+        missing_hrefs: Vec<(String, StatusCode)>,
     ) -> DavResult<Response<Body>> {
         let mut resp = Response::new(Body::empty());
 
@@ -479,6 +480,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             Vec::new(),
             self.fs.clone(),
             self.ls.as_ref(),
+            self.acl.as_ref(),
             self.principal.clone(),
             self.credentials.clone(),
             &empty_path,
@@ -491,8 +493,8 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 pw.write_vcard_data_response(&href, &etag, &vcard_data)?;
             }
 
-            for missing_href in missing_hrefs {
-                pw.write_vcard_not_found_response(&missing_href)?;
+            for (missing_href, status) in missing_hrefs {
+                pw.write_vcard_status_response(&missing_href, status)?;
             }
 
             pw.close().await?;
@@ -0,0 +1,278 @@
+//
+// Nextcloud / ownCloud chunked upload (version 2).
+//
+// The client creates an upload collection (usually below
+// /remote.php/dav/uploads/<user>/), PUTs the chunks into it with
+// numbers as names, and then MOVEs the virtual member ".file" of the
+// upload collection to the destination:
+//
+//   MKCOL /uploads/alice/1234/
+//   PUT   /uploads/alice/1234/00001
+//   PUT   /uploads/alice/1234/00002
+//   MOVE  /uploads/alice/1234/.file  (Destination: /files/big.bin)
+//
+// The chunks and the upload collection are normal resources, only the
+// MOVE is special. The chunks are assembled in a temporary file in the
+// upload collection, which is renamed to the destination once its
+// length and checksum have been checked.
+//
+use std::time::{Duration, SystemTime};
+
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+
+use crate::body::Body;
+use crate::checksum::{ChecksumType, Hasher, format_checksums, parse_oc_checksum};
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::quota::quota_exceeded;
+use crate::{DavInner, DavResult};
+
+// The virtual member of an upload collection that is moved.
+const CHUNKED_FILE: &[u8] = b".file";
+// Where the chunks are assembled.
+const ASSEMBLY_FILE: &[u8] = b".file.assembling";
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // Is this the MOVE of the ".file" of an upload collection.
+    pub(crate) async fn is_chunked_move(&self, path: &DavPath) -> bool {
+        path.file_name_bytes() == CHUNKED_FILE
+            && self
+                .fs
+                .symlink_metadata(path, &self.credentials)
+                .await
+                .is_err()
+            && self
+                .fs
+                .metadata(&path.parent(), &self.credentials)
+                .await
+                .is_ok_and(|m| m.is_dir())
+    }
+
+    // The chunks of an upload collection, in order, and their total size.
+    async fn upload_chunks(&self, dir: &DavPath) -> DavResult<(Vec<DavPath>, u64)> {
+        let mut entries = self
+            .fs
+            .read_dir(dir, ReadDirMeta::Data, &self.credentials)
+            .await?;
+        let mut chunks = Vec::new();
+        while let Some(dirent) = futures_util::StreamExt::next(&mut entries).await {
+            let Ok(dirent) = dirent else {
+                continue;
+            };
+            let name = dirent.name();
+            let Some(n) = std::str::from_utf8(&name)
+                .ok()
+                .and_then(|n| n.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            let meta = dirent.metadata().await?;
+            if meta.is_file() {
+                let mut path = dir.clone();
+                path.push_segment(&name);
+                chunks.push((n, path, meta.len()));
+            }
+        }
+        chunks.sort_by_key(|(n, _, _)| *n);
+        let total = chunks.iter().map(|(_, _, len)| len).sum();
+        Ok((chunks.into_iter().map(|(_, p, _)| p).collect(), total))
+    }
+
+    // Copy the chunks to the assembly file, and return the checksum
+    // of the content if one was asked for.
+    async fn assemble_chunks(
+        &self,
+        chunks: &[DavPath],
+        tmp: &DavPath,
+        total: u64,
+        checksum: Option<&(ChecksumType, String)>,
+    ) -> DavResult<Option<String>> {
+        let mut oo = OpenOptions::write();
+        oo.create = true;
+        oo.truncate = true;
+        oo.size = Some(total);
+        // only kept if it matches, the file is removed otherwise.
+        oo.checksums = checksum.map(|c| format_checksums(std::slice::from_ref(c)));
+        let mut hasher = checksum.map(|(t, _)| Hasher::new(*t));
+        let mut file = self.fs.open(tmp, oo, &self.credentials).await?;
+        let size = self.read_buf_size.unwrap_or(65536);
+        for chunk in chunks {
+            let mut src = self
+                .fs
+                .open(chunk, OpenOptions::read(), &self.credentials)
+                .await?;
+            loop {
+                let buf = src.read_bytes(size).await?;
+                if buf.is_empty() {
+                    break;
+                }
+                if let Some(ref mut h) = hasher {
+                    h.update(&buf);
+                }
+                file.write_bytes(buf).await?;
+            }
+        }
+        file.flush().await?;
+        Ok(hasher.map(|h| h.finish()))
+    }
+
+    /// MOVE of ".file": put the chunks of the upload collection together
+    /// at the destination, and remove the upload collection.
+    pub(crate) async fn handle_chunked_move(
+        &self,
+        req: &Request<()>,
+        path: &DavPath,
+        dest: &DavPath,
+    ) -> DavResult<Response<Body>> {
+        let overwrite = req
+            .headers()
+            .typed_get::<davheaders::Overwrite>()
+            .is_none_or(|o| o.0);
+        let mut dir = path.parent();
+        dir.add_slash();
+
+        // the destination must be a file, in an existing collection.
+        if !self.has_parent(dest).await {
+            return Err(StatusCode::CONFLICT.into());
+        }
+        let dmeta = self.fs.metadata(dest, &self.credentials).await;
+        if dmeta.as_ref().is_ok_and(|m| m.is_dir()) {
+            return Err(StatusCode::CONFLICT.into());
+        }
+        if !overwrite && dmeta.is_ok() {
+            return Err(StatusCode::PRECONDITION_FAILED.into());
+        }
+
+        // check the If headers and the locks of the destination.
+        let tokens = match if_match_get_tokens(
+            req,
+            dmeta.as_ref().map(|m| m.as_ref()).ok(),
+            self.fs.as_ref(),
+            &self.ls,
+            dest,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(s.into()),
+        };
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            for p in [&dir, dest] {
+                if locksystem
+                    .check(p, principal, false, true, &tokens)
+                    .await
+                    .is_err()
+                {
+                    return Err(StatusCode::LOCKED.into());
+                }
+            }
+        }
+
+        // all the chunks must be there.
+        let (chunks, total) = self.upload_chunks(&dir).await?;
+        let expected = req
+            .headers()
+            .get("OC-Total-Length")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if expected.is_some_and(|len| len != total) {
+            debug!("chunked upload {dir}: expected {expected:?} bytes, have {total}");
+            return Err(StatusCode::BAD_REQUEST.into());
+        }
+        if let Some(room) = self.quota_room(dest).await {
+            let old_len = dmeta.as_ref().map(|m| m.len()).unwrap_or(0);
+            if total > room + old_len {
+                return Ok(quota_exceeded());
+            }
+        }
+
+        let checksum = req
+            .headers()
+            .get("OC-Checksum")
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_oc_checksum);
+        let mut tmp = dir.clone();
+        tmp.push_segment(ASSEMBLY_FILE);
+        let digest = match self
+            .assemble_chunks(&chunks, &tmp, total, checksum.as_ref())
+            .await
+        {
+            Ok(digest) => digest,
+            Err(e) => {
+                let _ = self.fs.remove_file(&tmp, &self.credentials).await;
+                return Err(e);
+            }
+        };
+        if let (Some((ctype, want)), Some(have)) = (&checksum, &digest)
+            && want != have
+        {
+            debug!(
+                "chunked upload {dir}: {} checksum {have}, expected {want}",
+                ctype.name()
+            );
+            let _ = self.fs.remove_file(&tmp, &self.credentials).await;
+            return Err(StatusCode::BAD_REQUEST.into());
+        }
+
+        self.autoversion_before_put(dest, dmeta.is_ok()).await;
+        if let Err(e) = self.fs.rename(&tmp, dest, &self.credentials).await {
+            let _ = self.fs.remove_file(&tmp, &self.credentials).await;
+            return Err(e.into());
+        }
+        self.autoversion_after_put(dest).await;
+
+        // the upload collection is not needed anymore.
+        for chunk in &chunks {
+            let _ = self.fs.remove_file(chunk, &self.credentials).await;
+        }
+        let _ = self.fs.remove_dir(&dir, &self.credentials).await;
+        if let Some(ref locksystem) = self.ls {
+            let _ = locksystem.delete(&dir).await;
+        }
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = match dmeta {
+            Ok(_) => StatusCode::NO_CONTENT,
+            Err(_) => StatusCode::CREATED,
+        };
+        res.headers_mut().typed_insert(headers::ContentLength(0));
+
+        // the client can tell us the modification time of the file.
+        let mtime = req
+            .headers()
+            .get("X-OC-Mtime")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if let Some(mtime) = mtime {
+            let tm = SystemTime::UNIX_EPOCH + Duration::from_secs(mtime);
+            if self
+                .fs
+                .set_modified(dest, tm, &self.credentials)
+                .await
+                .is_ok()
+            {
+                res.headers_mut()
+                    .insert("X-OC-MTime", "accepted".parse().unwrap());
+            }
+        }
+        if let Ok(meta) = self.fs.metadata(dest, &self.credentials).await
+            && let Some(etag) = davheaders::ETag::from_meta(meta.as_ref())
+        {
+            let oc_etag = etag.to_string();
+            res.headers_mut().typed_insert(etag);
+            res.headers_mut()
+                .insert("OC-ETag", oc_etag.parse().unwrap());
+        }
+        if let Ok(id) = self.fs.resource_id(dest, &self.credentials).await
+            && let Ok(id) = id.parse()
+        {
+            res.headers_mut().insert("OC-FileId", id);
+        }
+        Ok(res)
+    }
+}
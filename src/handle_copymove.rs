@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use futures_util::{FutureExt, StreamExt, future::BoxFuture};
 use headers::HeaderMapExt;
 use http::{Request, Response, StatusCode};
@@ -10,6 +12,7 @@ use crate::davpath::DavPath;
 use crate::errors::*;
 use crate::fs::*;
 use crate::multierror::{MultiError, multi_error};
+use crate::quota::quota_exceeded;
 use crate::{DavInner, DavResult, util::DavMethod};
 
 // map_err helper.
@@ -32,6 +35,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         topdest: &'a DavPath,
         dest: &'a DavPath,
         depth: Depth,
+        copied: &'a mut HashMap<String, DavPath>,
         multierror: &'a mut MultiError,
     ) -> BoxFuture<'a, DavResult<()>> {
         async move {
@@ -47,6 +51,23 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 Ok(m) => m,
             };
 
+            // RFC 5842 2.3: if we already made a copy of this resource
+            // (it has multiple bindings), bind to that copy instead.
+            // This also stops us from copying a binding loop forever.
+            let id = self.fs.resource_id(source, &self.credentials).await.ok();
+            if let Some(prev) = id.as_ref().and_then(|id| copied.get(id)) {
+                return match self.fs.bind(prev, dest, &self.credentials).await {
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        debug!("do_copy: self.fs.bind error: {e:?}");
+                        add_status(multierror, dest, e).await
+                    }
+                };
+            }
+            if let Some(id) = id {
+                copied.insert(id, dest.clone());
+            }
+
             // if it's a file we can overwrite it.
             if !meta.is_dir() {
                 return match self.fs.copy(source, dest, &self.credentials).await {
@@ -111,7 +132,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 }
                 // recurse.
                 if let Err(e) = self
-                    .do_copy(&nsrc, topdest, &ndest, depth, multierror)
+                    .do_copy(&nsrc, topdest, &ndest, depth, copied, multierror)
                     .await
                 {
                     retval = Err(e);
@@ -171,6 +192,14 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             None => return Err(StatusCode::BAD_REQUEST.into()),
         };
 
+        // Nextcloud / ownCloud chunked upload, see handle_chunked.rs.
+        if method == DavMethod::Move {
+            let path = self.path(req);
+            if self.is_chunked_move(&path).await {
+                return self.handle_chunked_move(req, &path, &dest).await;
+            }
+        }
+
         // for MOVE, tread with care- if the path ends in "/" but it actually
         // is a symlink, we want to move the symlink, not what it points to.
         let mut path = self.path(req);
@@ -258,6 +287,33 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         }
 
+        // a copy must fit in the quota of the destination. What is
+        // overwritten does not count.
+        if method == DavMethod::Copy
+            && let Some(room) = self.quota_room(&dest).await
+        {
+            let replaced = match exists && overwrite {
+                true => self.usage(&dest).await,
+                false => 0,
+            };
+            let size = match depth {
+                Depth::Zero if meta.is_dir() => 0,
+                _ => self.usage(&path).await,
+            };
+            if size > room + replaced {
+                return Ok(quota_exceeded());
+            }
+        }
+
+        // RFC 3648: the destination can have a place in an ordered collection.
+        let position = req
+            .headers()
+            .typed_try_get::<davheaders::Position>()
+            .map_err(|_| DavError::Status(StatusCode::BAD_REQUEST))?;
+        if !exists && let Some(res) = self.check_position(&dest, position.as_ref()).await {
+            return Ok(res);
+        }
+
         let req_path = path.clone();
 
         let items = AsyncStream::new(|tx| {
@@ -278,15 +334,26 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     if let Some(ref locksystem) = self.ls {
                         let _ = locksystem.delete(&dest).await;
                     }
+                    if let Some(ref acl) = self.acl {
+                        acl.delete(&dest).await;
+                    }
                 }
 
                 // COPY or MOVE.
                 if method == DavMethod::Copy {
                     if self
-                        .do_copy(&path, &dest, &dest, depth, &mut multierror)
+                        .do_copy(
+                            &path,
+                            &dest,
+                            &dest,
+                            depth,
+                            &mut HashMap::new(),
+                            &mut multierror,
+                        )
                         .await
                         .is_ok()
                     {
+                        self.apply_position(&dest, position, !exists).await;
                         let s = if exists {
                             StatusCode::NO_CONTENT
                         } else {
@@ -300,6 +367,10 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                         if let Some(ref locksystem) = self.ls {
                             locksystem.delete(&path).await.ok();
                         }
+                        if let Some(ref acl) = self.acl {
+                            acl.rename(&path, &dest).await;
+                        }
+                        self.apply_position(&dest, position, !exists).await;
                         let s = if exists {
                             StatusCode::NO_CONTENT
                         } else {
@@ -50,6 +50,21 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     Err(e) => Err(add_status(res, path, e).await),
                 };
             }
+            // a collection that has other bindings is not destroyed, only
+            // this binding is removed (RFC 5842). This also makes sure
+            // we never walk into a binding loop.
+            if self
+                .fs
+                .bindings(path, &self.credentials)
+                .await
+                .is_ok_and(|b| b.len() > 1)
+            {
+                trace!("delete_items (unbind) {path}");
+                return match self.fs.unbind(path, &self.credentials).await {
+                    Ok(x) => Ok(x),
+                    Err(e) => Err(add_status(res, path, e).await),
+                };
+            }
             if depth == Depth::Zero {
                 trace!("delete_items (dir) {path} {depth:?}");
                 return match self.fs.remove_dir(path, &self.credentials).await {
@@ -163,6 +178,10 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         }
 
+        // the attendees of an event are told that it was removed (RFC 6638).
+        #[cfg(feature = "caldav")]
+        let scheduled = self.scheduling_object(&path).await;
+
         let req_path = path.clone();
 
         let items = AsyncStream::new(|tx| {
@@ -178,6 +197,11 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     if let Some(ref locksystem) = self.ls {
                         locksystem.delete(&path).await.ok();
                     }
+                    if let Some(ref acl) = self.acl {
+                        acl.delete(&path).await;
+                    }
+                    #[cfg(feature = "caldav")]
+                    self.schedule_after_delete(&path, scheduled).await;
                     let _ = multierror.add_status(&path, StatusCode::NO_CONTENT).await;
                 }
                 Ok(())
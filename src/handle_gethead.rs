@@ -10,12 +10,14 @@ use bytes::Bytes;
 
 use crate::async_stream::AsyncStream;
 use crate::body::Body;
+use crate::checksum::insert_digest_headers;
 use crate::conditional;
 use crate::davhandler::DavOptionHide;
 use crate::davheaders;
 use crate::davpath::DavPath;
 use crate::errors::*;
 use crate::fs::*;
+use crate::handle_version::version_query;
 use crate::{DavInner, DavMethod};
 
 struct Range {
@@ -31,14 +33,14 @@ const READ_BUF_SIZE: usize = 16384;
 
 impl<C: Clone + Send + Sync + 'static> DavInner<C> {
     pub(crate) fn get_read_dir_meta(&self) -> ReadDirMeta {
-        if self.hide_symlinks {
+        if self.hide_symlinks || self.redirectrefs {
             ReadDirMeta::DataSymlink
         } else {
             ReadDirMeta::Data
         }
     }
 
-    /// Returns the metadata depending on hide_symlinks & hide_dot_prefix
+    /// Returns the metadata depending on hide_symlinks, redirectrefs & hide_dot_prefix
     pub(crate) async fn visible_metadata(&self, path: &DavPath) -> DavResult<Box<dyn DavMetaData>> {
         if (self.hide_dot_prefix == DavOptionHide::Always
             || self.hide_dot_prefix == DavOptionHide::ForDirectPaths)
@@ -47,6 +49,11 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             return Err(DavError::Status(StatusCode::NOT_FOUND));
         }
 
+        // redirect references (symlinks) are never followed.
+        if self.redirectrefs {
+            return Ok(self.fs.symlink_metadata(path, &self.credentials).await?);
+        }
+
         if self.hide_symlinks {
             let meta = self.fs.symlink_metadata(path, &self.credentials).await?;
             if meta.is_symlink() {
@@ -105,10 +112,18 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         }
 
         // double check, is it a regular file.
-        let mut file = self
-            .fs
-            .open(&path, OpenOptions::read(), &self.credentials)
-            .await?;
+        let mut file = match version_query(req) {
+            Some(version) if !meta.is_dir() => {
+                self.fs
+                    .open_version(&path, &version, &self.credentials)
+                    .await?
+            }
+            _ => {
+                self.fs
+                    .open(&path, OpenOptions::read(), &self.credentials)
+                    .await?
+            }
+        };
         #[allow(unused_mut)]
         let mut meta = file.metadata().await?;
         if !meta.is_file() {
@@ -138,6 +153,13 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             res.headers_mut().typed_insert(etag);
         }
 
+        // the checksums that were stored when the file was written.
+        if version_query(req).is_none()
+            && let Ok(checksums) = self.fs.checksums(&path, &self.credentials).await
+        {
+            insert_digest_headers(res.headers_mut(), &checksums);
+        }
+
         if let Some(redirect) = self.redirect
             && redirect
             && let Some(url) = file.redirect_url().await?
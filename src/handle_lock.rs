@@ -3,7 +3,7 @@ use http::StatusCode as SC;
 use http::{Request, Response};
 use std::cmp;
 use std::io::Cursor;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use xmltree::{self, Element};
 
 use crate::body::Body;
@@ -187,6 +187,9 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     return Err(e.into());
                 }
             };
+            if self.lock_null {
+                locksystem.add_lock_null(&path, &lock.token).await;
+            }
         }
 
         // output result
@@ -232,12 +235,50 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
 
         match locksystem.unlock(&path, token).await {
             Ok(_) => {
+                self.remove_stale_lock_nulls().await;
                 *res.status_mut() = SC::NO_CONTENT;
                 Ok(res)
             }
             Err(_) => Err(SC::CONFLICT.into()),
         }
     }
+
+    // Remove the lock-null resources whose lock went away before they
+    // were written to. They must still be empty, and not locked again.
+    // This runs before LOCK, UNLOCK and PROPFIND, and after an UNLOCK.
+    pub(crate) async fn remove_stale_lock_nulls(&self) {
+        let Some(ref locksystem) = self.ls else {
+            return;
+        };
+        if !self.lock_null {
+            return;
+        }
+        let now = SystemTime::now();
+        for path in locksystem.stale_lock_nulls().await {
+            if !self
+                .fs
+                .metadata(&path, &self.credentials)
+                .await
+                .is_ok_and(|m| m.is_file() && m.is_empty())
+            {
+                continue;
+            }
+            let locks = locksystem.discover(&path).await;
+            if locks.iter().all(|l| l.timeout_at.is_some_and(|t| t <= now)) {
+                debug!("removing lock-null resource {path}");
+                let _ = self.fs.remove_file(&path, &self.credentials).await;
+            }
+        }
+    }
+
+    // A lock-null resource that is written to becomes a normal resource.
+    pub(crate) async fn lock_null_written(&self, path: &DavPath) {
+        if let Some(ref locksystem) = self.ls
+            && self.lock_null
+        {
+            locksystem.remove_lock_null(path).await;
+        }
+    }
 }
 
 #[allow(clippy::borrowed_box)]
@@ -1,14 +1,106 @@
+#[cfg(feature = "proppatch")]
+use std::io::Cursor;
+
 use headers::HeaderMapExt;
 use http::{Request, Response, StatusCode};
+#[cfg(feature = "proppatch")]
+use xmltree::Element;
 
 use crate::body::Body;
 use crate::conditional::*;
 use crate::davheaders;
+#[cfg(feature = "proppatch")]
+use crate::davpath::DavPath;
 use crate::fs::*;
+#[cfg(feature = "proppatch")]
+use crate::handle_props::{davprop_to_element, element_to_davprop, element_to_davprop_full};
+use crate::quota::quota_exceeded;
+#[cfg(feature = "proppatch")]
+use crate::util::{MemBuffer, dav_xml_error_response};
+#[cfg(feature = "proppatch")]
+use crate::xmltree_ext::{self, *};
 use crate::{DavError, DavInner, DavResult};
 
+// The properties from a RFC 5689 DAV:mkcol body.
+#[cfg(feature = "proppatch")]
+fn parse_mkcol(xmldata: &[u8]) -> DavResult<Vec<Element>> {
+    let root = Element::parse2(Cursor::new(xmldata))?;
+    if root.name != "mkcol" || root.namespace.as_deref() != Some("DAV:") {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE.into());
+    }
+    Ok(root
+        .child_elems_into_iter()
+        .filter(|e| e.name == "set")
+        .flat_map(|e| e.child_elems_into_iter())
+        .filter(|e| e.name == "prop")
+        .flat_map(|e| e.child_elems_into_iter())
+        .collect())
+}
+
+// Is this a DAV:resourcetype we can create at `path`.
+#[cfg(feature = "proppatch")]
+#[allow(unused_variables)]
+fn valid_resourcetype(path: &DavPath, elem: &Element) -> bool {
+    let mut collection = false;
+    for e in elem.child_elems_iter() {
+        match (e.namespace.as_deref(), e.name.as_str()) {
+            (Some("DAV:"), "collection") => collection = true,
+            #[cfg(feature = "caldav")]
+            (Some(crate::caldav::NS_CALDAV_URI), "calendar")
+                if crate::caldav::is_path_in_caldav_directory(path) => {}
+            #[cfg(feature = "carddav")]
+            (Some(crate::carddav::NS_CARDDAV_URI), "addressbook")
+                if crate::carddav::is_path_in_carddav_directory(path) => {}
+            _ => return false,
+        }
+    }
+    collection
+}
+
+// The DAV:mkcol-response for a failed extended MKCOL.
+#[cfg(feature = "proppatch")]
+fn mkcol_response(ret: Vec<(StatusCode, Element)>) -> DavResult<Response<Body>> {
+    let mut statuses = ret.iter().map(|(s, _)| *s).collect::<Vec<_>>();
+    statuses.sort();
+    statuses.dedup();
+
+    let mut root = Element::new2("D:mkcol-response").ns("D", "DAV:");
+    for status in &statuses {
+        let props = ret
+            .iter()
+            .filter(|(s, _)| s == status)
+            .map(|(_, p)| p.clone())
+            .collect();
+        root.push_element(Element::new3(
+            "D",
+            "propstat",
+            vec![
+                Element::new3("D", "prop", props),
+                Element::new2("D:status").text("HTTP/1.1 ".to_string() + &status.to_string()),
+            ],
+        ));
+    }
+    let mut emitter = xmltree_ext::emitter(MemBuffer::new())?;
+    root.write_ev(&mut emitter)?;
+
+    let mut res = Response::new(Body::from(emitter.into_inner().take()));
+    // the failed property decides the status of the response.
+    *res.status_mut() = statuses
+        .into_iter()
+        .find(|s| *s != StatusCode::FAILED_DEPENDENCY)
+        .unwrap_or(StatusCode::FORBIDDEN);
+    res.headers_mut().typed_insert(davheaders::ContentType(
+        "application/xml; charset=utf-8".to_owned(),
+    ));
+    Ok(res)
+}
+
 impl<C: Clone + Send + Sync + 'static> DavInner<C> {
-    pub(crate) async fn handle_mkcol(&self, req: &Request<()>) -> DavResult<Response<Body>> {
+    pub(crate) async fn handle_mkcol(
+        &self,
+        req: &Request<()>,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
         let mut path = self.path(req);
         let meta = self.fs.metadata(&path, &self.credentials).await;
 
@@ -38,6 +130,75 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         }
 
+        // RFC 5689 extended MKCOL: the body has the initial properties.
+        // They are checked before the collection is created, so that
+        // either everything succeeds, or nothing is created at all.
+        #[cfg(feature = "proppatch")]
+        let mut patch = Vec::new();
+        #[cfg(feature = "proppatch")]
+        let mut live = Vec::new();
+        #[cfg(feature = "proppatch")]
+        if !xmldata.is_empty() {
+            if meta.is_ok() {
+                return Err(DavError::Status(StatusCode::METHOD_NOT_ALLOWED));
+            }
+            let can_deadprop = self.fs.have_props(&path, &self.credentials).await;
+            let mut pending = Vec::new();
+            let mut ret = Vec::new();
+            for prop in parse_mkcol(xmldata)? {
+                if prop.namespace.as_deref() == Some("DAV:") && prop.name == "resourcetype" {
+                    if !valid_resourcetype(&path, &prop) {
+                        return Ok(dav_xml_error_response(
+                            StatusCode::FORBIDDEN,
+                            "<D:valid-resourcetype/>",
+                        ));
+                    }
+                    continue;
+                }
+                match self.liveprop_set(&prop, can_deadprop) {
+                    StatusCode::CONTINUE => pending.push(prop),
+                    // live properties, set once the collection exists.
+                    StatusCode::OK => live.push(prop),
+                    s => ret.push((s, davprop_to_element(element_to_davprop(&prop)))),
+                }
+            }
+            if !ret.is_empty() {
+                ret.extend(pending.iter().map(|p| {
+                    let p = davprop_to_element(element_to_davprop(p));
+                    (StatusCode::FAILED_DEPENDENCY, p)
+                }));
+                ret.extend(live.iter().map(|p| {
+                    let p = davprop_to_element(element_to_davprop(p));
+                    (StatusCode::FAILED_DEPENDENCY, p)
+                }));
+                return mkcol_response(ret);
+            }
+            patch = pending
+                .iter()
+                .map(|p| (true, element_to_davprop_full(p)))
+                .collect();
+        }
+        #[cfg(not(feature = "proppatch"))]
+        if !xmldata.is_empty() {
+            return Err(DavError::Status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        }
+
+        // RFC 3648: the new collection can have a place in an ordered collection.
+        let position = req
+            .headers()
+            .typed_try_get::<davheaders::Position>()
+            .map_err(|_| DavError::Status(StatusCode::BAD_REQUEST))?;
+        if meta.is_err()
+            && let Some(res) = self.check_position(&path, position.as_ref()).await
+        {
+            return Ok(res);
+        }
+
+        // no room for anything anymore.
+        if meta.is_err() && self.quota_room(&path).await == Some(0) {
+            return Ok(quota_exceeded());
+        }
+
         let mut res = Response::new(Body::empty());
 
         match self.fs.create_dir(&path, &self.credentials).await {
@@ -56,6 +217,45 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         }
 
+        // store the properties. If that fails, remove the collection again.
+        // The live properties go last, storing the dead properties might
+        // change the timestamps.
+        #[cfg(feature = "proppatch")]
+        if !patch.is_empty() || !live.is_empty() {
+            let mut ret = Vec::new();
+            if !patch.is_empty() {
+                match self.fs.patch_props(&path, patch, &self.credentials).await {
+                    Ok(r) => ret.extend(r.into_iter().map(|(s, p)| (s, davprop_to_element(p)))),
+                    Err(e) => {
+                        let _ = self.fs.remove_dir(&path, &self.credentials).await;
+                        return Err(e.into());
+                    }
+                }
+            }
+            let meta = self.fs.metadata(&path, &self.credentials).await?;
+            for prop in &live {
+                let status = match ret.iter().all(|(s, _)| *s == StatusCode::OK) {
+                    true => self.liveprop_apply(&path, meta.as_ref(), prop).await,
+                    false => StatusCode::FAILED_DEPENDENCY,
+                };
+                ret.push((status, davprop_to_element(element_to_davprop(prop))));
+            }
+            if ret.iter().any(|(s, _)| *s != StatusCode::OK) {
+                let _ = self.fs.remove_dir(&path, &self.credentials).await;
+                let ret = ret
+                    .into_iter()
+                    .map(|(s, p)| match s {
+                        StatusCode::OK => (StatusCode::FAILED_DEPENDENCY, p),
+                        s => (s, p),
+                    })
+                    .collect();
+                return mkcol_response(ret);
+            }
+        }
+
+        self.apply_ordering_type(req, &path).await;
+        self.apply_position(&path, position, true).await;
+
         Ok(res)
     }
 }
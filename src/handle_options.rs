@@ -2,6 +2,7 @@ use headers::HeaderMapExt;
 use http::{Request, Response};
 
 use crate::body::Body;
+use crate::fs::FsError;
 use crate::util::{DavMethod, dav_method};
 use crate::{DavInner, DavResult};
 
@@ -22,6 +23,31 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         let dav = "1,2,3,sabredav-partialupdate,addressbook";
         #[cfg(not(any(feature = "caldav", feature = "carddav")))]
         let dav = "1,2,3,sabredav-partialupdate";
+        let mut dav = dav.to_string();
+        if self.acl.is_some() {
+            dav.push_str(",access-control");
+        }
+        let path = self.path(req);
+        let history = self.fs.version_history(&path, &self.credentials).await;
+        if history.is_ok() {
+            dav.push_str(",version-control");
+        }
+        let can_bind = !matches!(
+            self.fs.bindings(&path, &self.credentials).await,
+            Err(FsError::NotImplemented)
+        );
+        if can_bind {
+            dav.push_str(",bind");
+        }
+        if self.redirectrefs {
+            dav.push_str(",redirectrefs");
+        }
+        #[cfg(feature = "caldav")]
+        if !self.calendar_users.is_empty() {
+            dav.push_str(",calendar-auto-schedule");
+        }
+        #[cfg(feature = "proppatch")]
+        dav.push_str(",extended-mkcol,ordered-collections");
         h.insert("DAV", dav.parse().unwrap());
         h.insert("MS-Author-Via", "DAV".parse().unwrap());
         h.typed_insert(headers::ContentLength(0));
@@ -41,9 +67,12 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         };
 
-        let path = self.path(req);
-        let meta = self.fs.metadata(&path, &self.credentials).await;
+        let meta = match self.redirectrefs {
+            true => self.fs.symlink_metadata(&path, &self.credentials).await,
+            false => self.fs.metadata(&path, &self.credentials).await,
+        };
         let is_unmapped = meta.is_err();
+        let is_redirectref = meta.as_ref().is_ok_and(|m| m.is_symlink());
         let is_file = meta.map(|m| m.is_file()).unwrap_or_default();
         let is_star = path.is_star() && method == DavMethod::Options;
 
@@ -57,6 +86,15 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             mm(&mut v, "MKCALENDAR", DavMethod::MkCalendar);
             #[cfg(feature = "carddav")]
             mm(&mut v, "MKADDRESSBOOK", DavMethod::MkAddressbook);
+            if self.redirectrefs {
+                mm(&mut v, "MKREDIRECTREF", DavMethod::MkRedirectRef);
+            }
+        } else if is_redirectref {
+            mm(&mut v, "OPTIONS", DavMethod::Options);
+            mm(&mut v, "PROPFIND", DavMethod::PropFind);
+            mm(&mut v, "MOVE", DavMethod::Move);
+            mm(&mut v, "DELETE", DavMethod::Delete);
+            mm(&mut v, "UPDATEREDIRECTREF", DavMethod::UpdateRedirectRef);
         } else {
             if is_file || is_star {
                 mm(&mut v, "HEAD", DavMethod::Head);
@@ -73,8 +111,31 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
             mm(&mut v, "LOCK", DavMethod::Lock);
             mm(&mut v, "UNLOCK", DavMethod::Unlock);
-            #[cfg(any(feature = "caldav", feature = "carddav"))]
             mm(&mut v, "REPORT", DavMethod::Report);
+            if !is_file {
+                mm(&mut v, "SEARCH", DavMethod::Search);
+                #[cfg(feature = "proppatch")]
+                mm(&mut v, "ORDERPATCH", DavMethod::OrderPatch);
+            }
+            if self.acl.is_some() {
+                mm(&mut v, "ACL", DavMethod::Acl);
+            }
+            if can_bind && !is_file {
+                mm(&mut v, "BIND", DavMethod::Bind);
+                mm(&mut v, "UNBIND", DavMethod::Unbind);
+                mm(&mut v, "REBIND", DavMethod::Rebind);
+            }
+            match history {
+                Ok(None) if is_file => {
+                    mm(&mut v, "VERSION-CONTROL", DavMethod::VersionControl);
+                }
+                Ok(Some(h)) if h.checked_out => {
+                    mm(&mut v, "CHECKIN", DavMethod::Checkin);
+                    mm(&mut v, "UNCHECKOUT", DavMethod::Uncheckout);
+                }
+                Ok(Some(_)) => mm(&mut v, "CHECKOUT", DavMethod::Checkout),
+                _ => {}
+            }
             #[cfg(feature = "caldav")]
             if is_unmapped {
                 mm(&mut v, "MKCALENDAR", DavMethod::MkCalendar);
@@ -85,6 +146,18 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             }
         }
 
+        // RFC 6638: free-busy requests are POSTed to the outbox.
+        #[cfg(feature = "caldav")]
+        if self.is_outbox(&path) {
+            mm(&mut v, "POST", DavMethod::Post);
+        }
+
+        // RFC 5323: the search grammars that SEARCH supports here.
+        if v.iter().any(|m| m == "SEARCH") {
+            res.headers_mut()
+                .insert("DASL", "<DAV:basicsearch>".parse().unwrap());
+        }
+
         let a = v.join(",").parse().unwrap();
         res.headers_mut().insert("allow", a);
 
@@ -0,0 +1,444 @@
+//
+// RFC 3648, WebDAV Ordered Collections.
+//
+// The ordering of a collection is kept in a dead property of the
+// collection, so every filesystem that supports `patch_props` can have
+// ordered collections. Members that are not in the stored order (e.g.
+// created behind our back) come after the ones that are, and members
+// that no longer exist are dropped the next time the order is written.
+//
+use std::io::Cursor;
+
+use futures_util::StreamExt;
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+#[cfg(feature = "proppatch")]
+use percent_encoding::{NON_ALPHANUMERIC, percent_encode};
+use xmltree::Element;
+
+use crate::async_stream::AsyncStream;
+use crate::body::Body;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders::{self, Position};
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::handle_props::{NS_DAV_SERVER_URI, PropWriter};
+use crate::util::dav_xml_error_response;
+use crate::xmltree_ext::*;
+use crate::{DavError, DavInner, DavResult};
+
+/// The DAV:ordering-type of a collection that is not ordered.
+pub(crate) const UNORDERED: &str = "DAV:unordered";
+
+// The ordering of a collection.
+#[derive(Debug, Clone)]
+pub(crate) struct MemberOrder {
+    // the DAV:ordering-type, an URI that says what the order means.
+    pub ordering_type: String,
+    // names of the members, in order.
+    pub members: Vec<Vec<u8>>,
+}
+
+// The dead property the order is stored in.
+fn order_prop(xml: Option<Vec<u8>>) -> DavProp {
+    DavProp {
+        name: "member-order".to_string(),
+        prefix: Some("ds".to_string()),
+        namespace: Some(NS_DAV_SERVER_URI.to_string()),
+        xml,
+    }
+}
+
+impl MemberOrder {
+    fn new(ordering_type: String) -> MemberOrder {
+        MemberOrder {
+            ordering_type,
+            members: Vec::new(),
+        }
+    }
+
+    fn from_xml(xml: &[u8]) -> Option<MemberOrder> {
+        let elem = Element::parse2(Cursor::new(xml)).ok()?;
+        let mut order = MemberOrder::new(elem.attributes.get("type")?.clone());
+        for m in elem.child_elems_iter().filter(|e| e.name == "member") {
+            let name = m.get_text().unwrap_or_default();
+            order.members.push(percent_decode_str(&name).collect());
+        }
+        Some(order)
+    }
+
+    #[cfg(feature = "proppatch")]
+    fn to_xml(&self) -> DavResult<Vec<u8>> {
+        let mut elem = Element::new2("ds:member-order").ns("ds", NS_DAV_SERVER_URI);
+        elem.attributes
+            .insert("type".to_string(), self.ordering_type.clone());
+        for m in &self.members {
+            let name = percent_encode(m, NON_ALPHANUMERIC).to_string();
+            elem.push_element(Element::new2("ds:member").text(name));
+        }
+        let mut emitter = crate::xmltree_ext::emitter(Cursor::new(Vec::new()))?;
+        elem.write_ev(&mut emitter)?;
+        Ok(emitter.into_inner().into_inner())
+    }
+
+    // Where a member is in the order. Unknown members go last.
+    fn index(&self, name: &[u8]) -> usize {
+        self.members
+            .iter()
+            .position(|m| m == name)
+            .unwrap_or(self.members.len())
+    }
+
+    // Move a member to a new position. Fails if the member it is
+    // positioned before or after does not exist.
+    fn place(&mut self, name: &[u8], position: &Position) -> bool {
+        let reference = match position {
+            Position::Before(r) | Position::After(r) => Some(r),
+            _ => None,
+        };
+        if reference.is_some_and(|r| r == name || !self.members.contains(r)) {
+            return false;
+        }
+        self.members.retain(|m| m != name);
+        let idx = match position {
+            Position::First => 0,
+            Position::Last => self.members.len(),
+            Position::Before(r) => self.index(r),
+            Position::After(r) => self.index(r) + 1,
+        };
+        self.members.insert(idx, name.to_vec());
+        true
+    }
+}
+
+/// The stored order of a collection, None if it is not ordered.
+pub(crate) async fn member_order<C: Clone + Send + Sync + 'static>(
+    fs: &dyn GuardedFileSystem<C>,
+    path: &DavPath,
+    credentials: &C,
+) -> Option<MemberOrder> {
+    let xml = fs
+        .get_prop(path, order_prop(None), credentials)
+        .await
+        .ok()?;
+    MemberOrder::from_xml(&xml)
+}
+
+// A DAV:position element from an ORDERPATCH body.
+fn parse_position(elem: &Element) -> DavResult<Position> {
+    let pos = elem
+        .child_elems_iter()
+        .next()
+        .ok_or(DavError::XmlParseError)?;
+    let segment = || {
+        pos.get_child("segment")
+            .and_then(|s| s.get_text())
+            .map(|s| percent_decode_str(s.trim()).collect::<Vec<u8>>())
+            .ok_or(DavError::XmlParseError)
+    };
+    match pos.name.as_str() {
+        "first" => Ok(Position::First),
+        "last" => Ok(Position::Last),
+        "before" => Ok(Position::Before(segment()?)),
+        "after" => Ok(Position::After(segment()?)),
+        _ => Err(DavError::XmlParseError),
+    }
+}
+
+// The DAV:order-member elements of an ORDERPATCH: segment and position.
+type OrderMembers = Vec<(Vec<u8>, Position)>;
+
+// The new DAV:ordering-type and the DAV:order-member elements.
+fn parse_orderpatch(xmldata: &[u8]) -> DavResult<(Option<String>, OrderMembers)> {
+    let root = Element::parse2(Cursor::new(xmldata))?;
+    if root.name != "orderpatch" || root.namespace.as_deref() != Some("DAV:") {
+        return Err(DavError::XmlParseError);
+    }
+    let mut ordering_type = None;
+    let mut members = Vec::new();
+    for elem in root.child_elems_iter() {
+        match elem.name.as_str() {
+            "ordering-type" => {
+                let href = elem
+                    .get_child("href")
+                    .and_then(|e| e.get_text())
+                    .ok_or(DavError::XmlParseError)?;
+                ordering_type = Some(href.trim().to_string());
+            }
+            "order-member" => {
+                let segment = elem
+                    .get_child("segment")
+                    .and_then(|e| e.get_text())
+                    .ok_or(DavError::XmlParseError)?;
+                let position = elem.get_child("position").ok_or(DavError::XmlParseError)?;
+                members.push((
+                    percent_decode_str(segment.trim()).collect(),
+                    parse_position(position)?,
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok((ordering_type, members))
+}
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    /// Like read_dir, but in the order of the collection if it is ordered.
+    pub(crate) async fn read_dir_ordered(
+        &self,
+        path: &DavPath,
+        meta: ReadDirMeta,
+    ) -> FsResult<FsStream<Box<dyn DavDirEntry>>> {
+        let entries = self.fs.read_dir(path, meta, &self.credentials).await?;
+        let Some(order) = member_order(self.fs.as_ref(), path, &self.credentials).await else {
+            return Ok(entries);
+        };
+        let mut entries = entries.collect::<Vec<_>>().await;
+        entries.sort_by_key(|e| match e {
+            Ok(e) => order.index(&e.name()),
+            Err(_) => usize::MAX,
+        });
+        Ok(Box::pin(futures_util::stream::iter(entries)))
+    }
+
+    // The order of a collection, with the members that currently exist.
+    async fn current_order(&self, path: &DavPath) -> Option<MemberOrder> {
+        let order = member_order(self.fs.as_ref(), path, &self.credentials).await?;
+        Some(self.sync_members(path, order).await)
+    }
+
+    // Drop the members that are gone, and add the ones that are missing.
+    async fn sync_members(&self, path: &DavPath, mut order: MemberOrder) -> MemberOrder {
+        let mut names = Vec::new();
+        if let Ok(mut entries) = self
+            .fs
+            .read_dir(path, ReadDirMeta::None, &self.credentials)
+            .await
+        {
+            while let Some(Ok(dirent)) = entries.next().await {
+                names.push(dirent.name());
+            }
+        }
+        order.members.retain(|m| names.contains(m));
+        for name in names {
+            if !order.members.contains(&name) {
+                order.members.push(name);
+            }
+        }
+        order
+    }
+
+    async fn store_order(&self, path: &DavPath, order: Option<&MemberOrder>) -> DavResult<()> {
+        #[cfg(feature = "proppatch")]
+        {
+            let patch = match order {
+                Some(order) => (true, order_prop(Some(order.to_xml()?))),
+                None => (false, order_prop(None)),
+            };
+            let ret = self
+                .fs
+                .patch_props(path, vec![patch], &self.credentials)
+                .await?;
+            match ret.into_iter().find(|(s, _)| *s != StatusCode::OK) {
+                Some((status, _)) => Err(DavError::Status(status)),
+                None => Ok(()),
+            }
+        }
+        #[cfg(not(feature = "proppatch"))]
+        {
+            let _ = (path, order);
+            Err(FsError::NotImplemented.into())
+        }
+    }
+
+    /// Check that a new member can be created at the position in the
+    /// Position header. Returns the error response if it cannot.
+    pub(crate) async fn check_position(
+        &self,
+        path: &DavPath,
+        position: Option<&Position>,
+    ) -> Option<Response<Body>> {
+        let position = position?;
+        let Some(order) = self.current_order(&path.parent()).await else {
+            return Some(dav_xml_error_response(
+                StatusCode::CONFLICT,
+                "<D:collection-must-be-ordered/>",
+            ));
+        };
+        let name = path.file_name_bytes();
+        if let Position::Before(r) | Position::After(r) = position
+            && (r == name || !order.members.contains(r))
+        {
+            return Some(dav_xml_error_response(
+                StatusCode::CONFLICT,
+                "<D:segment-must-identify-member/>",
+            ));
+        }
+        None
+    }
+
+    /// Put a new member of an ordered collection in its place: where the
+    /// Position header says, or last. Members that already existed keep
+    /// their place, unless there is a Position header.
+    pub(crate) async fn apply_position(
+        &self,
+        path: &DavPath,
+        position: Option<Position>,
+        created: bool,
+    ) {
+        if !created && position.is_none() {
+            return;
+        }
+        let parent = path.parent();
+        let Some(mut order) = self.current_order(&parent).await else {
+            return;
+        };
+        let position = position.unwrap_or(Position::Last);
+        if order.place(path.file_name_bytes(), &position)
+            && let Err(e) = self.store_order(&parent, Some(&order)).await
+        {
+            debug!("apply_position {path}: {e:?}");
+        }
+    }
+
+    /// Make a new collection ordered, if asked for with the Ordering-Type header.
+    pub(crate) async fn apply_ordering_type(&self, req: &Request<()>, path: &DavPath) {
+        let Some(ordering_type) = req.headers().typed_get::<davheaders::OrderingType>() else {
+            return;
+        };
+        if ordering_type.0 == UNORDERED {
+            return;
+        }
+        let order = MemberOrder::new(ordering_type.0);
+        if let Err(e) = self.store_order(path, Some(&order)).await {
+            debug!("apply_ordering_type {path}: {e:?}");
+        }
+    }
+
+    pub(crate) async fn handle_orderpatch(
+        &self,
+        req: &Request<()>,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
+        let mut path = self.path(req);
+        let meta = self.fs.metadata(&path, &self.credentials).await?;
+        if !meta.is_dir() {
+            return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        path.add_slash();
+        if !self.fs.have_props(&path, &self.credentials).await {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+        let (ordering_type, members) = parse_orderpatch(xmldata)?;
+
+        // check the If and If-* headers.
+        let tokens = match if_match_get_tokens(
+            req,
+            Some(meta.as_ref()),
+            self.fs.as_ref(),
+            &self.ls,
+            &path,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(s.into()),
+        };
+
+        // if locked check if we hold that lock.
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            if let Err(_l) = locksystem
+                .check(&path, principal, false, false, &tokens)
+                .await
+            {
+                return Err(StatusCode::LOCKED.into());
+            }
+        }
+
+        let stored = member_order(self.fs.as_ref(), &path, &self.credentials).await;
+        let order = match (ordering_type, stored) {
+            (Some(t), _) if t == UNORDERED => None,
+            (Some(t), Some(mut order)) => {
+                order.ordering_type = t;
+                Some(order)
+            }
+            (Some(t), None) => Some(MemberOrder::new(t)),
+            (None, order) => order,
+        };
+        let mut order = match order {
+            Some(order) => Some(self.sync_members(&path, order).await),
+            None => None,
+        };
+
+        if !members.is_empty() {
+            let Some(ref mut order) = order else {
+                return Ok(dav_xml_error_response(
+                    StatusCode::CONFLICT,
+                    "<D:collection-must-be-ordered/>",
+                ));
+            };
+            // all members are put in place, or none at all.
+            let mut failed = Vec::new();
+            for (idx, (name, position)) in members.iter().enumerate() {
+                if !order.members.contains(name) || !order.place(name, position) {
+                    failed.push(idx);
+                }
+            }
+            if !failed.is_empty() {
+                return self.orderpatch_failed(req, &path, members, failed);
+            }
+        }
+
+        self.store_order(&path, order.as_ref()).await?;
+
+        let mut res = Response::new(Body::empty());
+        res.headers_mut().typed_insert(headers::ContentLength(0));
+        *res.status_mut() = StatusCode::OK;
+        Ok(res)
+    }
+
+    // The multistatus for an ORDERPATCH where some of the members
+    // could not be put in place.
+    fn orderpatch_failed(
+        &self,
+        req: &Request<()>,
+        path: &DavPath,
+        members: OrderMembers,
+        failed: Vec<usize>,
+    ) -> DavResult<Response<Body>> {
+        let mut res = Response::new(Body::empty());
+        let mut pw = PropWriter::new(
+            req,
+            &mut res,
+            "prop",
+            Vec::new(),
+            self.fs.clone(),
+            None,
+            None,
+            self.principal.clone(),
+            self.credentials.clone(),
+            #[cfg(any(feature = "caldav", feature = "carddav"))]
+            path,
+        )?;
+        let path = path.clone();
+        *res.body_mut() = Body::from(AsyncStream::new(|tx| async move {
+            pw.set_tx(tx);
+            for (idx, (name, _)) in members.into_iter().enumerate() {
+                let mut mpath = path.clone();
+                mpath.push_segment(&name);
+                if failed.contains(&idx) {
+                    let error = Element::new2("D:segment-must-identify-member");
+                    pw.write_error_response(&mpath, StatusCode::CONFLICT, error)?;
+                } else {
+                    pw.write_status_response(&mpath, StatusCode::FAILED_DEPENDENCY)?;
+                }
+            }
+            pw.close().await?;
+            Ok(())
+        }));
+        Ok(res)
+    }
+}
@@ -15,15 +15,23 @@ use xml::writer::EventWriter;
 use xml::writer::XmlEvent as XmlWEvent;
 use xmltree::{Element, XMLNode};
 
+use crate::acl::{DavAccessControl, DavPrivilege, same_href};
 use crate::async_stream::AsyncStream;
 use crate::body::Body;
 use crate::davheaders;
 use crate::davpath::*;
 use crate::errors::*;
 use crate::fs::*;
+use crate::handle_acl::{
+    PrincipalMetaData, acl_element, privilege_set_element, supported_privilege_set_element,
+};
 use crate::handle_lock::{list_lockdiscovery, list_supportedlock};
+use crate::handle_orderpatch::{UNORDERED, member_order};
+use crate::handle_version::version_href;
 use crate::ls::*;
 use crate::util::MemBuffer;
+#[cfg(feature = "proppatch")]
+use crate::util::httpdate_to_systemtime;
 use crate::util::{
     dav_xml_error, systemtime_to_httpdate, systemtime_to_rfc3339_without_nanosecond,
 };
@@ -33,12 +41,20 @@ use crate::{DavInner, DavResult};
 use crate::caldav::*;
 #[cfg(feature = "carddav")]
 use crate::carddav::*;
+#[cfg(feature = "caldav")]
+use crate::handle_caldav::calendar_components;
+#[cfg(feature = "caldav")]
+use crate::handle_schedule::same_path;
+#[cfg(feature = "caldav")]
+use crate::schedule::CalendarUser;
 
 const NS_APACHE_URI: &str = "http://apache.org/dav/props/";
 const NS_DAV_URI: &str = "DAV:";
 const NS_MS_URI: &str = "urn:schemas-microsoft-com:";
 const NS_NEXTCLOUD_URI: &str = "http://nextcloud.org/ns";
 const NS_OWNCLOUD_URI: &str = "http://owncloud.org/ns";
+// dead properties that we use internally, e.g. for ordered collections.
+pub(crate) const NS_DAV_SERVER_URI: &str = "urn:dav-server:";
 
 // list returned by PROPFIND <propname/>.
 #[cfg(all(feature = "caldav", feature = "carddav"))]
@@ -206,6 +222,8 @@ const MS_ALLPROP_STR: &[&str] = &[
     "D:lockdiscovery",
     "D:resourcetype",
     "D:supportedlock",
+    "D:ishidden",
+    "D:isreadonly",
     "Z:Win32CreationTime",
     "Z:Win32FileAttributes",
     "Z:Win32LastAccessTime",
@@ -231,10 +249,14 @@ pub(crate) struct PropWriter<C> {
     props: Vec<Element>,
     fs: Box<dyn GuardedFileSystem<C>>,
     ls: Option<Box<dyn DavLockSystem>>,
+    acl: Option<Box<dyn DavAccessControl>>,
     useragent: String,
     q_cache: QuotaCache,
     credentials: C,
     principal: Option<String>,
+    minimal: bool,
+    #[cfg(feature = "caldav")]
+    calendar_users: Vec<CalendarUser>,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -289,9 +311,15 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             None => davheaders::Depth::Default,
         };
 
-        // path and meta
+        // path and meta. principals do not have to exist in the filesystem.
         let mut path = self.path(req);
-        let meta = self.visible_metadata(&path).await?;
+        let (meta, is_principal) = match self.visible_metadata(&path).await {
+            Ok(meta) => (meta, false),
+            Err(e) => match self.principal_metadata(&path).await {
+                Some(meta) => (meta, true),
+                None => return Err(e),
+            },
+        };
         let meta = self.fixpath(&mut res, &mut path, meta);
 
         let mut root = None;
@@ -338,28 +366,64 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             props,
             self.fs.clone(),
             self.ls.as_ref(),
+            self.acl.as_ref(),
             self.principal.clone(),
             self.credentials.clone(),
             #[cfg(any(feature = "caldav", feature = "carddav"))]
             &path,
         )?;
+        #[cfg(feature = "caldav")]
+        pw.set_calendar_users(self.calendar_users.clone());
+
+        // RFC 8144: Depth 1 with depth-noroot lists only the members.
+        let noroot = depth == davheaders::Depth::One
+            && meta.is_dir()
+            && req
+                .headers()
+                .typed_get::<davheaders::Prefer>()
+                .is_some_and(|p| p.depth_noroot);
+        if noroot {
+            res.headers_mut().append(
+                davheaders::PREFERENCE_APPLIED.clone(),
+                "depth-noroot".parse().unwrap(),
+            );
+        }
+
+        // list redirect references themselves, or just where they point to.
+        let apply_to_refs = req
+            .headers()
+            .typed_get::<davheaders::ApplyToRedirectRef>()
+            .is_some_and(|a| a.0);
 
         *res.body_mut() = Body::from(AsyncStream::new(|tx| async move {
             pw.set_tx(tx);
             let is_dir = meta.is_dir();
 
             // Handle Depth::Default case: no target resource, only Depth 1 children
-            if depth != davheaders::Depth::Default {
+            if depth != davheaders::Depth::Default && !noroot {
                 pw.write_props(&path, meta).await?;
                 pw.flush().await?;
             }
 
-            if is_dir
+            if is_principal && depth != davheaders::Depth::Zero {
+                for mpath in self.principal_members(&path).await {
+                    pw.write_props(&mpath, Box::new(PrincipalMetaData)).await?;
+                    pw.flush().await?;
+                }
+            } else if is_dir
                 && (depth == davheaders::Depth::One
                     || depth == davheaders::Depth::Default
                     || depth == davheaders::Depth::Infinity)
             {
-                self.propfind_directory(&path, depth, &mut pw).await?;
+                // resource-ids of the collections we are in, to detect loops.
+                let mut ancestors = Vec::new();
+                if depth == davheaders::Depth::Infinity
+                    && let Ok(id) = self.fs.resource_id(&path, &self.credentials).await
+                {
+                    ancestors.push(id);
+                }
+                self.propfind_directory(&path, depth, apply_to_refs, &mut pw, &mut ancestors)
+                    .await?;
             }
             pw.close().await?;
 
@@ -373,14 +437,12 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         &'a self,
         path: &'a DavPath,
         depth: davheaders::Depth,
+        apply_to_refs: bool,
         propwriter: &'a mut PropWriter<C>,
+        ancestors: &'a mut Vec<String>,
     ) -> BoxFuture<'a, DavResult<()>> {
         async move {
-            let mut entries = match self
-                .fs
-                .read_dir(path, self.get_read_dir_meta(), &self.credentials)
-                .await
-            {
+            let mut entries = match self.read_dir_ordered(path, self.get_read_dir_meta()).await {
                 Ok(entries) => entries,
                 Err(e) => {
                     // if we cannot read_dir, just skip it.
@@ -414,18 +476,58 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     }
                 };
                 if meta.is_symlink() {
-                    continue;
+                    if !self.redirectrefs {
+                        continue;
+                    }
+                    // RFC 4437 9.1: unless asked for the redirect reference
+                    // itself, only report where it points to.
+                    if !apply_to_refs {
+                        if let Ok(r) = self.fs.redirectref(&npath, &self.credentials).await {
+                            let status = match r.permanent {
+                                true => StatusCode::MOVED_PERMANENTLY,
+                                false => StatusCode::FOUND,
+                            };
+                            propwriter.write_location_response(&npath, status, &r.target)?;
+                            propwriter.flush().await?;
+                        }
+                        continue;
+                    }
                 }
                 let is_dir = meta.is_dir();
                 if is_dir {
                     npath.add_slash();
                 }
+                // members we cannot read are left out.
+                if !self.has_privilege(&npath, DavPrivilege::Read).await {
+                    continue;
+                }
+                // RFC 5842 loop detection: a collection that is also one
+                // of its own ancestors is reported, but not descended into.
+                let recurse = depth == davheaders::Depth::Infinity && is_dir;
+                let id = match recurse {
+                    true => self.fs.resource_id(&npath, &self.credentials).await.ok(),
+                    false => None,
+                };
+                if let Some(id) = &id
+                    && ancestors.contains(id)
+                {
+                    propwriter.write_error_response(
+                        &npath,
+                        StatusCode::LOOP_DETECTED,
+                        Element::new2("D:loop-detected"),
+                    )?;
+                    propwriter.flush().await?;
+                    continue;
+                }
                 propwriter.write_props(&npath, meta).await?;
                 propwriter.flush().await?;
                 // For Depth::Default, treat it like Depth::One (no recursion)
                 // Only recurse for Depth::Infinity
-                if depth == davheaders::Depth::Infinity && is_dir {
-                    self.propfind_directory(&npath, depth, propwriter).await?;
+                if recurse {
+                    ancestors.push(id.unwrap_or_default());
+                    self.propfind_directory(&npath, depth, apply_to_refs, propwriter, ancestors)
+                        .await?;
+                    ancestors.pop();
                 }
             }
             Ok(())
@@ -435,9 +537,10 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
 
     // set/change a live property. returns StatusCode::CONTINUE if
     // this wasnt't  a live property (or, if we want it handled
-    // as a dead property, e.g. DAV:displayname).
+    // as a dead property, e.g. DAV:displayname). StatusCode::OK means
+    // that it still has to be applied with liveprop_apply().
     #[cfg(feature = "proppatch")]
-    fn liveprop_set(&self, prop: &Element, can_deadprop: bool) -> StatusCode {
+    pub(crate) fn liveprop_set(&self, prop: &Element, can_deadprop: bool) -> StatusCode {
         match prop.namespace.as_deref() {
             Some(NS_DAV_URI) => {
                 match prop.name.as_str() {
@@ -468,29 +571,25 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                         }
                     }
                     "getlastmodified" => {
-                        // we might allow setting modified time
-                        // by using utimes() on unix. Not yet though.
-                        if prop.get_text().is_none() || prop.has_child_elems() {
-                            return StatusCode::CONFLICT;
+                        let text = prop.get_text().filter(|_| !prop.has_child_elems());
+                        match text.and_then(|t| httpdate_to_systemtime(&t)) {
+                            Some(_) => StatusCode::OK,
+                            None => StatusCode::CONFLICT,
                         }
-                        StatusCode::FORBIDDEN
                     }
                     _ => StatusCode::FORBIDDEN,
                 }
             }
-            Some(NS_APACHE_URI) => {
-                match prop.name.as_str() {
-                    "executable" => {
-                        // we could allow toggling the execute bit.
-                        // to be implemented.
-                        if prop.get_text().is_none() || prop.has_child_elems() {
-                            return StatusCode::CONFLICT;
-                        }
-                        StatusCode::FORBIDDEN
+            Some(NS_APACHE_URI) => match prop.name.as_str() {
+                "executable" => {
+                    let text = prop.get_text().filter(|_| !prop.has_child_elems());
+                    match text.as_deref().map(str::trim) {
+                        Some("T") | Some("F") => StatusCode::OK,
+                        _ => StatusCode::CONFLICT,
                     }
-                    _ => StatusCode::FORBIDDEN,
                 }
-            }
+                _ => StatusCode::FORBIDDEN,
+            },
             Some(NS_MS_URI) => {
                 match prop.name.as_str() {
                     "Win32CreationTime"
@@ -508,10 +607,61 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     _ => StatusCode::FORBIDDEN,
                 }
             }
+            Some(NS_DAV_SERVER_URI) => StatusCode::FORBIDDEN,
             _ => StatusCode::CONTINUE,
         }
     }
 
+    // Change a live property that liveprop_set() accepted. The Win32
+    // properties always succeed, even if the filesystem cannot change
+    // them -- makes the windows webdav client work.
+    #[cfg(feature = "proppatch")]
+    pub(crate) async fn liveprop_apply(
+        &self,
+        path: &DavPath,
+        meta: &dyn DavMetaData,
+        prop: &Element,
+    ) -> StatusCode {
+        let text = prop.get_text().unwrap_or_default();
+        let time = httpdate_to_systemtime(&text);
+        let fs = &self.fs;
+        let creds = &self.credentials;
+        let ns = prop.namespace.as_deref();
+        let res = match (ns, prop.name.as_str(), time) {
+            (Some(NS_DAV_URI), "getlastmodified", Some(tm))
+            | (Some(NS_MS_URI), "Win32LastModifiedTime", Some(tm)) => {
+                fs.set_modified(path, tm, creds).await
+            }
+            (Some(NS_MS_URI), "Win32LastAccessTime", Some(tm)) => {
+                fs.set_accessed(path, tm, creds).await
+            }
+            (Some(NS_MS_URI), "Win32CreationTime", Some(tm)) => {
+                fs.set_created(path, tm, creds).await
+            }
+            (Some(NS_APACHE_URI), "executable", _) => {
+                fs.set_executable(path, text.trim() == "T", creds).await
+            }
+            // only the readonly bit can be changed, and only on files:
+            // on directories it means "all files in the directory".
+            (Some(NS_MS_URI), "Win32FileAttributes", _) if meta.is_file() => {
+                match u32::from_str_radix(text.trim(), 16) {
+                    Ok(attrs) => fs.set_readonly(path, attrs & 0x0001 != 0, creds).await,
+                    Err(_) => Err(FsError::GeneralFailure),
+                }
+            }
+            _ => Ok(()),
+        };
+        match res {
+            Ok(()) => StatusCode::OK,
+            Err(_) if ns == Some(NS_MS_URI) => StatusCode::OK,
+            Err(FsError::NotImplemented) | Err(FsError::Forbidden) => StatusCode::FORBIDDEN,
+            Err(e) => {
+                debug!("liveprop_apply {path}: {}: {e:?}", prop.name);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
     // In general, live properties cannot be removed, with the
     // exception of getcontentlanguage and displayname.
     #[cfg(feature = "proppatch")]
@@ -527,7 +677,9 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 }
                 _ => StatusCode::FORBIDDEN,
             },
-            Some(NS_APACHE_URI) | Some(NS_MS_URI) => StatusCode::FORBIDDEN,
+            Some(NS_APACHE_URI) | Some(NS_MS_URI) | Some(NS_DAV_SERVER_URI) => {
+                StatusCode::FORBIDDEN
+            }
             _ => StatusCode::CONTINUE,
         }
     }
@@ -581,6 +733,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         }
 
         let mut patch = Vec::new();
+        let mut live = Vec::new();
         let mut ret = Vec::new();
         let can_deadprop = self.fs.have_props(&path, &self.credentials).await;
 
@@ -596,6 +749,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 match elem.name.as_str() {
                     "set" => match self.liveprop_set(n, can_deadprop) {
                         StatusCode::CONTINUE => patch.push((true, element_to_davprop_full(n))),
+                        StatusCode::OK => live.push(n),
                         s => ret.push((s, element_to_davprop(n))),
                     },
                     "remove" => match self.liveprop_remove(n, can_deadprop) {
@@ -624,13 +778,29 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                     .into_iter()
                     .map(|(_, p)| (StatusCode::FAILED_DEPENDENCY, p)),
             );
-        } else if !patch.is_empty() {
+            ret.extend(
+                live.into_iter()
+                    .map(|n| (StatusCode::FAILED_DEPENDENCY, element_to_davprop(n))),
+            );
+        } else {
             // hmmm ... we assume nothing goes wrong here at the
             // moment. if it does, we should roll back the earlier
-            // made changes to live props, but come on, we're not
+            // made changes, but come on, we're not
             // builing a transaction engine here.
-            let deadret = self.fs.patch_props(&path, patch, &self.credentials).await?;
-            ret.extend(deadret);
+            if !patch.is_empty() {
+                let deadret = self.fs.patch_props(&path, patch, &self.credentials).await?;
+                ret.extend(deadret);
+            }
+            // the live properties go last, storing the dead properties
+            // might change the timestamps.
+            let ok = ret.iter().all(|(s, _)| s == &StatusCode::OK);
+            for n in live {
+                let status = match ok {
+                    true => self.liveprop_apply(&path, meta.as_ref(), n).await,
+                    false => StatusCode::FAILED_DEPENDENCY,
+                };
+                ret.push((status, element_to_davprop(n)));
+            }
         }
 
         // group by statuscode.
@@ -641,6 +811,20 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             v.push(davprop_to_element(prop));
         }
 
+        // RFC 8144: with return=minimal, a PROPPATCH that fully succeeded
+        // does not need a multistatus body.
+        let minimal = req
+            .headers()
+            .typed_get::<davheaders::Prefer>()
+            .is_some_and(|p| p.return_minimal);
+        if minimal && hm.keys().all(|s| *s == StatusCode::OK) {
+            res.headers_mut()
+                .typed_insert(davheaders::PreferenceApplied("return=minimal".to_string()));
+            res.headers_mut().typed_insert(headers::ContentLength(0));
+            *res.status_mut() = StatusCode::OK;
+            return Ok(res);
+        }
+
         // And reply.
         let mut pw = PropWriter::new(
             req,
@@ -649,6 +833,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             Vec::new(),
             self.fs.clone(),
             None,
+            None,
             self.principal.clone(),
             self.credentials,
             #[cfg(any(feature = "caldav", feature = "carddav"))]
@@ -675,6 +860,7 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         mut props: Vec<Element>,
         fs: Box<dyn GuardedFileSystem<C>>,
         ls: Option<&Box<dyn DavLockSystem>>,
+        acl: Option<&Box<dyn DavAccessControl>>,
         principal: Option<String>,
         credentials: C,
         #[cfg(any(feature = "caldav", feature = "carddav"))] dav_path: &DavPath,
@@ -726,6 +912,18 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
             props.append(&mut v);
         }
 
+        // RFC 8144: with return=minimal, the properties that were asked
+        // for but that we do not have are left out.
+        let minimal = name == "prop"
+            && req
+                .headers()
+                .typed_get::<davheaders::Prefer>()
+                .is_some_and(|p| p.return_minimal);
+        if minimal {
+            res.headers_mut()
+                .typed_insert(davheaders::PreferenceApplied("return=minimal".to_string()));
+        }
+
         // check the prop namespaces to see what namespaces
         // we need to put in the preamble.
         let mut ev = XmlWEvent::start_element("D:multistatus").ns("D", NS_DAV_URI);
@@ -792,10 +990,14 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
             props,
             fs,
             ls: ls.cloned(),
+            acl: acl.cloned(),
             useragent: ua.to_string(),
             q_cache: Default::default(),
             credentials,
             principal,
+            minimal,
+            #[cfg(feature = "caldav")]
+            calendar_users: Vec::new(),
         })
     }
 
@@ -803,6 +1005,35 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         self.tx = Some(tx)
     }
 
+    // for the scheduling properties (RFC 6638).
+    #[cfg(feature = "caldav")]
+    pub fn set_calendar_users(&mut self, users: Vec<CalendarUser>) {
+        self.calendar_users = users
+    }
+
+    // the calendar user whose principal this is.
+    #[cfg(feature = "caldav")]
+    fn calendar_user(&self, path: &DavPath) -> Option<&CalendarUser> {
+        let href = path.with_prefix().as_url_string();
+        self.calendar_users
+            .iter()
+            .find(|u| same_href(&u.principal, &href))
+    }
+
+    // the resource type of a scheduling inbox or outbox.
+    #[cfg(feature = "caldav")]
+    fn schedule_box_type(&self, path: &DavPath) -> Option<&'static str> {
+        self.calendar_users.iter().find_map(|u| {
+            if same_path(path, &u.inbox) {
+                Some("C:schedule-inbox")
+            } else if same_path(path, &u.outbox) {
+                Some("C:schedule-outbox")
+            } else {
+                None
+            }
+        })
+    }
+
     fn build_elem<T>(
         &self,
         content: bool,
@@ -880,10 +1111,38 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
             Some(NS_DAV_URI) => {
                 pfx = "D";
                 match prop.name.as_str() {
-                    #[cfg(feature = "caldav")]
                     "supported-report-set" => {
+                        let report = |pfx: &str, name: &str| {
+                            Element::new3(
+                                "D",
+                                "supported-report",
+                                vec![Element::new3(
+                                    "D",
+                                    "report",
+                                    vec![Element::new3(pfx, name, vec![])],
+                                )],
+                            )
+                        };
+                        #[allow(unused_mut)]
                         let mut ns: xmltree::Namespace = xmltree::Namespace::empty();
-                        ns.put("C".to_string(), NS_CALDAV_URI.to_string());
+                        let mut reports = Vec::new();
+                        if meta.is_dir()
+                            && self.fs.sync_token(path, &self.credentials).await.is_ok()
+                        {
+                            reports.push(report("D", "sync-collection"));
+                        }
+                        if !meta.is_dir()
+                            && let Ok(Some(_)) =
+                                self.fs.version_history(path, &self.credentials).await
+                        {
+                            reports.push(report("D", "version-tree"));
+                        }
+                        #[cfg(feature = "caldav")]
+                        {
+                            ns.put("C".to_string(), NS_CALDAV_URI.to_string());
+                            reports.push(report("C", "calendar-query"));
+                            reports.push(report("C", "calendar-multiget"));
+                        }
 
                         return Ok(StatusElement {
                             status: StatusCode::OK,
@@ -893,29 +1152,7 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                                 namespaces: Some(ns),
                                 name: "supported-report-set".to_string(),
                                 attributes: HashMap::new(),
-                                children: vec![
-                                    Element::new3(
-                                        "D",
-                                        "supported-report",
-                                        vec![Element::new3(
-                                            "D",
-                                            "report",
-                                            vec![Element::new3("C", "calendar-query", vec![])],
-                                        )],
-                                    ),
-                                    Element::new3(
-                                        "D",
-                                        "supported-report",
-                                        vec![Element::new3(
-                                            "D",
-                                            "report",
-                                            vec![Element::new3("C", "calendar-multiget", vec![])],
-                                        )],
-                                    ),
-                                ]
-                                .into_iter()
-                                .map(XMLNode::Element)
-                                .collect(),
+                                children: reports.into_iter().map(XMLNode::Element).collect(),
                             },
                         });
                     }
@@ -967,7 +1204,9 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                             elem.children.push(XMLNode::Element(dir));
 
                             #[cfg(feature = "caldav")]
-                            if meta.is_calendar(path) {
+                            if let Some(name) = self.schedule_box_type(path) {
+                                elem.children.push(XMLNode::Element(Element::new2(name)));
+                            } else if meta.is_calendar(path) {
                                 let calendar = Element::new2("C:calendar");
                                 elem.children.push(XMLNode::Element(calendar));
                             }
@@ -977,6 +1216,15 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                                 let addressbook = Element::new2("CARD:addressbook");
                                 elem.children.push(XMLNode::Element(addressbook));
                             }
+
+                            if self.is_principal(path).await {
+                                let principal = Element::new2("D:principal");
+                                elem.children.push(XMLNode::Element(principal));
+                            }
+                        }
+                        if meta.is_symlink() && docontent {
+                            let redirectref = Element::new2("D:redirectref");
+                            elem.children.push(XMLNode::Element(redirectref));
                         }
                         return Ok(StatusElement {
                             status: StatusCode::OK,
@@ -1008,6 +1256,177 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                             element: list_lockdiscovery(self.ls.as_ref(), path).await,
                         });
                     }
+                    "acl" | "current-user-privilege-set" if self.acl.is_some() => {
+                        let acl = self.acl.as_ref().unwrap();
+                        let principal = self.principal.as_deref();
+                        let privileges = acl.privileges(principal, path).await;
+                        let needed = if prop.name == "acl" {
+                            DavPrivilege::ReadAcl
+                        } else {
+                            DavPrivilege::ReadCurrentUserPrivilegeSet
+                        };
+                        if !privileges.contains(needed) {
+                            return Ok(StatusElement {
+                                status: StatusCode::FORBIDDEN,
+                                element: self.build_elem(false, pfx, prop, "")?.element,
+                            });
+                        }
+                        let element = if prop.name == "acl" {
+                            acl_element(&acl.acl(path).await)
+                        } else {
+                            privilege_set_element(privileges)
+                        };
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element,
+                        });
+                    }
+                    "owner" if self.acl.is_some() => {
+                        if let Some(owner) = self.acl.as_ref().unwrap().owner(path).await {
+                            let href = Element::new2("D:href").text(owner);
+                            return Ok(StatusElement {
+                                status: StatusCode::OK,
+                                element: Element::new3("D", "owner", vec![href]),
+                            });
+                        }
+                    }
+                    "principal-collection-set" if self.acl.is_some() => {
+                        let hrefs = self.acl.as_ref().unwrap().principal_collections().await;
+                        let hrefs = hrefs
+                            .into_iter()
+                            .map(|h| Element::new2("D:href").text(h))
+                            .collect();
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element: Element::new3("D", "principal-collection-set", hrefs),
+                        });
+                    }
+                    "principal-URL" if self.is_principal(path).await => {
+                        let href = Element::new2("D:href").text(path.with_prefix().as_url_string());
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element: Element::new3("D", "principal-URL", vec![href]),
+                        });
+                    }
+                    "supported-privilege-set" if self.acl.is_some() => {
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element: supported_privilege_set_element(),
+                        });
+                    }
+                    "acl-restrictions" if self.acl.is_some() => {
+                        let restrictions = vec![Element::new2("D:no-invert")];
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element: Element::new3("D", "acl-restrictions", restrictions),
+                        });
+                    }
+                    "checked-in" | "checked-out" | "auto-version" if !meta.is_dir() => {
+                        if let Ok(Some(h)) = self.fs.version_history(path, &self.credentials).await
+                        {
+                            let children = match prop.name.as_str() {
+                                "auto-version" => vec![Element::new2("D:checkout-checkin")],
+                                "checked-out" if !h.checked_out => vec![],
+                                "checked-in" if h.checked_out => vec![],
+                                _ => h
+                                    .versions
+                                    .last()
+                                    .map(|v| {
+                                        Element::new2("D:href").text(version_href(path, &v.name))
+                                    })
+                                    .into_iter()
+                                    .collect(),
+                            };
+                            if !children.is_empty() {
+                                return Ok(StatusElement {
+                                    status: StatusCode::OK,
+                                    element: Element::new3("D", &prop.name, children),
+                                });
+                            }
+                        }
+                    }
+                    "resource-id" => {
+                        if let Ok(id) = self.fs.resource_id(path, &self.credentials).await {
+                            return Ok(StatusElement {
+                                status: StatusCode::OK,
+                                element: Element::new3(
+                                    "D",
+                                    "resource-id",
+                                    vec![Element::new2("D:href").text(id)],
+                                ),
+                            });
+                        }
+                    }
+                    "parent-set" => {
+                        if let Ok(bindings) = self.fs.bindings(path, &self.credentials).await {
+                            let parents = bindings
+                                .into_iter()
+                                .filter_map(|mut b| {
+                                    b.add_prefix(path.prefix());
+                                    let url = b.with_prefix().as_url_string();
+                                    let segment = url.trim_end_matches('/').rsplit('/').next()?;
+                                    if segment.is_empty() {
+                                        return None;
+                                    }
+                                    let parent = b.parent().with_prefix().as_url_string();
+                                    Some(Element::new3(
+                                        "D",
+                                        "parent",
+                                        vec![
+                                            Element::new2("D:href").text(parent),
+                                            Element::new2("D:segment").text(segment),
+                                        ],
+                                    ))
+                                })
+                                .collect();
+                            return Ok(StatusElement {
+                                status: StatusCode::OK,
+                                element: Element::new3("D", "parent-set", parents),
+                            });
+                        }
+                    }
+                    "reftarget" | "redirect-lifetime" if meta.is_symlink() => {
+                        if let Ok(r) = self.fs.redirectref(path, &self.credentials).await {
+                            let child = match prop.name.as_str() {
+                                "reftarget" => Element::new2("D:href").text(r.target),
+                                _ if r.permanent => Element::new2("D:permanent"),
+                                _ => Element::new2("D:temporary"),
+                            };
+                            return Ok(StatusElement {
+                                status: StatusCode::OK,
+                                element: Element::new3("D", &prop.name, vec![child]),
+                            });
+                        }
+                    }
+                    "ordering-type" if meta.is_dir() => {
+                        let order = member_order(self.fs.as_ref(), path, &self.credentials).await;
+                        let ordering_type =
+                            order.map_or(UNORDERED.to_string(), |o| o.ordering_type);
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element: Element::new3(
+                                "D",
+                                "ordering-type",
+                                vec![Element::new2("D:href").text(ordering_type)],
+                            ),
+                        });
+                    }
+                    "sync-token" if meta.is_dir() => {
+                        if let Ok(token) = self.fs.sync_token(path, &self.credentials).await {
+                            return self.build_elem(docontent, pfx, prop, token);
+                        }
+                    }
+                    // the Microsoft extensions, 1 or 0.
+                    "ishidden" => {
+                        let hidden = is_hidden(meta, path) as u8;
+                        return self.build_elem(docontent, pfx, prop, hidden.to_string());
+                    }
+                    "isreadonly" => {
+                        if let Ok(readonly) = meta.readonly() {
+                            let readonly = readonly as u8;
+                            return self.build_elem(docontent, pfx, prop, readonly.to_string());
+                        }
+                    }
                     "quota-available-bytes" => {
                         if let Ok((_, Some(avail))) = self.get_quota(qc, meta).await {
                             return self.build_elem(docontent, pfx, prop, avail.to_string());
@@ -1028,6 +1447,21 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                     _ => {}
                 }
             }
+            Some(NS_OWNCLOUD_URI) => {
+                pfx = "oc";
+                if prop.name == "checksums"
+                    && !meta.is_dir()
+                    && let Ok(checksums) = self.fs.checksums(path, &self.credentials).await
+                {
+                    let mut res = self.build_elem(false, pfx, prop, "")?;
+                    if docontent {
+                        let checksum = Element::new2("oc:checksum").text(checksums);
+                        res.element.children.push(XMLNode::Element(checksum));
+                    }
+                    return Ok(res);
+                }
+                try_deadprop = true;
+            }
             Some(NS_APACHE_URI) => {
                 pfx = "A";
                 if prop.name.as_str() == "executable"
@@ -1041,15 +1475,33 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
             Some(NS_CALDAV_URI) => {
                 pfx = "C";
 
+                // the scheduling properties of a calendar user (RFC 6638).
+                if let Some(user) = self.calendar_user(path) {
+                    let href =
+                        |p: &str| Element::new2("D:href").text(format!("{}{p}", path.prefix()));
+                    let hrefs = match prop.name.as_str() {
+                        "calendar-home-set" => Some(vec![href(&user.home)]),
+                        "calendar-user-address-set" => {
+                            Some(vec![Element::new2("D:href").text(user.address.clone())])
+                        }
+                        "schedule-inbox-URL" => Some(vec![href(&user.inbox)]),
+                        "schedule-outbox-URL" => Some(vec![href(&user.outbox)]),
+                        _ => None,
+                    };
+                    if let Some(hrefs) = hrefs {
+                        return Ok(StatusElement {
+                            status: StatusCode::OK,
+                            element: Element::new3("C", &prop.name, hrefs),
+                        });
+                    }
+                }
+
                 if meta.is_calendar(path) {
                     match prop.name.as_str() {
                         "supported-calendar-component-set" => {
-                            let components = vec![
-                                CalendarComponentType::VEvent,
-                                CalendarComponentType::VTodo,
-                                CalendarComponentType::VJournal,
-                                CalendarComponentType::VFreeBusy,
-                            ];
+                            let components =
+                                calendar_components(self.fs.as_ref(), path, &self.credentials)
+                                    .await;
                             let elem = create_supported_calendar_component_set(&components);
                             return Ok(StatusElement {
                                 status: StatusCode::OK,
@@ -1083,8 +1535,8 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                             return self.build_elem(docontent, pfx, prop, timezone);
                         }
                         "max-resource-size" => {
-                            return self.build_elem(docontent, pfx, prop, "1048576");
-                            // 1MB
+                            let size = DEFAULT_MAX_CALENDAR_RESOURCE_SIZE.to_string();
+                            return self.build_elem(docontent, pfx, prop, size);
                         }
                         "min-date-time" => {
                             return self.build_elem(docontent, pfx, prop, "19000101T000000Z");
@@ -1165,11 +1617,12 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
                     }
                     "Win32FileAttributes" => {
                         let mut attr = 0u32;
-                        // Enable when we implement permissions() on DavMetaData.
-                        //if meta.permissions().readonly() {
-                        //    attr |= 0x0001;
-                        //}
-                        if path.file_name_bytes().starts_with(b".") {
+                        // readonly on directories means "all files in the
+                        // directory", so it is only set on files.
+                        if meta.is_file() && meta.readonly().unwrap_or(false) {
+                            attr |= 0x0001;
+                        }
+                        if is_hidden(meta, path) {
                             attr |= 0x0002;
                         }
                         if meta.is_dir() {
@@ -1216,6 +1669,21 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         })
     }
 
+    // is this resource a principal.
+    async fn is_principal(&self, path: &DavPath) -> bool {
+        #[cfg(feature = "caldav")]
+        if self.calendar_user(path).is_some() {
+            return true;
+        }
+        match self.acl {
+            Some(ref acl) => {
+                let href = path.with_prefix().as_url_string();
+                acl.principals().await.iter().any(|p| same_href(p, &href))
+            }
+            None => false,
+        }
+    }
+
     pub async fn write_props<'a>(
         &'a mut self,
         path: &'a DavPath,
@@ -1227,12 +1695,20 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         // Get properties one-by-one
         let do_content = self.name != "propname";
         let mut qc = self.q_cache;
+        let mut missing = Vec::new();
         for p in &self.props {
             let res = self
                 .build_prop(p, path, &*meta, &mut qc, do_content)
                 .await?;
-            if res.status == StatusCode::OK {
-                add_sc_elem(&mut props, res.status, res.element);
+            match res.status {
+                StatusCode::OK | StatusCode::FORBIDDEN => {
+                    add_sc_elem(&mut props, res.status, res.element)
+                }
+                // only report what was asked for by name.
+                StatusCode::NOT_FOUND if self.name == "prop" && !self.minimal => {
+                    missing.push(res.element)
+                }
+                _ => {}
             }
         }
         self.q_cache = qc;
@@ -1266,10 +1742,22 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
             && let Ok(v) = self.fs.get_props(path, true, &self.credentials).await
         {
             v.into_iter()
+                .filter(|p| p.namespace.as_deref() != Some(NS_DAV_SERVER_URI))
                 .map(davprop_to_element)
                 .for_each(|e| add_sc_elem(&mut props, StatusCode::OK, e));
         }
 
+        // properties that were added above are not missing after all.
+        let found = props.get(&StatusCode::OK).cloned().unwrap_or_default();
+        missing
+            .into_iter()
+            .filter(|m| {
+                !found
+                    .iter()
+                    .any(|e| e.name == m.name && e.namespace == m.namespace)
+            })
+            .for_each(|e| add_sc_elem(&mut props, StatusCode::NOT_FOUND, e));
+
         self.write_propresponse(path, props)
     }
 
@@ -1278,10 +1766,19 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         path: &DavPath,
         props: HashMap<StatusCode, Vec<Element>>,
     ) -> Result<(), DavError> {
-        self.emitter.write(XmlWEvent::start_element("D:response"))?;
         let p = path.with_prefix().as_url_string();
+        self.write_href_propresponse(&p, props)
+    }
+
+    // like write_propresponse, for resources that have no DavPath (versions).
+    pub(crate) fn write_href_propresponse(
+        &mut self,
+        href: &str,
+        props: HashMap<StatusCode, Vec<Element>>,
+    ) -> Result<(), DavError> {
+        self.emitter.write(XmlWEvent::start_element("D:response"))?;
         Element::new2("D:href")
-            .text(p)
+            .text(href)
             .write_ev(&mut self.emitter)?;
 
         let mut keys = props.keys().collect::<Vec<_>>();
@@ -1305,6 +1802,74 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         Ok(())
     }
 
+    // a response with just a status, e.g. for removed members.
+    pub(crate) fn write_status_response(
+        &mut self,
+        path: &DavPath,
+        status: StatusCode,
+    ) -> DavResult<()> {
+        self.emitter.write(XmlWEvent::start_element("D:response"))?;
+        Element::new2("D:href")
+            .text(path.with_prefix().as_url_string())
+            .write_ev(&mut self.emitter)?;
+        Element::new2("D:status")
+            .text("HTTP/1.1 ".to_string() + &status.to_string())
+            .write_ev(&mut self.emitter)?;
+        self.emitter.write(XmlWEvent::end_element())?; // response
+        Ok(())
+    }
+
+    // a response with a status and a DAV:error, e.g. for a binding loop.
+    pub(crate) fn write_error_response(
+        &mut self,
+        path: &DavPath,
+        status: StatusCode,
+        error: Element,
+    ) -> DavResult<()> {
+        self.emitter.write(XmlWEvent::start_element("D:response"))?;
+        Element::new2("D:href")
+            .text(path.with_prefix().as_url_string())
+            .write_ev(&mut self.emitter)?;
+        Element::new2("D:status")
+            .text("HTTP/1.1 ".to_string() + &status.to_string())
+            .write_ev(&mut self.emitter)?;
+        Element::new3("D", "error", vec![error]).write_ev(&mut self.emitter)?;
+        self.emitter.write(XmlWEvent::end_element())?; // response
+        Ok(())
+    }
+
+    // a response for a redirect reference, with its target.
+    pub(crate) fn write_location_response(
+        &mut self,
+        path: &DavPath,
+        status: StatusCode,
+        location: &str,
+    ) -> DavResult<()> {
+        self.emitter.write(XmlWEvent::start_element("D:response"))?;
+        Element::new2("D:href")
+            .text(path.with_prefix().as_url_string())
+            .write_ev(&mut self.emitter)?;
+        Element::new2("D:status")
+            .text("HTTP/1.1 ".to_string() + &status.to_string())
+            .write_ev(&mut self.emitter)?;
+        Element::new3(
+            "D",
+            "location",
+            vec![Element::new2("D:href").text(location)],
+        )
+        .write_ev(&mut self.emitter)?;
+        self.emitter.write(XmlWEvent::end_element())?; // response
+        Ok(())
+    }
+
+    // the sync-token at the end of a sync-collection report.
+    pub(crate) fn write_sync_token(&mut self, token: &str) -> DavResult<()> {
+        Element::new2("D:sync-token")
+            .text(token)
+            .write_ev(&mut self.emitter)?;
+        Ok(())
+    }
+
     pub async fn flush(&mut self) -> DavResult<()> {
         let buffer = self.emitter.inner_mut().take();
         self.tx.as_mut().unwrap().send(buffer).await;
@@ -1356,7 +1921,11 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
     }
 
     #[cfg(feature = "caldav")]
-    pub(crate) fn write_calendar_not_found_response(&mut self, href: &str) -> DavResult<()> {
+    pub(crate) fn write_calendar_status_response(
+        &mut self,
+        href: &str,
+        status: StatusCode,
+    ) -> DavResult<()> {
         self.emitter.write(XmlWEvent::start_element("D:response"))?;
 
         Element::new2("D:href")
@@ -1366,7 +1935,7 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         self.emitter.write(XmlWEvent::start_element("D:propstat"))?;
 
         Element::new2("D:status")
-            .text("HTTP/1.1 404 Not Found".to_string())
+            .text("HTTP/1.1 ".to_string() + &status.to_string())
             .write_ev(&mut self.emitter)?;
 
         self.emitter.write(XmlWEvent::end_element())?; // D:propstat
@@ -1415,7 +1984,11 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
     }
 
     #[cfg(feature = "carddav")]
-    pub(crate) fn write_vcard_not_found_response(&mut self, href: &str) -> DavResult<()> {
+    pub(crate) fn write_vcard_status_response(
+        &mut self,
+        href: &str,
+        status: StatusCode,
+    ) -> DavResult<()> {
         self.emitter.write(XmlWEvent::start_element("D:response"))?;
 
         Element::new2("D:href")
@@ -1425,7 +1998,7 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
         self.emitter.write(XmlWEvent::start_element("D:propstat"))?;
 
         Element::new2("D:status")
-            .text("HTTP/1.1 404 Not Found".to_string())
+            .text("HTTP/1.1 ".to_string() + &status.to_string())
             .write_ev(&mut self.emitter)?;
 
         self.emitter.write(XmlWEvent::end_element())?; // D:propstat
@@ -1435,13 +2008,19 @@ impl<C: Clone + Send + Sync + 'static> PropWriter<C> {
     }
 }
 
+// Filesystems that do not know follow the unix convention.
+fn is_hidden(meta: &dyn DavMetaData, path: &DavPath) -> bool {
+    meta.hidden(path)
+        .unwrap_or_else(|_| path.file_name_bytes().starts_with(b"."))
+}
+
 fn add_sc_elem(hm: &mut HashMap<StatusCode, Vec<Element>>, sc: StatusCode, e: Element) {
     hm.entry(sc).or_default();
     hm.get_mut(&sc).unwrap().push(e)
 }
 
 #[allow(dead_code)]
-fn element_to_davprop_full(elem: &Element) -> DavProp {
+pub(crate) fn element_to_davprop_full(elem: &Element) -> DavProp {
     let mut emitter = EventWriter::new(Cursor::new(Vec::new()));
     elem.write_ev(&mut emitter).ok();
     let xml = emitter.into_inner().into_inner();
@@ -1453,7 +2032,7 @@ fn element_to_davprop_full(elem: &Element) -> DavProp {
     }
 }
 
-fn element_to_davprop(elem: &Element) -> DavProp {
+pub(crate) fn element_to_davprop(elem: &Element) -> DavProp {
     DavProp {
         name: elem.name.clone(),
         prefix: elem.prefix.clone(),
@@ -1462,7 +2041,7 @@ fn element_to_davprop(elem: &Element) -> DavProp {
     }
 }
 
-fn davprop_to_element(prop: DavProp) -> Element {
+pub(crate) fn davprop_to_element(prop: DavProp) -> Element {
     if let Some(xml) = prop.xml {
         match Element::parse2(Cursor::new(xml)) {
             Ok(result) => {
@@ -11,9 +11,11 @@ use http_body::Body as HttpBody;
 use http_body_util::BodyExt;
 
 use crate::body::Body;
+use crate::checksum::{Hasher, format_checksums, parse_digest_fields, parse_oc_checksum};
 use crate::conditional::if_match_get_tokens;
 use crate::davheaders;
 use crate::fs::*;
+use crate::quota::quota_exceeded;
 use crate::{DavError, DavInner, DavResult};
 
 const SABRE: &str = "application/x-sabredav-partialupdate";
@@ -73,6 +75,7 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         let mut oo = OpenOptions::write();
         oo.create = true;
         oo.truncate = true;
+        oo.atomic = self.atomic_put;
 
         if let Some(n) = req.headers().typed_get::<headers::ContentLength>() {
             count = n.0;
@@ -90,12 +93,6 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 oo.size = Some(count);
             }
         }
-        let checksum = req
-            .headers()
-            .get("OC-Checksum")
-            .and_then(|v| v.to_str().ok().map(|s| s.to_string()));
-        oo.checksum = checksum;
-
         let path = self.path(req);
         let meta = self.fs.metadata(&path, &self.credentials).await;
 
@@ -170,6 +167,41 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             Err(_) => return Err(DavError::StatusClose(SC::BAD_REQUEST)),
         }
 
+        // the checksums to verify. Content-Digest is about the body, the
+        // others are about the whole file, and are only checked if the
+        // body is the whole file.
+        let whole = !do_range && !oo.append;
+        let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+        let mut checksums = Vec::new();
+        if whole {
+            checksums.extend(header("OC-Checksum").and_then(parse_oc_checksum));
+            checksums.extend(
+                header("Repr-Digest")
+                    .map(parse_digest_fields)
+                    .unwrap_or_default(),
+            );
+        }
+        checksums.extend(
+            header("Content-Digest")
+                .map(parse_digest_fields)
+                .unwrap_or_default(),
+        );
+        let mut hashers: Vec<Hasher> = Vec::new();
+        for (ctype, _) in &checksums {
+            if !hashers.iter().any(|h| h.ctype() == *ctype) {
+                hashers.push(Hasher::new(*ctype));
+            }
+        }
+        // stored with the file, once they are verified.
+        if whole && !checksums.is_empty() {
+            let stored: Vec<_> = hashers
+                .iter()
+                .filter_map(|h| checksums.iter().find(|(t, _)| *t == h.ctype()).cloned())
+                .collect();
+            oo.checksums = Some(format_checksums(&stored));
+        }
+        oo.checksum = header("OC-Checksum").map(|s| s.to_string());
+
         // check the If and If-* headers.
         let tokens = if_match_get_tokens(
             req,
@@ -211,6 +243,47 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             oo.create_new = true;
         }
 
+        // RFC 3648: a new member can have a place in an ordered collection.
+        let position = req
+            .headers()
+            .typed_try_get::<davheaders::Position>()
+            .map_err(|_| DavError::StatusClose(SC::BAD_REQUEST))?;
+        if meta.is_err()
+            && let Some(mut res) = self.check_position(&path, position.as_ref()).await
+        {
+            res.headers_mut().typed_insert(headers::Connection::close());
+            return Ok(res);
+        }
+
+        // the size of the file must stay within the quota.
+        let old_len = meta.as_ref().map(|m| m.len()).unwrap_or(0);
+        let offset = match oo.append {
+            true => old_len,
+            false => start,
+        };
+        let max_len = self.quota_room(&path).await.map(|room| room + old_len);
+        if let Some(max_len) = max_len
+            && have_count
+            && offset + count > max_len
+        {
+            let mut res = quota_exceeded();
+            res.headers_mut().typed_insert(headers::Connection::close());
+            return Ok(res);
+        }
+        // without a length we only find out halfway. Do not leave an
+        // existing file truncated then, but write to a temporary file.
+        if max_len.is_some() && !have_count && meta.is_ok() {
+            oo.atomic = true;
+        }
+
+        // keep the old content if we're auto-versioning.
+        self.autoversion_before_put(&path, meta.is_ok()).await;
+
+        // the attendees of an event are told what changed (RFC 6638).
+        #[cfg(feature = "caldav")]
+        let scheduled = self.scheduling_object(&path).await;
+
+        let atomic = oo.atomic;
         let create = oo.create;
         let create_new = oo.create_new;
         let mut file = match self.fs.open(&path, oo, &self.credentials).await {
@@ -253,6 +326,17 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             if have_count && total > count {
                 break;
             }
+            if max_len.is_some_and(|max_len| offset + total > max_len) {
+                break;
+            }
+            if !hashers.is_empty() {
+                let bytes = buf.copy_to_bytes(buf.remaining());
+                for hasher in &mut hashers {
+                    hasher.update(&bytes);
+                }
+                file.write_bytes(bytes).await?;
+                continue;
+            }
             // The `Buf` might actually be a `Bytes`.
             let b = {
                 let b: &mut dyn std::any::Any = &mut buf;
@@ -265,7 +349,29 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
                 file.write_buf(Box::new(buf)).await?;
             }
         }
-        file.flush().await?;
+
+        let computed: Vec<_> = hashers
+            .into_iter()
+            .map(|h| (h.ctype(), h.finish()))
+            .collect();
+        let digest_ok = checksums.iter().all(|c| computed.contains(c));
+
+        // an atomic PUT only replaces the file when the body is complete.
+        let complete = !(have_count && total != count)
+            && max_len.is_none_or(|max_len| offset + total <= max_len);
+        if digest_ok && (complete || !atomic) {
+            file.flush().await?;
+        }
+
+        if max_len.is_some_and(|max_len| offset + total > max_len) {
+            error!("PUT file: quota exceeded");
+            if meta.is_err() && !atomic {
+                let _ = self.fs.remove_file(&path, &self.credentials).await;
+            }
+            let mut res = quota_exceeded();
+            res.headers_mut().typed_insert(headers::Connection::close());
+            return Ok(res);
+        }
 
         if have_count && total > count {
             error!("PUT file: sender is sending more bytes than expected");
@@ -277,6 +383,25 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
             return Err(DavError::StatusClose(SC::BAD_REQUEST));
         }
 
+        if !digest_ok {
+            error!("PUT file: checksum mismatch");
+            // do not leave a new file with the wrong content behind.
+            if !atomic && meta.is_err() {
+                let _ = self.fs.remove_file(&path, &self.credentials).await;
+            }
+            return Err(DavError::StatusClose(SC::BAD_REQUEST));
+        }
+
+        self.autoversion_after_put(&path).await;
+        self.apply_position(&path, position, meta.is_err()).await;
+        if meta.is_ok() {
+            self.lock_null_written(&path).await;
+        }
+        #[cfg(feature = "caldav")]
+        let rewritten = self.schedule_after_put(&path, scheduled).await;
+        #[cfg(not(feature = "caldav"))]
+        let rewritten = false;
+
         // Report whether we created or updated the file.
         *res.status_mut() = match meta {
             Ok(_) => SC::NO_CONTENT,
@@ -289,7 +414,18 @@ impl<C: Clone + Send + Sync + 'static> DavInner<C> {
         // no errors, connection may be kept open.
         res.headers_mut().remove(http::header::CONNECTION);
 
-        if let Ok(meta) = file.metadata().await {
+        // RFC 8144: the response to a PUT never has a body.
+        if req
+            .headers()
+            .typed_get::<davheaders::Prefer>()
+            .is_some_and(|p| p.return_minimal)
+        {
+            res.headers_mut()
+                .typed_insert(davheaders::PreferenceApplied("return=minimal".to_string()));
+        }
+
+        // not when the server changed what was stored (RFC 4791, section 5.3.4).
+        if !rewritten && let Ok(meta) = file.metadata().await {
             if let Some(etag) = davheaders::ETag::from_meta(meta.as_ref()) {
                 res.headers_mut().typed_insert(etag);
             }
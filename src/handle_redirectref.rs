@@ -0,0 +1,265 @@
+//
+// RFC 4437, WebDAV Redirect Reference Resources.
+//
+// A redirect reference is stored in the filesystem as a symbolic link.
+// MKREDIRECTREF creates one, UPDATEREDIRECTREF changes it. Requests for a
+// redirect reference, or for anything below it, are answered with a
+// redirect to the target, unless `Apply-To-Redirect-Ref: T` is sent.
+//
+use std::io::Cursor;
+
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::body::Body;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::util::{DavMethod, dav_xml_error_response};
+use crate::xmltree_ext::*;
+use crate::{DavError, DavInner, DavResult};
+
+// The DAV:reftarget and DAV:redirect-lifetime from the request body.
+fn parse_body(method: DavMethod, xmldata: &[u8]) -> DavResult<(Option<String>, Option<bool>)> {
+    let root = Element::parse2(Cursor::new(xmldata))?;
+    let name = match method {
+        DavMethod::MkRedirectRef => "mkredirectref",
+        _ => "updateredirectref",
+    };
+    if root.name != name || root.namespace.as_deref() != Some("DAV:") {
+        return Err(DavError::XmlParseError);
+    }
+    let mut target = None;
+    let mut permanent = None;
+    for elem in root.child_elems_iter() {
+        match elem.name.as_str() {
+            "reftarget" => {
+                let href = elem
+                    .child_elems_iter()
+                    .find(|e| e.name == "href")
+                    .and_then(|e| e.get_text())
+                    .ok_or(DavError::XmlParseError)?;
+                target = Some(href.trim().to_string());
+            }
+            "redirect-lifetime" => {
+                permanent = match elem.child_elems_iter().next().map(|e| e.name.as_str()) {
+                    Some("permanent") => Some(true),
+                    Some("temporary") => Some(false),
+                    _ => return Err(DavError::XmlParseError),
+                };
+            }
+            _ => {}
+        }
+    }
+    Ok((target, permanent))
+}
+
+// A DAV:href can be relative to the request URL. We store absolute URLs
+// as-is, everything else as an absolute path.
+pub(crate) fn resolve_href(base: &DavPath, href: &str) -> Option<String> {
+    if href.is_empty() {
+        return None;
+    }
+    if url::Url::parse(href).is_ok() {
+        return Some(href.to_string());
+    }
+    let base = url::Url::parse("http://localhost")
+        .ok()?
+        .join(&base.with_prefix().as_url_string())
+        .ok()?;
+    let url = base.join(href).ok()?;
+    match url.query() {
+        Some(q) => Some(format!("{}?{}", url.path(), q)),
+        None => Some(url.path().to_string()),
+    }
+}
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // If the path is (or is below) a redirect reference, return the
+    // redirect response.
+    pub(crate) async fn redirect_ref(
+        &self,
+        req: &Request<()>,
+        method: DavMethod,
+        path: &DavPath,
+    ) -> DavResult<Option<Response<Body>>> {
+        if !self.redirectrefs
+            || matches!(
+                method,
+                DavMethod::MkRedirectRef | DavMethod::UpdateRedirectRef
+            )
+            || path.is_star()
+        {
+            return Ok(None);
+        }
+        let apply_to_ref = req
+            .headers()
+            .typed_get::<davheaders::ApplyToRedirectRef>()
+            .is_some_and(|a| a.0);
+
+        // walk down from the root, a redirect reference can be anywhere.
+        let mut paths = vec![path.clone()];
+        while paths.last().unwrap().as_bytes() != b"/" {
+            let parent = paths.last().unwrap().parent();
+            paths.push(parent);
+        }
+        paths.pop();
+        paths.reverse();
+
+        for (idx, p) in paths.iter().enumerate() {
+            let meta = match self.fs.symlink_metadata(p, &self.credentials).await {
+                Ok(meta) => meta,
+                Err(_) => return Ok(None),
+            };
+            if !meta.is_symlink() {
+                continue;
+            }
+            // A redirect reference has no content of its own, so
+            // GET and HEAD are always redirected.
+            let is_get = matches!(method, DavMethod::Get | DavMethod::Head);
+            let is_last = idx == paths.len() - 1;
+            if is_last && apply_to_ref && !is_get {
+                return Ok(None);
+            }
+            let redirect = self.fs.redirectref(p, &self.credentials).await?;
+
+            // a redirect reference higher up: append the rest of the path.
+            let mut location = redirect.target;
+            if !is_last {
+                let url = path.with_prefix().as_url_string();
+                let rest = &url[p.with_prefix().as_url_string().trim_end_matches('/').len()..];
+                location = format!("{}{}", location.trim_end_matches('/'), rest);
+            }
+            let status = match (redirect.permanent, is_get) {
+                (false, true) => StatusCode::FOUND,
+                (false, false) => StatusCode::TEMPORARY_REDIRECT,
+                (true, true) => StatusCode::MOVED_PERMANENTLY,
+                (true, false) => StatusCode::PERMANENT_REDIRECT,
+            };
+            // Redirect-Ref is empty if the request URL is the reference itself.
+            let refhref = match is_last {
+                true => String::new(),
+                false => {
+                    let href = p.with_prefix().as_url_string();
+                    href.trim_end_matches('/').to_string()
+                }
+            };
+            debug!("{method:?} {path}: redirect reference {p} to {location}");
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = status;
+            res.headers_mut().typed_insert(headers::ContentLength(0));
+            res.headers_mut()
+                .typed_insert(davheaders::RedirectRef(refhref));
+            res.headers_mut().insert(
+                "location",
+                location
+                    .parse()
+                    .map_err(|_| DavError::Status(StatusCode::INTERNAL_SERVER_ERROR))?,
+            );
+            return Ok(Some(res));
+        }
+        Ok(None)
+    }
+
+    pub(crate) async fn handle_redirectref(
+        &self,
+        req: &Request<()>,
+        method: DavMethod,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
+        if !self.redirectrefs {
+            return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let path = self.path(req);
+        let (target, permanent) = parse_body(method, xmldata)?;
+        let target = match target {
+            Some(href) => Some(resolve_href(&path, &href).ok_or(DavError::XmlParseError)?),
+            None => None,
+        };
+        let meta = self.fs.symlink_metadata(&path, &self.credentials).await;
+
+        let redirect = if method == DavMethod::MkRedirectRef {
+            if meta.is_ok() {
+                return Ok(dav_xml_error_response(
+                    StatusCode::CONFLICT,
+                    "<D:resource-must-be-null/>",
+                ));
+            }
+            if !self.has_parent(&path).await {
+                return Ok(dav_xml_error_response(
+                    StatusCode::CONFLICT,
+                    "<D:parent-resource-must-be-non-null/>",
+                ));
+            }
+            DavRedirectRef {
+                target: target.ok_or(DavError::XmlParseError)?,
+                permanent: permanent.unwrap_or(false),
+            }
+        } else {
+            if !meta.as_ref().is_ok_and(|m| m.is_symlink()) {
+                return Ok(dav_xml_error_response(
+                    StatusCode::FORBIDDEN,
+                    "<D:must-be-redirectref/>",
+                ));
+            }
+            let current = self.fs.redirectref(&path, &self.credentials).await?;
+            DavRedirectRef {
+                target: target.unwrap_or(current.target),
+                permanent: permanent.unwrap_or(current.permanent),
+            }
+        };
+
+        // check the If and If-* headers.
+        let tokens = match if_match_get_tokens(
+            req,
+            meta.as_ref().ok().map(|m| m.as_ref()),
+            self.fs.as_ref(),
+            &self.ls,
+            &path,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(s.into()),
+        };
+
+        // if locked check if we hold that lock.
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            if let Err(_l) = locksystem
+                .check(&path, principal, false, false, &tokens)
+                .await
+            {
+                return Err(StatusCode::LOCKED.into());
+            }
+        }
+
+        let mut res = Response::new(Body::empty());
+        res.headers_mut().typed_insert(headers::ContentLength(0));
+        let result = if method == DavMethod::MkRedirectRef {
+            *res.status_mut() = StatusCode::CREATED;
+            self.fs
+                .create_redirectref(&path, &redirect, &self.credentials)
+                .await
+        } else {
+            self.fs
+                .update_redirectref(&path, &redirect, &self.credentials)
+                .await
+        };
+        match result {
+            Ok(()) => Ok(res),
+            // the filesystem does not support this kind of redirect.
+            Err(FsError::NotImplemented) if redirect.permanent => {
+                let cond = match method {
+                    DavMethod::MkRedirectRef => "<D:redirect-lifetime-supported/>",
+                    _ => "<D:redirect-lifetime-update-supported/>",
+                };
+                Ok(dav_xml_error_response(StatusCode::FORBIDDEN, cond))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
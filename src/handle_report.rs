@@ -0,0 +1,285 @@
+use std::io::Cursor;
+
+use futures_util::{FutureExt, StreamExt, future::BoxFuture};
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::acl::DavPrivilege;
+use crate::async_stream::AsyncStream;
+use crate::body::Body;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::errors::*;
+use crate::fs::*;
+use crate::handle_props::PropWriter;
+use crate::util::dav_xml_error_response;
+use crate::xmltree_ext::*;
+use crate::{DavInner, DavOptionHide, DavResult};
+
+// A member of a collection in a sync-collection report. No
+// metadata means that it has been removed.
+type SyncMember = (DavPath, Option<Box<dyn DavMetaData>>);
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    /// Handle the REPORT method.
+    ///
+    /// The DAV:sync-collection (RFC 6578) and DAV:version-tree (RFC 3253)
+    /// reports are handled here, everything else is routed to the
+    /// CalDAV / CardDAV handlers.
+    pub(crate) async fn handle_report(
+        &self,
+        req: &Request<()>,
+        body: &[u8],
+    ) -> DavResult<Response<Body>> {
+        if let Ok(root) = Element::parse(Cursor::new(body))
+            && root.namespace.as_deref() == Some("DAV:")
+        {
+            if root.name == "sync-collection" {
+                return self.handle_sync_collection(req, root).await;
+            }
+            if root.name == "version-tree" {
+                return self.handle_version_tree(req, root).await;
+            }
+        }
+
+        #[cfg(feature = "caldav")]
+        let res = self.handle_caldav_report(req, body).await;
+        #[cfg(all(feature = "carddav", not(feature = "caldav")))]
+        let res = self.handle_carddav_report(req, body).await;
+        #[cfg(not(any(feature = "caldav", feature = "carddav")))]
+        let res: DavResult<Response<Body>> =
+            Err(DavError::StatusClose(StatusCode::NOT_IMPLEMENTED));
+
+        // RFC 8144: the CalDAV and CardDAV reports only list the members
+        // of the collection, so depth-noroot is what we do anyway.
+        res.map(|mut res| {
+            let noroot = req.headers().typed_get::<davheaders::Depth>()
+                == Some(davheaders::Depth::One)
+                && req
+                    .headers()
+                    .typed_get::<davheaders::Prefer>()
+                    .is_some_and(|p| p.depth_noroot);
+            if noroot && res.status() == StatusCode::MULTI_STATUS {
+                res.headers_mut()
+                    .typed_insert(davheaders::PreferenceApplied("depth-noroot".to_string()));
+            }
+            res
+        })
+    }
+
+    // RFC 6578, DAV:sync-collection report.
+    async fn handle_sync_collection(
+        &self,
+        req: &Request<()>,
+        root: Element,
+    ) -> DavResult<Response<Body>> {
+        // Depth must be 0, the sync-level element is used instead.
+        if let Some(depth) = req.headers().typed_get::<davheaders::Depth>()
+            && depth != davheaders::Depth::Zero
+        {
+            return Err(StatusCode::BAD_REQUEST.into());
+        }
+
+        let mut res = Response::new(Body::empty());
+        let mut path = self.path(req);
+        let meta = self.visible_metadata(&path).await?;
+        if !meta.is_dir() {
+            return Ok(dav_xml_error_response(
+                StatusCode::FORBIDDEN,
+                "<D:supported-report/>",
+            ));
+        }
+        self.fixpath(&mut res, &mut path, meta);
+
+        let mut token = String::new();
+        let mut deep = false;
+        let mut limit = None;
+        let mut props = Vec::new();
+        for elem in root.child_elems_into_iter() {
+            match elem.name.as_str() {
+                "sync-token" => {
+                    token = elem.get_text().unwrap_or_default().trim().to_string();
+                }
+                "sync-level" => {
+                    deep = match elem.get_text().unwrap_or_default().trim() {
+                        "1" => false,
+                        "infinite" => true,
+                        _ => return Err(DavError::XmlParseError),
+                    };
+                }
+                "limit" => {
+                    let nresults = elem
+                        .get_child("nresults")
+                        .and_then(|e| e.get_text())
+                        .and_then(|t| t.trim().parse::<usize>().ok())
+                        .ok_or(DavError::XmlParseError)?;
+                    limit = Some(nresults);
+                }
+                "prop" => props = elem.take_child_elems(),
+                _ => {}
+            }
+        }
+
+        if deep && !self.allow_infinity_depth {
+            return Ok(dav_xml_error_response(
+                StatusCode::FORBIDDEN,
+                "<D:sync-traversal-supported/>",
+            ));
+        }
+
+        // Without a token, this is the initial sync and we return all members.
+        let (new_token, members) = if token.is_empty() {
+            let new_token = match self.fs.sync_token(&path, &self.credentials).await {
+                Ok(t) => t,
+                Err(FsError::NotImplemented) => {
+                    return Ok(dav_xml_error_response(
+                        StatusCode::FORBIDDEN,
+                        "<D:supported-report/>",
+                    ));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let mut members = Vec::new();
+            self.sync_list_members(&path, deep, &mut members).await?;
+            (new_token, members)
+        } else {
+            let changes = match self
+                .fs
+                .sync_changes(&path, &token, deep, &self.credentials)
+                .await
+            {
+                Ok(c) => c,
+                Err(FsError::Forbidden) | Err(FsError::NotImplemented) => {
+                    return Ok(dav_xml_error_response(
+                        StatusCode::FORBIDDEN,
+                        "<D:valid-sync-token/>",
+                    ));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let mut members = Vec::new();
+            for name in changes.members {
+                if let Some(m) = self.sync_member(&path, &name).await {
+                    members.push(m);
+                }
+            }
+            (changes.token, members)
+        };
+
+        if let Some(limit) = limit
+            && members.len() > limit
+        {
+            return Ok(dav_xml_error_response(
+                StatusCode::INSUFFICIENT_STORAGE,
+                "<D:number-of-matches-within-limits/>",
+            ));
+        }
+
+        let mut pw = PropWriter::new(
+            req,
+            &mut res,
+            "prop",
+            props,
+            self.fs.clone(),
+            self.ls.as_ref(),
+            self.acl.as_ref(),
+            self.principal.clone(),
+            self.credentials.clone(),
+            #[cfg(any(feature = "caldav", feature = "carddav"))]
+            &path,
+        )?;
+
+        *res.body_mut() = Body::from(AsyncStream::new(|tx| async move {
+            pw.set_tx(tx);
+            for (mpath, meta) in members {
+                match meta {
+                    Some(meta) => pw.write_props(&mpath, meta).await?,
+                    None => pw.write_status_response(&mpath, StatusCode::NOT_FOUND)?,
+                }
+                pw.flush().await?;
+            }
+            pw.write_sync_token(&new_token)?;
+            pw.close().await?;
+            Ok(())
+        }));
+
+        Ok(res)
+    }
+
+    // Is this member hidden from listings.
+    fn sync_hidden(&self, name: &[u8]) -> bool {
+        (self.hide_dot_prefix == DavOptionHide::InListings
+            || self.hide_dot_prefix == DavOptionHide::Always)
+            && name.split(|&c| c == b'/').any(|s| s.starts_with(b"."))
+    }
+
+    // Look up a member that was reported as changed by the filesystem.
+    async fn sync_member(&self, coll: &DavPath, name: &[u8]) -> Option<SyncMember> {
+        if self.sync_hidden(name) {
+            return None;
+        }
+        let mut path = coll.clone();
+        path.push_segment(name);
+        let meta = if self.hide_symlinks {
+            self.fs.symlink_metadata(&path, &self.credentials).await
+        } else {
+            self.fs.metadata(&path, &self.credentials).await
+        };
+        match meta {
+            Ok(meta) if meta.is_symlink() => None,
+            Ok(meta) => {
+                path.add_slash_if(meta.is_dir());
+                if !self.has_privilege(&path, DavPrivilege::Read).await {
+                    return None;
+                }
+                Some((path, Some(meta)))
+            }
+            Err(FsError::NotFound) => Some((path, None)),
+            Err(_) => None,
+        }
+    }
+
+    // List all members of a collection, for the initial sync.
+    fn sync_list_members<'a>(
+        &'a self,
+        path: &'a DavPath,
+        deep: bool,
+        members: &'a mut Vec<SyncMember>,
+    ) -> BoxFuture<'a, DavResult<()>> {
+        async move {
+            let mut entries = self
+                .fs
+                .read_dir(path, self.get_read_dir_meta(), &self.credentials)
+                .await?;
+            while let Some(dirent) = entries.next().await {
+                let Ok(dirent) = dirent else {
+                    continue;
+                };
+                let name = dirent.name();
+                if self.sync_hidden(&name) {
+                    continue;
+                }
+                let Ok(meta) = dirent.metadata().await else {
+                    continue;
+                };
+                if meta.is_symlink() {
+                    continue;
+                }
+                let mut npath = path.clone();
+                npath.push_segment(&name);
+                let is_dir = meta.is_dir();
+                npath.add_slash_if(is_dir);
+                if !self.has_privilege(&npath, DavPrivilege::Read).await {
+                    continue;
+                }
+                members.push((npath.clone(), Some(meta)));
+                if deep && is_dir {
+                    self.sync_list_members(&npath, deep, members).await?;
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
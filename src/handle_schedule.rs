@@ -0,0 +1,623 @@
+//
+// CalDAV scheduling (RFC 6638), see the `schedule` module.
+//
+// Scheduling is implicit: the messages are generated when a calendar
+// user stores or removes an event in their calendar home. Messages for
+// calendar users on this server are written to their inbox, others are
+// handed to the ItipDelivery.
+//
+use bytes::Bytes;
+use chrono::Utc;
+use futures_util::StreamExt;
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::acl::same_href;
+use crate::body::Body;
+use crate::caldav::NS_CALDAV_URI;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::handle_caldav::{Period, busy_periods, merge_periods};
+use crate::ical::{self, Component, Property, Timezones};
+use crate::quota::in_subtree;
+use crate::schedule::CalendarUser;
+use crate::util::{MemBuffer, dav_xml_error_response};
+use crate::xmltree_ext::{self, ElementExt};
+use crate::{DavError, DavInner, DavResult};
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // The calendar user that makes this request.
+    fn current_calendar_user(&self) -> Option<&CalendarUser> {
+        let principal = self.principal.as_deref()?;
+        self.calendar_users
+            .iter()
+            .find(|u| same_href(&u.principal, principal))
+    }
+
+    fn calendar_user_by_address(&self, address: &str) -> Option<&CalendarUser> {
+        self.calendar_users.iter().find(|u| u.has_address(address))
+    }
+
+    // The calendar user that makes this request, if `path` is a resource
+    // in their calendar home, and not in their inbox or outbox.
+    fn schedule_user(&self, path: &DavPath) -> Option<&CalendarUser> {
+        let user = self.current_calendar_user()?;
+        let below = |p: &str| DavPath::new(p).is_ok_and(|p| in_subtree(path, &p));
+        let schedules = !path.is_collection()
+            && below(&user.home)
+            && !below(&user.inbox)
+            && !below(&user.outbox);
+        schedules.then_some(user)
+    }
+
+    // Is `path` the scheduling outbox of a calendar user.
+    pub(crate) fn is_outbox(&self, path: &DavPath) -> bool {
+        self.calendar_users
+            .iter()
+            .any(|u| same_path(path, &u.outbox))
+    }
+
+    // Is `path` the scheduling inbox or outbox of a calendar user.
+    pub(crate) fn is_schedule_box(&self, path: &DavPath) -> bool {
+        self.calendar_users
+            .iter()
+            .any(|u| same_path(path, &u.inbox) || same_path(path, &u.outbox))
+    }
+
+    // The event at `path` before it is changed or removed, if
+    // changing it might mean that scheduling messages have to be sent.
+    pub(crate) async fn scheduling_object(&self, path: &DavPath) -> Option<Component> {
+        self.schedule_user(path)?;
+        ical::parse_calendar(&self.read_file(path).await?)
+    }
+
+    // Send the scheduling messages for a PUT (RFC 6638, section 3.2). The
+    // organizer's copy gets the SCHEDULE-STATUS of each attendee that a
+    // request was sent to; returns whether the stored object was changed.
+    pub(crate) async fn schedule_after_put(&self, path: &DavPath, old: Option<Component>) -> bool {
+        let Some(user) = self.schedule_user(path) else {
+            return false;
+        };
+        let new = self.read_file(path).await;
+        let Some(mut new) = new.as_deref().and_then(ical::parse_calendar) else {
+            return false;
+        };
+        let statuses = self.schedule_changes(user, old.as_ref(), Some(&new)).await;
+        if statuses.is_empty() {
+            return false;
+        }
+        for comp in &mut new.components {
+            if !is_scheduling_component(comp) {
+                continue;
+            }
+            for prop in comp.properties.iter_mut().filter(|p| p.name == "ATTENDEE") {
+                if let Some((_, status)) =
+                    statuses.iter().find(|(a, _)| same_address(a, &prop.value))
+                {
+                    prop.set_param("SCHEDULE-STATUS", status);
+                }
+            }
+        }
+        match self.write_file(path, new.to_ics()).await {
+            Ok(()) => true,
+            Err(e) => {
+                error!("schedule: cannot update {path}: {e:?}");
+                false
+            }
+        }
+    }
+
+    // Send the scheduling messages for a DELETE.
+    pub(crate) async fn schedule_after_delete(&self, path: &DavPath, old: Option<Component>) {
+        if let Some(user) = self.schedule_user(path)
+            && let Some(old) = old
+        {
+            self.schedule_changes(user, Some(&old), None).await;
+        }
+    }
+
+    // Returns the request status of each attendee that got a REQUEST.
+    async fn schedule_changes(
+        &self,
+        user: &CalendarUser,
+        old: Option<&Component>,
+        new: Option<&Component>,
+    ) -> Vec<(String, &'static str)> {
+        let mut statuses = Vec::new();
+        let Some(organizer) = new.or(old).and_then(organizer) else {
+            return statuses;
+        };
+
+        if !user.has_address(&organizer) {
+            if let Some(reply) = attendee_reply(user, old, new) {
+                self.deliver(user, &organizer, &reply).await;
+                if let Some(organizer) = self.calendar_user_by_address(&organizer) {
+                    self.apply_reply(organizer, &reply).await;
+                }
+            }
+            return statuses;
+        }
+
+        // the organizer: the attendees get the new version, and
+        // the attendees that were removed get a cancellation. The
+        // SCHEDULE-* parameters are not a change: the server sets them.
+        let old_attendees = old.map(|c| attendees(c, &organizer)).unwrap_or_default();
+        let new_attendees = new.map(|c| attendees(c, &organizer)).unwrap_or_default();
+        if let Some(new) = new {
+            let request = itip_message(new, "REQUEST");
+            if old.is_none_or(|old| itip_message(old, "REQUEST").to_ics() != request.to_ics()) {
+                for attendee in &new_attendees {
+                    let status = self.deliver(user, attendee, &request).await;
+                    statuses.push((attendee.clone(), status));
+                }
+            }
+        }
+        if let Some(old) = old {
+            for attendee in &old_attendees {
+                if !new_attendees
+                    .iter()
+                    .any(|a| a.eq_ignore_ascii_case(attendee))
+                {
+                    self.deliver(user, attendee, &cancel_message(old, attendee))
+                        .await;
+                }
+            }
+        }
+        statuses
+    }
+
+    // Deliver a message, and return the request status (RFC 6638, section 3.2.9).
+    async fn deliver(
+        &self,
+        from: &CalendarUser,
+        recipient: &str,
+        message: &Component,
+    ) -> &'static str {
+        let ics = message.to_ics();
+        let status = match self.calendar_user_by_address(recipient) {
+            Some(user) => match self.deliver_to_inbox(user, ics).await {
+                Ok(()) => "1.2",
+                Err(e) => {
+                    error!("schedule: cannot deliver to inbox {}: {e:?}", user.inbox);
+                    "5.1"
+                }
+            },
+            None => match self.itip_delivery {
+                Some(ref delivery) => {
+                    match delivery.deliver(&from.address, recipient, &ics).await {
+                        Ok(()) => "1.1",
+                        Err(e) => {
+                            error!("schedule: cannot deliver to {recipient}: {e}");
+                            "5.1"
+                        }
+                    }
+                }
+                None => "5.3",
+            },
+        };
+        debug!(
+            "schedule: message from {} to {recipient}: {status}",
+            from.address
+        );
+        status
+    }
+
+    async fn deliver_to_inbox(&self, user: &CalendarUser, message: String) -> FsResult<()> {
+        let mut path = DavPath::new(&user.inbox).map_err(|_| FsError::GeneralFailure)?;
+        // the inbox is created when the first message arrives.
+        if self.fs.metadata(&path, &self.credentials).await.is_err() {
+            self.fs.create_dir(&path, &self.credentials).await?;
+        }
+        path.push_segment(format!("{}.ics", uuid::Uuid::new_v4()).as_bytes());
+        self.write_file(&path, message).await
+    }
+
+    // Update the participation status in the organizer's copy of the event.
+    async fn apply_reply(&self, organizer: &CalendarUser, reply: &Component) {
+        let Some(uid) = uid(reply) else {
+            return;
+        };
+        let Some((path, mut calendar)) = self.find_by_uid(organizer, uid).await else {
+            return;
+        };
+        let tzs = Timezones::new(&calendar);
+        let reply_tzs = Timezones::new(reply);
+        let mut changed = false;
+        for answer in scheduling_components(reply) {
+            let Some(attendee) = answer.property("ATTENDEE") else {
+                continue;
+            };
+            let partstat = attendee.param("PARTSTAT").unwrap_or("NEEDS-ACTION");
+            let rid = answer.recurrence_id(&reply_tzs);
+            for comp in &mut calendar.components {
+                if !is_scheduling_component(comp) || comp.recurrence_id(&tzs) != rid {
+                    continue;
+                }
+                for prop in &mut comp.properties {
+                    if prop.name == "ATTENDEE" && same_address(&prop.value, &attendee.value) {
+                        prop.set_param("PARTSTAT", partstat);
+                        prop.set_param("SCHEDULE-STATUS", "2.0");
+                        changed = true;
+                    }
+                }
+            }
+        }
+        if changed && let Err(e) = self.write_file(&path, calendar.to_ics()).await {
+            error!("schedule: cannot update {path}: {e:?}");
+        }
+    }
+
+    // The event with this UID in the calendars of a calendar user.
+    async fn find_by_uid(&self, user: &CalendarUser, uid: &str) -> Option<(DavPath, Component)> {
+        for collection in self.calendar_collections(user).await {
+            for (path, _, content) in self
+                .read_calendar_objects(&collection)
+                .await
+                .unwrap_or_default()
+            {
+                if let Some(calendar) = ical::parse_calendar(&content)
+                    && self::uid(&calendar) == Some(uid)
+                {
+                    return Some((path, calendar));
+                }
+            }
+        }
+        None
+    }
+
+    // The calendar collections in the calendar home of a calendar user.
+    async fn calendar_collections(&self, user: &CalendarUser) -> Vec<DavPath> {
+        let Ok(home) = DavPath::new(&user.home) else {
+            return Vec::new();
+        };
+        let Ok(mut entries) = self
+            .fs
+            .read_dir(&home, ReadDirMeta::Data, &self.credentials)
+            .await
+        else {
+            return Vec::new();
+        };
+        let mut collections = Vec::new();
+        while let Some(dirent) = entries.next().await {
+            let Ok(dirent) = dirent else {
+                continue;
+            };
+            if !dirent.metadata().await.is_ok_and(|m| m.is_dir()) {
+                continue;
+            }
+            let mut path = home.clone();
+            path.push_segment(&dirent.name());
+            path.add_slash();
+            if !same_path(&path, &user.inbox) && !same_path(&path, &user.outbox) {
+                collections.push(path);
+            }
+        }
+        collections
+    }
+
+    pub(crate) async fn read_file(&self, path: &DavPath) -> Option<String> {
+        let mut file = self
+            .fs
+            .open(path, OpenOptions::read(), &self.credentials)
+            .await
+            .ok()?;
+        let len = file.metadata().await.ok()?.len();
+        let data = file.read_bytes(len as usize).await.ok()?;
+        String::from_utf8(data.to_vec()).ok()
+    }
+
+    async fn write_file(&self, path: &DavPath, content: String) -> FsResult<()> {
+        let mut oo = OpenOptions::write();
+        oo.create = true;
+        oo.truncate = true;
+        let mut file = self.fs.open(path, oo, &self.credentials).await?;
+        file.write_bytes(Bytes::from(content)).await?;
+        file.flush().await
+    }
+
+    // POST of a free-busy request to the scheduling outbox.
+    pub(crate) async fn handle_post(
+        &self,
+        req: &Request<()>,
+        body: &[u8],
+    ) -> DavResult<Response<Body>> {
+        let path = self.path(req);
+        if !self.is_outbox(&path) {
+            return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let user = match self.current_calendar_user() {
+            Some(user) if same_path(&path, &user.outbox) => user,
+            _ => return Err(DavError::Status(StatusCode::FORBIDDEN)),
+        };
+
+        let Some(request) = std::str::from_utf8(body)
+            .ok()
+            .and_then(ical::parse_calendar)
+        else {
+            return Ok(precondition_failed("valid-calendar-data"));
+        };
+        let freebusy = request.components_named("VFREEBUSY").next();
+        let is_request = request
+            .property("METHOD")
+            .is_some_and(|p| p.value.trim().eq_ignore_ascii_case("REQUEST"));
+        let Some(freebusy) = freebusy.filter(|_| is_request) else {
+            return Ok(precondition_failed("valid-scheduling-message"));
+        };
+        if !freebusy
+            .property("ORGANIZER")
+            .is_some_and(|p| user.has_address(&p.value))
+        {
+            return Ok(precondition_failed("organizer-allowed"));
+        }
+        let tzs = Timezones::new(&request);
+        let time = |name| freebusy.date_time(name).map(|t| tzs.to_utc(&t));
+        let (Some(start), Some(end)) = (time("DTSTART"), time("DTEND")) else {
+            return Ok(precondition_failed("valid-scheduling-message"));
+        };
+        let attendees: Vec<_> = freebusy.properties_named("ATTENDEE").collect();
+        if attendees.is_empty() {
+            return Ok(precondition_failed("recipient-specified"));
+        }
+
+        let mut root = Element::new2("C:schedule-response")
+            .ns("C", NS_CALDAV_URI)
+            .ns("D", "DAV:");
+        for attendee in attendees {
+            let recipient = Element::new2("D:href").text(attendee.value.trim());
+            let mut response = vec![Element::new3("C", "recipient", vec![recipient])];
+            match self.calendar_user_by_address(&attendee.value) {
+                Some(recipient) => {
+                    let reply = self
+                        .freebusy_reply(recipient, freebusy, attendee, (start, end))
+                        .await;
+                    response.push(Element::new2("C:request-status").text("2.0;Success"));
+                    response.push(Element::new2("C:calendar-data").text(reply.to_ics()));
+                }
+                None => {
+                    let status = "3.7;Invalid calendar user";
+                    response.push(Element::new2("C:request-status").text(status));
+                }
+            }
+            root.push_element(Element::new3("C", "response", response));
+        }
+
+        let mut emitter = xmltree_ext::emitter(MemBuffer::new())?;
+        root.write_ev(&mut emitter)?;
+        let mut res = Response::new(Body::from(emitter.into_inner().take()));
+        res.headers_mut().insert(
+            "content-type",
+            "application/xml; charset=utf-8".parse().unwrap(),
+        );
+        Ok(res)
+    }
+
+    // The VFREEBUSY reply for one recipient of a free-busy request.
+    async fn freebusy_reply(
+        &self,
+        recipient: &CalendarUser,
+        request: &Component,
+        attendee: &Property,
+        range: Period,
+    ) -> Component {
+        let mut periods = Vec::new();
+        for collection in self.calendar_collections(recipient).await {
+            for (_, _, content) in self
+                .read_calendar_objects(&collection)
+                .await
+                .unwrap_or_default()
+            {
+                if let Some(calendar) = ical::parse_calendar(&content) {
+                    busy_periods(&calendar, range, &mut periods);
+                }
+            }
+        }
+
+        let mut freebusy = Component {
+            name: "VFREEBUSY".to_string(),
+            ..Component::default()
+        };
+        match request.property("UID") {
+            Some(uid) => freebusy.properties.push(uid.clone()),
+            None => freebusy.set_value("UID", &uuid::Uuid::new_v4().to_string()),
+        }
+        freebusy.set_date_time("DTSTAMP", Utc::now(), false);
+        freebusy.set_date_time("DTSTART", range.0, false);
+        freebusy.set_date_time("DTEND", range.1, false);
+        freebusy
+            .properties
+            .extend(request.property("ORGANIZER").cloned());
+        freebusy.properties.push(attendee.clone());
+        for (fbtype, (start, end)) in merge_periods(periods) {
+            freebusy.properties.push(Property {
+                name: "FREEBUSY".to_string(),
+                params: vec![("FBTYPE".to_string(), fbtype.to_string())],
+                value: format!("{}/{}", ical::format_utc(start), ical::format_utc(end)),
+            });
+        }
+
+        let mut reply = Component {
+            name: "VCALENDAR".to_string(),
+            ..Component::default()
+        };
+        reply.set_value("VERSION", "2.0");
+        reply.set_value("PRODID", "-//DAV-SERVER//CalDAV//EN");
+        reply.set_value("METHOD", "REPLY");
+        reply.components.push(freebusy);
+        reply
+    }
+}
+
+fn precondition_failed(condition: &str) -> Response<Body> {
+    dav_xml_error_response(
+        StatusCode::FORBIDDEN,
+        &format!(r#"<C:{condition} xmlns:C="{NS_CALDAV_URI}"/>"#),
+    )
+}
+
+// is `path` the collection `collection`.
+pub(crate) fn same_path(path: &DavPath, collection: &str) -> bool {
+    let trim = |p: &[u8]| p.strip_suffix(b"/").unwrap_or(p).to_vec();
+    DavPath::new(collection).is_ok_and(|c| trim(c.as_bytes()) == trim(path.as_bytes()))
+}
+
+fn same_address(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+fn is_scheduling_component(comp: &Component) -> bool {
+    comp.name == "VEVENT" || comp.name == "VTODO"
+}
+
+fn scheduling_components(calendar: &Component) -> impl Iterator<Item = &Component> {
+    calendar
+        .components
+        .iter()
+        .filter(|c| is_scheduling_component(c))
+}
+
+fn uid(calendar: &Component) -> Option<&str> {
+    scheduling_components(calendar)
+        .find_map(|c| c.property("UID"))
+        .map(|p| p.value.trim())
+}
+
+fn organizer(calendar: &Component) -> Option<String> {
+    scheduling_components(calendar)
+        .find_map(|c| c.property("ORGANIZER"))
+        .map(|p| p.value.trim().to_string())
+}
+
+// The attendees that the server schedules for (SCHEDULE-AGENT=SERVER,
+// the default), other than the organizer.
+fn attendees(calendar: &Component, organizer: &str) -> Vec<String> {
+    let mut attendees: Vec<String> = Vec::new();
+    for comp in scheduling_components(calendar) {
+        for prop in comp.properties_named("ATTENDEE") {
+            let server = prop
+                .param("SCHEDULE-AGENT")
+                .is_none_or(|a| a.eq_ignore_ascii_case("SERVER"));
+            if server
+                && !same_address(&prop.value, organizer)
+                && !attendees.iter().any(|a| same_address(a, &prop.value))
+            {
+                attendees.push(prop.value.trim().to_string());
+            }
+        }
+    }
+    attendees
+}
+
+// The calendar as an iTIP message. Alarms are personal, and the
+// SCHEDULE-* parameters are only meant for the server.
+fn itip_message(calendar: &Component, method: &str) -> Component {
+    let mut message = calendar.clone();
+    message.set_value("METHOD", method);
+    for comp in &mut message.components {
+        if !is_scheduling_component(comp) {
+            continue;
+        }
+        comp.components.retain(|c| c.name != "VALARM");
+        for prop in &mut comp.properties {
+            if prop.name == "ORGANIZER" || prop.name == "ATTENDEE" {
+                prop.params.retain(|(n, _)| !n.starts_with("SCHEDULE-"));
+            }
+        }
+    }
+    message
+}
+
+// The CANCEL for an attendee that was removed, or for
+// all attendees when the event itself is removed.
+fn cancel_message(calendar: &Component, attendee: &str) -> Component {
+    let mut message = itip_message(calendar, "CANCEL");
+    for comp in &mut message.components {
+        if !is_scheduling_component(comp) {
+            continue;
+        }
+        comp.properties
+            .retain(|p| p.name != "ATTENDEE" || same_address(&p.value, attendee));
+        let sequence = comp
+            .property("SEQUENCE")
+            .and_then(|p| p.value.trim().parse::<u32>().ok())
+            .unwrap_or(0);
+        comp.set_value("SEQUENCE", &(sequence + 1).to_string());
+        comp.set_value("STATUS", "CANCELLED");
+    }
+    message
+}
+
+// The participation status of a calendar user in a component.
+fn participation<'a>(comp: &'a Component, user: &CalendarUser) -> Option<&'a str> {
+    comp.properties_named("ATTENDEE")
+        .find(|p| user.has_address(&p.value))
+        .map(|p| p.param("PARTSTAT").unwrap_or("NEEDS-ACTION"))
+}
+
+// The REPLY of an attendee, for the components in which they changed
+// their participation status. Removing the event declines it.
+fn attendee_reply(
+    user: &CalendarUser,
+    old: Option<&Component>,
+    new: Option<&Component>,
+) -> Option<Component> {
+    let calendar = new.or(old)?;
+    let old_tzs = old.map(Timezones::new);
+    let tzs = Timezones::new(calendar);
+
+    let mut reply = Component {
+        name: "VCALENDAR".to_string(),
+        ..Component::default()
+    };
+    reply.properties = calendar
+        .properties
+        .iter()
+        .filter(|p| p.name != "METHOD")
+        .cloned()
+        .collect();
+    reply.set_value("METHOD", "REPLY");
+    reply.components = calendar.components_named("VTIMEZONE").cloned().collect();
+
+    for comp in scheduling_components(calendar) {
+        let Some(attendee) = comp
+            .properties_named("ATTENDEE")
+            .find(|p| user.has_address(&p.value))
+        else {
+            continue;
+        };
+        let partstat = match new {
+            Some(_) => attendee.param("PARTSTAT").unwrap_or("NEEDS-ACTION"),
+            None => "DECLINED",
+        };
+        // what it was: the same instance, or else the whole event.
+        let rid = comp.recurrence_id(&tzs);
+        let was = old.zip(old_tzs.as_ref()).and_then(|(old, old_tzs)| {
+            let mut comps = scheduling_components(old);
+            comps
+                .find(|c| c.recurrence_id(old_tzs) == rid)
+                .or_else(|| {
+                    scheduling_components(old).find(|c| c.property("RECURRENCE-ID").is_none())
+                })
+                .and_then(|c| participation(c, user))
+        });
+        if new.is_some() && was.unwrap_or("NEEDS-ACTION").eq_ignore_ascii_case(partstat) {
+            continue;
+        }
+
+        let mut answer = Component {
+            name: comp.name.clone(),
+            ..Component::default()
+        };
+        for name in ["UID", "RECURRENCE-ID", "DTSTART", "SEQUENCE", "ORGANIZER"] {
+            answer.properties.extend(comp.property(name).cloned());
+        }
+        let mut attendee = attendee.clone();
+        attendee.params.retain(|(n, _)| !n.starts_with("SCHEDULE-"));
+        attendee.set_param("PARTSTAT", partstat);
+        answer.properties.push(attendee);
+        answer.set_date_time("DTSTAMP", Utc::now(), false);
+        reply.components.push(answer);
+    }
+
+    let answered = scheduling_components(&reply).next().is_some();
+    answered.then_some(reply)
+}
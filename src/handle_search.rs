@@ -0,0 +1,624 @@
+//
+// RFC 5323, WebDAV SEARCH, with the DAV:basicsearch grammar.
+//
+// The scope is walked with read_dir, every resource is matched against
+// the DAV:where condition, and the matches are written as a multistatus
+// with the properties from DAV:select, just like a PROPFIND.
+//
+// Comparisons use the three-valued logic from RFC 5323 5.5: a condition
+// on a property that a resource does not have is "unknown", and only
+// resources for which the condition is true are returned.
+//
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures_util::{FutureExt, StreamExt, future::BoxFuture};
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::DavOptionHide;
+use crate::acl::DavPrivilege;
+use crate::async_stream::AsyncStream;
+use crate::body::Body;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::handle_props::PropWriter;
+use crate::handle_redirectref::resolve_href;
+use crate::util::dav_xml_error_response;
+use crate::xmltree_ext::*;
+use crate::{DavError, DavInner, DavResult};
+
+// Namespace and name of a property.
+#[derive(Debug, Clone)]
+struct PropName {
+    namespace: Option<String>,
+    name: String,
+}
+
+impl PropName {
+    fn is_dav(&self, name: &str) -> bool {
+        self.namespace.as_deref() == Some("DAV:") && self.name == name
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+}
+
+// The DAV:where condition.
+#[derive(Debug)]
+enum Condition {
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+    Compare(CompareOp, PropName, String, bool),
+    Like(PropName, String, bool),
+    IsCollection,
+    IsDefined(PropName),
+}
+
+#[derive(Debug)]
+struct Scope {
+    href: String,
+    depth: ScopeDepth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScopeDepth {
+    Zero,
+    One,
+    Infinity,
+}
+
+#[derive(Debug)]
+struct OrderBy {
+    prop: PropName,
+    descending: bool,
+    caseless: bool,
+}
+
+#[derive(Debug)]
+struct BasicSearch {
+    // "prop" or "allprop", as for PROPFIND.
+    select: &'static str,
+    props: Vec<Element>,
+    scopes: Vec<Scope>,
+    condition: Option<Condition>,
+    orderby: Vec<OrderBy>,
+    limit: Option<usize>,
+}
+
+// The value of a property, as far as we can compare it.
+#[derive(Debug, Clone)]
+enum Value {
+    Int(u64),
+    Time(i64),
+    Text(String),
+}
+
+impl Value {
+    fn as_text(&self) -> String {
+        match self {
+            Value::Int(n) => n.to_string(),
+            Value::Time(t) => t.to_string(),
+            Value::Text(s) => s.clone(),
+        }
+    }
+
+    // Convert a literal to the same kind of value, so that they compare.
+    fn literal(&self, lit: &str) -> Option<Value> {
+        let lit = lit.trim();
+        match self {
+            Value::Int(_) => lit.parse().ok().map(Value::Int),
+            Value::Time(_) => parse_time(lit).map(Value::Time),
+            Value::Text(_) => Some(Value::Text(lit.to_string())),
+        }
+    }
+
+    fn compare(&self, other: &Value, caseless: bool) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) if caseless => {
+                Some(a.to_lowercase().cmp(&b.to_lowercase()))
+            }
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+fn unix_time(t: SystemTime) -> i64 {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+// Dates can be given as in DAV:creationdate or as in DAV:getlastmodified.
+fn parse_time(s: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .or_else(|_| chrono::DateTime::parse_from_rfc2822(s))
+        .ok()
+        .map(|t| t.timestamp())
+}
+
+// SQL-style LIKE: '%' matches any sequence, '_' one character,
+// and '\' escapes the next character.
+fn like_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('%', rest)) => (0..=text.len()).any(|i| like_match(rest, &text[i..])),
+        Some(('_', rest)) => !text.is_empty() && like_match(rest, &text[1..]),
+        Some(('\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && like_match(&rest[1..], &text[1..])
+        }
+        Some((c, rest)) => text.first() == Some(c) && like_match(rest, &text[1..]),
+    }
+}
+
+// The one property element in a DAV:prop.
+fn parse_propname(elem: &Element) -> DavResult<PropName> {
+    let prop = elem
+        .child_elems_iter()
+        .find(|e| e.name == "prop")
+        .and_then(|p| p.child_elems_iter().next())
+        .ok_or(DavError::XmlParseError)?;
+    Ok(PropName {
+        namespace: prop.namespace.clone(),
+        name: prop.name.clone(),
+    })
+}
+
+fn parse_literal(elem: &Element) -> DavResult<String> {
+    elem.child_elems_iter()
+        .find(|e| e.name == "literal" || e.name == "typed-literal")
+        .map(|e| e.get_text().unwrap_or_default().to_string())
+        .ok_or(DavError::XmlParseError)
+}
+
+// Comparisons are caseless, unless caseless="no".
+fn parse_caseless(elem: &Element) -> bool {
+    elem.attributes.get("caseless").map(|s| s.as_str()) != Some("no")
+}
+
+fn parse_condition(elem: &Element) -> DavResult<Condition> {
+    let op = match elem.name.as_str() {
+        "and" | "or" => {
+            let conds = elem
+                .child_elems_iter()
+                .map(parse_condition)
+                .collect::<DavResult<Vec<_>>>()?;
+            return Ok(match elem.name.as_str() {
+                "and" => Condition::And(conds),
+                _ => Condition::Or(conds),
+            });
+        }
+        "not" => {
+            let cond = elem
+                .child_elems_iter()
+                .next()
+                .ok_or(DavError::XmlParseError)?;
+            return Ok(Condition::Not(Box::new(parse_condition(cond)?)));
+        }
+        "is-collection" => return Ok(Condition::IsCollection),
+        "is-defined" => return Ok(Condition::IsDefined(parse_propname(elem)?)),
+        "like" => {
+            return Ok(Condition::Like(
+                parse_propname(elem)?,
+                parse_literal(elem)?,
+                parse_caseless(elem),
+            ));
+        }
+        "eq" => CompareOp::Eq,
+        "lt" => CompareOp::Lt,
+        "gt" => CompareOp::Gt,
+        "lte" => CompareOp::Lte,
+        "gte" => CompareOp::Gte,
+        _ => return Err(DavError::XmlParseError),
+    };
+    Ok(Condition::Compare(
+        op,
+        parse_propname(elem)?,
+        parse_literal(elem)?,
+        parse_caseless(elem),
+    ))
+}
+
+fn parse_basicsearch(root: Element) -> DavResult<BasicSearch> {
+    let mut search = BasicSearch {
+        select: "allprop",
+        props: Vec::new(),
+        scopes: Vec::new(),
+        condition: None,
+        orderby: Vec::new(),
+        limit: None,
+    };
+    for elem in root.child_elems_into_iter() {
+        match elem.name.as_str() {
+            "select" => {
+                let sel = elem
+                    .child_elems_into_iter()
+                    .next()
+                    .ok_or(DavError::XmlParseError)?;
+                match sel.name.as_str() {
+                    "prop" => {
+                        search.select = "prop";
+                        search.props = sel.take_child_elems();
+                    }
+                    "allprop" => search.select = "allprop",
+                    _ => return Err(DavError::XmlParseError),
+                }
+            }
+            "from" => {
+                for scope in elem.child_elems_iter().filter(|e| e.name == "scope") {
+                    let href = scope
+                        .get_child("href")
+                        .and_then(|e| e.get_text())
+                        .ok_or(DavError::XmlParseError)?;
+                    let depth = match scope.get_child("depth").and_then(|e| e.get_text()) {
+                        None => ScopeDepth::Infinity,
+                        Some(d) => match d.trim() {
+                            "0" => ScopeDepth::Zero,
+                            "1" => ScopeDepth::One,
+                            "infinity" => ScopeDepth::Infinity,
+                            _ => return Err(DavError::XmlParseError),
+                        },
+                    };
+                    search.scopes.push(Scope {
+                        href: href.trim().to_string(),
+                        depth,
+                    });
+                }
+            }
+            "where" => {
+                let cond = elem
+                    .child_elems_iter()
+                    .next()
+                    .ok_or(DavError::XmlParseError)?;
+                search.condition = Some(parse_condition(cond)?);
+            }
+            "orderby" => {
+                for order in elem.child_elems_iter().filter(|e| e.name == "order") {
+                    search.orderby.push(OrderBy {
+                        prop: parse_propname(order)?,
+                        descending: order.get_child("descending").is_some(),
+                        caseless: parse_caseless(order),
+                    });
+                }
+            }
+            "limit" => {
+                let nresults = elem
+                    .get_child("nresults")
+                    .and_then(|e| e.get_text())
+                    .and_then(|t| t.trim().parse::<usize>().ok())
+                    .ok_or(DavError::XmlParseError)?;
+                search.limit = Some(nresults);
+            }
+            _ => {}
+        }
+    }
+    if search.scopes.is_empty() {
+        return Err(DavError::XmlParseError);
+    }
+    Ok(search)
+}
+
+// A resource that matched, with the values to sort on.
+type SearchMatch = (DavPath, Box<dyn DavMetaData>, Vec<Option<Value>>);
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    pub(crate) async fn handle_search(
+        &self,
+        req: &Request<()>,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
+        let root = Element::parse2(Cursor::new(xmldata))?;
+        if root.name != "searchrequest" || root.namespace.as_deref() != Some("DAV:") {
+            return Err(DavError::XmlParseError);
+        }
+        let Some(basicsearch) = root
+            .child_elems_into_iter()
+            .find(|e| e.name == "basicsearch" && e.namespace.as_deref() == Some("DAV:"))
+        else {
+            return Ok(dav_xml_error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "<D:search-grammar-supported/>",
+            ));
+        };
+        let mut search = parse_basicsearch(basicsearch)?;
+
+        // the scopes must be existing collections, relative to the request URL.
+        let path = self.path(req);
+        let mut scopes = Vec::new();
+        for scope in &search.scopes {
+            let spath = resolve_href(&path, &scope.href)
+                .and_then(|href| url::Url::parse("http://localhost").ok()?.join(&href).ok())
+                .and_then(|url| DavPath::from_str_and_prefix(url.path(), &self.prefix).ok());
+            let meta = match spath {
+                Some(ref p) => self.visible_metadata(p).await.ok(),
+                None => None,
+            };
+            let (Some(mut spath), Some(meta)) = (spath, meta) else {
+                return Ok(dav_xml_error_response(
+                    StatusCode::CONFLICT,
+                    "<D:search-scope-valid/>",
+                ));
+            };
+            if !meta.is_dir() {
+                return Ok(dav_xml_error_response(
+                    StatusCode::CONFLICT,
+                    "<D:search-scope-valid/>",
+                ));
+            }
+            spath.add_slash();
+            if !self.has_privilege(&spath, DavPrivilege::Read).await {
+                return Err(StatusCode::FORBIDDEN.into());
+            }
+            scopes.push((spath, meta, scope.depth));
+        }
+
+        let mut matches = Vec::new();
+        let mut seen = HashSet::new();
+        for (spath, meta, depth) in scopes {
+            self.search_resource(&search, spath.clone(), meta, &mut matches)
+                .await;
+            if depth != ScopeDepth::Zero {
+                self.search_collection(&search, &spath, depth, &mut seen, &mut matches)
+                    .await;
+            }
+        }
+
+        // overlapping scopes can find the same resource twice.
+        let mut hrefs = HashSet::new();
+        matches.retain(|(p, _, _)| hrefs.insert(p.as_url_string()));
+
+        if !search.orderby.is_empty() {
+            let orderby = &search.orderby;
+            matches.sort_by(|a, b| {
+                for (idx, order) in orderby.iter().enumerate() {
+                    // resources without the property sort last.
+                    let ord = match (&a.2[idx], &b.2[idx]) {
+                        (Some(x), Some(y)) => {
+                            x.compare(y, order.caseless).unwrap_or(Ordering::Equal)
+                        }
+                        (Some(_), None) => Ordering::Less,
+                        (None, Some(_)) => Ordering::Greater,
+                        (None, None) => Ordering::Equal,
+                    };
+                    let ord = if order.descending { ord.reverse() } else { ord };
+                    if ord != Ordering::Equal {
+                        return ord;
+                    }
+                }
+                Ordering::Equal
+            });
+        }
+        if let Some(limit) = search.limit {
+            matches.truncate(limit);
+        }
+
+        let mut res = Response::new(Body::empty());
+        let mut pw = PropWriter::new(
+            req,
+            &mut res,
+            search.select,
+            std::mem::take(&mut search.props),
+            self.fs.clone(),
+            self.ls.as_ref(),
+            self.acl.as_ref(),
+            self.principal.clone(),
+            self.credentials.clone(),
+            #[cfg(any(feature = "caldav", feature = "carddav"))]
+            &path,
+        )?;
+
+        *res.body_mut() = Body::from(AsyncStream::new(|tx| async move {
+            pw.set_tx(tx);
+            for (mpath, meta, _) in matches {
+                pw.write_props(&mpath, meta).await?;
+                pw.flush().await?;
+            }
+            pw.close().await?;
+            Ok(())
+        }));
+
+        Ok(res)
+    }
+
+    // Walk a collection, and match all its members.
+    fn search_collection<'a>(
+        &'a self,
+        search: &'a BasicSearch,
+        path: &'a DavPath,
+        depth: ScopeDepth,
+        seen: &'a mut HashSet<String>,
+        matches: &'a mut Vec<SearchMatch>,
+    ) -> BoxFuture<'a, ()> {
+        async move {
+            // a collection with several bindings is only searched once.
+            if let Ok(id) = self.fs.resource_id(path, &self.credentials).await
+                && !seen.insert(id)
+            {
+                return;
+            }
+            let mut entries = match self
+                .fs
+                .read_dir(path, self.get_read_dir_meta(), &self.credentials)
+                .await
+            {
+                Ok(entries) => entries,
+                Err(e) => {
+                    error!("read_dir error {e:?}");
+                    return;
+                }
+            };
+            let hide_dot_prefix = self.hide_dot_prefix == DavOptionHide::InListings
+                || self.hide_dot_prefix == DavOptionHide::Always;
+            while let Some(dirent) = entries.next().await {
+                let Ok(dirent) = dirent else {
+                    continue;
+                };
+                let name = dirent.name();
+                if hide_dot_prefix && name.starts_with(b".") {
+                    continue;
+                }
+                let Ok(meta) = dirent.metadata().await else {
+                    continue;
+                };
+                if meta.is_symlink() {
+                    continue;
+                }
+                let mut npath = path.clone();
+                npath.push_segment(&name);
+                let is_dir = meta.is_dir();
+                npath.add_slash_if(is_dir);
+                if !self.has_privilege(&npath, DavPrivilege::Read).await {
+                    continue;
+                }
+                self.search_resource(search, npath.clone(), meta, matches)
+                    .await;
+                if depth == ScopeDepth::Infinity && is_dir {
+                    self.search_collection(search, &npath, depth, seen, matches)
+                        .await;
+                }
+            }
+        }
+        .boxed()
+    }
+
+    // Add the resource to the matches if the condition is true.
+    async fn search_resource(
+        &self,
+        search: &BasicSearch,
+        path: DavPath,
+        meta: Box<dyn DavMetaData>,
+        matches: &mut Vec<SearchMatch>,
+    ) {
+        if let Some(ref cond) = search.condition
+            && self.search_eval(cond, &path, meta.as_ref()).await != Some(true)
+        {
+            return;
+        }
+        let mut keys = Vec::new();
+        for order in &search.orderby {
+            keys.push(self.search_value(&order.prop, &path, meta.as_ref()).await);
+        }
+        matches.push((path, meta, keys));
+    }
+
+    // Evaluate a condition. None means "unknown".
+    fn search_eval<'a>(
+        &'a self,
+        cond: &'a Condition,
+        path: &'a DavPath,
+        meta: &'a dyn DavMetaData,
+    ) -> BoxFuture<'a, Option<bool>> {
+        async move {
+            match cond {
+                Condition::And(conds) => {
+                    let mut res = Some(true);
+                    for c in conds {
+                        match self.search_eval(c, path, meta).await {
+                            Some(false) => return Some(false),
+                            None => res = None,
+                            Some(true) => {}
+                        }
+                    }
+                    res
+                }
+                Condition::Or(conds) => {
+                    let mut res = Some(false);
+                    for c in conds {
+                        match self.search_eval(c, path, meta).await {
+                            Some(true) => return Some(true),
+                            None => res = None,
+                            Some(false) => {}
+                        }
+                    }
+                    res
+                }
+                Condition::Not(c) => self.search_eval(c, path, meta).await.map(|b| !b),
+                Condition::IsCollection => Some(meta.is_dir()),
+                Condition::IsDefined(prop) => {
+                    Some(self.search_value(prop, path, meta).await.is_some())
+                }
+                Condition::Like(prop, pattern, caseless) => {
+                    let value = self.search_value(prop, path, meta).await?.as_text();
+                    let (value, pattern) = match caseless {
+                        true => (value.to_lowercase(), pattern.to_lowercase()),
+                        false => (value, pattern.clone()),
+                    };
+                    let pattern = pattern.chars().collect::<Vec<_>>();
+                    let value = value.chars().collect::<Vec<_>>();
+                    Some(like_match(&pattern, &value))
+                }
+                Condition::Compare(op, prop, literal, caseless) => {
+                    let value = self.search_value(prop, path, meta).await?;
+                    let literal = value.literal(literal)?;
+                    let ord = value.compare(&literal, *caseless)?;
+                    Some(match op {
+                        CompareOp::Eq => ord == Ordering::Equal,
+                        CompareOp::Lt => ord == Ordering::Less,
+                        CompareOp::Gt => ord == Ordering::Greater,
+                        CompareOp::Lte => ord != Ordering::Greater,
+                        CompareOp::Gte => ord != Ordering::Less,
+                    })
+                }
+            }
+        }
+        .boxed()
+    }
+
+    // The value of a live or dead property of a resource.
+    async fn search_value(
+        &self,
+        prop: &PropName,
+        path: &DavPath,
+        meta: &dyn DavMetaData,
+    ) -> Option<Value> {
+        if prop.is_dav("getcontentlength") {
+            return (!meta.is_dir()).then(|| Value::Int(meta.len()));
+        }
+        if prop.is_dav("getlastmodified") {
+            return meta.modified().ok().map(|t| Value::Time(unix_time(t)));
+        }
+        if prop.is_dav("creationdate") {
+            return meta.created().ok().map(|t| Value::Time(unix_time(t)));
+        }
+        if prop.is_dav("getcontenttype") {
+            return Some(Value::Text(match meta.is_dir() {
+                true => "httpd/unix-directory".to_string(),
+                false => path.get_mime_type_str().to_string(),
+            }));
+        }
+        if prop.is_dav("getetag") {
+            return meta.etag().map(Value::Text);
+        }
+        let davprop = DavProp {
+            name: prop.name.clone(),
+            prefix: None,
+            namespace: prop.namespace.clone(),
+            xml: None,
+        };
+        let text = match self.fs.get_prop(path, davprop, &self.credentials).await {
+            Ok(xml) => Element::parse2(Cursor::new(xml))
+                .ok()
+                .map(|e| e.get_text().unwrap_or_default().to_string()),
+            Err(_) => None,
+        };
+        // without a DAV:displayname property, the name is the displayname.
+        if text.is_none() && prop.is_dav("displayname") {
+            let name = path.file_name_bytes();
+            return Some(Value::Text(String::from_utf8_lossy(name).to_string()));
+        }
+        text.map(Value::Text)
+    }
+}
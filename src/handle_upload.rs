@@ -0,0 +1,517 @@
+//
+// Resumable uploads (draft-ietf-httpbis-resumable-upload), the
+// successor of the tus protocol.
+//
+// A PUT with an Upload-Complete header creates an upload resource, which
+// is the URL of the target with "?upload=<id>" added. The data is staged
+// in a file in the upload directory, outside of the filesystem, and is
+// only written to the target when the upload is complete. Until then:
+//
+// - HEAD on the upload resource returns the offset to continue at,
+// - PATCH appends to it (at that offset),
+// - DELETE cancels the upload.
+//
+// Uploads that have not been written to for a while expire.
+//
+use std::error::Error as StdError;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+
+use bytes::{Buf, Bytes};
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use http_body::Body as HttpBody;
+use http_body_util::BodyExt;
+
+use crate::body::Body;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::quota::quota_exceeded;
+use crate::{DavError, DavInner, DavResult};
+
+// Content types of a PATCH that appends to an upload.
+const PARTIAL_UPLOAD: &str = "application/partial-upload";
+const TUS_UPLOAD: &str = "application/offset+octet-stream";
+
+// The upload in an upload URL (e.g. `/file.bin?upload=<id>`).
+pub(crate) fn upload_query(req: &Request<()>) -> Option<String> {
+    req.uri()
+        .query()?
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("upload="))
+        .map(|v| v.to_string())
+}
+
+// Run blocking file I/O on the staged data.
+async fn blocking<F, R>(func: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(func).await.unwrap()
+}
+
+// The files of a staged upload: the data, the target and principal
+// it was created for, and the total length if the client told us.
+struct Staged {
+    data: PathBuf,
+    target: PathBuf,
+    length: PathBuf,
+}
+
+impl Staged {
+    // The id is a uuid, so that it cannot point outside of the directory.
+    fn new(dir: &Path, id: &str) -> Option<Staged> {
+        uuid::Uuid::parse_str(id).ok()?;
+        Some(Staged {
+            data: dir.join(id),
+            target: dir.join(format!("{id}.target")),
+            length: dir.join(format!("{id}.length")),
+        })
+    }
+
+    // Current offset, None if the upload does not exist (anymore).
+    async fn offset(&self, expiry: std::time::Duration) -> Option<u64> {
+        let data = self.data.clone();
+        let meta = blocking(move || std::fs::metadata(data)).await.ok()?;
+        let age = meta.modified().ok()?.elapsed().unwrap_or_default();
+        if age > expiry {
+            self.remove().await;
+            return None;
+        }
+        Some(meta.len())
+    }
+
+    async fn length(&self) -> Option<u64> {
+        let length = self.length.clone();
+        let s = blocking(move || std::fs::read_to_string(length))
+            .await
+            .ok()?;
+        s.trim().parse().ok()
+    }
+
+    // Who the upload is for, as written by `create`.
+    async fn owner(&self) -> Option<String> {
+        let target = self.target.clone();
+        blocking(move || std::fs::read_to_string(target)).await.ok()
+    }
+
+    async fn create(&self, owner: String, length: Option<u64>) -> io::Result<()> {
+        let data = self.data.clone();
+        let tpath = self.target.clone();
+        let lpath = self.length.clone();
+        blocking(move || {
+            std::fs::write(tpath, owner)?;
+            std::fs::File::create(data)?;
+            if let Some(length) = length {
+                std::fs::write(lpath, length.to_string())?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove(&self) {
+        let files = [self.data.clone(), self.target.clone(), self.length.clone()];
+        blocking(move || {
+            for file in files {
+                let _ = std::fs::remove_file(file);
+            }
+        })
+        .await
+    }
+
+    // Append the request body. Returns the new offset. If the body
+    // could not be read completely, what we got is kept.
+    async fn append<ReqBody, ReqData, ReqError>(&self, body: ReqBody) -> DavResult<u64>
+    where
+        ReqBody: HttpBody<Data = ReqData, Error = ReqError>,
+        ReqData: Buf + Send + 'static,
+        ReqError: StdError + Send + Sync + 'static,
+    {
+        let data = self.data.clone();
+        let mut file = blocking(move || {
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(data)
+                .map(io::BufWriter::new)
+        })
+        .await?;
+
+        let mut body = pin!(body);
+        while let Some(frame) = body.frame().await {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    debug!("resumable upload interrupted: {e}");
+                    let _ = blocking(move || file.flush()).await;
+                    return Err(DavError::StatusClose(StatusCode::BAD_REQUEST));
+                }
+            };
+            let Ok(mut buf) = frame.into_data() else {
+                continue;
+            };
+            let bytes = buf.copy_to_bytes(buf.remaining());
+            file = blocking(move || file.write_all(&bytes).map(|_| file)).await?;
+        }
+        let file = blocking(move || file.into_inner().map_err(|e| e.into_error())).await?;
+        Ok(blocking(move || file.metadata()).await?.len())
+    }
+
+    // Read the next part of the staged data.
+    async fn read(
+        &self,
+        mut file: Option<std::fs::File>,
+        size: usize,
+    ) -> io::Result<(std::fs::File, Bytes)> {
+        let data = self.data.clone();
+        blocking(move || {
+            let mut file = match file.take() {
+                Some(file) => file,
+                None => std::fs::File::open(data)?,
+            };
+            let mut buf = vec![0; size];
+            let n = file.read(&mut buf)?;
+            buf.truncate(n);
+            Ok((file, Bytes::from(buf)))
+        })
+        .await
+    }
+}
+
+// Remove the uploads that have expired.
+async fn expire_uploads(dir: PathBuf, expiry: std::time::Duration) {
+    blocking(move || {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            // the data file is written to, so its age is the age of the upload.
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if uuid::Uuid::parse_str(&name).is_err() {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t.elapsed().unwrap_or_default() > expiry);
+            if expired {
+                for file in [
+                    name.clone(),
+                    format!("{name}.target"),
+                    format!("{name}.length"),
+                ] {
+                    let _ = std::fs::remove_file(dir.join(file));
+                }
+            }
+        }
+    })
+    .await
+}
+
+// The problem details (RFC 9457) for a PATCH at the wrong offset.
+fn mismatching_offset(expected: u64, provided: u64) -> Response<Body> {
+    let body = format!(
+        "{{\"type\":\"https://iana.org/assignments/http-problem-types#mismatching-upload-offset\",\
+         \"title\":\"offset from request does not match offset of resource\",\
+         \"expected-offset\":{expected},\"provided-offset\":{provided}}}"
+    );
+    let mut res = Response::new(Body::from(body));
+    *res.status_mut() = StatusCode::CONFLICT;
+    res.headers_mut().typed_insert(davheaders::ContentType(
+        "application/problem+json".to_owned(),
+    ));
+    res.headers_mut()
+        .typed_insert(davheaders::UploadOffset(expected));
+    res
+}
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // Is this a request for the resumable upload code.
+    pub(crate) fn is_upload_request(&self, req: &Request<()>) -> bool {
+        self.upload_dir.is_some()
+            && (upload_query(req).is_some()
+                || (req.method() == http::Method::PUT
+                    && req.headers().contains_key(&davheaders::UPLOAD_COMPLETE)))
+    }
+
+    fn upload_limit(&self) -> http::HeaderValue {
+        let limit = format!("expires={}", self.upload_expiry.as_secs());
+        limit.parse().unwrap()
+    }
+
+    // The target and principal an upload belongs to.
+    fn upload_owner(&self, path: &DavPath) -> String {
+        let principal = self.principal.as_deref().unwrap_or_default();
+        format!("{}\n{principal}", path.as_url_string())
+    }
+
+    // The upload resource of an upload URL. It only exists at the URL
+    // of its target, and for the principal that created it.
+    async fn staged(&self, req: &Request<()>) -> DavResult<(Staged, u64)> {
+        let dir = self.upload_dir.as_ref().unwrap();
+        let staged = upload_query(req)
+            .and_then(|id| Staged::new(dir, &id))
+            .ok_or(DavError::Status(StatusCode::NOT_FOUND))?;
+        if staged.owner().await != Some(self.upload_owner(&self.path(req))) {
+            return Err(DavError::Status(StatusCode::NOT_FOUND));
+        }
+        match staged.offset(self.upload_expiry).await {
+            Some(offset) => Ok((staged, offset)),
+            None => Err(DavError::Status(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    // Check the If headers and locks of the target.
+    async fn check_upload_target(
+        &self,
+        req: &Request<()>,
+        path: &DavPath,
+        meta: Option<&(dyn DavMetaData + 'static)>,
+    ) -> DavResult<()> {
+        let tokens = match if_match_get_tokens(
+            req,
+            meta,
+            self.fs.as_ref(),
+            &self.ls,
+            path,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(DavError::StatusClose(s)),
+        };
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            if let Err(_l) = locksystem
+                .check(path, principal, false, false, &tokens)
+                .await
+            {
+                return Err(DavError::StatusClose(StatusCode::LOCKED));
+            }
+        }
+        Ok(())
+    }
+
+    /// HEAD and DELETE on an upload resource.
+    pub(crate) async fn handle_upload_resource(
+        &self,
+        req: &Request<()>,
+    ) -> DavResult<Response<Body>> {
+        let (staged, offset) = self.staged(req).await?;
+        let mut res = Response::new(Body::empty());
+        match *req.method() {
+            http::Method::HEAD => {
+                let h = res.headers_mut();
+                h.typed_insert(davheaders::UploadOffset(offset));
+                h.typed_insert(davheaders::UploadComplete(false));
+                if let Some(length) = staged.length().await {
+                    h.typed_insert(davheaders::UploadLength(length));
+                }
+                h.insert("upload-limit", self.upload_limit());
+                h.typed_insert(headers::CacheControl::new().with_no_store());
+            }
+            http::Method::DELETE => staged.remove().await,
+            _ => return Err(DavError::Status(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        Ok(res)
+    }
+
+    /// Upload creation (PUT) and appending (PATCH).
+    pub(crate) async fn handle_upload<ReqBody, ReqData, ReqError>(
+        &self,
+        req: &Request<()>,
+        body: ReqBody,
+    ) -> DavResult<Response<Body>>
+    where
+        ReqBody: HttpBody<Data = ReqData, Error = ReqError>,
+        ReqData: Buf + Send + 'static,
+        ReqError: StdError + Send + Sync + 'static,
+    {
+        let path = self.path(req);
+        let complete = req
+            .headers()
+            .typed_try_get::<davheaders::UploadComplete>()
+            .map_err(|_| DavError::StatusClose(StatusCode::BAD_REQUEST))?;
+        let length = req
+            .headers()
+            .typed_try_get::<davheaders::UploadLength>()
+            .map_err(|_| DavError::StatusClose(StatusCode::BAD_REQUEST))?;
+
+        let (staged, offset) = if req.method() == http::Method::PUT {
+            // a new upload for the target.
+            let meta = self.fs.metadata(&path, &self.credentials).await;
+            if meta.as_ref().is_ok_and(|m| m.is_dir()) {
+                return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+            }
+            if !self.has_parent(&path).await {
+                return Err(DavError::StatusClose(StatusCode::CONFLICT));
+            }
+            self.check_upload_target(req, &path, meta.as_ref().map(|m| m.as_ref()).ok())
+                .await?;
+            if let Some(room) = self.quota_room(&path).await {
+                let old_len = meta.as_ref().map(|m| m.len()).unwrap_or(0);
+                if length.is_some_and(|l| l.0 > room + old_len) {
+                    let mut res = quota_exceeded();
+                    res.headers_mut().typed_insert(headers::Connection::close());
+                    return Ok(res);
+                }
+            }
+
+            let dir = self.upload_dir.clone().unwrap();
+            expire_uploads(dir.clone(), self.upload_expiry).await;
+            let id = uuid::Uuid::new_v4().to_string();
+            let staged = Staged::new(&dir, &id).unwrap();
+            if let Err(e) = staged
+                .create(self.upload_owner(&path), length.map(|l| l.0))
+                .await
+            {
+                error!("resumable upload: cannot create {:?}: {e}", staged.data);
+                return Err(DavError::StatusClose(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+            (staged, 0)
+        } else {
+            // appending to an upload.
+            let ct = req.headers().typed_get::<davheaders::ContentType>();
+            if !ct.is_some_and(|ct| ct.0 == PARTIAL_UPLOAD || ct.0 == TUS_UPLOAD) {
+                return Err(DavError::StatusClose(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+            }
+            let (staged, offset) = self.staged(req).await.map_err(|e| match e {
+                DavError::Status(s) => DavError::StatusClose(s),
+                e => e,
+            })?;
+            let provided = req
+                .headers()
+                .typed_get::<davheaders::UploadOffset>()
+                .ok_or(DavError::StatusClose(StatusCode::BAD_REQUEST))?;
+            if provided.0 != offset {
+                let mut res = mismatching_offset(offset, provided.0);
+                res.headers_mut().typed_insert(headers::Connection::close());
+                return Ok(res);
+            }
+            (staged, offset)
+        };
+        let location = match upload_query(req) {
+            Some(_) => None,
+            None => Some(format!(
+                "{}?upload={}",
+                path.with_prefix().as_url_string(),
+                staged.data.file_name().unwrap().to_string_lossy()
+            )),
+        };
+
+        // the declared length cannot change, and we cannot go past it.
+        let known = staged.length().await;
+        if let (Some(known), Some(length)) = (known, length)
+            && known != length.0
+        {
+            return Err(DavError::StatusClose(StatusCode::BAD_REQUEST));
+        }
+        let new_offset = staged.append(body).await?;
+        if known.is_some_and(|l| new_offset > l) {
+            staged.remove().await;
+            return Err(DavError::StatusClose(StatusCode::BAD_REQUEST));
+        }
+        trace!("resumable upload {path}: {offset} -> {new_offset}");
+
+        // complete when the client says so, or when we have it all.
+        let done = match complete {
+            Some(c) => c.0,
+            None => known.is_some_and(|l| new_offset == l),
+        };
+        if done {
+            if known.is_some_and(|l| new_offset != l) {
+                staged.remove().await;
+                return Err(DavError::StatusClose(StatusCode::BAD_REQUEST));
+            }
+            return self.finish_upload(req, &path, &staged, new_offset).await;
+        }
+
+        let mut res = Response::new(Body::empty());
+        let h = res.headers_mut();
+        h.typed_insert(davheaders::UploadOffset(new_offset));
+        h.typed_insert(davheaders::UploadComplete(false));
+        h.insert("upload-limit", self.upload_limit());
+        match location {
+            Some(location) => {
+                h.insert("location", location.parse().unwrap());
+                h.typed_insert(headers::ContentLength(0));
+                *res.status_mut() = StatusCode::CREATED;
+            }
+            None => *res.status_mut() = StatusCode::NO_CONTENT,
+        }
+        Ok(res)
+    }
+
+    // Write the staged data to the target.
+    async fn finish_upload(
+        &self,
+        req: &Request<()>,
+        path: &DavPath,
+        staged: &Staged,
+        len: u64,
+    ) -> DavResult<Response<Body>> {
+        let meta = self.fs.metadata(path, &self.credentials).await;
+        self.check_upload_target(req, path, meta.as_ref().map(|m| m.as_ref()).ok())
+            .await?;
+        if let Some(room) = self.quota_room(path).await {
+            let old_len = meta.as_ref().map(|m| m.len()).unwrap_or(0);
+            if len > room + old_len {
+                staged.remove().await;
+                return Ok(quota_exceeded());
+            }
+        }
+
+        self.autoversion_before_put(path, meta.is_ok()).await;
+
+        let mut oo = OpenOptions::write();
+        oo.create = true;
+        oo.truncate = true;
+        oo.size = Some(len);
+        let mut file = match self.fs.open(path, oo, &self.credentials).await {
+            Ok(f) => f,
+            Err(FsError::NotFound) => return Err(DavError::StatusClose(StatusCode::CONFLICT)),
+            Err(e) => return Err(DavError::FsError(e)),
+        };
+        let size = self.read_buf_size.unwrap_or(65536);
+        let mut reader = None;
+        loop {
+            let (f, bytes) = staged.read(reader.take(), size).await?;
+            if bytes.is_empty() {
+                break;
+            }
+            file.write_bytes(bytes).await?;
+            reader = Some(f);
+        }
+        file.flush().await?;
+        staged.remove().await;
+
+        self.autoversion_after_put(path).await;
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = match meta {
+            Ok(_) => StatusCode::NO_CONTENT,
+            Err(_) => {
+                res.headers_mut().typed_insert(headers::ContentLength(0));
+                StatusCode::CREATED
+            }
+        };
+        res.headers_mut()
+            .typed_insert(davheaders::UploadComplete(true));
+        if let Ok(meta) = file.metadata().await {
+            if let Some(etag) = davheaders::ETag::from_meta(meta.as_ref()) {
+                res.headers_mut().typed_insert(etag);
+            }
+            if let Ok(modified) = meta.modified() {
+                res.headers_mut()
+                    .typed_insert(headers::LastModified::from(modified));
+            }
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,301 @@
+//
+// RFC 3253, Versioning Extensions to WebDAV.
+//
+// The VERSION-CONTROL, CHECKOUT, CHECKIN and UNCHECKOUT methods, the
+// DAV:version-tree report, and auto-versioning on PUT.
+//
+// Versions are not resources in the filesystem. They are read-only and
+// available by adding "?version=<name>" to the URL of the file.
+//
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use headers::HeaderMapExt;
+use http::{Request, Response, StatusCode};
+use xmltree::Element;
+
+use crate::async_stream::AsyncStream;
+use crate::body::Body;
+use crate::conditional::if_match_get_tokens;
+use crate::davheaders;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::handle_props::PropWriter;
+use crate::util::{
+    DavMethod, dav_xml_error_response, systemtime_to_httpdate,
+    systemtime_to_rfc3339_without_nanosecond,
+};
+use crate::xmltree_ext::*;
+use crate::{DavError, DavInner, DavResult};
+
+// The version in a version URL (e.g. `/file.txt?version=2`).
+pub(crate) fn version_query(req: &Request<()>) -> Option<String> {
+    req.uri()
+        .query()?
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("version="))
+        .map(|v| v.to_string())
+}
+
+// URL of a version of a file.
+pub(crate) fn version_href(path: &DavPath, name: &str) -> String {
+    format!("{}?version={}", path.with_prefix().as_url_string(), name)
+}
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    pub(crate) async fn handle_version(
+        &self,
+        req: &Request<()>,
+        method: DavMethod,
+        xmldata: &[u8],
+    ) -> DavResult<Response<Body>> {
+        // only files can be put under version control.
+        let path = self.path(req);
+        let meta = self.visible_metadata(&path).await?;
+        if meta.is_dir() {
+            return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+        }
+
+        // check the If and If-* headers.
+        let tokens = match if_match_get_tokens(
+            req,
+            Some(meta.as_ref()),
+            self.fs.as_ref(),
+            &self.ls,
+            &path,
+            &self.credentials,
+        )
+        .await
+        {
+            Ok(t) => t,
+            Err(s) => return Err(s.into()),
+        };
+
+        // if locked check if we hold that lock.
+        if let Some(ref locksystem) = self.ls {
+            let principal = self.principal.as_deref();
+            if let Err(_l) = locksystem
+                .check(&path, principal, false, false, &tokens)
+                .await
+            {
+                return Err(StatusCode::LOCKED.into());
+            }
+        }
+
+        let history = self.fs.version_history(&path, &self.credentials).await?;
+
+        let mut res = Response::new(Body::empty());
+        res.headers_mut()
+            .typed_insert(headers::CacheControl::new().with_no_cache());
+        res.headers_mut().typed_insert(headers::ContentLength(0));
+
+        match (method, history) {
+            (DavMethod::VersionControl, _) => {
+                self.fs.version_control(&path, &self.credentials).await?;
+            }
+            (_, None) => {
+                return Err(DavError::StatusClose(StatusCode::METHOD_NOT_ALLOWED));
+            }
+            (DavMethod::Checkout, Some(h)) => {
+                if h.checked_out {
+                    return Ok(dav_xml_error_response(
+                        StatusCode::CONFLICT,
+                        "<D:must-be-checked-in/>",
+                    ));
+                }
+                self.fs.checkout(&path, &self.credentials).await?;
+            }
+            (DavMethod::Checkin, Some(h)) => {
+                if !h.checked_out {
+                    return Ok(dav_xml_error_response(
+                        StatusCode::CONFLICT,
+                        "<D:must-be-checked-out/>",
+                    ));
+                }
+                let keep_checked_out = !xmldata.is_empty() && {
+                    let tree = Element::parse2(Cursor::new(xmldata))?;
+                    if tree.name != "checkin" {
+                        return Err(DavError::XmlParseError);
+                    }
+                    tree.child_elems_iter()
+                        .any(|e| e.name == "keep-checked-out")
+                };
+                let version = self
+                    .fs
+                    .checkin(&path, keep_checked_out, &self.credentials)
+                    .await?;
+                let href = version_href(&path, &version.name);
+                res.headers_mut().insert("Location", href.parse().unwrap());
+                *res.status_mut() = StatusCode::CREATED;
+            }
+            (DavMethod::Uncheckout, Some(h)) => {
+                if !h.checked_out {
+                    return Ok(dav_xml_error_response(
+                        StatusCode::CONFLICT,
+                        "<D:must-be-checked-out-version-controlled-resource/>",
+                    ));
+                }
+                self.fs.uncheckout(&path, &self.credentials).await?;
+            }
+            _ => unreachable!(),
+        }
+        Ok(res)
+    }
+
+    // Is `version` a version of the file at `path`.
+    pub(crate) async fn is_version(&self, path: &DavPath, version: &str) -> bool {
+        match self.fs.version_history(path, &self.credentials).await {
+            Ok(Some(h)) => h.versions.iter().any(|v| v.name == version),
+            _ => false,
+        }
+    }
+
+    // Auto-versioning, before the file is written by PUT. With `autoversion`
+    // on, an existing file is put under version control first so that
+    // the old content is kept.
+    pub(crate) async fn autoversion_before_put(&self, path: &DavPath, exists: bool) {
+        if !self.autoversion || !exists {
+            return;
+        }
+        if let Ok(None) = self.fs.version_history(path, &self.credentials).await
+            && let Err(e) = self.fs.version_control(path, &self.credentials).await
+        {
+            debug!("autoversion: version_control {path}: {e:?}");
+        }
+    }
+
+    // Auto-versioning, after the file was written by PUT: a file that
+    // is checked in gets a new version (DAV:checkout-checkin).
+    pub(crate) async fn autoversion_after_put(&self, path: &DavPath) {
+        let res = match self.fs.version_history(path, &self.credentials).await {
+            Ok(Some(h)) if !h.checked_out => self
+                .fs
+                .checkin(path, false, &self.credentials)
+                .await
+                .map(|_| ()),
+            Ok(None) if self.autoversion => self.fs.version_control(path, &self.credentials).await,
+            _ => Ok(()),
+        };
+        if let Err(e) = res {
+            debug!("autoversion {path}: {e:?}");
+        }
+    }
+
+    // RFC 3253, DAV:version-tree report.
+    pub(crate) async fn handle_version_tree(
+        &self,
+        req: &Request<()>,
+        root: Element,
+    ) -> DavResult<Response<Body>> {
+        if let Some(depth) = req.headers().typed_get::<davheaders::Depth>()
+            && depth != davheaders::Depth::Zero
+        {
+            return Err(StatusCode::BAD_REQUEST.into());
+        }
+
+        let path = self.path(req);
+        let meta = self.visible_metadata(&path).await?;
+        let history = match self.fs.version_history(&path, &self.credentials).await {
+            Ok(Some(h)) if !meta.is_dir() => h,
+            _ => {
+                return Ok(dav_xml_error_response(
+                    StatusCode::FORBIDDEN,
+                    "<D:supported-report/>",
+                ));
+            }
+        };
+
+        let props = root
+            .child_elems_into_iter()
+            .find(|e| e.name == "prop")
+            .map(|e| e.take_child_elems())
+            .unwrap_or_default();
+
+        let mut res = Response::new(Body::empty());
+        let mut pw = PropWriter::new(
+            req,
+            &mut res,
+            "prop",
+            props.clone(),
+            self.fs.clone(),
+            None,
+            None,
+            self.principal.clone(),
+            self.credentials.clone(),
+            #[cfg(any(feature = "caldav", feature = "carddav"))]
+            &path,
+        )?;
+
+        *res.body_mut() = Body::from(AsyncStream::new(|tx| async move {
+            pw.set_tx(tx);
+            let versions = &history.versions;
+            for (idx, version) in versions.iter().enumerate() {
+                let mut found = HashMap::new();
+                for prop in &props {
+                    let (status, elem) = version_prop(prop, &path, versions, idx);
+                    found.entry(status).or_insert_with(Vec::new).push(elem);
+                }
+                pw.write_href_propresponse(&version_href(&path, &version.name), found)?;
+                pw.flush().await?;
+            }
+            pw.close().await?;
+            Ok(())
+        }));
+
+        Ok(res)
+    }
+}
+
+// A property of a version in the version-tree report.
+fn version_prop(
+    prop: &Element,
+    path: &DavPath,
+    versions: &[DavVersion],
+    idx: usize,
+) -> (StatusCode, Element) {
+    let version = &versions[idx];
+    let href = |v: Option<&DavVersion>| {
+        v.map(|v| Element::new2("D:href").text(version_href(path, &v.name)))
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    if prop.namespace.as_deref() == Some("DAV:") {
+        let elem = match prop.name.as_str() {
+            "version-name" => Some(Element::new2("D:version-name").text(version.name.clone())),
+            "creationdate" => Some(
+                Element::new2("D:creationdate")
+                    .text(systemtime_to_rfc3339_without_nanosecond(version.created)),
+            ),
+            "getlastmodified" => Some(
+                Element::new2("D:getlastmodified").text(systemtime_to_httpdate(version.created)),
+            ),
+            "getcontentlength" => {
+                Some(Element::new2("D:getcontentlength").text(version.size.to_string()))
+            }
+            "getcontenttype" => {
+                Some(Element::new2("D:getcontenttype").text(path.get_mime_type_str()))
+            }
+            "predecessor-set" => Some(Element::new3(
+                "D",
+                "predecessor-set",
+                href(idx.checked_sub(1).and_then(|i| versions.get(i))),
+            )),
+            "successor-set" => Some(Element::new3(
+                "D",
+                "successor-set",
+                href(versions.get(idx + 1)),
+            )),
+            _ => None,
+        };
+        return match elem {
+            Some(e) => (StatusCode::OK, e),
+            None => (
+                StatusCode::NOT_FOUND,
+                Element::new3("D", &prop.name, vec![]),
+            ),
+        };
+    }
+    let mut elem = prop.clone();
+    elem.children.clear();
+    (StatusCode::NOT_FOUND, elem)
+}
@@ -0,0 +1,1320 @@
+//
+// iCalendar data, as needed by the CalDAV reports: the parsed components,
+// date-times in the time zones of the calendar, and the instances of
+// recurring components (RFC 5545).
+//
+// This is the one parser that decides whether a calendar object is valid
+// (for a PUT into a calendar collection) and what it means (for the
+// reports). Values are kept as they were written, escaped, so that a
+// calendar object can be written back unchanged; the parser of the
+// `icalendar` crate unescapes them, and its recurrence support needs
+// the `rrule` crate.
+//
+// Recurrence rules are expanded from where the time range of interest
+// starts; only a rule with COUNT has to be counted from DTSTART. The
+// number of periods and instances that are looked at is limited, so that
+// a rule that (almost) never matches cannot hang a request.
+//
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use chrono::{
+    DateTime, Datelike, Days, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc,
+    Weekday,
+};
+
+// Limits for the expansion of recurrence rules.
+const MAX_PERIODS: u32 = 50_000;
+const MAX_INSTANCES: usize = 10_000;
+
+/// A component of an iCalendar object, like VCALENDAR or VEVENT.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Component {
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub components: Vec<Component>,
+}
+
+/// A property, with its parameters. Names are uppercase, values
+/// are kept as they are in the iCalendar data.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Property {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+/// Parse an iCalendar object, and return its VCALENDAR component.
+pub(crate) fn parse_calendar(content: &str) -> Option<Component> {
+    let mut stack: Vec<Component> = Vec::new();
+    for line in unfold(content) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let prop = parse_line(&line)?;
+        match prop.name.as_str() {
+            "BEGIN" => stack.push(Component {
+                name: prop.value.trim().to_ascii_uppercase(),
+                ..Component::default()
+            }),
+            "END" => {
+                let comp = stack.pop()?;
+                if !comp.name.eq_ignore_ascii_case(prop.value.trim()) {
+                    return None;
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.components.push(comp),
+                    None => return (comp.name == "VCALENDAR").then_some(comp),
+                }
+            }
+            _ => stack.last_mut()?.properties.push(prop),
+        }
+    }
+    None
+}
+
+// The content lines, with folded lines joined.
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// Lines longer than 75 octets are folded (RFC 5545, section 3.1).
+fn write_line(out: &mut String, line: &str) {
+    let mut rest = line;
+    let mut max = 75;
+    while rest.len() > max {
+        let mut n = max;
+        while !rest.is_char_boundary(n) {
+            n -= 1;
+        }
+        out.push_str(&rest[..n]);
+        out.push_str("\r\n ");
+        rest = &rest[n..];
+        // the space counts too.
+        max = 74;
+    }
+    out.push_str(rest);
+    out.push_str("\r\n");
+}
+
+// NAME;PARAM=value;PARAM="quoted value":value
+fn parse_line(line: &str) -> Option<Property> {
+    let end = line.find([';', ':'])?;
+    let name = line[..end].trim().to_ascii_uppercase();
+    let mut params = Vec::new();
+    let mut rest = &line[end..];
+    while let Some(param) = rest.strip_prefix(';') {
+        let (key, after) = param.split_once('=')?;
+        // the value ends at the first ';' or ':' that is not quoted.
+        let mut quoted = false;
+        let len = after
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                !quoted && (c == ';' || c == ':')
+            })
+            .map(|(n, _)| n)?;
+        params.push((key.trim().to_ascii_uppercase(), after[..len].to_string()));
+        rest = &after[len..];
+    }
+    Some(Property {
+        name,
+        params,
+        value: rest.strip_prefix(':')?.to_string(),
+    })
+}
+
+impl Component {
+    /// The component as iCalendar data.
+    pub fn to_ics(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut String) {
+        write_line(out, &format!("BEGIN:{}", self.name));
+        for prop in &self.properties {
+            let mut line = prop.name.clone();
+            for (name, value) in &prop.params {
+                line.push_str(&format!(";{name}={value}"));
+            }
+            line.push(':');
+            line.push_str(&prop.value);
+            write_line(out, &line);
+        }
+        for comp in &self.components {
+            comp.write(out);
+        }
+        write_line(out, &format!("END:{}", self.name));
+    }
+
+    /// Set a DATE or DATE-TIME property to a time in UTC.
+    pub fn set_date_time(&mut self, name: &str, t: DateTime<Utc>, date: bool) {
+        let prop = Property {
+            name: name.to_string(),
+            params: match date {
+                true => vec![("VALUE".to_string(), "DATE".to_string())],
+                false => Vec::new(),
+            },
+            value: match date {
+                true => t.format("%Y%m%d").to_string(),
+                false => format_utc(t),
+            },
+        };
+        match self.properties.iter_mut().find(|p| p.name == name) {
+            Some(p) => *p = prop,
+            None => self.properties.push(prop),
+        }
+    }
+
+    /// Set a property, replacing the first one with the same name.
+    pub fn set_value(&mut self, name: &str, value: &str) {
+        let prop = Property {
+            name: name.to_string(),
+            params: Vec::new(),
+            value: value.to_string(),
+        };
+        match self.properties.iter_mut().find(|p| p.name == name) {
+            Some(p) => *p = prop,
+            None => self.properties.push(prop),
+        }
+    }
+
+    /// Convert all date-times with a TZID to UTC, in this
+    /// component and its subcomponents.
+    pub fn convert_to_utc(&mut self, tzs: &Timezones) {
+        for prop in &mut self.properties {
+            let Some(tzid) = prop.param("TZID") else {
+                continue;
+            };
+            let values: Option<Vec<_>> = prop
+                .value
+                .split(',')
+                .map(|v| DateTimeValue::parse(v, Some(tzid)).filter(|t| !t.date))
+                .map(|t| Some(format_utc(tzs.to_utc(&t?))))
+                .collect();
+            if let Some(values) = values {
+                prop.value = values.join(",");
+                prop.params.retain(|(n, _)| n != "TZID");
+            }
+        }
+        for comp in &mut self.components {
+            comp.convert_to_utc(tzs);
+        }
+    }
+
+    /// The first property with this name.
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    /// All properties with this name.
+    pub fn properties_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.properties.iter().filter(move |p| p.name == name)
+    }
+
+    /// All subcomponents with this name.
+    pub fn components_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Component> {
+        self.components.iter().filter(move |c| c.name == name)
+    }
+
+    /// A date or date-time property.
+    pub fn date_time(&self, name: &str) -> Option<DateTimeValue> {
+        self.property(name).and_then(DateTimeValue::from_property)
+    }
+
+    /// The DURATION property.
+    pub fn duration(&self) -> Option<Duration> {
+        self.property("DURATION")
+            .and_then(|p| parse_duration(&p.value))
+    }
+
+    /// The RECURRENCE-ID of an overridden instance, in UTC.
+    pub fn recurrence_id(&self, tzs: &Timezones) -> Option<DateTime<Utc>> {
+        self.date_time("RECURRENCE-ID").map(|t| tzs.to_utc(&t))
+    }
+
+    /// Call `f` with the instances of this component, in order. A component
+    /// without RRULE or RDATE has one instance, at DTSTART. Instances that
+    /// are in `overridden` (by a component with a RECURRENCE-ID) are left out.
+    ///
+    /// Instances that start before `from` are skipped, and do not count
+    /// towards the limit on the number of instances. Instances that start
+    /// at or after `until` are not generated.
+    pub fn for_each_instance(
+        &self,
+        tzs: &Timezones,
+        overridden: &[DateTime<Utc>],
+        (from, until): (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+        mut f: impl FnMut(Instance) -> ControlFlow<()>,
+    ) {
+        let Some(dtstart) = self.date_time("DTSTART") else {
+            return;
+        };
+        let after_from = |t: DateTime<Utc>| from.is_none_or(|f| t >= f);
+        let before_until = |t: DateTime<Utc>| until.is_none_or(|u| t < u);
+        let mut starts = Vec::new();
+        if after_from(tzs.to_utc(&dtstart)) {
+            starts.push(tzs.to_utc(&dtstart));
+        }
+
+        // a RECURRENCE-ID instance is not expanded.
+        if self.property("RECURRENCE-ID").is_none() {
+            for rrule in self.properties_named("RRULE") {
+                let Some(rule) = RRule::parse(&rrule.value) else {
+                    continue;
+                };
+                let rule_until = rule.until.as_ref().map(|u| match u.zone {
+                    // UNTIL in UTC is compared with the instances in UTC.
+                    Zone::Utc => tzs.to_local(tzs.to_utc(u), &dtstart.zone),
+                    _ => u.local,
+                });
+                let rule_from = from.map(|f| tzs.to_local(f, &dtstart.zone));
+                recur(dtstart.local, &rule, rule_from, rule_until, |local| {
+                    let start = tzs.to_utc(&dtstart.with_local(local));
+                    if !before_until(start) || starts.len() >= MAX_INSTANCES {
+                        return ControlFlow::Break(());
+                    }
+                    if after_from(start) {
+                        starts.push(start);
+                    }
+                    ControlFlow::Continue(())
+                });
+            }
+            for rdate in self.properties_named("RDATE") {
+                for value in rdate.value.split(',') {
+                    // a PERIOD value: only its start counts here.
+                    let value = value.split('/').next().unwrap_or_default();
+                    if let Some(t) = DateTimeValue::parse(value, rdate.param("TZID"))
+                        && after_from(tzs.to_utc(&t))
+                    {
+                        starts.push(tzs.to_utc(&t));
+                    }
+                }
+            }
+        }
+        let mut excluded: Vec<_> = overridden.to_vec();
+        for exdate in self.properties_named("EXDATE") {
+            for value in exdate.value.split(',') {
+                if let Some(t) = DateTimeValue::parse(value, exdate.param("TZID")) {
+                    excluded.push(tzs.to_utc(&t));
+                }
+            }
+        }
+
+        starts.sort();
+        starts.dedup();
+        for start in starts {
+            if !before_until(start) {
+                break;
+            }
+            if excluded.contains(&start) {
+                continue;
+            }
+            let instance = Instance {
+                start,
+                date: dtstart.date,
+            };
+            if f(instance).is_break() {
+                break;
+            }
+        }
+    }
+}
+
+impl Property {
+    /// The value of a parameter, without quotes.
+    pub fn param<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        self.params_named(name).next()
+    }
+
+    /// Set a parameter, replacing the ones with the same name.
+    pub fn set_param(&mut self, name: &str, value: &str) {
+        self.params.retain(|(n, _)| n != name);
+        self.params.push((name.to_string(), value.to_string()));
+    }
+
+    /// The values of all parameters with this name, without quotes.
+    pub fn params_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.params
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, v)| {
+                v.strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(v)
+            })
+    }
+
+    /// The value as text, with backslash escapes undone.
+    pub fn text(&self) -> Cow<'_, str> {
+        if !self.value.contains('\\') {
+            return Cow::Borrowed(&self.value);
+        }
+        let mut text = String::with_capacity(self.value.len());
+        let mut chars = self.value.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('n' | 'N') => text.push('\n'),
+                    Some(c) => text.push(c),
+                    None => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        Cow::Owned(text)
+    }
+}
+
+/// One instance of a (recurring) component.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Instance {
+    /// When it starts, the DTSTART of this instance.
+    pub start: DateTime<Utc>,
+    /// Is DTSTART a DATE instead of a DATE-TIME.
+    pub date: bool,
+}
+
+/// The time zone of a date-time value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Zone {
+    Utc,
+    Floating,
+    Tzid(String),
+}
+
+/// A DATE or DATE-TIME value, in local time.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DateTimeValue {
+    pub local: NaiveDateTime,
+    pub date: bool,
+    pub zone: Zone,
+}
+
+impl DateTimeValue {
+    pub fn from_property(prop: &Property) -> Option<DateTimeValue> {
+        DateTimeValue::parse(&prop.value, prop.param("TZID"))
+    }
+
+    /// `20240101`, `20240101T120000`, or `20240101T120000Z`.
+    pub fn parse(value: &str, tzid: Option<&str>) -> Option<DateTimeValue> {
+        let value = value.trim();
+        if value.len() == 8 {
+            let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+            return Some(DateTimeValue {
+                local: date.and_time(NaiveTime::MIN),
+                date: true,
+                zone: Zone::Floating,
+            });
+        }
+        let (value, zone) = match value.strip_suffix(['Z', 'z']) {
+            Some(value) => (value, Zone::Utc),
+            None => match tzid {
+                Some(tzid) => (value, Zone::Tzid(tzid.to_string())),
+                None => (value, Zone::Floating),
+            },
+        };
+        let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        Some(DateTimeValue {
+            local,
+            date: false,
+            zone,
+        })
+    }
+
+    /// The same, at another local time.
+    pub fn with_local(&self, local: NaiveDateTime) -> DateTimeValue {
+        DateTimeValue {
+            local,
+            ..self.clone()
+        }
+    }
+}
+
+/// Parse a UTC date-time, like the start and end of a CalDAV time-range.
+pub(crate) fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    match DateTimeValue::parse(value, None)? {
+        DateTimeValue {
+            local,
+            zone: Zone::Utc,
+            ..
+        } => Some(local.and_utc()),
+        _ => None,
+    }
+}
+
+/// Format a date-time in UTC, as `20240101T120000Z`.
+pub(crate) fn format_utc(t: DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// A DURATION value, like `PT1H30M` or `-P1D`.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (sign, value) = match value.as_bytes().first()? {
+        b'-' => (-1, &value[1..]),
+        b'+' => (1, &value[1..]),
+        _ => (1, value),
+    };
+    let value = value.strip_prefix(['P', 'p'])?;
+    let mut secs: i64 = 0;
+    let mut num = String::new();
+    let mut in_time = false;
+    let mut any = false;
+    for c in value.chars() {
+        match c.to_ascii_uppercase() {
+            '0'..='9' => num.push(c),
+            'T' if !in_time => in_time = true,
+            unit => {
+                let n: i64 = num.parse().ok()?;
+                num.clear();
+                any = true;
+                secs += n * match (unit, in_time) {
+                    ('W', false) => 7 * 86400,
+                    ('D', false) => 86400,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    (any && num.is_empty()).then(|| Duration::seconds(sign * secs))
+}
+
+/// The time zones of a calendar, from its VTIMEZONE components.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Timezones {
+    zones: HashMap<String, TzRules>,
+    // for floating times, the time zone of the calendar.
+    floating: Option<TzRules>,
+}
+
+impl Timezones {
+    pub fn new(calendar: &Component) -> Timezones {
+        let mut zones = HashMap::new();
+        for vtimezone in calendar.components_named("VTIMEZONE") {
+            if let Some(tzid) = vtimezone.property("TZID") {
+                zones.insert(tzid.value.clone(), TzRules::new(vtimezone));
+            }
+        }
+        Timezones {
+            zones,
+            floating: None,
+        }
+    }
+
+    /// Use the VTIMEZONE in `calendar` (an iCalendar object, like the value
+    /// of the CALDAV:calendar-timezone property) for floating times.
+    pub fn set_floating(&mut self, calendar: &str) {
+        self.floating = parse_calendar(calendar)
+            .and_then(|cal| cal.components_named("VTIMEZONE").next().map(TzRules::new));
+    }
+
+    fn rules(&self, zone: &Zone) -> Option<&TzRules> {
+        match zone {
+            Zone::Utc => None,
+            Zone::Floating => self.floating.as_ref(),
+            // an unknown time zone is treated as UTC.
+            Zone::Tzid(tzid) => self.zones.get(tzid),
+        }
+    }
+
+    /// A date-time in UTC.
+    pub fn to_utc(&self, t: &DateTimeValue) -> DateTime<Utc> {
+        let local = t.local.and_utc();
+        match self.rules(&t.zone) {
+            Some(rules) => {
+                // the offset is that of the UTC time we are looking for,
+                // so look twice.
+                let guess = local - rules.offset_at(local);
+                local - rules.offset_at(guess)
+            }
+            None => local,
+        }
+    }
+
+    /// A PERIOD value, `start/end` or `start/duration`.
+    pub fn period(
+        &self,
+        value: &str,
+        tzid: Option<&str>,
+    ) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let (start, end) = value.split_once('/')?;
+        let start = self.to_utc(&DateTimeValue::parse(start, tzid)?);
+        let end = match parse_duration(end) {
+            Some(duration) => start + duration,
+            None => self.to_utc(&DateTimeValue::parse(end, tzid)?),
+        };
+        Some((start, end))
+    }
+
+    /// A UTC time as local time in a zone.
+    pub fn to_local(&self, t: DateTime<Utc>, zone: &Zone) -> NaiveDateTime {
+        match self.rules(zone) {
+            Some(rules) => (t + rules.offset_at(t)).naive_utc(),
+            None => t.naive_utc(),
+        }
+    }
+}
+
+// The observances (STANDARD and DAYLIGHT) of a VTIMEZONE.
+#[derive(Debug, Clone, Default)]
+struct TzRules {
+    observances: Vec<Observance>,
+}
+
+#[derive(Debug, Clone)]
+struct Observance {
+    // in local time, before the change.
+    start: NaiveDateTime,
+    offset_from: Duration,
+    offset_to: Duration,
+    rule: Option<RRule>,
+    rdates: Vec<NaiveDateTime>,
+}
+
+impl TzRules {
+    fn new(vtimezone: &Component) -> TzRules {
+        let mut observances = Vec::new();
+        for comp in &vtimezone.components {
+            let offset = |name| comp.property(name).and_then(|p| parse_utc_offset(&p.value));
+            let (Some(start), Some(offset_from), Some(offset_to)) = (
+                comp.date_time("DTSTART"),
+                offset("TZOFFSETFROM"),
+                offset("TZOFFSETTO"),
+            ) else {
+                continue;
+            };
+            let rdates = comp
+                .properties_named("RDATE")
+                .flat_map(|p| p.value.split(','))
+                .filter_map(|v| DateTimeValue::parse(v, None))
+                .map(|t| t.local)
+                .collect();
+            observances.push(Observance {
+                start: start.local,
+                offset_from,
+                offset_to,
+                rule: comp.property("RRULE").and_then(|p| RRule::parse(&p.value)),
+                rdates,
+            });
+        }
+        TzRules { observances }
+    }
+
+    // The UTC offset at a time.
+    fn offset_at(&self, t: DateTime<Utc>) -> Duration {
+        // the observance that started last before `t`.
+        let mut last: Option<(DateTime<Utc>, Duration)> = None;
+        for obs in &self.observances {
+            let onset = |local: NaiveDateTime| (local - obs.offset_from).and_utc();
+            let mut latest = None;
+            let mut consider = |local: NaiveDateTime| {
+                let at = onset(local);
+                if at <= t && latest.is_none_or(|l| at > l) {
+                    latest = Some(at);
+                }
+            };
+            consider(obs.start);
+            obs.rdates.iter().for_each(|&r| consider(r));
+            if let Some(ref rule) = obs.rule {
+                let rule_until = rule.until.as_ref().map(|u| match u.zone {
+                    Zone::Utc => (u.local.and_utc() + obs.offset_from).naive_utc(),
+                    _ => u.local,
+                });
+                recur(obs.start, rule, None, rule_until, |local| {
+                    if onset(local) > t {
+                        return ControlFlow::Break(());
+                    }
+                    consider(local);
+                    ControlFlow::Continue(())
+                });
+            }
+            if let Some(at) = latest
+                && last.is_none_or(|(l, _)| at > l)
+            {
+                last = Some((at, obs.offset_to));
+            }
+        }
+        match last {
+            Some((_, offset)) => offset,
+            // before the first observance.
+            None => self
+                .observances
+                .iter()
+                .min_by_key(|o| o.start)
+                .map(|o| o.offset_from)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// "+0100", "-0500", "+053000".
+fn parse_utc_offset(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let sign = match value.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    let num = |r: std::ops::Range<usize>| digits.get(r)?.parse::<i64>().ok();
+    let secs = match digits.len() {
+        4 => num(0..2)? * 3600 + num(2..4)? * 60,
+        6 => num(0..2)? * 3600 + num(2..4)? * 60 + num(4..6)?,
+        _ => return None,
+    };
+    Some(Duration::seconds(sign * secs))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Freq {
+    Secondly,
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A recurrence rule (RRULE).
+#[derive(Debug, Clone)]
+pub(crate) struct RRule {
+    freq: Freq,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<DateTimeValue>,
+    by_second: Vec<u32>,
+    by_minute: Vec<u32>,
+    by_hour: Vec<u32>,
+    // (ordinal, weekday), ordinal 0 is every such day.
+    by_day: Vec<(i32, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_year_day: Vec<i32>,
+    by_week_no: Vec<i32>,
+    by_month: Vec<u32>,
+    by_set_pos: Vec<i32>,
+    wkst: Weekday,
+}
+
+impl RRule {
+    /// `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE`.
+    pub fn parse(value: &str) -> Option<RRule> {
+        let mut rule = RRule {
+            freq: Freq::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_second: Vec::new(),
+            by_minute: Vec::new(),
+            by_hour: Vec::new(),
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_year_day: Vec::new(),
+            by_week_no: Vec::new(),
+            by_month: Vec::new(),
+            by_set_pos: Vec::new(),
+            wkst: Weekday::Mon,
+        };
+        let mut freq = None;
+        for part in value.trim().split(';') {
+            let (key, val) = part.split_once('=')?;
+            let list = || val.split(',');
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match val.to_ascii_uppercase().as_str() {
+                        "SECONDLY" => Freq::Secondly,
+                        "MINUTELY" => Freq::Minutely,
+                        "HOURLY" => Freq::Hourly,
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        "MONTHLY" => Freq::Monthly,
+                        "YEARLY" => Freq::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => rule.interval = val.parse().ok().filter(|&i| i > 0)?,
+                "COUNT" => rule.count = Some(val.parse().ok()?),
+                "UNTIL" => rule.until = Some(DateTimeValue::parse(val, None)?),
+                "BYSECOND" => {
+                    rule.by_second = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYMINUTE" => {
+                    rule.by_minute = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYHOUR" => rule.by_hour = list().map(|v| v.parse().ok()).collect::<Option<_>>()?,
+                "BYDAY" => rule.by_day = list().map(parse_weekday_num).collect::<Option<_>>()?,
+                "BYMONTHDAY" => {
+                    rule.by_month_day = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYYEARDAY" => {
+                    rule.by_year_day = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYWEEKNO" => {
+                    rule.by_week_no = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYMONTH" => {
+                    rule.by_month = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYSETPOS" => {
+                    rule.by_set_pos = list().map(|v| v.parse().ok()).collect::<Option<_>>()?
+                }
+                "WKST" => rule.wkst = parse_weekday(val)?,
+                _ => {}
+            }
+        }
+        rule.freq = freq?;
+        Some(rule)
+    }
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    Some(match value.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+// "MO", "2TU", "-1FR".
+fn parse_weekday_num(value: &str) -> Option<(i32, Weekday)> {
+    let value = value.trim();
+    let split = value.len().checked_sub(2)?;
+    let (num, day) = value.split_at_checked(split)?;
+    let num = match num {
+        "" => 0,
+        n => n.trim_start_matches('+').parse().ok()?,
+    };
+    Some((num, parse_weekday(day)?))
+}
+
+// Call `f` with the local start times that a rule generates from `start`,
+// in order, until `until` (inclusive) or COUNT is reached, or `f` breaks.
+// Without COUNT, the periods before the one that `from` is in are skipped,
+// so times before `from` may or may not be generated.
+fn recur(
+    start: NaiveDateTime,
+    rule: &RRule,
+    from: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+    mut f: impl FnMut(NaiveDateTime) -> ControlFlow<()>,
+) {
+    let mut count = 0;
+    let first = from.map_or(0, |from| first_period(start, rule, from));
+    for n in first..first.saturating_add(MAX_PERIODS) {
+        let Some(mut set) = period_candidates(start, rule, n) else {
+            return;
+        };
+        set.sort();
+        set.dedup();
+        if !rule.by_set_pos.is_empty() {
+            set = select_positions(&set, &rule.by_set_pos);
+        }
+        for t in set {
+            if t < start {
+                continue;
+            }
+            if until.is_some_and(|u| t > u) || f(t).is_break() {
+                return;
+            }
+            count += 1;
+            if rule.count.is_some_and(|c| count >= c) {
+                return;
+            }
+        }
+    }
+}
+
+// The period of a rule that `from` is in, or the one before it, to be safe
+// at the edges of periods. A rule with COUNT starts with the first period.
+fn first_period(start: NaiveDateTime, rule: &RRule, from: NaiveDateTime) -> u32 {
+    if rule.count.is_some() || from <= start {
+        return 0;
+    }
+    let years = i64::from(from.year() - start.year());
+    let units = match rule.freq {
+        Freq::Yearly => years,
+        Freq::Monthly => years * 12 + i64::from(from.month()) - i64::from(start.month()),
+        Freq::Weekly => (from - start).num_weeks(),
+        Freq::Daily => (from - start).num_days(),
+        Freq::Hourly => (from - start).num_hours(),
+        Freq::Minutely => (from - start).num_minutes(),
+        Freq::Secondly => (from - start).num_seconds(),
+    };
+    let n = units / i64::from(rule.interval) - 1;
+    u32::try_from(n.max(0)).unwrap_or(u32::MAX)
+}
+
+// The candidate times in the n-th period of a rule.
+fn period_candidates(start: NaiveDateTime, rule: &RRule, n: u32) -> Option<Vec<NaiveDateTime>> {
+    let step = n.checked_mul(rule.interval)?;
+    let date = start.date();
+    let dates = match rule.freq {
+        Freq::Yearly => {
+            let year = date.year().checked_add(i32::try_from(step).ok()?)?;
+            year_dates(year, start, rule)
+        }
+        Freq::Monthly => {
+            let first = date.with_day(1)?.checked_add_months(Months::new(step))?;
+            if !rule.by_month.is_empty() && !rule.by_month.contains(&first.month()) {
+                Vec::new()
+            } else {
+                month_dates(first, start, rule)
+            }
+        }
+        Freq::Weekly => {
+            let back = days_since(date.weekday(), rule.wkst);
+            let week = date
+                .checked_sub_days(Days::new(back))?
+                .checked_add_days(Days::new(u64::from(step) * 7))?;
+            (0..7)
+                .filter_map(|d| week.checked_add_days(Days::new(d)))
+                .filter(|d| match rule.by_day.is_empty() {
+                    true => d.weekday() == date.weekday(),
+                    false => rule.by_day.iter().any(|(_, wd)| *wd == d.weekday()),
+                })
+                .filter(|d| rule.by_month.is_empty() || rule.by_month.contains(&d.month()))
+                .collect()
+        }
+        Freq::Daily => {
+            let day = date.checked_add_days(Days::new(u64::from(step)))?;
+            match day_matches(day, rule) {
+                true => vec![day],
+                false => Vec::new(),
+            }
+        }
+        // below a day, the period is an instant.
+        freq => {
+            let unit = match freq {
+                Freq::Hourly => Duration::hours(1),
+                Freq::Minutely => Duration::minutes(1),
+                _ => Duration::seconds(1),
+            };
+            let t = start.checked_add_signed(unit * i32::try_from(step).ok()?)?;
+            if !day_matches(t.date(), rule)
+                || (freq <= Freq::Hourly && !limited(&rule.by_hour, t.hour()))
+                || (freq <= Freq::Minutely && !limited(&rule.by_minute, t.minute()))
+            {
+                return Some(Vec::new());
+            }
+            let hours = [t.hour()];
+            let minutes = expand(&rule.by_minute, t.minute(), freq > Freq::Minutely);
+            let seconds = expand(&rule.by_second, t.second(), true);
+            let seconds = match freq {
+                Freq::Secondly => seconds.into_iter().filter(|&s| s == t.second()).collect(),
+                _ => seconds,
+            };
+            return Some(times(t.date(), &hours, &minutes, &seconds));
+        }
+    };
+    let hours = expand(&rule.by_hour, start.hour(), true);
+    let minutes = expand(&rule.by_minute, start.minute(), true);
+    let seconds = expand(&rule.by_second, start.second(), true);
+    Some(
+        dates
+            .into_iter()
+            .flat_map(|d| times(d, &hours, &minutes, &seconds))
+            .collect(),
+    )
+}
+
+// A BYxxx list that expands: its values, or the value of DTSTART.
+fn expand(by: &[u32], default: u32, use_by: bool) -> Vec<u32> {
+    match by.is_empty() || !use_by {
+        true => vec![default],
+        false => by.to_vec(),
+    }
+}
+
+// A BYxxx list that limits.
+fn limited(by: &[u32], value: u32) -> bool {
+    by.is_empty() || by.contains(&value)
+}
+
+fn times(date: NaiveDate, hours: &[u32], minutes: &[u32], seconds: &[u32]) -> Vec<NaiveDateTime> {
+    let mut v = Vec::new();
+    for &h in hours {
+        for &m in minutes {
+            for &s in seconds {
+                if let Some(t) = date.and_hms_opt(h, m, s.min(59)) {
+                    v.push(t);
+                }
+            }
+        }
+    }
+    v
+}
+
+fn days_since(day: Weekday, since: Weekday) -> u64 {
+    u64::from((7 + day.num_days_from_monday() - since.num_days_from_monday()) % 7)
+}
+
+fn days_in_month(first: NaiveDate) -> Vec<NaiveDate> {
+    first
+        .iter_days()
+        .take_while(|d| d.month() == first.month())
+        .collect()
+}
+
+// A (possibly negative) day number in a list of days.
+fn nth<T: Copy>(list: &[T], n: i32) -> Option<T> {
+    match n {
+        n if n > 0 => list.get(n as usize - 1).copied(),
+        n if n < 0 => list
+            .len()
+            .checked_sub(n.unsigned_abs() as usize)
+            .map(|i| list[i]),
+        _ => None,
+    }
+}
+
+fn select_positions<T: Copy + Ord>(set: &[T], positions: &[i32]) -> Vec<T> {
+    let mut v: Vec<_> = positions.iter().filter_map(|&p| nth(set, p)).collect();
+    v.sort();
+    v.dedup();
+    v
+}
+
+// The days of a scope (month or year) that match BYDAY.
+fn by_day_in(days: &[NaiveDate], by_day: &[(i32, Weekday)]) -> Vec<NaiveDate> {
+    let mut v = Vec::new();
+    for &(n, wd) in by_day {
+        let matching: Vec<_> = days.iter().copied().filter(|d| d.weekday() == wd).collect();
+        match n {
+            0 => v.extend(matching),
+            n => v.extend(nth(&matching, n)),
+        }
+    }
+    v
+}
+
+fn month_dates(first: NaiveDate, start: NaiveDateTime, rule: &RRule) -> Vec<NaiveDate> {
+    let days = days_in_month(first);
+    if rule.by_month_day.is_empty() && rule.by_day.is_empty() {
+        return days
+            .into_iter()
+            .filter(|d| d.day() == start.day())
+            .collect();
+    }
+    let mut v: Vec<_> = match rule.by_month_day.is_empty() {
+        true => days.clone(),
+        false => rule
+            .by_month_day
+            .iter()
+            .filter_map(|&n| nth(&days, n))
+            .collect(),
+    };
+    if !rule.by_day.is_empty() {
+        let by_day = by_day_in(&days, &rule.by_day);
+        v.retain(|d| by_day.contains(d));
+    }
+    v
+}
+
+fn year_dates(year: i32, start: NaiveDateTime, rule: &RRule) -> Vec<NaiveDate> {
+    let Some(jan1) = NaiveDate::from_ymd_opt(year, 1, 1) else {
+        return Vec::new();
+    };
+    let days: Vec<_> = jan1.iter_days().take_while(|d| d.year() == year).collect();
+    let in_months = |d: &NaiveDate| rule.by_month.is_empty() || rule.by_month.contains(&d.month());
+
+    if !rule.by_week_no.is_empty() {
+        let weeks = week_numbers(year, rule.wkst);
+        return days
+            .iter()
+            .copied()
+            .filter(|d| {
+                let (w, total) = weeks(*d);
+                rule.by_week_no
+                    .iter()
+                    .any(|&n| n == w || (n < 0 && total + 1 + n == w))
+            })
+            .filter(|d| weekday_matches(*d, rule))
+            .filter(in_months)
+            .collect();
+    }
+    if !rule.by_year_day.is_empty() {
+        return rule
+            .by_year_day
+            .iter()
+            .filter_map(|&n| nth(&days, n))
+            .filter(in_months)
+            .filter(|d| rule.by_month_day.is_empty() || month_day_matches(*d, &rule.by_month_day))
+            .filter(|d| weekday_matches(*d, rule))
+            .collect();
+    }
+    if rule.by_month_day.is_empty() && rule.by_day.is_empty() {
+        let months = match rule.by_month.is_empty() {
+            true => vec![start.month()],
+            false => rule.by_month.clone(),
+        };
+        return months
+            .into_iter()
+            .filter_map(|m| NaiveDate::from_ymd_opt(year, m, start.day()))
+            .collect();
+    }
+    if rule.by_month.is_empty() && rule.by_month_day.is_empty() {
+        // BYDAY only: the ordinals are within the year.
+        return by_day_in(&days, &rule.by_day);
+    }
+    // per month.
+    let months = match rule.by_month.is_empty() {
+        true => (1..=12).collect(),
+        false => rule.by_month.clone(),
+    };
+    let mut v = Vec::new();
+    for m in months {
+        if let Some(first) = NaiveDate::from_ymd_opt(year, m, 1) {
+            v.extend(month_dates(first, start, rule));
+        }
+    }
+    v
+}
+
+// The week number of a day, and the number of weeks in the year that week
+// belongs to. Days at the start or end of a year can be in a week of the
+// previous or next year.
+fn week_numbers(year: i32, wkst: Weekday) -> impl Fn(NaiveDate) -> (i32, i32) {
+    // week 1 is the first week with at least four days in the year.
+    let first_week = |y: i32| {
+        let jan4 = NaiveDate::from_ymd_opt(y, 1, 4).unwrap_or_default();
+        jan4 - Days::new(days_since(jan4.weekday(), wkst))
+    };
+    let weeks: Vec<_> = (year - 1..=year + 2).map(first_week).collect();
+    move |d: NaiveDate| {
+        let i = weeks.iter().rposition(|w| *w <= d).unwrap_or(0).min(2);
+        let w = (d - weeks[i]).num_days() / 7 + 1;
+        let total = (weeks[i + 1] - weeks[i]).num_days() / 7;
+        (w as i32, total as i32)
+    }
+}
+
+fn month_day_matches(d: NaiveDate, by_month_day: &[i32]) -> bool {
+    let days = days_in_month(d.with_day(1).unwrap_or(d));
+    by_month_day.iter().any(|&n| nth(&days, n) == Some(d))
+}
+
+// BYDAY as a limit: only the weekday counts.
+fn weekday_matches(d: NaiveDate, rule: &RRule) -> bool {
+    rule.by_day.is_empty() || rule.by_day.iter().any(|(_, wd)| *wd == d.weekday())
+}
+
+// The BYxxx rules that limit a day, for the DAILY and smaller frequencies.
+fn day_matches(d: NaiveDate, rule: &RRule) -> bool {
+    let year_day = || {
+        let days: Vec<_> = NaiveDate::from_ymd_opt(d.year(), 1, 1)
+            .map(|jan1| {
+                jan1.iter_days()
+                    .take_while(|x| x.year() == d.year())
+                    .collect()
+            })
+            .unwrap_or_default();
+        rule.by_year_day.iter().any(|&n| nth(&days, n) == Some(d))
+    };
+    (rule.by_month.is_empty() || rule.by_month.contains(&d.month()))
+        && (rule.by_month_day.is_empty() || month_day_matches(d, &rule.by_month_day))
+        && (rule.by_year_day.is_empty() || year_day())
+        && weekday_matches(d, rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").unwrap()
+    }
+
+    fn occurrences(start: &str, rule: &str, max: usize) -> Vec<String> {
+        let rule = RRule::parse(rule).unwrap();
+        let until = rule.until.as_ref().map(|u| u.local);
+        let mut v = Vec::new();
+        recur(dt(start), &rule, None, until, |t| {
+            v.push(t.format("%Y%m%dT%H%M%S").to_string());
+            match v.len() < max {
+                true => ControlFlow::Continue(()),
+                false => ControlFlow::Break(()),
+            }
+        });
+        v
+    }
+
+    #[test]
+    fn test_rrule() {
+        assert_eq!(
+            occurrences("20240101T090000", "FREQ=DAILY;COUNT=3", 10),
+            ["20240101T090000", "20240102T090000", "20240103T090000"]
+        );
+        assert_eq!(
+            occurrences(
+                "20240101T090000",
+                "FREQ=WEEKLY;BYDAY=MO,WE;UNTIL=20240110T000000Z",
+                10
+            ),
+            ["20240101T090000", "20240103T090000", "20240108T090000"]
+        );
+        // last friday of the month.
+        assert_eq!(
+            occurrences("20240126T100000", "FREQ=MONTHLY;BYDAY=-1FR", 3),
+            ["20240126T100000", "20240223T100000", "20240329T100000"]
+        );
+        // the 31st, in months that have one.
+        assert_eq!(
+            occurrences("20240131T100000", "FREQ=MONTHLY;COUNT=3", 10),
+            ["20240131T100000", "20240331T100000", "20240531T100000"]
+        );
+        // last weekday of the month.
+        assert_eq!(
+            occurrences(
+                "20240131T100000",
+                "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+                3
+            ),
+            ["20240131T100000", "20240229T100000", "20240329T100000"]
+        );
+        assert_eq!(
+            occurrences("20240310T020000", "FREQ=YEARLY;BYMONTH=3;BYDAY=2SU", 2),
+            ["20240310T020000", "20250309T020000"]
+        );
+        assert_eq!(
+            occurrences("20240101T000000", "FREQ=HOURLY;INTERVAL=6;COUNT=3", 10),
+            ["20240101T000000", "20240101T060000", "20240101T120000"]
+        );
+        assert_eq!(
+            occurrences("20240101T000000", "FREQ=YEARLY;BYWEEKNO=1;BYDAY=MO", 2),
+            ["20240101T000000", "20241230T000000"]
+        );
+        // BYHOUR and BYMINUTE limit the rules with a smaller frequency.
+        assert_eq!(
+            occurrences("20240101T090000", "FREQ=MINUTELY;INTERVAL=30;BYHOUR=9", 3),
+            ["20240101T090000", "20240101T093000", "20240102T090000"]
+        );
+        assert_eq!(
+            occurrences("20240101T090000", "FREQ=SECONDLY;INTERVAL=20;BYMINUTE=0", 4),
+            [
+                "20240101T090000",
+                "20240101T090020",
+                "20240101T090040",
+                "20240101T100000"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rrule_from() {
+        // without COUNT, the periods before `from` are skipped.
+        let rule = RRule::parse("FREQ=MINUTELY;INTERVAL=7").unwrap();
+        let from = dt("20260301T000000");
+        let mut first = None;
+        recur(dt("20000101T000000"), &rule, Some(from), None, |t| {
+            if t < from {
+                return ControlFlow::Continue(());
+            }
+            first = Some(t);
+            ControlFlow::Break(())
+        });
+        assert_eq!(first, Some(dt("20260301T000200")));
+
+        // with COUNT, the instances before `from` count.
+        let rule = RRule::parse("FREQ=DAILY;COUNT=3").unwrap();
+        let mut n = 0;
+        recur(dt("20240101T090000"), &rule, Some(from), None, |_| {
+            n += 1;
+            ControlFlow::Continue(())
+        });
+        assert_eq!(n, 3);
+    }
+
+    #[test]
+    fn test_parse_and_write() {
+        let long = "x".repeat(100);
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\n\
+             BEGIN:VEVENT\r\n\
+             ATTENDEE;CN=\"Doe; John\";ROLE=CHAIR:mailto:john@example.com\r\n\
+             CATEGORIES:a\\,b,c\r\n\
+             DESCRIPTION:{}\r\n {}\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n",
+            &long[..60],
+            &long[60..]
+        );
+        let cal = parse_calendar(&ics).unwrap();
+        let event = &cal.components[0];
+        let attendee = event.property("ATTENDEE").unwrap();
+        assert_eq!(attendee.param("CN"), Some("Doe; John"));
+        assert_eq!(attendee.param("ROLE"), Some("CHAIR"));
+        assert_eq!(attendee.value, "mailto:john@example.com");
+        assert_eq!(event.property("CATEGORIES").unwrap().text(), "a,b,c");
+        assert_eq!(event.property("DESCRIPTION").unwrap().value, long);
+
+        let out = cal.to_ics();
+        assert!(out.lines().all(|l| l.len() <= 76));
+        assert!(out.contains("CATEGORIES:a\\,b,c\r\n"));
+        let again = parse_calendar(&out).unwrap();
+        assert_eq!(again.to_ics(), out);
+        assert!(parse_calendar("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nEND:VCALENDAR\r\n").is_none());
+    }
+
+    #[test]
+    fn test_timezone() {
+        let cal = parse_calendar(
+            "BEGIN:VCALENDAR\r\n\
+             BEGIN:VTIMEZONE\r\n\
+             TZID:Europe/Berlin\r\n\
+             BEGIN:DAYLIGHT\r\n\
+             TZOFFSETFROM:+0100\r\n\
+             TZOFFSETTO:+0200\r\n\
+             DTSTART:19700329T020000\r\n\
+             RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n\
+             END:DAYLIGHT\r\n\
+             BEGIN:STANDARD\r\n\
+             TZOFFSETFROM:+0200\r\n\
+             TZOFFSETTO:+0100\r\n\
+             DTSTART:19701025T030000\r\n\
+             RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\n\
+             END:STANDARD\r\n\
+             END:VTIMEZONE\r\n\
+             END:VCALENDAR\r\n",
+        )
+        .unwrap();
+        let tzs = Timezones::new(&cal);
+        let berlin = |s: &str| DateTimeValue::parse(s, Some("Europe/Berlin")).unwrap();
+        assert_eq!(
+            format_utc(tzs.to_utc(&berlin("20240115T120000"))),
+            "20240115T110000Z"
+        );
+        assert_eq!(
+            format_utc(tzs.to_utc(&berlin("20240715T120000"))),
+            "20240715T100000Z"
+        );
+        assert_eq!(
+            format_utc(tzs.to_utc(&berlin("20240331T030000"))),
+            "20240331T010000Z"
+        );
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("-P1W"), Some(Duration::days(-7)));
+        assert_eq!(parse_duration("P1H"), None);
+    }
+}
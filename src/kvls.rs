@@ -0,0 +1,378 @@
+//! Locksystem on top of a key-value store.
+//!
+//! If several instances of a server share the same storage, they also
+//! have to share their locks. `KvLs` keeps the locks in a key-value store
+//! that all instances can reach, like Redis, etcd or a database table.
+//! The store only has to implement the small [`LockKv`] trait, the
+//! locking logic itself is the same as that of [`MemLs`](crate::memls::MemLs).
+//!
+//! All locks are kept as one value, under one key. Changes are made
+//! with compare-and-set, and retried if another instance changed the
+//! locks in the meantime. Locks that have expired are dropped the next
+//! time the locks are read.
+//!
+//! [`MemKv`] is an in-process implementation of `LockKv`, for testing.
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use dyn_clone::{DynClone, clone_trait_object};
+use futures_util::FutureExt;
+use xmltree::Element;
+
+use crate::davpath::DavPath;
+use crate::ls::*;
+use crate::memls::LockTree;
+
+/// A key-value store, as used by [`KvLs`].
+pub trait LockKv: Debug + Send + Sync + DynClone {
+    /// Get the value of a key. Returns `None` if it does not exist,
+    /// or if it has expired.
+    fn get<'a>(&'a self, key: &'a str) -> LsFuture<'a, io::Result<Option<Vec<u8>>>>;
+
+    /// Set the value of a key, if its current value is `old`
+    /// (`None`: if it does not exist). Returns `false` if it was not.
+    ///
+    /// If `ttl` is set, the key expires after that time.
+    fn compare_and_set<'a>(
+        &'a self,
+        key: &'a str,
+        old: Option<&'a [u8]>,
+        new: &'a [u8],
+        ttl: Option<Duration>,
+    ) -> LsFuture<'a, io::Result<bool>>;
+
+    /// Delete a key, if its current value is `old`.
+    /// Returns `false` if it was not.
+    fn delete<'a>(&'a self, key: &'a str, old: &'a [u8]) -> LsFuture<'a, io::Result<bool>>;
+}
+
+clone_trait_object! {LockKv}
+
+/// LockSystem that keeps its locks in a key-value store.
+#[derive(Debug, Clone)]
+pub struct KvLs {
+    kv: Box<dyn LockKv>,
+    key: String,
+}
+
+// How often we try to change the locks if others keep changing them as well.
+const MAX_ATTEMPTS: usize = 16;
+
+impl KvLs {
+    /// Create a new "kvls" locksystem, that keeps its locks
+    /// in `kv` under `key`.
+    pub fn new(kv: Box<dyn LockKv>, key: impl Into<String>) -> Box<KvLs> {
+        Box::new(KvLs {
+            kv,
+            key: key.into(),
+        })
+    }
+
+    // The current locks, and the value they were read from.
+    async fn read(&self) -> io::Result<(LockTree, Option<Vec<u8>>)> {
+        let data = self.kv.get(&self.key).await?;
+        let tree = data
+            .as_deref()
+            .map(LockTree::load)
+            .unwrap_or_else(LockTree::new);
+        Ok((tree, data))
+    }
+
+    // Change the locks, and write them back if `f` says they were changed.
+    async fn update<F, R>(&self, mut f: F) -> io::Result<R>
+    where
+        F: FnMut(&mut LockTree) -> (R, bool) + Send,
+        R: Send,
+    {
+        for _ in 0..MAX_ATTEMPTS {
+            let (mut tree, old) = self.read().await?;
+            let (res, changed) = f(&mut tree);
+            if !changed {
+                return Ok(res);
+            }
+            let done = match (tree.is_empty(), old.as_deref()) {
+                (true, Some(old)) => self.kv.delete(&self.key, old).await?,
+                (true, None) => true,
+                (false, old) => {
+                    let new = tree.save();
+                    // lock-null resources are kept until they are cleaned up.
+                    let ttl = match tree.has_lock_nulls() {
+                        true => None,
+                        false => expires_in(&tree.all_locks()),
+                    };
+                    self.kv.compare_and_set(&self.key, old, &new, ttl).await?
+                }
+            };
+            if done {
+                return Ok(res);
+            }
+            trace!("kvls: locks changed while updating them, retrying");
+        }
+        Err(io::Error::other("kvls: too many concurrent updates"))
+    }
+}
+
+// When the last lock expires, so that the key can expire with it.
+fn expires_in(locks: &[DavLock]) -> Option<Duration> {
+    let mut last = SystemTime::UNIX_EPOCH;
+    for lock in locks {
+        last = last.max(lock.timeout_at?);
+    }
+    // round up, a key should not disappear before its locks.
+    let ttl = last.duration_since(SystemTime::now()).unwrap_or_default();
+    Some(ttl + Duration::from_secs(1))
+}
+
+// If the store cannot be reached, treat everything as locked.
+fn unavailable(path: &DavPath, err: io::Error) -> DavLock {
+    error!("kvls: {err}");
+    DavLock {
+        token: String::new(),
+        path: Box::new(path.clone()),
+        principal: None,
+        owner: None,
+        timeout_at: None,
+        timeout: None,
+        shared: false,
+        deep: true,
+    }
+}
+
+impl DavLockSystem for KvLs {
+    fn lock(
+        &'_ self,
+        path: &DavPath,
+        principal: Option<&str>,
+        owner: Option<&Element>,
+        timeout: Option<Duration>,
+        shared: bool,
+        deep: bool,
+    ) -> LsFuture<'_, Result<DavLock, DavLock>> {
+        let path = path.clone();
+        let principal = principal.map(|p| p.to_string());
+        let owner = owner.cloned();
+        async move {
+            let res = self
+                .update(|tree| {
+                    let res = tree.lock(
+                        &path,
+                        principal.as_deref(),
+                        owner.as_ref(),
+                        timeout,
+                        shared,
+                        deep,
+                    );
+                    let changed = res.is_ok();
+                    (res, changed)
+                })
+                .await;
+            res.unwrap_or_else(|e| Err(unavailable(&path, e)))
+        }
+        .boxed()
+    }
+
+    fn unlock(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, Result<(), ()>> {
+        let path = path.clone();
+        let token = token.to_string();
+        async move {
+            let res = self
+                .update(|tree| {
+                    let res = tree.unlock(&path, &token);
+                    let changed = res.is_ok();
+                    (res, changed)
+                })
+                .await;
+            res.unwrap_or_else(|e| {
+                error!("kvls: {e}");
+                Err(())
+            })
+        }
+        .boxed()
+    }
+
+    fn refresh(
+        &'_ self,
+        path: &DavPath,
+        token: &str,
+        timeout: Option<Duration>,
+    ) -> LsFuture<'_, Result<DavLock, ()>> {
+        let path = path.clone();
+        let token = token.to_string();
+        async move {
+            let res = self
+                .update(|tree| {
+                    let res = tree.refresh(&path, &token, timeout);
+                    let changed = res.is_ok();
+                    (res, changed)
+                })
+                .await;
+            res.unwrap_or_else(|e| {
+                error!("kvls: {e}");
+                Err(())
+            })
+        }
+        .boxed()
+    }
+
+    fn check(
+        &'_ self,
+        path: &DavPath,
+        principal: Option<&str>,
+        ignore_principal: bool,
+        deep: bool,
+        submitted_tokens: &[String],
+    ) -> LsFuture<'_, Result<(), DavLock>> {
+        let path = path.clone();
+        let principal = principal.map(|p| p.to_string());
+        let submitted_tokens = submitted_tokens.to_vec();
+        async move {
+            match self.read().await {
+                Ok((tree, _)) => tree.check(
+                    &path,
+                    principal.as_deref(),
+                    ignore_principal,
+                    deep,
+                    &submitted_tokens,
+                ),
+                Err(e) => Err(unavailable(&path, e)),
+            }
+        }
+        .boxed()
+    }
+
+    fn discover(&'_ self, path: &DavPath) -> LsFuture<'_, Vec<DavLock>> {
+        let path = path.clone();
+        async move {
+            match self.read().await {
+                Ok((tree, _)) => tree.discover(&path),
+                Err(e) => {
+                    error!("kvls: {e}");
+                    Vec::new()
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&'_ self, path: &DavPath) -> LsFuture<'_, Result<(), ()>> {
+        let path = path.clone();
+        async move {
+            let res = self
+                .update(|tree| {
+                    let changed = tree.delete(&path);
+                    ((), changed)
+                })
+                .await;
+            res.map_err(|e| {
+                error!("kvls: {e}");
+            })
+        }
+        .boxed()
+    }
+
+    fn add_lock_null(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, ()> {
+        let path = path.clone();
+        let token = token.to_string();
+        async move {
+            let res = self
+                .update(|tree| ((), tree.add_lock_null(&path, &token)))
+                .await;
+            if let Err(e) = res {
+                error!("kvls: {e}");
+            }
+        }
+        .boxed()
+    }
+
+    fn remove_lock_null(&'_ self, path: &DavPath) -> LsFuture<'_, ()> {
+        let path = path.clone();
+        async move {
+            let res = self.update(|tree| ((), tree.remove_lock_null(&path))).await;
+            if let Err(e) = res {
+                error!("kvls: {e}");
+            }
+        }
+        .boxed()
+    }
+
+    fn stale_lock_nulls(&'_ self) -> LsFuture<'_, Vec<DavPath>> {
+        async move {
+            let res = self
+                .update(|tree| {
+                    let stale = tree.stale_lock_nulls();
+                    let changed = !stale.is_empty();
+                    (stale, changed)
+                })
+                .await;
+            res.unwrap_or_else(|e| {
+                error!("kvls: {e}");
+                Vec::new()
+            })
+        }
+        .boxed()
+    }
+}
+
+/// In-process key-value store, for testing [`KvLs`].
+///
+/// Clones share the same data, like several servers
+/// that use the same store.
+#[derive(Debug, Clone, Default)]
+pub struct MemKv(Arc<Mutex<KvMap>>);
+
+// The values, and when they expire.
+type KvMap = HashMap<String, (Vec<u8>, Option<Instant>)>;
+
+impl MemKv {
+    /// Create a new, empty, store.
+    pub fn new() -> Box<MemKv> {
+        Box::default()
+    }
+
+    // The current value of a key, removing it if it has expired.
+    fn current<'a>(map: &'a mut KvMap, key: &str) -> Option<&'a [u8]> {
+        if map
+            .get(key)
+            .is_some_and(|(_, exp)| exp.is_some_and(|e| e <= Instant::now()))
+        {
+            map.remove(key);
+        }
+        map.get(key).map(|(value, _)| value.as_slice())
+    }
+}
+
+impl LockKv for MemKv {
+    fn get<'a>(&'a self, key: &'a str) -> LsFuture<'a, io::Result<Option<Vec<u8>>>> {
+        let map = &mut *self.0.lock().unwrap();
+        let value = MemKv::current(map, key).map(|v| v.to_vec());
+        std::future::ready(Ok(value)).boxed()
+    }
+
+    fn compare_and_set<'a>(
+        &'a self,
+        key: &'a str,
+        old: Option<&'a [u8]>,
+        new: &'a [u8],
+        ttl: Option<Duration>,
+    ) -> LsFuture<'a, io::Result<bool>> {
+        let map = &mut *self.0.lock().unwrap();
+        let ok = MemKv::current(map, key) == old;
+        if ok {
+            let expires = ttl.map(|ttl| Instant::now() + ttl);
+            map.insert(key.to_string(), (new.to_vec(), expires));
+        }
+        std::future::ready(Ok(ok)).boxed()
+    }
+
+    fn delete<'a>(&'a self, key: &'a str, old: &'a [u8]) -> LsFuture<'a, io::Result<bool>> {
+        let map = &mut *self.0.lock().unwrap();
+        let ok = MemKv::current(map, key) == Some(old);
+        if ok {
+            map.remove(key);
+        }
+        std::future::ready(Ok(ok)).boxed()
+    }
+}
@@ -49,13 +49,95 @@
 //!
 //! CalDAV support implements the core CalDAV specification from [RFC4791], including:
 //! - Calendar collections (MKCALENDAR method)
-//! - Calendar queries (REPORT method with calendar-query)
+//! - Calendar queries (REPORT method with calendar-query), with the full
+//!   filter semantics: time ranges, recurring events and time zones
 //! - Calendar multiget (REPORT method with calendar-multiget)
+//! - Partial retrieval of calendar data: expanded recurrences, and a
+//!   selection of components and properties
+//! - Free/busy queries (REPORT method with free-busy-query), that take
+//!   recurring events and VAVAILABILITY ([RFC7953]) into account
 //! - CalDAV properties (supported-calendar-component-set, etc.)
-//! - iCalendar data validation and processing
-//!
-//! The litmus test suite also has tests for RFC3744 "acl" and "principal",
-//! RFC5842 "bind", and RFC3253 "versioning". Those we do not support right now.
+//! - iCalendar data validation and processing: a PUT into a calendar collection
+//!   must be a valid calendar object, with a UID that is unique in the collection
+//!
+//! Scheduling ([RFC6638]) is done for the calendar users that are configured
+//! with [`DavConfig::calendar_user`]: organizers and attendees get iTIP messages
+//! in their inbox when events change, and free/busy requests can be POSTed to
+//! the outbox. See the [`schedule`] module.
+//!
+//! Collection synchronization ([RFC6578]) is supported through the
+//! `sync-collection` REPORT and the `DAV:sync-token` property, for
+//! filesystems that keep a changelog (both `LocalFs` and `MemFs` do).
+//!
+//! Access control ([RFC3744]) is available by configuring an
+//! [access control backend][acl] with [`DavConfig::acl`]: privileges are
+//! checked on every request, the `ACL` method can change them, and the
+//! `DAV:acl`, `DAV:current-user-privilege-set`, `DAV:owner` and
+//! `DAV:principal-collection-set` properties and principal resources are
+//! served. [`MemAcl`] is a simple in-memory implementation.
+//!
+//! Versioning ([RFC3253]) of files is supported through the `VERSION-CONTROL`,
+//! `CHECKOUT`, `CHECKIN` and `UNCHECKOUT` methods and the `version-tree`
+//! REPORT. Versions are read-only, and are available by adding `?version=<name>`
+//! to the URL of the file. A PUT on a file that is checked in creates a new
+//! version, and with [`DavConfig::autoversion`] every file written by PUT is
+//! put under version control. `MemFs` keeps versions in memory, `LocalFs`
+//! stores them in a `.davversions` directory next to the file.
+//!
+//! Bindings ([RFC5842]) are supported through the `BIND`, `UNBIND` and `REBIND`
+//! methods and the `resource-id` and `parent-set` properties. A binding loop
+//! is reported as `508 Loop Detected` by a Depth: infinity PROPFIND. `MemFs`
+//! can bind files and collections, `LocalFs` only files (as hard links).
+//!
+//! Redirect references ([RFC4437]) are enabled with [`DavConfig::redirectrefs`].
+//! They are created with `MKREDIRECTREF` and changed with `UPDATEREDIRECTREF`,
+//! and have the `reftarget` and `redirect-lifetime` properties. Requests for
+//! a redirect reference get a `302`/`307` redirect (`301`/`308` if it is
+//! permanent), unless the `Apply-To-Redirect-Ref: T` header is sent. `LocalFs`
+//! maps them to symbolic links, `MemFs` has a node type of its own.
+//!
+//! The quota properties ([RFC4331]) are served if the filesystem implements
+//! `get_quota`, `LocalFs` does so using `statvfs` on unix. Subtrees can be
+//! given a byte limit with [`DavConfig::quota_limit`].
+//!
+//! The `SEARCH` method ([RFC5323]) supports the `DAV:basicsearch` grammar,
+//! with conditions on the size, content type, modification time, name and
+//! dead properties of the resources in the scope, and `orderby` / `limit`.
+//!
+//! With the `proppatch` feature, `MKCOL` accepts an extended MKCOL ([RFC5689])
+//! body that sets the resource type and the initial properties. This can
+//! also be used to create calendars and address books.
+//!
+//! Ordered collections ([RFC3648]) are supported on filesystems that can store
+//! dead properties: the `ORDERPATCH` method, the `Ordering-Type` and `Position`
+//! headers, and the `DAV:ordering-type` property.
+//!
+//! The `Prefer` header ([RFC8144]) is honoured for `return=minimal`, which leaves
+//! out the properties that do not exist in a `PROPFIND`, and `depth-noroot`, which
+//! leaves out the collection itself in a `Depth: 1` listing.
+//!
+//! Large uploads can be resumed after the connection was lost, with the
+//! [resumable uploads] protocol (the successor of tus). It is enabled
+//! with [`DavConfig::resumable_uploads`].
+//!
+//! The chunked upload protocol (v2) of the Nextcloud and ownCloud clients is
+//! supported: the chunks are PUT in an upload collection, and a `MOVE` of its
+//! `.file` member puts them together at the destination, after checking the
+//! `OC-Total-Length` and `OC-Checksum` headers.
+//!
+//! With [`DavConfig::atomic_put`], a `PUT` writes to a temporary file that only replaces
+//! the old file when the whole body was received, so that an interrupted upload
+//! never leaves a truncated file behind.
+//!
+//! A `LOCK` of an unmapped URL creates an empty resource. With
+//! [`DavConfig::lock_null`] it is removed again when it is unlocked, or (on the
+//! next request) when its lock has expired, without having been written to
+//! (RFC2518 lock-null resources).
+//!
+//! The checksums in the `OC-Checksum`, `Content-Digest` and `Repr-Digest` ([RFC9530])
+//! headers of a `PUT` are verified, and a mismatch is rejected. `LocalFs` and `MemFs`
+//! store them with the file, and return them in the `oc:checksums` property and the
+//! `Digest` and `Repr-Digest` headers of a `GET`.
 //!
 //! The relevant parts of the HTTP RFCs are also implemented, such as the
 //! preconditions (If-Match, If-None-Match, If-Modified-Since, If-Unmodified-Since,
@@ -70,7 +152,8 @@
 //!
 //! Included are two filesystems:
 //!
-//! - [`LocalFs`]: serves a directory on the local filesystem
+//! - [`LocalFs`]: serves a directory on the local filesystem. supports DAV properties,
+//!   stored in extended attributes or in hidden `.davprops` files.
 //! - [`MemFs`]: ephemeral in-memory filesystem. supports DAV properties.
 //!
 //! You're able to implement custom filesystem adapter:
@@ -78,9 +161,12 @@
 //! - [`DavFileSystem`]: without authorization.
 //! - [`GuardedFileSystem`]: when access control is required.
 //!
-//! Also included are two locksystems:
+//! Also included are four locksystems:
 //!
 //! - [`MemLs`]: ephemeral in-memory locksystem.
+//! - [`FileLs`]: persistent locksystem, keeps the locks in a local file.
+//! - [`KvLs`]: shared locksystem for multiple servers, keeps the locks in a
+//!   key-value store like Redis or etcd.
 //! - [`FakeLs`]: fake locksystem. just enough LOCK/UNLOCK support for macOS/Windows.
 //!
 //! External filesystem adapter implementations:
@@ -100,6 +186,7 @@
 //! This adds support for:
 //! - `MKCALENDAR` method for creating calendar collections
 //! - `REPORT` method for calendar queries
+//! - `POST` of free/busy requests to a scheduling outbox
 //! - CalDAV-specific properties and resource types
 //! - iCalendar data validation
 //! - Calendar-specific WebDAV extensions
@@ -170,10 +257,28 @@
 //! [RFC4918]: https://tools.ietf.org/html/rfc4918
 //! [`CalDAV`]: https://tools.ietf.org/html/rfc4791
 //! [RFC4791]: https://tools.ietf.org/html/rfc4791
+//! [RFC6578]: https://tools.ietf.org/html/rfc6578
+//! [RFC3744]: https://tools.ietf.org/html/rfc3744
+//! [RFC3253]: https://tools.ietf.org/html/rfc3253
+//! [RFC5842]: https://tools.ietf.org/html/rfc5842
+//! [RFC4437]: https://tools.ietf.org/html/rfc4437
+//! [RFC4331]: https://tools.ietf.org/html/rfc4331
+//! [RFC7953]: https://tools.ietf.org/html/rfc7953
+//! [RFC6638]: https://tools.ietf.org/html/rfc6638
+//! [RFC5323]: https://tools.ietf.org/html/rfc5323
+//! [RFC5689]: https://tools.ietf.org/html/rfc5689
+//! [RFC3648]: https://tools.ietf.org/html/rfc3648
+//! [RFC8144]: https://tools.ietf.org/html/rfc8144
+//! [RFC9530]: https://tools.ietf.org/html/rfc9530
+//! [resumable uploads]: https://datatracker.ietf.org/doc/draft-ietf-httpbis-resumable-upload/
+//! [acl]: acl/index.html
+//! [`MemAcl`]: memacl/index.html
 //! [`MemLs`]: memls/index.html
 //! [`MemFs`]: memfs/index.html
 //! [`LocalFs`]: localfs/index.html
 //! [`FakeLs`]: fakels/index.html
+//! [`FileLs`]: filels/index.html
+//! [`KvLs`]: kvls/index.html
 //! [actix-compat]: actix/index.html
 //! [warp-compat]: warp/index.html
 //! [README_litmus]: https://github.com/messense/dav-server-rs/blob/main/README.litmus-test.md
@@ -190,38 +295,65 @@
 extern crate log;
 
 mod async_stream;
+#[cfg(any(feature = "memfs", feature = "localfs"))]
+mod changelog;
+mod checksum;
 mod conditional;
 #[cfg(any(feature = "caldav", feature = "carddav"))]
 pub mod dav_filters;
 mod davhandler;
 mod davheaders;
 mod errors;
+mod handle_acl;
+mod handle_bind;
 #[cfg(any(docsrs, feature = "caldav"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "caldav")))]
 mod handle_caldav;
 #[cfg(any(docsrs, feature = "carddav"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "carddav")))]
 mod handle_carddav;
+mod handle_chunked;
 mod handle_copymove;
 mod handle_delete;
 mod handle_gethead;
 mod handle_lock;
 mod handle_mkcol;
 mod handle_options;
+mod handle_orderpatch;
 mod handle_props;
 mod handle_put;
+mod handle_redirectref;
+mod handle_report;
+#[cfg(any(docsrs, feature = "caldav"))]
+mod handle_schedule;
+mod handle_search;
+mod handle_upload;
+mod handle_version;
+#[cfg(any(docsrs, feature = "caldav"))]
+mod ical;
 #[cfg(any(docsrs, feature = "localfs"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "localfs")))]
 mod localfs_macos;
 #[cfg(any(docsrs, feature = "localfs"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "localfs")))]
+mod localfs_props;
+#[cfg(any(docsrs, feature = "localfs"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "localfs")))]
+mod localfs_versions;
+#[cfg(any(docsrs, feature = "localfs"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "localfs")))]
 mod localfs_windows;
+#[cfg(any(docsrs, feature = "localfs"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "localfs")))]
+mod localfs_xattr;
 mod multierror;
+mod quota;
 mod tree;
 mod util;
 mod voidfs;
 mod xmltree_ext;
 
+pub mod acl;
 pub mod body;
 #[cfg(any(docsrs, feature = "caldav"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "caldav")))]
@@ -231,15 +363,21 @@ pub mod caldav;
 pub mod carddav;
 pub mod davpath;
 pub mod fakels;
+pub mod filels;
 pub mod fs;
+pub mod kvls;
 #[cfg(any(docsrs, feature = "localfs"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "localfs")))]
 pub mod localfs;
 pub mod ls;
+pub mod memacl;
 #[cfg(any(docsrs, feature = "memfs"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "memfs")))]
 pub mod memfs;
 pub mod memls;
+#[cfg(any(docsrs, feature = "caldav"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "caldav")))]
+pub mod schedule;
 
 #[cfg(any(docsrs, feature = "actix-compat"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "actix-compat")))]
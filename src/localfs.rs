@@ -3,36 +3,50 @@
 //! This implementation is stateless. So the easiest way to use it
 //! is to create a new instance in your handler every time
 //! you need one.
+//!
+//! The exception is the changelog that backs the RFC 6578 sync tokens.
+//! If you want those tokens to stay valid between requests, create the
+//! instance once and clone it instead.
 
 use std::any::Any;
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, VecDeque};
 use std::future::{self, Future};
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::mem;
+#[cfg(target_os = "macos")]
+use std::os::macos::fs::FileTimesExt;
 #[cfg(unix)]
 use std::os::unix::{
     ffi::OsStrExt,
     fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
 };
 #[cfg(target_os = "windows")]
+use std::os::windows::fs::FileTimesExt;
+#[cfg(target_os = "windows")]
 use std::os::windows::prelude::*;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::pin::pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bytes::{Buf, Bytes, BytesMut};
 use futures_util::{FutureExt, Stream, future::BoxFuture};
+#[cfg(feature = "proppatch")]
+use http::StatusCode;
 use tokio::task;
 
 use libc;
 use reflink_copy::reflink_or_copy;
 
+use crate::changelog::ChangeLog;
 use crate::davpath::DavPath;
 use crate::fs::*;
 use crate::localfs_macos::DUCacheBuilder;
+pub use crate::localfs_props::{PropStore, SidecarPropStore, XattrPropStore};
+use crate::localfs_versions::{self, VERSIONS_DIR, is_versions_path};
+use crate::localfs_xattr;
 
 // Run some code via block_in_place() or spawn_blocking().
 //
@@ -70,12 +84,54 @@ pub(crate) struct LocalFsInner {
     pub macos: bool,
     pub is_file: bool,
     pub fs_access_guard: Option<Box<dyn Fn() -> Box<dyn Any> + Send + Sync + 'static>>,
+    pub changes: Mutex<ChangeLog>,
+    pub props: Arc<dyn PropStore>,
 }
 
 #[derive(Debug)]
 struct LocalFsFile {
     file: Option<std::fs::File>,
     buf: BytesMut,
+    // atomic write: the temporary file, the file it replaces on flush,
+    // and the store of the properties that go with it.
+    atomic: Option<(PathBuf, PathBuf, Arc<dyn PropStore>)>,
+    // checksums to store on flush.
+    checksum: Option<String>,
+}
+
+// Prefix of the temporary files of atomic writes. They are never
+// visible through webdav.
+const ATOMIC_TMP_PREFIX: &str = ".davtmp-";
+
+fn is_atomic_tmp(name: &[u8]) -> bool {
+    name.starts_with(ATOMIC_TMP_PREFIX.as_bytes())
+}
+
+// Extended attribute with the checksums of a file, as
+// "<size> <mtime in ns> <checksums>". The size and mtime tell
+// whether the file was changed after the checksums were stored.
+const CHECKSUMS_XATTR: &str = "user.dav-server.checksums";
+
+fn checksums_stamp(meta: &std::fs::Metadata) -> Option<String> {
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{} {}", meta.len(), mtime.as_nanos()))
+}
+
+// The stored checksums, if the file was not changed since.
+fn stored_checksums(path: &Path) -> Option<String> {
+    let meta = std::fs::metadata(path).ok()?;
+    let value = localfs_xattr::get(path, CHECKSUMS_XATTR).ok()?;
+    let value = String::from_utf8(value).ok()?;
+    let checksums = value.strip_prefix(&checksums_stamp(&meta)?)?;
+    checksums.strip_prefix(' ').map(|c| c.to_string())
+}
+
+// Errors are ignored, not every filesystem has xattrs.
+fn store_checksums(file: &std::fs::File, checksums: &str) {
+    if let Some(stamp) = file.metadata().ok().as_ref().and_then(checksums_stamp) {
+        let value = format!("{stamp} {checksums}");
+        let _ = localfs_xattr::fset(file, CHECKSUMS_XATTR, value.as_bytes());
+    }
 }
 
 struct LocalFsReadDir {
@@ -138,6 +194,8 @@ impl LocalFs {
             case_insensitive,
             is_file: false,
             fs_access_guard: None,
+            changes: Mutex::new(ChangeLog::new()),
+            props: Arc::new(XattrPropStore::new()),
         };
         Box::new({
             LocalFs {
@@ -158,6 +216,8 @@ impl LocalFs {
             case_insensitive: false,
             is_file: true,
             fs_access_guard: None,
+            changes: Mutex::new(ChangeLog::new()),
+            props: Arc::new(XattrPropStore::new()),
         };
         Box::new({
             LocalFs {
@@ -190,6 +250,8 @@ impl LocalFs {
             case_insensitive,
             is_file: false,
             fs_access_guard,
+            changes: Mutex::new(ChangeLog::new()),
+            props: Arc::new(XattrPropStore::new()),
         };
         Box::new({
             LocalFs {
@@ -198,6 +260,17 @@ impl LocalFs {
         })
     }
 
+    /// Use another store for the dead properties.
+    ///
+    /// The default is [`XattrPropStore`]. Must be called before the
+    /// filesystem is cloned.
+    pub fn with_prop_store(mut self: Box<Self>, store: impl PropStore + 'static) -> Box<Self> {
+        Arc::get_mut(&mut self.inner)
+            .expect("LocalFs::with_prop_store: filesystem already in use")
+            .props = Arc::new(store);
+        self
+    }
+
     fn fspath_dbg(&self, path: &DavPath) -> PathBuf {
         let mut pathbuf = self.inner.basedir.clone();
         if !self.inner.is_file {
@@ -218,6 +291,74 @@ impl LocalFs {
         }
     }
 
+    // Change the timestamps of a file or directory. The content stays
+    // the same, so the stored checksums do as well.
+    fn set_times<'a>(&'a self, path: &'a DavPath, times: std::fs::FileTimes) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: set_times {:?}", self.fspath_dbg(path));
+            if self.is_forbidden(path) || is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let fspath = self.fspath(path);
+            self.blocking(move || {
+                let checksums = stored_checksums(&fspath);
+                #[cfg(unix)]
+                let file = std::fs::File::open(&fspath)?;
+                // FILE_WRITE_ATTRIBUTES, and FILE_FLAG_BACKUP_SEMANTICS for directories.
+                #[cfg(windows)]
+                let file = std::fs::OpenOptions::new()
+                    .access_mode(0x0100)
+                    .custom_flags(0x0200_0000)
+                    .open(&fspath)?;
+                file.set_times(times)?;
+                if let Some(checksums) = checksums {
+                    store_checksums(&file, &checksums);
+                }
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(path))
+        }
+        .boxed()
+    }
+
+    // Change the permissions of a file. Directories are left alone.
+    fn set_permissions<'a, F>(&'a self, path: &'a DavPath, func: F) -> FsFuture<'a, ()>
+    where
+        F: FnOnce(&mut std::fs::Permissions) + Send + 'static,
+    {
+        async move {
+            trace!("FS: set_permissions {:?}", self.fspath_dbg(path));
+            if self.is_forbidden(path) || is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let fspath = self.fspath(path);
+            self.blocking(move || {
+                let meta = std::fs::metadata(&fspath)?;
+                if !meta.is_file() {
+                    return Err(FsError::Forbidden);
+                }
+                let mut perms = meta.permissions();
+                func(&mut perms);
+                std::fs::set_permissions(&fspath, perms)?;
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(path))
+        }
+        .boxed()
+    }
+
+    // Files that are used internally, and are never visible.
+    pub(crate) fn is_hidden(&self, path: &DavPath) -> bool {
+        is_hidden(self, path.file_name_bytes())
+    }
+
+    // record a change in the changelog.
+    fn changed(&self, path: &DavPath) {
+        self.inner.changes.lock().unwrap().record(path.as_bytes());
+    }
+
     // threadpool::blocking() adapter, also runs the before/after hooks.
     #[doc(hidden)]
     pub async fn blocking<F, R>(&self, func: F) -> R
@@ -243,7 +384,7 @@ impl DavFileSystem for LocalFs {
                 return Ok(meta);
             }
             let path = self.fspath(davpath);
-            if self.is_notfound(&path) {
+            if self.is_notfound(&path) || is_versions_path(davpath) || self.is_hidden(davpath) {
                 return Err(FsError::NotFound);
             }
             self.blocking(move || match std::fs::metadata(path) {
@@ -261,7 +402,7 @@ impl DavFileSystem for LocalFs {
                 return Ok(meta);
             }
             let path = self.fspath(davpath);
-            if self.is_notfound(&path) {
+            if self.is_notfound(&path) || is_versions_path(davpath) || self.is_hidden(davpath) {
                 return Err(FsError::NotFound);
             }
             self.blocking(move || match std::fs::symlink_metadata(path) {
@@ -282,6 +423,9 @@ impl DavFileSystem for LocalFs {
     ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
         async move {
             trace!("FS: read_dir {:?}", self.fspath_dbg(davpath));
+            if is_versions_path(davpath) {
+                return Err(FsError::NotFound);
+            }
             let path = self.fspath(davpath);
             let path2 = path.clone();
             let iter = self.blocking(move || std::fs::read_dir(&path)).await;
@@ -310,13 +454,21 @@ impl DavFileSystem for LocalFs {
     ) -> FsFuture<'a, Box<dyn DavFile>> {
         async move {
             trace!("FS: open {:?}", self.fspath_dbg(path));
-            if self.is_forbidden(path) {
+            if self.is_forbidden(path) || is_versions_path(path) {
                 return Err(FsError::Forbidden);
             }
             #[cfg(unix)]
             let mode = if self.inner.public { 0o644 } else { 0o600 };
-            let path = self.fspath(path);
+            let write = options.write;
+            let fspath = self.fspath(path);
+            let props = self.inner.props.clone();
             self.blocking(move || {
+                if options.atomic && options.write {
+                    #[cfg(unix)]
+                    return open_atomic(fspath, options, props, mode);
+                    #[cfg(windows)]
+                    return open_atomic(fspath, options, props);
+                }
                 #[cfg(unix)]
                 let res = std::fs::OpenOptions::new()
                     .read(options.read)
@@ -326,7 +478,7 @@ impl DavFileSystem for LocalFs {
                     .create(options.create)
                     .create_new(options.create_new)
                     .mode(mode)
-                    .open(path);
+                    .open(fspath);
                 #[cfg(windows)]
                 let res = std::fs::OpenOptions::new()
                     .read(options.read)
@@ -335,16 +487,23 @@ impl DavFileSystem for LocalFs {
                     .truncate(options.truncate)
                     .create(options.create)
                     .create_new(options.create_new)
-                    .open(path);
+                    .open(fspath);
                 match res {
                     Ok(file) => Ok(Box::new(LocalFsFile {
                         file: Some(file),
                         buf: BytesMut::new(),
+                        atomic: None,
+                        checksum: options.checksums,
                     }) as Box<dyn DavFile>),
                     Err(e) => Err(e.into()),
                 }
             })
             .await
+            .inspect(|_| {
+                if write {
+                    self.changed(path);
+                }
+            })
         }
         .boxed()
     }
@@ -352,28 +511,29 @@ impl DavFileSystem for LocalFs {
     fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
         async move {
             trace!("FS: create_dir {:?}", self.fspath_dbg(path));
-            if self.is_forbidden(path) {
+            if self.is_forbidden(path) || is_versions_path(path) {
                 return Err(FsError::Forbidden);
             }
             #[cfg(unix)]
             let mode = if self.inner.public { 0o755 } else { 0o700 };
-            let path = self.fspath(path);
+            let fspath = self.fspath(path);
             self.blocking(move || {
                 #[cfg(unix)]
                 {
                     std::fs::DirBuilder::new()
                         .mode(mode)
-                        .create(path)
+                        .create(fspath)
                         .map_err(|e| e.into())
                 }
                 #[cfg(windows)]
                 {
                     std::fs::DirBuilder::new()
-                        .create(path)
+                        .create(fspath)
                         .map_err(|e| e.into())
                 }
             })
             .await
+            .inspect(|_| self.changed(path))
         }
         .boxed()
     }
@@ -381,9 +541,15 @@ impl DavFileSystem for LocalFs {
     fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
         async move {
             trace!("FS: remove_dir {:?}", self.fspath_dbg(path));
-            let path = self.fspath(path);
-            self.blocking(move || std::fs::remove_dir(path).map_err(|e| e.into()))
-                .await
+            let fspath = self.fspath(path);
+            let props = self.inner.props.clone();
+            self.blocking(move || {
+                localfs_versions::remove_dir(&fspath);
+                let _ = props.remove(&fspath);
+                std::fs::remove_dir(fspath).map_err(|e| e.into())
+            })
+            .await
+            .inspect(|_| self.changed(path))
         }
         .boxed()
     }
@@ -394,9 +560,16 @@ impl DavFileSystem for LocalFs {
             if self.is_forbidden(path) {
                 return Err(FsError::Forbidden);
             }
-            let path = self.fspath(path);
-            self.blocking(move || std::fs::remove_file(path).map_err(|e| e.into()))
-                .await
+            let fspath = self.fspath(path);
+            let props = self.inner.props.clone();
+            self.blocking(move || {
+                let _ = props.remove(&fspath);
+                std::fs::remove_file(&fspath)?;
+                localfs_versions::remove(&fspath);
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(path))
         }
         .boxed()
     }
@@ -408,29 +581,52 @@ impl DavFileSystem for LocalFs {
                 self.fspath_dbg(from),
                 self.fspath_dbg(to)
             );
-            if self.is_forbidden(from) || self.is_forbidden(to) {
+            if self.is_forbidden(from) || self.is_forbidden(to) || is_versions_path(to) {
                 return Err(FsError::Forbidden);
             }
             let frompath = self.fspath(from);
             let topath = self.fspath(to);
-            self.blocking(move || {
-                match std::fs::rename(&frompath, &topath) {
-                    Ok(v) => Ok(v),
-                    Err(e) => {
-                        // webdav allows a rename from a directory to a file.
-                        // note that this check is racy, and I'm not quite sure what
-                        // we should do if the source is a symlink. anyway ...
-                        if e.raw_os_error() == Some(libc::ENOTDIR) && frompath.is_dir() {
-                            // remove and try again.
-                            let _ = std::fs::remove_file(&topath);
-                            std::fs::rename(frompath, topath).map_err(|e| e.into())
-                        } else {
-                            Err(e.into())
+            let props = self.inner.props.clone();
+            let moved = self
+                .blocking(move || {
+                    match std::fs::rename(&frompath, &topath) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            // webdav allows a rename from a directory to a file.
+                            // note that this check is racy, and I'm not quite sure what
+                            // we should do if the source is a symlink. anyway ...
+                            if e.raw_os_error() == Some(libc::ENOTDIR) && frompath.is_dir() {
+                                // remove and try again.
+                                let _ = std::fs::remove_file(&topath);
+                                std::fs::rename(&frompath, &topath)?;
+                            } else {
+                                return Err(e.into());
+                            }
                         }
                     }
+                    localfs_versions::rename(&frompath, &topath);
+                    if let Err(e) = props.rename(&frompath, &topath) {
+                        debug!("rename {frompath:?}: moving the properties failed: {e}");
+                    }
+                    // everything below a moved directory is new as well.
+                    let mut moved = BTreeSet::new();
+                    changed_since(&topath, &[], UNIX_EPOCH, true, &*props, &mut moved);
+                    Ok::<_, FsError>(moved)
+                })
+                .await?;
+
+            let mut changes = self.inner.changes.lock().unwrap();
+            changes.record(from.as_bytes());
+            changes.record(to.as_bytes());
+            for m in moved {
+                let mut path = to.as_bytes().to_vec();
+                if !path.ends_with(b"/") {
+                    path.push(b'/');
                 }
-            })
-            .await
+                path.extend_from_slice(&m);
+                changes.record(&path);
+            }
+            Ok(())
         }
         .boxed()
     }
@@ -442,17 +638,27 @@ impl DavFileSystem for LocalFs {
                 self.fspath_dbg(from),
                 self.fspath_dbg(to)
             );
-            if self.is_forbidden(from) || self.is_forbidden(to) {
+            if self.is_forbidden(from) || self.is_forbidden(to) || is_versions_path(to) {
                 return Err(FsError::Forbidden);
             }
             let path_from = self.fspath(from);
             let path_to = self.fspath(to);
+            let props = self.inner.props.clone();
 
             match self
-                .blocking(move || reflink_or_copy(path_from, path_to))
+                .blocking(move || {
+                    reflink_or_copy(&path_from, &path_to)?;
+                    if let Err(e) = props.copy(&path_from, &path_to) {
+                        debug!("copy {path_from:?}: copying the properties failed: {e}");
+                    }
+                    Ok::<_, io::Error>(())
+                })
                 .await
             {
-                Ok(_) => Ok(()),
+                Ok(_) => {
+                    self.changed(to);
+                    Ok(())
+                }
                 Err(e) => {
                     debug!(
                         "copy({:?}, {:?}) failed: {}",
@@ -466,6 +672,623 @@ impl DavFileSystem for LocalFs {
         }
         .boxed()
     }
+
+    fn set_accessed<'a>(&'a self, path: &'a DavPath, tm: SystemTime) -> FsFuture<'a, ()> {
+        self.set_times(path, std::fs::FileTimes::new().set_accessed(tm))
+    }
+
+    fn set_modified<'a>(&'a self, path: &'a DavPath, tm: SystemTime) -> FsFuture<'a, ()> {
+        self.set_times(path, std::fs::FileTimes::new().set_modified(tm))
+    }
+
+    // Not every OS can change the creation time.
+    #[cfg(any(windows, target_os = "macos"))]
+    fn set_created<'a>(&'a self, path: &'a DavPath, tm: SystemTime) -> FsFuture<'a, ()> {
+        self.set_times(path, std::fs::FileTimes::new().set_created(tm))
+    }
+
+    // Removes or adds the write permission of the owner.
+    fn set_readonly<'a>(&'a self, path: &'a DavPath, readonly: bool) -> FsFuture<'a, ()> {
+        self.set_permissions(path, move |perms| {
+            #[cfg(unix)]
+            {
+                let mode = perms.mode();
+                perms.set_mode(if readonly {
+                    mode & !0o222
+                } else {
+                    mode | 0o200
+                });
+            }
+            #[cfg(windows)]
+            perms.set_readonly(readonly);
+        })
+    }
+
+    // Executable by whoever can read the file.
+    #[cfg(unix)]
+    fn set_executable<'a>(&'a self, path: &'a DavPath, executable: bool) -> FsFuture<'a, ()> {
+        self.set_permissions(path, move |perms| {
+            let mode = perms.mode();
+            perms.set_mode(match executable {
+                true => mode | ((mode & 0o444) >> 2),
+                false => mode & !0o111,
+            });
+        })
+    }
+
+    // Dead properties are kept by the PropStore.
+    fn have_props<'a>(&'a self, _path: &'a DavPath) -> BoxFuture<'a, bool> {
+        future::ready(true).boxed()
+    }
+
+    #[cfg(feature = "proppatch")]
+    fn patch_props<'a>(
+        &'a self,
+        path: &'a DavPath,
+        patch: Vec<(bool, DavProp)>,
+    ) -> FsFuture<'a, Vec<(StatusCode, DavProp)>> {
+        async move {
+            trace!("FS: patch_props {:?}", self.fspath_dbg(path));
+            if self.is_forbidden(path) || is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let fspath = self.fspath(path);
+            let props = self.inner.props.clone();
+            let res = self
+                .blocking(move || {
+                    std::fs::symlink_metadata(&fspath)?;
+                    let mut stored = props.load(&fspath)?;
+                    let mut res = Vec::new();
+                    for (set, p) in patch {
+                        stored.retain(|s| s.name != p.name || s.namespace != p.namespace);
+                        // removing a property that does not exist succeeds as well.
+                        res.push((StatusCode::OK, cloneprop(&p)));
+                        if set {
+                            stored.push(p);
+                        }
+                    }
+                    props.store(&fspath, &stored)?;
+                    Ok::<_, FsError>(res)
+                })
+                .await?;
+            self.changed(path);
+            Ok(res)
+        }
+        .boxed()
+    }
+
+    fn get_props<'a>(&'a self, path: &'a DavPath, do_content: bool) -> FsFuture<'a, Vec<DavProp>> {
+        async move {
+            if is_versions_path(path) {
+                return Err(FsError::NotFound);
+            }
+            let fspath = self.fspath(path);
+            let props = self.inner.props.clone();
+            self.blocking(move || {
+                std::fs::symlink_metadata(&fspath)?;
+                let props = props.load(&fspath)?;
+                Ok(match do_content {
+                    true => props,
+                    false => props.iter().map(cloneprop).collect(),
+                })
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn get_prop<'a>(&'a self, path: &'a DavPath, prop: DavProp) -> FsFuture<'a, Vec<u8>> {
+        async move {
+            let props = DavFileSystem::get_props(self, path, true).await?;
+            props
+                .into_iter()
+                .find(|p| p.name == prop.name && p.namespace == prop.namespace)
+                .and_then(|p| p.xml)
+                .ok_or(FsError::NotFound)
+        }
+        .boxed()
+    }
+
+    // Used and total space of the filesystem that the base directory is on.
+    // The total is what is used plus what is available to unprivileged
+    // users, so the space reserved for root is left out.
+    #[cfg(unix)]
+    fn get_quota(&'_ self) -> FsFuture<'_, (u64, Option<u64>)> {
+        async move {
+            let path = std::ffi::CString::new(self.inner.basedir.as_os_str().as_bytes())
+                .map_err(|_| FsError::GeneralFailure)?;
+            self.blocking(move || {
+                let mut st: libc::statvfs = unsafe { mem::zeroed() };
+                if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+                let frsize = st.f_frsize as u64;
+                let used = (st.f_blocks as u64).saturating_sub(st.f_bfree as u64) * frsize;
+                let avail = st.f_bavail as u64 * frsize;
+                Ok((used, Some(used + avail)))
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn sync_token<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        async move {
+            let meta = DavFileSystem::metadata(self, path).await?;
+            if !meta.is_dir() {
+                return Err(FsError::Forbidden);
+            }
+            Ok(self.inner.changes.lock().unwrap().token())
+        }
+        .boxed()
+    }
+
+    // Changes made through this LocalFs are in the changelog. Changes made
+    // by others are found by looking at the timestamps, but files that were
+    // removed by others cannot be detected that way.
+    fn sync_changes<'a>(
+        &'a self,
+        path: &'a DavPath,
+        token: &'a str,
+        deep: bool,
+    ) -> FsFuture<'a, DavSyncChanges> {
+        async move {
+            let meta = DavFileSystem::metadata(self, path).await?;
+            if !meta.is_dir() {
+                return Err(FsError::Forbidden);
+            }
+            let (new_token, token, mut members) = {
+                let changes = self.inner.changes.lock().unwrap();
+                let token = changes.parse_token(token)?;
+                let members = changes.changes(path.as_bytes(), &token, deep);
+                (changes.token(), token, members)
+            };
+            let dir = self.fspath(path);
+            let props = self.inner.props.clone();
+            let changed = self
+                .blocking(move || {
+                    let mut changed = BTreeSet::new();
+                    changed_since(&dir, &[], token.time, deep, &*props, &mut changed);
+                    changed
+                })
+                .await;
+            members.extend(changed);
+            Ok(DavSyncChanges {
+                token: new_token,
+                members: members.into_iter().collect(),
+            })
+        }
+        .boxed()
+    }
+
+    fn version_control<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: version_control {:?}", self.fspath_dbg(path));
+            let path = self.fspath(path);
+            self.blocking(move || localfs_versions::version_control(&path).map_err(|e| e.into()))
+                .await
+        }
+        .boxed()
+    }
+
+    fn version_history<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Option<DavVersionHistory>> {
+        async move {
+            let path = self.fspath(path);
+            self.blocking(move || localfs_versions::history(&path).map_err(|e| e.into()))
+                .await
+        }
+        .boxed()
+    }
+
+    fn checkout<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: checkout {:?}", self.fspath_dbg(path));
+            let path = self.fspath(path);
+            self.blocking(move || localfs_versions::checkout(&path).map_err(|e| e.into()))
+                .await
+        }
+        .boxed()
+    }
+
+    fn checkin<'a>(
+        &'a self,
+        path: &'a DavPath,
+        keep_checked_out: bool,
+    ) -> FsFuture<'a, DavVersion> {
+        async move {
+            trace!("FS: checkin {:?}", self.fspath_dbg(path));
+            let path = self.fspath(path);
+            self.blocking(move || {
+                localfs_versions::checkin(&path, keep_checked_out).map_err(|e| e.into())
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn uncheckout<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: uncheckout {:?}", self.fspath_dbg(path));
+            let fspath = self.fspath(path);
+            self.blocking(move || localfs_versions::uncheckout(&fspath).map_err(|e| e.into()))
+                .await
+                .inspect(|_| self.changed(path))
+        }
+        .boxed()
+    }
+
+    fn open_version<'a>(
+        &'a self,
+        path: &'a DavPath,
+        version: &'a str,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        async move {
+            let path = localfs_versions::version_path(&self.fspath(path), version)?;
+            self.blocking(move || match std::fs::File::open(path) {
+                Ok(file) => Ok(Box::new(LocalFsFile {
+                    file: Some(file),
+                    buf: BytesMut::new(),
+                    atomic: None,
+                    checksum: None,
+                }) as Box<dyn DavFile>),
+                Err(e) => Err(e.into()),
+            })
+            .await
+        }
+        .boxed()
+    }
+    // Only files can be bound, using hard links.
+    fn bind<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            trace!(
+                "FS: bind {:?} {:?}",
+                self.fspath_dbg(from),
+                self.fspath_dbg(to)
+            );
+            if self.is_forbidden(from)
+                || self.is_forbidden(to)
+                || is_versions_path(from)
+                || is_versions_path(to)
+            {
+                return Err(FsError::Forbidden);
+            }
+            let frompath = self.fspath(from);
+            let topath = self.fspath(to);
+            self.blocking(move || {
+                if std::fs::metadata(&frompath)?.is_dir() {
+                    return Err(FsError::Forbidden);
+                }
+                std::fs::hard_link(&frompath, &topath)?;
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(to))
+        }
+        .boxed()
+    }
+
+    // A directory only has one binding, so unbinding it removes it
+    // and everything below it.
+    fn unbind<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: unbind {:?}", self.fspath_dbg(path));
+            if self.is_forbidden(path) || is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let fspath = self.fspath(path);
+            let props = self.inner.props.clone();
+            self.blocking(move || {
+                if std::fs::symlink_metadata(&fspath)?.is_dir() {
+                    std::fs::remove_dir_all(&fspath)?;
+                } else {
+                    let _ = props.remove(&fspath);
+                    std::fs::remove_file(&fspath)?;
+                    localfs_versions::remove(&fspath);
+                }
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(path))
+        }
+        .boxed()
+    }
+
+    fn checksums<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        async move {
+            if is_versions_path(path) {
+                return Err(FsError::NotFound);
+            }
+            let path = self.fspath(path);
+            self.blocking(move || stored_checksums(&path).ok_or(FsError::NotFound))
+                .await
+        }
+        .boxed()
+    }
+
+    // Derived from the device and inode numbers.
+    #[cfg(unix)]
+    fn resource_id<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        async move {
+            if is_versions_path(path) {
+                return Err(FsError::NotFound);
+            }
+            let path = self.fspath(path);
+            self.blocking(move || {
+                let meta = std::fs::metadata(path)?;
+                let uuid = uuid::Uuid::from_u64_pair(meta.dev(), meta.ino());
+                Ok(format!("urn:uuid:{uuid}"))
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    // Other hard links to a file cannot be found without scanning
+    // the whole filesystem, so only the path itself is returned.
+    fn bindings<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Vec<DavPath>> {
+        async move {
+            let _ = <Self as DavFileSystem>::metadata(self, path).await?;
+            let path = DavPath::new(&path.as_url_string()).map_err(|_| FsError::GeneralFailure)?;
+            Ok(vec![path])
+        }
+        .boxed()
+    }
+
+    // Redirect references are symbolic links. Targets in this filesystem
+    // become relative links, absolute URLs are stored as-is.
+    #[cfg(unix)]
+    fn create_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+    ) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: create_redirectref {:?}", self.fspath_dbg(path));
+            if self.is_forbidden(path) || is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let link = redirect_link(path, redirect)?;
+            let fspath = self.fspath(path);
+            self.blocking(move || {
+                std::os::unix::fs::symlink(link, fspath)?;
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(path))
+        }
+        .boxed()
+    }
+
+    #[cfg(unix)]
+    fn update_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+    ) -> FsFuture<'a, ()> {
+        async move {
+            trace!("FS: update_redirectref {:?}", self.fspath_dbg(path));
+            if self.is_forbidden(path) || is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let link = redirect_link(path, redirect)?;
+            let fspath = self.fspath(path);
+            self.blocking(move || {
+                if !std::fs::symlink_metadata(&fspath)?.is_symlink() {
+                    return Err(FsError::Forbidden);
+                }
+                std::fs::remove_file(&fspath)?;
+                std::os::unix::fs::symlink(link, fspath)?;
+                Ok(())
+            })
+            .await
+            .inspect(|_| self.changed(path))
+        }
+        .boxed()
+    }
+
+    // Links that point outside of the filesystem are not followed.
+    #[cfg(unix)]
+    fn redirectref<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, DavRedirectRef> {
+        async move {
+            if is_versions_path(path) {
+                return Err(FsError::Forbidden);
+            }
+            let fspath = self.fspath(path);
+            let link = self.blocking(move || std::fs::read_link(fspath)).await?;
+            let link = link.as_os_str().as_bytes();
+            if let Some(url) = std::str::from_utf8(link)
+                .ok()
+                .filter(|l| url::Url::parse(l).is_ok())
+            {
+                return Ok(DavRedirectRef {
+                    target: url.to_string(),
+                    permanent: false,
+                });
+            }
+
+            // resolve the link relative to the directory it is in.
+            let mut segs: Vec<&[u8]> = path
+                .as_bytes()
+                .split(|&c| c == b'/')
+                .filter(|s| !s.is_empty())
+                .collect();
+            segs.pop();
+            let link = match link.strip_prefix(self.inner.basedir.as_os_str().as_bytes()) {
+                Some(rel) if link.starts_with(b"/") && (rel.is_empty() || rel[0] == b'/') => {
+                    segs.clear();
+                    rel
+                }
+                _ if link.starts_with(b"/") => return Err(FsError::Forbidden),
+                _ => link,
+            };
+            for seg in link.split(|&c| c == b'/') {
+                match seg {
+                    b"" | b"." => {}
+                    b".." => {
+                        segs.pop().ok_or(FsError::Forbidden)?;
+                    }
+                    _ => segs.push(seg),
+                }
+            }
+            let mut target = DavPath::new("/").unwrap();
+            for seg in &segs {
+                target.push_segment(seg);
+            }
+            let meta = <Self as DavFileSystem>::metadata(self, &target).await;
+            target.add_slash_if(meta.is_ok_and(|m| m.is_dir()));
+            target.add_prefix(path.prefix());
+            Ok(DavRedirectRef {
+                target: target.with_prefix().as_url_string(),
+                permanent: false,
+            })
+        }
+        .boxed()
+    }
+}
+
+// The content of the symbolic link for a redirect reference.
+#[cfg(unix)]
+fn redirect_link(path: &DavPath, redirect: &DavRedirectRef) -> FsResult<PathBuf> {
+    // a symlink cannot store the lifetime, it is always temporary.
+    if redirect.permanent {
+        return Err(FsError::NotImplemented);
+    }
+    if url::Url::parse(&redirect.target).is_ok() {
+        return Ok(PathBuf::from(&redirect.target));
+    }
+    let target = DavPath::from_str_and_prefix(&redirect.target, path.prefix())
+        .map_err(|_| FsError::Forbidden)?;
+    let segs = |p: &[u8]| {
+        p.split(|&c| c == b'/')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_vec())
+            .collect::<Vec<_>>()
+    };
+    let mut dir = segs(path.as_bytes());
+    dir.pop();
+    let target = segs(target.as_bytes());
+    let common = dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut link = Vec::new();
+    for _ in common..dir.len() {
+        link.push(b"..".to_vec());
+    }
+    link.extend_from_slice(&target[common..]);
+    if link.is_empty() {
+        link.push(b".".to_vec());
+    }
+    Ok(PathBuf::from(std::ffi::OsStr::from_bytes(
+        &link.join(&b'/'),
+    )))
+}
+
+// Walk a directory and collect the entries that were changed at or
+// after `since`. Must be run in `blocking()`.
+fn changed_since(
+    dir: &Path,
+    prefix: &[u8],
+    since: SystemTime,
+    deep: bool,
+    props: &dyn PropStore,
+    out: &mut BTreeSet<Vec<u8>>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        #[cfg(unix)]
+        let name = entry.file_name().as_bytes().to_vec();
+        #[cfg(windows)]
+        let name = entry.file_name().to_string_lossy().as_bytes().to_vec();
+        if name == VERSIONS_DIR.as_bytes() || is_atomic_tmp(&name) || props.is_hidden(&name) {
+            continue;
+        }
+        let mut rel = prefix.to_vec();
+        if !rel.is_empty() {
+            rel.push(b'/');
+        }
+        rel.extend_from_slice(&name);
+
+        let m = LocalFsMetaData(meta);
+        let changed = [m.modified(), m.status_changed()]
+            .into_iter()
+            .flatten()
+            .max();
+        if changed.is_some_and(|t| t >= since) {
+            out.insert(rel.clone());
+        }
+        if deep && m.0.is_dir() {
+            changed_since(&entry.path(), &rel, since, deep, props, out);
+        }
+    }
+}
+
+// Open a file for an atomic write: the data goes to a temporary file
+// in the same directory, which replaces the file on flush(). For a
+// partial write, the old content is copied to the temporary file first.
+// This is sync code, must be run in `blocking()`.
+fn open_atomic(
+    path: PathBuf,
+    options: OpenOptions,
+    props: Arc<dyn PropStore>,
+    #[cfg(unix)] mode: u32,
+) -> FsResult<Box<dyn DavFile>> {
+    let meta = match std::fs::metadata(&path) {
+        Ok(_) if options.create_new => return Err(FsError::Exists),
+        Ok(meta) if !meta.is_file() => return Err(FsError::Forbidden),
+        Ok(meta) => Some(meta),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && options.create => None,
+        Err(e) => return Err(e.into()),
+    };
+    let dir = path.parent().ok_or(FsError::Forbidden)?;
+    let tmp = dir.join(format!("{ATOMIC_TMP_PREFIX}{}", uuid::Uuid::new_v4()));
+    let copy = meta.is_some() && !options.truncate;
+    if copy {
+        reflink_or_copy(&path, &tmp)?;
+    }
+    let mut oo = std::fs::OpenOptions::new();
+    oo.read(options.read)
+        .write(true)
+        .append(options.append)
+        .create_new(!copy);
+    #[cfg(unix)]
+    oo.mode(mode);
+    let file = match oo.open(&tmp) {
+        Ok(file) => file,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+    };
+    // keep the permissions (and if we can, the owner) of the old file.
+    if let Some(meta) = meta {
+        let _ = file.set_permissions(meta.permissions());
+        #[cfg(unix)]
+        let _ = std::os::unix::fs::fchown(&file, Some(meta.uid()), Some(meta.gid()));
+    }
+    Ok(Box::new(LocalFsFile {
+        file: Some(file),
+        buf: BytesMut::new(),
+        atomic: Some((tmp, path, props)),
+        checksum: options.checksums,
+    }))
+}
+
+// A property without its value.
+fn cloneprop(p: &DavProp) -> DavProp {
+    DavProp {
+        name: p.name.clone(),
+        namespace: p.namespace.clone(),
+        prefix: p.prefix.clone(),
+        xml: None,
+    }
+}
+
+// Files that are never listed.
+fn is_hidden(fs: &LocalFs, name: &[u8]) -> bool {
+    is_atomic_tmp(name) || fs.inner.props.is_hidden(name)
 }
 
 // read_batch() result.
@@ -497,6 +1320,11 @@ fn read_batch(
     };
     for _ in 0..256 {
         match iterator.next() {
+            Some(Ok(entry)) if entry.file_name() == VERSIONS_DIR => {}
+            #[cfg(unix)]
+            Some(Ok(entry)) if is_hidden(&fs, entry.file_name().as_bytes()) => {}
+            #[cfg(windows)]
+            Some(Ok(entry)) if is_hidden(&fs, entry.file_name().to_string_lossy().as_bytes()) => {}
             Some(Ok(entry)) => {
                 let meta = match do_meta {
                     ReadDirMeta::Data => Meta::Data(std::fs::metadata(entry.path())),
@@ -750,7 +1578,30 @@ impl DavFile for LocalFsFile {
     fn flush(&'_ mut self) -> FsFuture<'_, ()> {
         async move {
             let mut file = self.file.take().unwrap();
-            let (res, file) = blocking(move || (file.flush(), file)).await;
+            let atomic = self.atomic.take();
+            let checksum = self.checksum.take();
+            let (res, file) = blocking(move || {
+                let res = file.flush();
+                if let Some(checksum) = checksum.filter(|_| res.is_ok()) {
+                    store_checksums(&file, &checksum);
+                }
+                let res = res.and_then(|_| match atomic {
+                    // make sure the data is on disk before it replaces the old
+                    // file. The properties of the old file are kept.
+                    Some((tmp, path, props)) => props
+                        .copy(&path, &tmp)
+                        .and_then(|_| file.sync_all())
+                        .and_then(|_| std::fs::rename(&tmp, &path))
+                        .and_then(|_| props.rename(&tmp, &path))
+                        .inspect_err(|_| {
+                            let _ = props.remove(&tmp);
+                            let _ = std::fs::remove_file(&tmp);
+                        }),
+                    None => Ok(()),
+                });
+                (res, file)
+            })
+            .await;
             self.file = Some(file);
             res.map_err(|e| e.into())
         }
@@ -758,6 +1609,15 @@ impl DavFile for LocalFsFile {
     }
 }
 
+impl Drop for LocalFsFile {
+    fn drop(&mut self) {
+        // an atomic write that was never flushed.
+        if let Some((tmp, _, _)) = self.atomic.take() {
+            let _ = std::fs::remove_file(tmp);
+        }
+    }
+}
+
 impl DavMetaData for LocalFsMetaData {
     fn len(&self) -> u64 {
         self.0.len()
@@ -820,6 +1680,25 @@ impl DavMetaData for LocalFsMetaData {
         }
     }
 
+    #[cfg(unix)]
+    fn readonly(&self) -> FsResult<bool> {
+        Ok((self.0.permissions().mode() & 0o200) == 0)
+    }
+
+    #[cfg(windows)]
+    fn readonly(&self) -> FsResult<bool> {
+        Ok(self.0.permissions().readonly())
+    }
+
+    fn hidden(&self, path: &DavPath) -> FsResult<bool> {
+        // FILE_ATTRIBUTE_HIDDEN.
+        #[cfg(windows)]
+        if self.0.file_attributes() & 0x0002 != 0 {
+            return Ok(true);
+        }
+        Ok(path.file_name_bytes().starts_with(b"."))
+    }
+
     // same as the default apache etag.
     #[cfg(unix)]
     fn etag(&self) -> Option<String> {
@@ -268,6 +268,9 @@ impl LocalFs {
     // This file can never exist.
     #[inline]
     pub(crate) fn is_forbidden(&self, path: &DavPath) -> bool {
+        if self.is_hidden(path) {
+            return true;
+        }
         if !self.inner.macos {
             return false;
         }
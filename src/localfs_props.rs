@@ -0,0 +1,333 @@
+//
+// Dead properties of files and directories on the local filesystem.
+//
+// The default store keeps them in an extended attribute. If the
+// filesystem does not support extended attributes, or the value does
+// not fit, they go into a hidden sidecar file in the directory:
+//
+//   dir/.davprops   the properties of "dir" itself (with key "."),
+//                   and those of the files in "dir".
+//
+// So the properties of a directory move with it. Sidecar files are
+// never visible through webdav.
+//
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, percent_encode};
+
+use crate::fs::DavProp;
+use crate::localfs_xattr;
+
+/// Storage for the dead properties of a [`LocalFs`](crate::localfs::LocalFs).
+///
+/// The paths are paths on the local filesystem. All methods do blocking
+/// I/O, `LocalFs` calls them from a thread where that is allowed.
+pub trait PropStore: Debug + Send + Sync {
+    /// All properties of a file or directory.
+    fn load(&self, path: &Path) -> io::Result<Vec<DavProp>>;
+
+    /// Replace all properties of a file or directory. An empty
+    /// list removes them.
+    fn store(&self, path: &Path, props: &[DavProp]) -> io::Result<()>;
+
+    /// Called after `from` was renamed to `to`. Whatever `to` had
+    /// before is replaced.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Called after the file `from` was copied to `to`.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let props = self.load(from)?;
+        self.store(to, &props)
+    }
+
+    /// Called before the file or directory at `path` is removed.
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// Is this the name of a file the store uses itself. These
+    /// files are hidden from webdav clients.
+    fn is_hidden(&self, name: &[u8]) -> bool {
+        let _ = name;
+        false
+    }
+}
+
+/// Stores the properties in the `user.dav-server.props` extended
+/// attribute, with a [`SidecarPropStore`] as fallback.
+///
+/// This is the default.
+#[derive(Debug, Default)]
+pub struct XattrPropStore {
+    sidecar: SidecarPropStore,
+}
+
+const PROPS_XATTR: &str = "user.dav-server.props";
+
+impl XattrPropStore {
+    pub fn new() -> XattrPropStore {
+        XattrPropStore::default()
+    }
+}
+
+impl PropStore for XattrPropStore {
+    fn load(&self, path: &Path) -> io::Result<Vec<DavProp>> {
+        match localfs_xattr::get(path, PROPS_XATTR) {
+            Ok(value) => Ok(decode_props(&value)),
+            // a file that is not there (yet) can have properties in
+            // the sidecar file too, the name is all that counts.
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound
+                    || localfs_xattr::is_missing(&e)
+                    || localfs_xattr::is_unsupported(&e) =>
+            {
+                self.sidecar.load(path)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn store(&self, path: &Path, props: &[DavProp]) -> io::Result<()> {
+        if !props.is_empty() {
+            match localfs_xattr::set(path, PROPS_XATTR, &encode_props(props)) {
+                // they might have been in the sidecar file before.
+                Ok(()) => return self.sidecar.store(path, &[]),
+                Err(e) if localfs_xattr::is_unsupported(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        match localfs_xattr::remove(path, PROPS_XATTR) {
+            Err(e) if !localfs_xattr::is_missing(&e) && !localfs_xattr::is_unsupported(&e) => {
+                return Err(e);
+            }
+            _ => {}
+        }
+        self.sidecar.store(path, props)
+    }
+
+    // the extended attributes move with the file by themselves.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.sidecar.rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.sidecar.remove(path)
+    }
+
+    fn is_hidden(&self, name: &[u8]) -> bool {
+        self.sidecar.is_hidden(name)
+    }
+}
+
+/// Stores the properties in a hidden `.davprops` file in each directory.
+///
+/// Use this directly for filesystems where extended attributes
+/// are not wanted.
+#[derive(Debug, Default)]
+pub struct SidecarPropStore {
+    // one update of a sidecar file at a time.
+    lock: Mutex<()>,
+}
+
+const SIDECAR: &str = ".davprops";
+const SIDECAR_TMP: &str = ".davprops.tmp";
+// The key of the directory itself.
+const DIR_KEY: &[u8] = b".";
+
+// All properties in one sidecar file, by file name.
+type Sidecar = BTreeMap<Vec<u8>, Vec<DavProp>>;
+
+impl SidecarPropStore {
+    pub fn new() -> SidecarPropStore {
+        SidecarPropStore::default()
+    }
+}
+
+impl PropStore for SidecarPropStore {
+    fn load(&self, path: &Path) -> io::Result<Vec<DavProp>> {
+        let (file, key) = sidecar_key(path, path.is_dir())?;
+        let _guard = self.lock.lock().unwrap();
+        Ok(read_sidecar(&file)?.remove(&key).unwrap_or_default())
+    }
+
+    fn store(&self, path: &Path, props: &[DavProp]) -> io::Result<()> {
+        let (file, key) = sidecar_key(path, path.is_dir())?;
+        let _guard = self.lock.lock().unwrap();
+        update_sidecar(&file, |sidecar| match props {
+            [] => sidecar.remove(&key).is_some(),
+            _ => {
+                sidecar.insert(key, props.to_vec());
+                true
+            }
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (to_file, to_key) = sidecar_key(to, false)?;
+        let _guard = self.lock.lock().unwrap();
+        // a directory takes its sidecar file with it. It might have
+        // replaced a file though.
+        let mut props = None;
+        if !to.is_dir() {
+            let (from_file, from_key) = sidecar_key(from, false)?;
+            update_sidecar(&from_file, |sidecar| {
+                props = sidecar.remove(&from_key);
+                props.is_some()
+            })?;
+        }
+        update_sidecar(&to_file, |sidecar| match props {
+            Some(props) => {
+                sidecar.insert(to_key, props);
+                true
+            }
+            None => sidecar.remove(&to_key).is_some(),
+        })
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let is_dir = std::fs::symlink_metadata(path)?.is_dir();
+        let (file, key) = sidecar_key(path, is_dir)?;
+        let _guard = self.lock.lock().unwrap();
+        if is_dir {
+            // so that the directory is empty and can be removed.
+            let _ = std::fs::remove_file(path.join(SIDECAR_TMP));
+            return match std::fs::remove_file(file) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        update_sidecar(&file, |sidecar| sidecar.remove(&key).is_some())
+    }
+
+    fn is_hidden(&self, name: &[u8]) -> bool {
+        name.starts_with(SIDECAR.as_bytes())
+    }
+}
+
+// The sidecar file with the properties of `path`, and their key.
+fn sidecar_key(path: &Path, is_dir: bool) -> io::Result<(PathBuf, Vec<u8>)> {
+    if is_dir {
+        return Ok((path.join(SIDECAR), DIR_KEY.to_vec()));
+    }
+    match (path.parent(), path.file_name()) {
+        #[cfg(unix)]
+        (Some(dir), Some(name)) => Ok((dir.join(SIDECAR), name.as_bytes().to_vec())),
+        #[cfg(windows)]
+        (Some(dir), Some(name)) => Ok((
+            dir.join(SIDECAR),
+            name.to_string_lossy().as_bytes().to_vec(),
+        )),
+        _ => Err(io::ErrorKind::InvalidInput.into()),
+    }
+}
+
+// Read a sidecar file, change it, and write it back if `f` says
+// that something was changed.
+fn update_sidecar(file: &Path, f: impl FnOnce(&mut Sidecar) -> bool) -> io::Result<()> {
+    let mut sidecar = read_sidecar(file)?;
+    match f(&mut sidecar) {
+        true => write_sidecar(file, &sidecar),
+        false => Ok(()),
+    }
+}
+
+// One line per property: "<key> <namespace> <name> <prefix> <xml>".
+fn read_sidecar(file: &Path) -> io::Result<Sidecar> {
+    let data = match std::fs::read(file) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Sidecar::new()),
+        Err(e) => return Err(e),
+    };
+    let mut sidecar = Sidecar::new();
+    for line in String::from_utf8_lossy(&data).lines() {
+        let Some((key, prop)) = line.split_once(' ') else {
+            continue;
+        };
+        if let (Some(key), Some(prop)) = (decode_field(key), decode_prop(prop)) {
+            sidecar.entry(key).or_default().push(prop);
+        }
+    }
+    Ok(sidecar)
+}
+
+// Write to a temporary file first, so that the sidecar file is
+// always complete. It is removed when there is nothing left.
+fn write_sidecar(file: &Path, sidecar: &Sidecar) -> io::Result<()> {
+    if sidecar.is_empty() {
+        return match std::fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let mut data = Vec::new();
+    for (key, props) in sidecar {
+        for prop in props {
+            data.extend_from_slice(encode_field(Some(key)).as_bytes());
+            data.push(b' ');
+            data.extend_from_slice(encode_prop(prop).as_bytes());
+            data.push(b'\n');
+        }
+    }
+    let tmp = file.with_file_name(SIDECAR_TMP);
+    let mut f = std::fs::File::create(&tmp)?;
+    f.write_all(&data)
+        .and_then(|_| f.sync_all())
+        .and_then(|_| std::fs::rename(&tmp, file))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp);
+        })
+}
+
+// The value of the extended attribute, one property per line.
+fn encode_props(props: &[DavProp]) -> Vec<u8> {
+    let lines: Vec<_> = props.iter().map(encode_prop).collect();
+    lines.join("\n").into_bytes()
+}
+
+fn decode_props(value: &[u8]) -> Vec<DavProp> {
+    String::from_utf8_lossy(value)
+        .lines()
+        .filter_map(decode_prop)
+        .collect()
+}
+
+// "<namespace> <name> <prefix> <xml>", percent-encoded, "-" for none.
+fn encode_prop(prop: &DavProp) -> String {
+    format!(
+        "{} {} {} {}",
+        encode_field(prop.namespace.as_ref().map(|n| n.as_bytes())),
+        encode_field(Some(prop.name.as_bytes())),
+        encode_field(prop.prefix.as_ref().map(|p| p.as_bytes())),
+        encode_field(prop.xml.as_deref()),
+    )
+}
+
+fn decode_prop(line: &str) -> Option<DavProp> {
+    let mut fields = line.split(' ').map(decode_field);
+    let mut string = || fields.next().map(|f| f.map(String::from_utf8));
+    let namespace = string()?.transpose().ok()?;
+    let name = string()??.ok()?;
+    let prefix = string()?.transpose().ok()?;
+    let xml = fields.next()?;
+    Some(DavProp {
+        name,
+        prefix,
+        namespace,
+        xml,
+    })
+}
+
+fn encode_field(field: Option<&[u8]>) -> String {
+    match field {
+        Some(f) => percent_encode(f, NON_ALPHANUMERIC).to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn decode_field(field: &str) -> Option<Vec<u8>> {
+    (field != "-").then(|| percent_decode_str(field).collect())
+}
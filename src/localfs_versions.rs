@@ -0,0 +1,180 @@
+// Versioning (RFC 3253) for the local filesystem.
+//
+// The versions of a file are stored in a sidecar directory next to it:
+//
+//   dir/file.txt
+//   dir/.davversions/file.txt/1
+//   dir/.davversions/file.txt/2
+//   dir/.davversions/file.txt/checkedout
+//
+// Every version is a plain copy of the file. The "checkedout" marker
+// is present while the file is checked out. The ".davversions"
+// directories are never visible through webdav.
+//
+// All these functions do blocking I/O, run them in LocalFs::blocking().
+//
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::davpath::DavPath;
+use crate::fs::{DavVersion, DavVersionHistory};
+
+pub(crate) const VERSIONS_DIR: &str = ".davversions";
+const CHECKED_OUT: &str = "checkedout";
+
+// Is this path, or one of its parents, a sidecar directory.
+pub(crate) fn is_versions_path(path: &DavPath) -> bool {
+    path.as_bytes()
+        .split(|&c| c == b'/')
+        .any(|s| s == VERSIONS_DIR.as_bytes())
+}
+
+// Sidecar directory of a file.
+fn version_dir(file: &Path) -> io::Result<PathBuf> {
+    match (file.parent(), file.file_name()) {
+        (Some(dir), Some(name)) => Ok(dir.join(VERSIONS_DIR).join(name)),
+        _ => Err(io::ErrorKind::InvalidInput.into()),
+    }
+}
+
+// All versions, oldest first.
+fn list_versions(vdir: &Path) -> io::Result<Vec<DavVersion>> {
+    let mut v = Vec::new();
+    for entry in std::fs::read_dir(vdir)? {
+        let entry = entry?;
+        let Some(n) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let meta = entry.metadata()?;
+        v.push((
+            n,
+            DavVersion {
+                name: n.to_string(),
+                created: meta.modified()?,
+                size: meta.len(),
+            },
+        ));
+    }
+    v.sort_by_key(|(n, _)| *n);
+    Ok(v.into_iter().map(|(_, v)| v).collect())
+}
+
+// Copy the current content of the file to a new version.
+fn new_version(file: &Path, vdir: &Path) -> io::Result<DavVersion> {
+    let next = list_versions(vdir)?
+        .last()
+        .and_then(|v| v.name.parse::<u64>().ok())
+        .unwrap_or(0)
+        + 1;
+    let vfile = vdir.join(next.to_string());
+    std::fs::copy(file, &vfile)?;
+    let meta = std::fs::metadata(&vfile)?;
+    Ok(DavVersion {
+        name: next.to_string(),
+        created: meta.modified()?,
+        size: meta.len(),
+    })
+}
+
+pub(crate) fn history(file: &Path) -> io::Result<Option<DavVersionHistory>> {
+    let vdir = version_dir(file)?;
+    if !vdir.is_dir() {
+        return Ok(None);
+    }
+    Ok(Some(DavVersionHistory {
+        versions: list_versions(&vdir)?,
+        checked_out: vdir.join(CHECKED_OUT).exists(),
+    }))
+}
+
+pub(crate) fn version_control(file: &Path) -> io::Result<()> {
+    if !std::fs::metadata(file)?.is_file() {
+        return Err(io::ErrorKind::PermissionDenied.into());
+    }
+    let vdir = version_dir(file)?;
+    if vdir.is_dir() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&vdir)?;
+    new_version(file, &vdir)?;
+    Ok(())
+}
+
+pub(crate) fn checkout(file: &Path) -> io::Result<()> {
+    let vdir = version_dir(file)?;
+    std::fs::File::create(vdir.join(CHECKED_OUT))?;
+    Ok(())
+}
+
+pub(crate) fn checkin(file: &Path, keep_checked_out: bool) -> io::Result<DavVersion> {
+    let vdir = version_dir(file)?;
+    if !vdir.is_dir() {
+        return Err(io::ErrorKind::PermissionDenied.into());
+    }
+    let version = new_version(file, &vdir)?;
+    if !keep_checked_out {
+        remove_if_exists(&vdir.join(CHECKED_OUT))?;
+    }
+    Ok(version)
+}
+
+pub(crate) fn uncheckout(file: &Path) -> io::Result<()> {
+    let vdir = version_dir(file)?;
+    if let Some(v) = list_versions(&vdir)?.last() {
+        std::fs::copy(vdir.join(&v.name), file)?;
+    }
+    remove_if_exists(&vdir.join(CHECKED_OUT))
+}
+
+// Path of the content of a version.
+pub(crate) fn version_path(file: &Path, version: &str) -> io::Result<PathBuf> {
+    if version.parse::<u64>().is_err() {
+        return Err(io::ErrorKind::NotFound.into());
+    }
+    Ok(version_dir(file)?.join(version))
+}
+
+// The file was removed, remove its history as well.
+pub(crate) fn remove(file: &Path) {
+    if let Ok(vdir) = version_dir(file)
+        && std::fs::remove_dir_all(&vdir).is_ok()
+        && let Some(parent) = vdir.parent()
+    {
+        // only succeeds if this was the last one.
+        let _ = std::fs::remove_dir(parent);
+    }
+}
+
+// A directory is about to be removed, remove the histories in it.
+pub(crate) fn remove_dir(dir: &Path) {
+    let _ = std::fs::remove_dir_all(dir.join(VERSIONS_DIR));
+}
+
+// The file was renamed, move its history along.
+pub(crate) fn rename(from: &Path, to: &Path) {
+    let (Ok(from_vdir), Ok(to_vdir)) = (version_dir(from), version_dir(to)) else {
+        return;
+    };
+    remove(to);
+    if from_vdir.is_dir() {
+        if let Some(parent) = to_vdir.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if std::fs::rename(&from_vdir, &to_vdir).is_ok()
+            && let Some(parent) = from_vdir.parent()
+        {
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,229 @@
+//
+// Extended attributes of files on the local filesystem.
+//
+// Only Linux and macOS are supported, everywhere else these functions
+// return an `ErrorKind::Unsupported` error. Filesystems that do not
+// support extended attributes return an error as well.
+//
+// All these functions do blocking I/O, run them in LocalFs::blocking().
+//
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    fn cstring(s: &[u8]) -> io::Result<CString> {
+        CString::new(s).map_err(|_| io::ErrorKind::InvalidInput.into())
+    }
+
+    pub(super) fn get(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+        let path = cstring(path.as_os_str().as_bytes())?;
+        let name = cstring(name.as_bytes())?;
+        let mut buf = Vec::new();
+        loop {
+            // the size can change between the two calls, so retry on ERANGE.
+            let size = unsafe { getxattr(&path, &name, std::ptr::null_mut(), 0) };
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            buf.resize(size as usize, 0);
+            let size = unsafe { getxattr(&path, &name, buf.as_mut_ptr().cast(), buf.len()) };
+            if size >= 0 {
+                buf.truncate(size as usize);
+                return Ok(buf);
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(err);
+            }
+        }
+    }
+
+    pub(super) fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+        let path = cstring(path.as_os_str().as_bytes())?;
+        let name = cstring(name.as_bytes())?;
+        let res = unsafe { setxattr(&path, &name, value.as_ptr().cast(), value.len()) };
+        match res {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    pub(super) fn fset(file: &File, name: &str, value: &[u8]) -> io::Result<()> {
+        let name = cstring(name.as_bytes())?;
+        let res = unsafe { fsetxattr(file.as_raw_fd(), &name, value.as_ptr().cast(), value.len()) };
+        match res {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    pub(super) fn remove(path: &Path, name: &str) -> io::Result<()> {
+        let path = cstring(path.as_os_str().as_bytes())?;
+        let name = cstring(name.as_bytes())?;
+        match unsafe { removexattr(&path, &name) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    pub(super) fn is_missing(err: &io::Error) -> bool {
+        #[cfg(target_os = "linux")]
+        let enoattr = libc::ENODATA;
+        #[cfg(target_os = "macos")]
+        let enoattr = libc::ENOATTR;
+        err.raw_os_error() == Some(enoattr)
+    }
+
+    pub(super) fn is_unsupported(err: &io::Error) -> bool {
+        let codes = [
+            libc::ENOTSUP,
+            libc::EOPNOTSUPP,
+            libc::E2BIG,
+            libc::ENOSPC,
+            libc::ERANGE,
+        ];
+        err.kind() == io::ErrorKind::Unsupported
+            || err.raw_os_error().is_some_and(|e| codes.contains(&e))
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn getxattr(
+        path: &CString,
+        name: &CString,
+        buf: *mut libc::c_void,
+        size: usize,
+    ) -> isize {
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size) }
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn getxattr(
+        path: &CString,
+        name: &CString,
+        buf: *mut libc::c_void,
+        size: usize,
+    ) -> isize {
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size, 0, 0) }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn setxattr(
+        path: &CString,
+        name: &CString,
+        value: *const libc::c_void,
+        size: usize,
+    ) -> libc::c_int {
+        unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value, size, 0) }
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn setxattr(
+        path: &CString,
+        name: &CString,
+        value: *const libc::c_void,
+        size: usize,
+    ) -> libc::c_int {
+        unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value, size, 0, 0) }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn fsetxattr(
+        fd: libc::c_int,
+        name: &CString,
+        value: *const libc::c_void,
+        size: usize,
+    ) -> libc::c_int {
+        unsafe { libc::fsetxattr(fd, name.as_ptr(), value, size, 0) }
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn fsetxattr(
+        fd: libc::c_int,
+        name: &CString,
+        value: *const libc::c_void,
+        size: usize,
+    ) -> libc::c_int {
+        unsafe { libc::fsetxattr(fd, name.as_ptr(), value, size, 0, 0) }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn removexattr(path: &CString, name: &CString) -> libc::c_int {
+        unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) }
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn removexattr(path: &CString, name: &CString) -> libc::c_int {
+        unsafe { libc::removexattr(path.as_ptr(), name.as_ptr(), 0) }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub(super) fn get(_path: &Path, _name: &str) -> io::Result<Vec<u8>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn fset(_file: &File, _name: &str, _value: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn remove(_path: &Path, _name: &str) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn is_missing(_err: &io::Error) -> bool {
+        false
+    }
+
+    pub(super) fn is_unsupported(err: &io::Error) -> bool {
+        err.kind() == io::ErrorKind::Unsupported
+    }
+}
+
+/// Get the value of an extended attribute.
+pub(crate) fn get(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+    sys::get(path, name)
+}
+
+/// Set an extended attribute.
+pub(crate) fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    sys::set(path, name, value)
+}
+
+/// Set an extended attribute on an open file.
+pub(crate) fn fset(file: &File, name: &str, value: &[u8]) -> io::Result<()> {
+    sys::fset(file, name, value)
+}
+
+/// Remove an extended attribute.
+pub(crate) fn remove(path: &Path, name: &str) -> io::Result<()> {
+    sys::remove(path, name)
+}
+
+/// Is this the error for an attribute that does not exist.
+pub(crate) fn is_missing(err: &io::Error) -> bool {
+    sys::is_missing(err)
+}
+
+/// Is this the error for a filesystem that does not support extended
+/// attributes, or one that cannot store a value this large.
+pub(crate) fn is_unsupported(err: &io::Error) -> bool {
+    sys::is_unsupported(err)
+}
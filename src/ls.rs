@@ -1,11 +1,8 @@
 //! Contains the structs and traits that define a `locksystem` backend.
 //!
-//! Note that the methods DO NOT return futures, they are synchronous.
-//! This is because currently only two locksystems exist, `MemLs` and `FakeLs`.
-//! Both of them do not do any I/O, all methods return instantly.
-//!
-//! If ever a locksystem gets built that does I/O (to a filesystem,
-//! a database, or over the network) we'll need to revisit this.
+//! The methods return futures, so that a locksystem can do I/O.
+//! `MemLs` and `FakeLs` do not, all their methods return instantly.
+//! `FileLs` writes its locks to a local file, `KvLs` to a key-value store.
 //!
 use crate::davpath::DavPath;
 use std::fmt::Debug;
@@ -81,6 +78,29 @@ pub trait DavLockSystem: Debug + Send + Sync + DynClone {
 
     /// Delete all locks at this path and below (after MOVE or DELETE)
     fn delete(&'_ self, path: &DavPath) -> LsFuture<'_, Result<(), ()>>;
+
+    /// Remember that the resource at `path` is a lock-null resource: an
+    /// empty placeholder that was created by the LOCK of `token`. It is
+    /// removed again if its lock goes away before it is written to.
+    ///
+    /// The default implementation does not remember anything.
+    fn add_lock_null(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, ()> {
+        let _ = (path, token);
+        Box::pin(std::future::ready(()))
+    }
+
+    /// The resource at `path` was written to, it is not a
+    /// lock-null resource anymore.
+    fn remove_lock_null(&'_ self, path: &DavPath) -> LsFuture<'_, ()> {
+        let _ = path;
+        Box::pin(std::future::ready(()))
+    }
+
+    /// Return, and forget, the lock-null resources whose lock
+    /// was removed or has expired.
+    fn stale_lock_nulls(&'_ self) -> LsFuture<'_, Vec<DavPath>> {
+        Box::pin(std::future::ready(Vec::new()))
+    }
 }
 
 clone_trait_object! {DavLockSystem}
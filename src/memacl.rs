@@ -0,0 +1,210 @@
+//! Simple in-memory access control backend.
+//!
+//! Like `MemLs`, this implementation has state. Create the instance
+//! once, using `MemAcl::new`, and clone() it every time you pass
+//! it to the DavHandler. As a MemAcl struct is just a handle, cloning is cheap.
+//!
+//! ACLs are evaluated from the resource itself up to the root, so an
+//! ACE on a collection is inherited by everything below it. A new
+//! `MemAcl` has one ACE on the root that grants `DAV:all` to `DAV:all`,
+//! so nothing is restricted until you change that.
+use std::collections::HashMap;
+use std::future;
+use std::sync::{Arc, Mutex};
+
+use crate::acl::*;
+use crate::davpath::DavPath;
+
+/// Ephemeral in-memory access control backend.
+#[derive(Debug, Clone)]
+pub struct MemAcl(Arc<Mutex<MemAclInner>>);
+
+#[derive(Debug)]
+struct MemAclInner {
+    aces: HashMap<Vec<u8>, Vec<DavAce>>,
+    owners: HashMap<Vec<u8>, String>,
+    principals: Vec<String>,
+}
+
+impl MemAcl {
+    /// Create a new "memacl" access control backend.
+    pub fn new() -> Box<MemAcl> {
+        let mut aces = HashMap::new();
+        aces.insert(
+            b"/".to_vec(),
+            vec![DavAce::grant(DavAcePrincipal::All, DavPrivilege::All)],
+        );
+        let inner = MemAclInner {
+            aces,
+            owners: HashMap::new(),
+            principals: Vec::new(),
+        };
+        Box::new(MemAcl(Arc::new(Mutex::new(inner))))
+    }
+
+    /// Add a principal (by URL). The principal collections are
+    /// the parents of the principals.
+    pub fn add_principal(&self, href: impl Into<String>) {
+        let href = href.into();
+        let inner = &mut *self.0.lock().unwrap();
+        if !inner.principals.iter().any(|p| same_href(p, &href)) {
+            inner.principals.push(href);
+        }
+    }
+
+    /// Set the owner of `path` and everything below it that has no owner of its own.
+    pub fn set_owner(&self, path: &DavPath, owner: impl Into<String>) {
+        let inner = &mut *self.0.lock().unwrap();
+        inner.owners.insert(key(path), owner.into());
+    }
+
+    /// Set the ACL of `path`, replacing all entries (protected ones too).
+    pub fn set_aces(&self, path: &DavPath, aces: Vec<DavAce>) {
+        let inner = &mut *self.0.lock().unwrap();
+        inner.aces.insert(key(path), aces);
+    }
+}
+
+impl MemAclInner {
+    // the owner of path is the owner of the nearest ancestor that has one.
+    fn owner(&self, path: &DavPath) -> Option<String> {
+        ancestors(path).find_map(|p| self.owners.get(&key(&p)).cloned())
+    }
+
+    // own entries first, then the inherited ones.
+    fn acl(&self, path: &DavPath) -> Vec<DavAce> {
+        let mut v = Vec::new();
+        for (n, p) in ancestors(path).enumerate() {
+            if let Some(aces) = self.aces.get(&key(&p)) {
+                let href = p.with_prefix().as_url_string();
+                v.extend(aces.iter().cloned().map(|mut ace| {
+                    if n > 0 && ace.inherited.is_none() {
+                        ace.inherited = Some(href.clone());
+                    }
+                    ace
+                }));
+            }
+        }
+        v
+    }
+}
+
+impl DavAccessControl for MemAcl {
+    fn privileges<'a>(
+        &'a self,
+        principal: Option<&'a str>,
+        path: &'a DavPath,
+    ) -> AclFuture<'a, DavPrivilegeSet> {
+        let inner = &*self.0.lock().unwrap();
+        let owner = inner.owner(path);
+        let href = path.with_prefix().as_url_string();
+        let privs = evaluate_acl(&inner.acl(path), principal, &href, owner.as_deref());
+        Box::pin(future::ready(privs))
+    }
+
+    fn acl<'a>(&'a self, path: &'a DavPath) -> AclFuture<'a, Vec<DavAce>> {
+        let inner = &*self.0.lock().unwrap();
+        Box::pin(future::ready(inner.acl(path)))
+    }
+
+    fn set_acl<'a>(
+        &'a self,
+        path: &'a DavPath,
+        aces: Vec<DavAce>,
+    ) -> AclFuture<'a, Result<(), DavAclError>> {
+        let inner = &mut *self.0.lock().unwrap();
+        let k = key(path);
+        let mut v = inner
+            .aces
+            .get(&k)
+            .map(|a| {
+                a.iter()
+                    .filter(|a| a.protected)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        v.extend(aces);
+        inner.aces.insert(k, v);
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn owner<'a>(&'a self, path: &'a DavPath) -> AclFuture<'a, Option<String>> {
+        let inner = &*self.0.lock().unwrap();
+        Box::pin(future::ready(inner.owner(path)))
+    }
+
+    fn principal_collections(&'_ self) -> AclFuture<'_, Vec<String>> {
+        let inner = &*self.0.lock().unwrap();
+        let mut v: Vec<String> = Vec::new();
+        for p in &inner.principals {
+            let p = p.strip_suffix('/').unwrap_or(p);
+            if let Some(n) = p.rfind('/') {
+                let coll = &p[..=n];
+                if !v.iter().any(|c| c == coll) {
+                    v.push(coll.to_string());
+                }
+            }
+        }
+        Box::pin(future::ready(v))
+    }
+
+    fn principals(&'_ self) -> AclFuture<'_, Vec<String>> {
+        let inner = &*self.0.lock().unwrap();
+        Box::pin(future::ready(inner.principals.clone()))
+    }
+
+    fn delete<'a>(&'a self, path: &'a DavPath) -> AclFuture<'a, ()> {
+        let inner = &mut *self.0.lock().unwrap();
+        let k = key(path);
+        inner.aces.retain(|p, _| !is_below(p, &k));
+        inner.owners.retain(|p, _| !is_below(p, &k));
+        Box::pin(future::ready(()))
+    }
+
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> AclFuture<'a, ()> {
+        let inner = &mut *self.0.lock().unwrap();
+        let (from, to) = (key(from), key(to));
+        inner.aces.retain(|p, _| !is_below(p, &to));
+        inner.owners.retain(|p, _| !is_below(p, &to));
+        let rename = |p: Vec<u8>| -> Vec<u8> {
+            if is_below(&p, &from) {
+                let mut n = to.clone();
+                n.extend_from_slice(&p[from.len()..]);
+                n
+            } else {
+                p
+            }
+        };
+        inner.aces = inner.aces.drain().map(|(p, a)| (rename(p), a)).collect();
+        inner.owners = inner.owners.drain().map(|(p, o)| (rename(p), o)).collect();
+        Box::pin(future::ready(()))
+    }
+}
+
+// the path and all its parents, up to the root.
+fn ancestors(path: &DavPath) -> impl Iterator<Item = DavPath> {
+    let mut next = Some(path.clone());
+    std::iter::from_fn(move || {
+        let p = next.take()?;
+        if p.as_bytes().len() > 1 {
+            next = Some(p.parent());
+        }
+        Some(p)
+    })
+}
+
+// path without trailing slash.
+fn key(path: &DavPath) -> Vec<u8> {
+    let p = path.as_bytes();
+    if p.len() > 1 && p.ends_with(b"/") {
+        p[..p.len() - 1].to_vec()
+    } else {
+        p.to_vec()
+    }
+}
+
+// is path equal to, or below, dir.
+fn is_below(path: &[u8], dir: &[u8]) -> bool {
+    dir == b"/" || path == dir || (path.starts_with(dir) && path.get(dir.len()) == Some(&b'/'))
+}
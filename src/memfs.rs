@@ -6,7 +6,7 @@
 //! This means you have to create the instance once, using `MemFs::new`, store
 //! it in your handler struct, and clone() it every time you pass
 //! it to the DavHandler. As a MemFs struct is just a handle, cloning is cheap.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind, SeekFrom};
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
@@ -18,6 +18,7 @@ use futures_util::{
 };
 use http::StatusCode;
 
+use crate::changelog::ChangeLog;
 use crate::davpath::DavPath;
 use crate::fs::*;
 use crate::tree;
@@ -28,12 +29,15 @@ type Tree = tree::Tree<Vec<u8>, MemFsNode>;
 #[derive(Debug)]
 pub struct MemFs {
     tree: Arc<Mutex<Tree>>,
+    changes: Arc<Mutex<ChangeLog>>,
+    instance: u64,
 }
 
 #[derive(Debug, Clone)]
 enum MemFsNode {
     Dir(MemFsDirNode),
     File(MemFsFileNode),
+    Symlink(MemFsSymlinkNode),
 }
 
 #[derive(Debug, Clone)]
@@ -49,6 +53,31 @@ struct MemFsFileNode {
     mtime: SystemTime,
     crtime: SystemTime,
     data: Vec<u8>,
+    versions: Option<MemFsVersions>,
+    // checksums of the data, see DavFileSystem::checksums.
+    checksums: Option<String>,
+}
+
+// a redirect reference (RFC4437).
+#[derive(Debug, Clone)]
+struct MemFsSymlinkNode {
+    props: HashMap<String, DavProp>,
+    mtime: SystemTime,
+    crtime: SystemTime,
+    target: DavRedirectRef,
+}
+
+// version history of a file under version control.
+#[derive(Debug, Clone, Default)]
+struct MemFsVersions {
+    versions: Vec<MemFsVersion>,
+    checked_out: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MemFsVersion {
+    created: SystemTime,
+    data: Bytes,
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +85,7 @@ struct MemFsDirEntry {
     mtime: SystemTime,
     crtime: SystemTime,
     is_dir: bool,
+    is_symlink: bool,
     name: Vec<u8>,
     size: u64,
 }
@@ -63,9 +93,24 @@ struct MemFsDirEntry {
 #[derive(Debug)]
 struct MemFsFile {
     tree: Arc<Mutex<Tree>>,
+    changes: Arc<Mutex<ChangeLog>>,
+    path: Vec<u8>,
     node_id: u64,
     pos: usize,
     append: bool,
+    written: bool,
+    // atomic writes go here, and replace the content of the file on flush.
+    staged: Option<Vec<u8>>,
+    // checksums to store on flush.
+    checksum: Option<String>,
+}
+
+// read-only handle to a version of a file.
+#[derive(Debug)]
+struct MemFsVersionFile {
+    meta: MemFsDirEntry,
+    data: Bytes,
+    pos: usize,
 }
 
 impl MemFs {
@@ -98,44 +143,103 @@ impl MemFs {
 
         Box::new(MemFs {
             tree: Arc::new(Mutex::new(tree)),
+            changes: Arc::new(Mutex::new(ChangeLog::new())),
+            instance: uuid::Uuid::new_v4().as_u64_pair().0,
         })
     }
 
+    // record a change in the changelog.
+    fn changed(&self, path: &[u8]) {
+        self.changes.lock().unwrap().record(path);
+    }
+
+    // record a change of a node and everything below it.
+    fn changed_subtree(&self, tree: &Tree, node_id: u64, path: &[u8]) {
+        self.do_changed_subtree(tree, node_id, path, &mut HashSet::new());
+    }
+
+    fn do_changed_subtree(&self, tree: &Tree, node_id: u64, path: &[u8], seen: &mut HashSet<u64>) {
+        self.changed(path);
+        // with bindings, a node can be reached twice (or be its own ancestor).
+        if !seen.insert(node_id) {
+            return;
+        }
+        if let Ok(children) = tree.get_children(node_id) {
+            for (name, child_id) in children {
+                let mut cpath = path.to_vec();
+                if !cpath.ends_with(b"/") {
+                    cpath.push(b'/');
+                }
+                cpath.extend_from_slice(&name);
+                self.do_changed_subtree(tree, child_id, &cpath, seen);
+            }
+        }
+    }
+
     fn do_open(
         &self,
         tree: &mut Tree,
         path: &[u8],
         options: OpenOptions,
     ) -> FsResult<Box<dyn DavFile>> {
+        let atomic = options.atomic && options.write;
         let node_id = match tree.lookup(path) {
             Ok(n) => {
                 if options.create_new {
                     return Err(FsError::Exists);
                 }
-                n
+                Some(n)
             }
             Err(FsError::NotFound) => {
                 if !options.create {
                     return Err(FsError::NotFound);
                 }
                 let parent_id = tree.lookup_parent(path)?;
-                tree.add_child(parent_id, file_name(path), MemFsNode::new_file(), true)?
+                match atomic {
+                    // created on flush.
+                    true => None,
+                    false => Some(tree.add_child(
+                        parent_id,
+                        file_name(path),
+                        MemFsNode::new_file(),
+                        true,
+                    )?),
+                }
             }
             Err(e) => return Err(e),
         };
-        let node = tree.get_node_mut(node_id).unwrap();
-        if node.is_dir() {
-            return Err(FsError::Forbidden);
+        let mut staged = atomic.then(Vec::new);
+        if let Some(node_id) = node_id {
+            let node = tree.get_node_mut(node_id).unwrap();
+            if !matches!(node, MemFsNode::File(_)) {
+                return Err(FsError::Forbidden);
+            }
+            if atomic {
+                if !options.truncate {
+                    staged = Some(node.as_file()?.data.clone());
+                }
+            } else if options.write {
+                let file = node.as_file_mut()?;
+                file.checksums = None;
+                if options.truncate {
+                    file.data.truncate(0);
+                    node.update_mtime(SystemTime::now());
+                }
+            }
         }
-        if options.truncate {
-            node.as_file_mut()?.data.truncate(0);
-            node.update_mtime(SystemTime::now());
+        if options.write && !atomic {
+            self.changed(path);
         }
         Ok(Box::new(MemFsFile {
             tree: self.tree.clone(),
-            node_id,
+            changes: self.changes.clone(),
+            path: path.to_vec(),
+            node_id: node_id.unwrap_or_default(),
             pos: 0,
             append: options.append,
+            written: false,
+            staged,
+            checksum: options.checksums,
         }))
     }
 }
@@ -144,6 +248,8 @@ impl Clone for MemFs {
     fn clone(&self) -> Self {
         MemFs {
             tree: Arc::clone(&self.tree),
+            changes: Arc::clone(&self.changes),
+            instance: self.instance,
         }
     }
 }
@@ -207,6 +313,7 @@ impl DavFileSystem for MemFs {
             tree.add_child(parent_id, file_name(path), MemFsNode::new_dir(), false)?;
             tree.get_node_mut(parent_id)?
                 .update_mtime(SystemTime::now());
+            self.changed(path);
             Ok(())
         }
         .boxed()
@@ -217,9 +324,13 @@ impl DavFileSystem for MemFs {
             let tree = &mut *self.tree.lock().unwrap();
             let parent_id = tree.lookup_parent(path.as_bytes())?;
             let node_id = tree.lookup(path.as_bytes())?;
-            tree.delete_node(node_id)?;
+            if tree.get_node(node_id)?.is_dir() {
+                return Err(FsError::Forbidden);
+            }
+            tree.unlink(parent_id, file_name(path.as_bytes()).as_slice())?;
             tree.get_node_mut(parent_id)?
                 .update_mtime(SystemTime::now());
+            self.changed(path.as_bytes());
             Ok(())
         }
         .boxed()
@@ -230,9 +341,18 @@ impl DavFileSystem for MemFs {
             let tree = &mut *self.tree.lock().unwrap();
             let parent_id = tree.lookup_parent(path.as_bytes())?;
             let node_id = tree.lookup(path.as_bytes())?;
-            tree.delete_node(node_id)?;
+            if !tree.get_node(node_id)?.is_dir() {
+                return Err(FsError::Forbidden);
+            }
+            // a non-empty directory can only be removed if this is not
+            // the last binding to it.
+            if tree.get_children(node_id)?.next().is_some() && tree.link_count(node_id)? < 2 {
+                return Err(FsError::Forbidden);
+            }
+            tree.unlink(parent_id, file_name(path.as_bytes()).as_slice())?;
             tree.get_node_mut(parent_id)?
                 .update_mtime(SystemTime::now());
+            self.changed(path.as_bytes());
             Ok(())
         }
         .boxed()
@@ -244,10 +364,18 @@ impl DavFileSystem for MemFs {
             let node_id = tree.lookup(from.as_bytes())?;
             let parent_id = tree.lookup_parent(from.as_bytes())?;
             let dst_id = tree.lookup_parent(to.as_bytes())?;
-            tree.move_node(node_id, dst_id, file_name(to.as_bytes()), true)?;
+            tree.move_node(
+                parent_id,
+                &file_name(from.as_bytes()),
+                dst_id,
+                file_name(to.as_bytes()),
+                true,
+            )?;
             tree.get_node_mut(parent_id)?
                 .update_mtime(SystemTime::now());
             tree.get_node_mut(dst_id)?.update_mtime(SystemTime::now());
+            self.changed(from.as_bytes());
+            self.changed_subtree(tree, node_id, to.as_bytes());
             Ok(())
         }
         .boxed()
@@ -272,7 +400,12 @@ impl DavFileSystem for MemFs {
             let mut data = (*tree.get_node_mut(snode_id)?).clone();
             match data {
                 MemFsNode::Dir(ref mut d) => d.crtime = SystemTime::now(),
-                MemFsNode::File(ref mut f) => f.crtime = SystemTime::now(),
+                MemFsNode::Symlink(ref mut l) => l.crtime = SystemTime::now(),
+                MemFsNode::File(ref mut f) => {
+                    // the copy is a new resource, it has no history.
+                    f.crtime = SystemTime::now();
+                    f.versions = None;
+                }
             }
             *tree.get_node_mut(dnode_id)? = data;
 
@@ -314,6 +447,7 @@ impl DavFileSystem for MemFs {
                 };
                 res.push((status, prop));
             }
+            self.changed(path.as_bytes());
             Ok(res)
         }
         .boxed()
@@ -346,6 +480,286 @@ impl DavFileSystem for MemFs {
         }
         .boxed()
     }
+
+    fn sync_token<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            if !tree.get_node(node_id)?.is_dir() {
+                return Err(FsError::Forbidden);
+            }
+            Ok(self.changes.lock().unwrap().token())
+        }
+        .boxed()
+    }
+
+    fn sync_changes<'a>(
+        &'a self,
+        path: &'a DavPath,
+        token: &'a str,
+        deep: bool,
+    ) -> FsFuture<'a, DavSyncChanges> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            if !tree.get_node(node_id)?.is_dir() {
+                return Err(FsError::Forbidden);
+            }
+            let changes = self.changes.lock().unwrap();
+            let token = changes.parse_token(token)?;
+            Ok(DavSyncChanges {
+                token: changes.token(),
+                members: changes
+                    .changes(path.as_bytes(), &token, deep)
+                    .into_iter()
+                    .collect(),
+            })
+        }
+        .boxed()
+    }
+
+    fn version_control<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let file = tree.get_node_mut(node_id)?.as_file_mut()?;
+            if file.versions.is_none() {
+                file.new_version();
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn version_history<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Option<DavVersionHistory>> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let node = tree.get_node(node_id)?;
+            let Ok(file) = node.as_file() else {
+                return Ok(None);
+            };
+            Ok(file.versions.as_ref().map(|v| DavVersionHistory {
+                versions: v
+                    .versions
+                    .iter()
+                    .enumerate()
+                    .map(|(n, v)| v.as_dav_version(n))
+                    .collect(),
+                checked_out: v.checked_out,
+            }))
+        }
+        .boxed()
+    }
+
+    fn checkout<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let file = tree.get_node_mut(node_id)?.as_file_mut()?;
+            file.versions
+                .as_mut()
+                .ok_or(FsError::Forbidden)?
+                .checked_out = true;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn checkin<'a>(
+        &'a self,
+        path: &'a DavPath,
+        keep_checked_out: bool,
+    ) -> FsFuture<'a, DavVersion> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let file = tree.get_node_mut(node_id)?.as_file_mut()?;
+            if file.versions.is_none() {
+                return Err(FsError::Forbidden);
+            }
+            let version = file.new_version();
+            file.versions.as_mut().unwrap().checked_out = keep_checked_out;
+            Ok(version)
+        }
+        .boxed()
+    }
+
+    fn uncheckout<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let file = tree.get_node_mut(node_id)?.as_file_mut()?;
+            let versions = file.versions.as_mut().ok_or(FsError::Forbidden)?;
+            if let Some(v) = versions.versions.last() {
+                file.data = v.data.to_vec();
+                file.mtime = SystemTime::now();
+                file.checksums = None;
+            }
+            versions.checked_out = false;
+            self.changed(path.as_bytes());
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn open_version<'a>(
+        &'a self,
+        path: &'a DavPath,
+        version: &'a str,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let file = tree.get_node(node_id)?.as_file()?;
+            let versions = file.versions.as_ref().ok_or(FsError::NotFound)?;
+            let n = version
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .ok_or(FsError::NotFound)?;
+            let v = versions.versions.get(n).ok_or(FsError::NotFound)?;
+            Ok(Box::new(MemFsVersionFile {
+                meta: MemFsDirEntry {
+                    mtime: v.created,
+                    crtime: v.created,
+                    is_dir: false,
+                    is_symlink: false,
+                    name: file_name(path.as_bytes()),
+                    size: v.data.len() as u64,
+                },
+                data: v.data.clone(),
+                pos: 0,
+            }) as Box<dyn DavFile>)
+        }
+        .boxed()
+    }
+    fn bind<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let node_id = tree.lookup(from.as_bytes())?;
+            let parent_id = tree.lookup_parent(to.as_bytes())?;
+            let name = file_name(to.as_bytes());
+            if name.is_empty() {
+                return Err(FsError::Forbidden);
+            }
+            tree.add_link(parent_id, name, node_id)?;
+            tree.get_node_mut(parent_id)?
+                .update_mtime(SystemTime::now());
+            self.changed_subtree(tree, node_id, to.as_bytes());
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn unbind<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let parent_id = tree.lookup_parent(path.as_bytes())?;
+            let name = file_name(path.as_bytes());
+            if name.is_empty() {
+                return Err(FsError::Forbidden);
+            }
+            tree.unlink(parent_id, name.as_slice())?;
+            tree.get_node_mut(parent_id)?
+                .update_mtime(SystemTime::now());
+            self.changed(path.as_bytes());
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn resource_id<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let uuid = uuid::Uuid::from_u64_pair(self.instance, node_id);
+            Ok(format!("urn:uuid:{uuid}"))
+        }
+        .boxed()
+    }
+
+    fn checksums<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, String> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let file = tree.get_node(node_id)?.as_file()?;
+            file.checksums.clone().ok_or(FsError::NotFound)
+        }
+        .boxed()
+    }
+
+    fn bindings<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Vec<DavPath>> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let is_dir = tree.get_node(node_id)?.is_dir();
+            let mut v = Vec::new();
+            for segs in tree.paths(node_id)? {
+                let mut p = DavPath::new("/").unwrap();
+                for seg in &segs {
+                    p.push_segment(seg);
+                }
+                p.add_slash_if(is_dir);
+                v.push(p);
+            }
+            Ok(v)
+        }
+        .boxed()
+    }
+
+    fn create_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+    ) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let path = path.as_bytes();
+            let parent_id = tree.lookup_parent(path)?;
+            let node = MemFsNode::Symlink(MemFsSymlinkNode {
+                crtime: SystemTime::now(),
+                mtime: SystemTime::now(),
+                props: HashMap::new(),
+                target: redirect.clone(),
+            });
+            tree.add_child(parent_id, file_name(path), node, false)?;
+            tree.get_node_mut(parent_id)?
+                .update_mtime(SystemTime::now());
+            self.changed(path);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn update_redirectref<'a>(
+        &'a self,
+        path: &'a DavPath,
+        redirect: &'a DavRedirectRef,
+    ) -> FsFuture<'a, ()> {
+        async move {
+            let tree = &mut *self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            let link = tree.get_node_mut(node_id)?.as_symlink_mut()?;
+            link.target = redirect.clone();
+            link.mtime = SystemTime::now();
+            self.changed(path.as_bytes());
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn redirectref<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, DavRedirectRef> {
+        async move {
+            let tree = &*self.tree.lock().unwrap();
+            let node_id = tree.lookup(path.as_bytes())?;
+            match tree.get_node(node_id)? {
+                MemFsNode::Symlink(link) => Ok(link.target.clone()),
+                _ => Err(FsError::Forbidden),
+            }
+        }
+        .boxed()
+    }
 }
 
 // small helper.
@@ -378,8 +792,15 @@ impl DavFile for MemFsFile {
     fn metadata(&'_ mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
         async move {
             let tree = &*self.tree.lock().unwrap();
-            let node = tree.get_node(self.node_id)?;
-            let meta = node.as_dirent(b"");
+            let mut meta = match (&self.staged, tree.get_node(self.node_id)) {
+                (_, Ok(node)) => node.as_dirent(b""),
+                // an atomic write of a file that does not exist yet.
+                (Some(_), Err(_)) => MemFsNode::new_file().as_dirent(b""),
+                (None, Err(e)) => return Err(e),
+            };
+            if let Some(ref data) = self.staged {
+                meta.size = data.len() as u64;
+            }
             Ok(Box::new(meta) as Box<dyn DavMetaData>)
         }
         .boxed()
@@ -388,9 +809,11 @@ impl DavFile for MemFsFile {
     fn read_bytes(&'_ mut self, count: usize) -> FsFuture<'_, Bytes> {
         async move {
             let tree = &*self.tree.lock().unwrap();
-            let node = tree.get_node(self.node_id)?;
-            let file = node.as_file()?;
-            let curlen = file.data.len();
+            let data = match self.staged {
+                Some(ref data) => data,
+                None => &tree.get_node(self.node_id)?.as_file()?.data,
+            };
+            let curlen = data.len();
             let mut start = self.pos;
             let mut end = self.pos + count;
             if start > curlen {
@@ -401,7 +824,7 @@ impl DavFile for MemFsFile {
             }
             let cnt = end - start;
             self.pos += cnt;
-            Ok(Bytes::copy_from_slice(&file.data[start..end]))
+            Ok(Bytes::copy_from_slice(&data[start..end]))
         }
         .boxed()
     }
@@ -409,17 +832,20 @@ impl DavFile for MemFsFile {
     fn write_bytes(&'_ mut self, buf: Bytes) -> FsFuture<'_, ()> {
         async move {
             let tree = &mut *self.tree.lock().unwrap();
-            let node = tree.get_node_mut(self.node_id)?;
-            let file = node.as_file_mut()?;
+            let data = match self.staged {
+                Some(ref mut data) => data,
+                None => &mut tree.get_node_mut(self.node_id)?.as_file_mut()?.data,
+            };
             if self.append {
-                self.pos = file.data.len();
+                self.pos = data.len();
             }
             let end = self.pos + buf.len();
-            if end > file.data.len() {
-                file.data.resize(end, 0);
+            if end > data.len() {
+                data.resize(end, 0);
             }
-            file.data[self.pos..end].copy_from_slice(&buf);
+            data[self.pos..end].copy_from_slice(&buf);
             self.pos = end;
+            self.written = true;
             Ok(())
         }
         .boxed()
@@ -428,29 +854,63 @@ impl DavFile for MemFsFile {
     fn write_buf(&'_ mut self, mut buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
         async move {
             let tree = &mut *self.tree.lock().unwrap();
-            let node = tree.get_node_mut(self.node_id)?;
-            let file = node.as_file_mut()?;
+            let data = match self.staged {
+                Some(ref mut data) => data,
+                None => &mut tree.get_node_mut(self.node_id)?.as_file_mut()?.data,
+            };
             if self.append {
-                self.pos = file.data.len();
+                self.pos = data.len();
             }
             let end = self.pos + buf.remaining();
-            if end > file.data.len() {
-                file.data.resize(end, 0);
+            if end > data.len() {
+                data.resize(end, 0);
             }
             while buf.has_remaining() {
                 let b = buf.chunk();
                 let len = b.len();
-                file.data[self.pos..self.pos + len].copy_from_slice(b);
+                data[self.pos..self.pos + len].copy_from_slice(b);
                 buf.advance(len);
                 self.pos += len;
             }
+            self.written = true;
             Ok(())
         }
         .boxed()
     }
 
     fn flush(&'_ mut self) -> FsFuture<'_, ()> {
-        future::ok(()).boxed()
+        async move {
+            // atomic write: swap in the new content.
+            if let Some(data) = self.staged.take() {
+                let tree = &mut *self.tree.lock().unwrap();
+                let node_id = match tree.lookup(&self.path) {
+                    Ok(n) => n,
+                    Err(FsError::NotFound) => {
+                        let parent_id = tree.lookup_parent(&self.path)?;
+                        let name = file_name(&self.path);
+                        tree.add_child(parent_id, name, MemFsNode::new_file(), false)?
+                    }
+                    Err(e) => return Err(e),
+                };
+                let node = tree.get_node_mut(node_id)?;
+                let file = node.as_file_mut()?;
+                file.data = data;
+                file.checksums = self.checksum.take();
+                node.update_mtime(SystemTime::now());
+                self.node_id = node_id;
+                self.written = true;
+            }
+            if let Some(checksum) = self.checksum.take() {
+                let tree = &mut *self.tree.lock().unwrap();
+                tree.get_node_mut(self.node_id)?.as_file_mut()?.checksums = Some(checksum);
+            }
+            if self.written {
+                self.changes.lock().unwrap().record(&self.path);
+                self.written = false;
+            }
+            Ok(())
+        }
+        .boxed()
     }
 
     fn seek(&'_ mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
@@ -463,9 +923,11 @@ impl DavFile for MemFsFile {
                 SeekFrom::Current(npos) => (self.pos as u64, npos),
                 SeekFrom::End(npos) => {
                     let tree = &*self.tree.lock().unwrap();
-                    let node = tree.get_node(self.node_id)?;
-                    let curlen = node.as_file()?.data.len() as u64;
-                    (curlen, npos)
+                    let curlen = match self.staged {
+                        Some(ref data) => data.len(),
+                        None => tree.get_node(self.node_id)?.as_file()?.data.len(),
+                    };
+                    (curlen as u64, npos)
                 }
             };
             if offset < 0 {
@@ -482,6 +944,50 @@ impl DavFile for MemFsFile {
     }
 }
 
+impl DavFile for MemFsVersionFile {
+    fn metadata(&'_ mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let meta = self.meta.clone();
+        Box::pin(future::ok(Box::new(meta) as Box<dyn DavMetaData>))
+    }
+
+    fn read_bytes(&'_ mut self, count: usize) -> FsFuture<'_, Bytes> {
+        let start = self.pos.min(self.data.len());
+        let end = (start + count).min(self.data.len());
+        self.pos = end;
+        Box::pin(future::ok(self.data.slice(start..end)))
+    }
+
+    fn write_bytes(&'_ mut self, _buf: Bytes) -> FsFuture<'_, ()> {
+        Box::pin(future::ready(Err(FsError::Forbidden)))
+    }
+
+    fn write_buf(&'_ mut self, _buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        Box::pin(future::ready(Err(FsError::Forbidden)))
+    }
+
+    fn flush(&'_ mut self) -> FsFuture<'_, ()> {
+        Box::pin(future::ok(()))
+    }
+
+    fn seek(&'_ mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        let len = self.data.len() as i64;
+        let npos = match pos {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::Current(n) => self.pos as i64 + n,
+            SeekFrom::End(n) => len + n,
+        };
+        if npos < 0 {
+            return Box::pin(future::ready(Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek",
+            )
+            .into())));
+        }
+        self.pos = npos as usize;
+        Box::pin(future::ok(npos as u64))
+    }
+}
+
 impl DavMetaData for MemFsDirEntry {
     fn len(&self) -> u64 {
         self.size
@@ -499,8 +1005,21 @@ impl DavMetaData for MemFsDirEntry {
         self.is_dir
     }
 
+    fn is_file(&self) -> bool {
+        !self.is_dir && !self.is_symlink
+    }
+
     fn is_symlink(&self) -> bool {
-        false
+        self.is_symlink
+    }
+
+    // there are no permissions.
+    fn readonly(&self) -> FsResult<bool> {
+        Ok(false)
+    }
+
+    fn hidden(&self, path: &DavPath) -> FsResult<bool> {
+        Ok(path.file_name_bytes().starts_with(b"."))
     }
 
     #[cfg(feature = "caldav")]
@@ -529,6 +1048,8 @@ impl MemFsNode {
             mtime: SystemTime::now(),
             props: HashMap::new(),
             data: Vec::new(),
+            versions: None,
+            checksums: None,
         })
     }
 
@@ -537,12 +1058,14 @@ impl MemFsNode {
         let (is_dir, size, mtime, crtime) = match *self {
             MemFsNode::File(ref file) => (false, file.data.len() as u64, file.mtime, file.crtime),
             MemFsNode::Dir(ref dir) => (true, 0, dir.mtime, dir.crtime),
+            MemFsNode::Symlink(ref link) => (false, 0, link.mtime, link.crtime),
         };
         MemFsDirEntry {
             name: name.to_vec(),
             mtime,
             crtime,
             is_dir,
+            is_symlink: matches!(self, MemFsNode::Symlink(_)),
             size,
         }
     }
@@ -551,13 +1074,21 @@ impl MemFsNode {
         match *self {
             MemFsNode::Dir(ref mut d) => d.mtime = tm,
             MemFsNode::File(ref mut f) => f.mtime = tm,
+            MemFsNode::Symlink(ref mut l) => l.mtime = tm,
         }
     }
 
     fn is_dir(&self) -> bool {
         match *self {
             MemFsNode::Dir(_) => true,
-            MemFsNode::File(_) => false,
+            MemFsNode::File(_) | MemFsNode::Symlink(_) => false,
+        }
+    }
+
+    fn as_symlink_mut(&mut self) -> FsResult<&mut MemFsSymlinkNode> {
+        match *self {
+            MemFsNode::Symlink(ref mut n) => Ok(n),
+            _ => Err(FsError::Forbidden),
         }
     }
 
@@ -579,6 +1110,7 @@ impl MemFsNode {
         match *self {
             MemFsNode::File(ref n) => &n.props,
             MemFsNode::Dir(ref d) => &d.props,
+            MemFsNode::Symlink(ref l) => &l.props,
         }
     }
 
@@ -586,6 +1118,31 @@ impl MemFsNode {
         match *self {
             MemFsNode::File(ref mut n) => &mut n.props,
             MemFsNode::Dir(ref mut d) => &mut d.props,
+            MemFsNode::Symlink(ref mut l) => &mut l.props,
+        }
+    }
+}
+
+impl MemFsFileNode {
+    // store the current content as a new version.
+    fn new_version(&mut self) -> DavVersion {
+        let versions = self.versions.get_or_insert_with(Default::default);
+        versions.versions.push(MemFsVersion {
+            created: SystemTime::now(),
+            data: Bytes::from(self.data.clone()),
+        });
+        let n = versions.versions.len() - 1;
+        versions.versions[n].as_dav_version(n)
+    }
+}
+
+impl MemFsVersion {
+    // versions are numbered from 1.
+    fn as_dav_version(&self, idx: usize) -> DavVersion {
+        DavVersion {
+            name: (idx + 1).to_string(),
+            created: self.created,
+            size: self.data.len() as u64,
         }
     }
 }
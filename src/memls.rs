@@ -12,6 +12,7 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
 use futures_util::FutureExt;
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, percent_encode};
 use uuid::Uuid;
 use xmltree::Element;
 
@@ -24,23 +25,23 @@ type Tree = tree::Tree<Vec<u8>, Vec<DavLock>>;
 
 /// Ephemeral in-memory LockSystem.
 #[derive(Debug, Clone)]
-pub struct MemLs(Arc<Mutex<MemLsInner>>);
+pub struct MemLs(Arc<Mutex<LockTree>>);
 
+// The locks, in a tree that follows the paths. The persistent
+// locksystems keep a copy of their locks in one of these as well.
 #[derive(Debug)]
-struct MemLsInner {
+pub(crate) struct LockTree {
     tree: Tree,
     #[allow(dead_code)]
     locks: HashMap<Vec<u8>, u64>,
+    // Lock-null resources, and the token of the lock that created them.
+    lock_nulls: Vec<(DavPath, String)>,
 }
 
 impl MemLs {
     /// Create a new "memls" locksystem.
     pub fn new() -> Box<MemLs> {
-        let inner = MemLsInner {
-            tree: Tree::new(Vec::new()),
-            locks: HashMap::new(),
-        };
-        Box::new(MemLs(Arc::new(Mutex::new(inner))))
+        Box::new(MemLs(Arc::new(Mutex::new(LockTree::new()))))
     }
 }
 
@@ -55,25 +56,98 @@ impl DavLockSystem for MemLs {
         deep: bool,
     ) -> LsFuture<'_, Result<DavLock, DavLock>> {
         let inner = &mut *self.0.lock().unwrap();
+        let res = inner.lock(path, principal, owner, timeout, shared, deep);
+        future::ready(res).boxed()
+    }
+
+    fn unlock(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, Result<(), ()>> {
+        let inner = &mut *self.0.lock().unwrap();
+        future::ready(inner.unlock(path, token)).boxed()
+    }
+
+    fn refresh(
+        &'_ self,
+        path: &DavPath,
+        token: &str,
+        timeout: Option<Duration>,
+    ) -> LsFuture<'_, Result<DavLock, ()>> {
+        let inner = &mut *self.0.lock().unwrap();
+        future::ready(inner.refresh(path, token, timeout)).boxed()
+    }
 
+    fn check(
+        &'_ self,
+        path: &DavPath,
+        principal: Option<&str>,
+        ignore_principal: bool,
+        deep: bool,
+        submitted_tokens: &[String],
+    ) -> LsFuture<'_, Result<(), DavLock>> {
+        let inner = &*self.0.lock().unwrap();
+        let res = inner.check(path, principal, ignore_principal, deep, submitted_tokens);
+        future::ready(res).boxed()
+    }
+
+    fn discover(&'_ self, path: &DavPath) -> LsFuture<'_, Vec<DavLock>> {
+        let inner = &*self.0.lock().unwrap();
+        future::ready(inner.discover(path)).boxed()
+    }
+
+    fn delete(&'_ self, path: &DavPath) -> LsFuture<'_, Result<(), ()>> {
+        let inner = &mut *self.0.lock().unwrap();
+        inner.delete(path);
+        future::ready(Ok(())).boxed()
+    }
+
+    fn add_lock_null(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, ()> {
+        let inner = &mut *self.0.lock().unwrap();
+        inner.add_lock_null(path, token);
+        future::ready(()).boxed()
+    }
+
+    fn remove_lock_null(&'_ self, path: &DavPath) -> LsFuture<'_, ()> {
+        let inner = &mut *self.0.lock().unwrap();
+        inner.remove_lock_null(path);
+        future::ready(()).boxed()
+    }
+
+    fn stale_lock_nulls(&'_ self) -> LsFuture<'_, Vec<DavPath>> {
+        let inner = &mut *self.0.lock().unwrap();
+        future::ready(inner.stale_lock_nulls()).boxed()
+    }
+}
+
+impl LockTree {
+    pub(crate) fn new() -> LockTree {
+        LockTree {
+            tree: Tree::new(Vec::new()),
+            locks: HashMap::new(),
+            lock_nulls: Vec::new(),
+        }
+    }
+
+    pub(crate) fn lock(
+        &mut self,
+        path: &DavPath,
+        principal: Option<&str>,
+        owner: Option<&Element>,
+        timeout: Option<Duration>,
+        shared: bool,
+        deep: bool,
+    ) -> Result<DavLock, DavLock> {
         // any locks in the path?
-        let rc = check_locks_to_path(&inner.tree, path, None, true, &Vec::new(), shared);
+        let rc = check_locks_to_path(&self.tree, path, None, true, &Vec::new(), shared);
         trace!("lock: check_locks_to_path: {rc:?}");
-        if let Err(err) = rc {
-            return future::ready(Err(err)).boxed();
-        }
+        rc?;
 
         // if it's a deep lock we need to check if there are locks furter along the path.
         if deep {
-            let rc = check_locks_from_path(&inner.tree, path, None, true, &Vec::new(), shared);
+            let rc = check_locks_from_path(&self.tree, path, None, true, &Vec::new(), shared);
             trace!("lock: check_locks_from_path: {rc:?}");
-            if let Err(err) = rc {
-                return future::ready(Err(err)).boxed();
-            }
+            rc?;
         }
 
         // create lock.
-        let node = get_or_create_path_node(&mut inner.tree, path);
         let timeout_at = timeout.map(|d| SystemTime::now() + d);
         let lock = DavLock {
             token: Uuid::new_v4().urn().to_string(),
@@ -86,68 +160,70 @@ impl DavLockSystem for MemLs {
             deep,
         };
         trace!("lock {} created", &lock.token);
-        let slock = lock.clone();
-        node.push(slock);
-        future::ready(Ok(lock)).boxed()
+        self.insert(lock.clone());
+        Ok(lock)
     }
 
-    fn unlock(&'_ self, path: &DavPath, token: &str) -> LsFuture<'_, Result<(), ()>> {
-        let inner = &mut *self.0.lock().unwrap();
-        let node_id = match lookup_lock(&inner.tree, path, token) {
+    // Add an existing lock, e.g. one that was loaded from storage.
+    pub(crate) fn insert(&mut self, lock: DavLock) {
+        let node = get_or_create_path_node(&mut self.tree, &lock.path);
+        node.push(lock);
+    }
+
+    pub(crate) fn unlock(&mut self, path: &DavPath, token: &str) -> Result<(), ()> {
+        let node_id = match lookup_lock(&self.tree, path, token) {
             None => {
                 trace!("unlock: {token} not found at {path}");
-                return future::ready(Err(())).boxed();
+                return Err(());
             }
             Some(n) => n,
         };
         let len = {
-            let node = inner.tree.get_node_mut(node_id).unwrap();
+            let node = self.tree.get_node_mut(node_id).unwrap();
             let idx = node.iter().position(|n| n.token.as_str() == token).unwrap();
             node.remove(idx);
             node.len()
         };
         if len == 0 {
-            inner.tree.delete_node(node_id).ok();
+            self.tree.delete_node(node_id).ok();
         }
-        future::ready(Ok(())).boxed()
+        Ok(())
     }
 
-    fn refresh(
-        &'_ self,
+    pub(crate) fn refresh(
+        &mut self,
         path: &DavPath,
         token: &str,
         timeout: Option<Duration>,
-    ) -> LsFuture<'_, Result<DavLock, ()>> {
+    ) -> Result<DavLock, ()> {
         trace!("refresh lock {token}");
-        let inner = &mut *self.0.lock().unwrap();
-        let node_id = match lookup_lock(&inner.tree, path, token) {
+        let node_id = match lookup_lock(&self.tree, path, token) {
             None => {
                 trace!("lock not found");
-                return future::ready(Err(())).boxed();
+                return Err(());
             }
             Some(n) => n,
         };
-        let node = inner.tree.get_node_mut(node_id).unwrap();
+        let node = self.tree.get_node_mut(node_id).unwrap();
         let idx = node.iter().position(|n| n.token.as_str() == token).unwrap();
         let lock = &mut node[idx];
         let timeout_at = timeout.map(|d| SystemTime::now() + d);
         lock.timeout = timeout;
         lock.timeout_at = timeout_at;
-        future::ready(Ok(lock.clone())).boxed()
+        Ok(lock.clone())
     }
 
-    fn check(
-        &'_ self,
+    pub(crate) fn check(
+        &self,
         path: &DavPath,
         principal: Option<&str>,
         ignore_principal: bool,
         deep: bool,
         submitted_tokens: &[String],
-    ) -> LsFuture<'_, Result<(), DavLock>> {
-        let inner = &*self.0.lock().unwrap();
+    ) -> Result<(), DavLock> {
         let _st = submitted_tokens;
         let rc = check_locks_to_path(
-            &inner.tree,
+            &self.tree,
             path,
             principal,
             ignore_principal,
@@ -155,14 +231,12 @@ impl DavLockSystem for MemLs {
             false,
         );
         trace!("check: check_lock_to_path: {_st:?}: {rc:?}");
-        if let Err(err) = rc {
-            return future::ready(Err(err)).boxed();
-        }
+        rc?;
 
         // if it's a deep lock we need to check if there are locks furter along the path.
         if deep {
             let rc = check_locks_from_path(
-                &inner.tree,
+                &self.tree,
                 path,
                 principal,
                 ignore_principal,
@@ -170,27 +244,216 @@ impl DavLockSystem for MemLs {
                 false,
             );
             trace!("check: check_locks_from_path: {rc:?}");
-            if let Err(err) = rc {
-                return future::ready(Err(err)).boxed();
+            rc?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn discover(&self, path: &DavPath) -> Vec<DavLock> {
+        list_locks(&self.tree, path)
+    }
+
+    // Returns false if there was nothing to delete.
+    pub(crate) fn delete(&mut self, path: &DavPath) -> bool {
+        match lookup_node(&self.tree, path) {
+            Some(node_id) => self.tree.delete_subtree(node_id).is_ok(),
+            None => false,
+        }
+    }
+
+    // All locks.
+    pub(crate) fn all_locks(&self) -> Vec<DavLock> {
+        let mut locks = Vec::new();
+        collect_locks(&self.tree, tree::ROOT_ID, &mut locks);
+        locks
+    }
+
+    // No locks, and no lock-null resources.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lock_nulls.is_empty() && self.all_locks().is_empty()
+    }
+
+    // Remember a resource that was created by the LOCK of `token`.
+    pub(crate) fn add_lock_null(&mut self, path: &DavPath, token: &str) -> bool {
+        if lookup_lock(&self.tree, path, token).is_none() {
+            return false;
+        }
+        self.lock_nulls.push((path.clone(), token.to_string()));
+        true
+    }
+
+    // The resource was written to, so it is a normal resource now.
+    pub(crate) fn remove_lock_null(&mut self, path: &DavPath) -> bool {
+        let len = self.lock_nulls.len();
+        self.lock_nulls.retain(|(p, _)| p != path);
+        self.lock_nulls.len() != len
+    }
+
+    pub(crate) fn has_lock_nulls(&self) -> bool {
+        !self.lock_nulls.is_empty()
+    }
+
+    // Forget the lock-null resources whose lock was removed or has
+    // expired, and return their paths.
+    pub(crate) fn stale_lock_nulls(&mut self) -> Vec<DavPath> {
+        let now = SystemTime::now();
+        let (stale, active): (Vec<_>, Vec<_>) = std::mem::take(&mut self.lock_nulls)
+            .into_iter()
+            .partition(|(path, token)| {
+                let lock = lookup_lock(&self.tree, path, token)
+                    .and_then(|id| self.tree.get_node(id).ok())
+                    .and_then(|locks| locks.iter().find(|l| &l.token == token));
+                lock.is_none_or(|l| l.timeout_at.is_some_and(|t| t <= now))
+            });
+        self.lock_nulls = active;
+        stale.into_iter().map(|(path, _)| path).collect()
+    }
+
+    // Load locks that were saved with `save()`. The ones
+    // that have expired in the meantime are dropped.
+    pub(crate) fn load(data: &[u8]) -> LockTree {
+        let mut tree = LockTree::new();
+        let now = SystemTime::now();
+        for line in String::from_utf8_lossy(data).lines() {
+            if let Some(lock) = decode_lock(line) {
+                if lock.timeout_at.is_none_or(|t| t > now) {
+                    tree.insert(lock);
+                }
+            } else if let Some(lock_null) = decode_lock_null(line) {
+                tree.lock_nulls.push(lock_null);
             }
         }
-        future::ready(Ok(())).boxed()
+        tree
     }
 
-    fn discover(&'_ self, path: &DavPath) -> LsFuture<'_, Vec<DavLock>> {
-        let inner = &*self.0.lock().unwrap();
-        future::ready(list_locks(&inner.tree, path)).boxed()
+    // All locks and lock-null resources, one per line.
+    pub(crate) fn save(&self) -> Vec<u8> {
+        let mut data = String::new();
+        for lock in self.all_locks() {
+            data.push_str(&encode_lock(&lock));
+            data.push('\n');
+        }
+        for (path, token) in &self.lock_nulls {
+            data.push_str(&encode_lock_null(path, token));
+            data.push('\n');
+        }
+        data.into_bytes()
     }
+}
 
-    fn delete(&'_ self, path: &DavPath) -> LsFuture<'_, Result<(), ()>> {
-        let inner = &mut *self.0.lock().unwrap();
-        if let Some(node_id) = lookup_node(&inner.tree, path) {
-            inner.tree.delete_subtree(node_id).ok();
+fn collect_locks(tree: &Tree, node_id: u64, out: &mut Vec<DavLock>) {
+    if let Ok(node) = tree.get_node(node_id) {
+        out.extend_from_slice(node);
+    }
+    if let Ok(children) = tree.get_children(node_id) {
+        for (_, child_id) in children {
+            collect_locks(tree, child_id, out);
         }
-        future::ready(Ok(())).boxed()
     }
 }
 
+// "<token> <path> <prefix> <principal> <owner> <timeout_at> <timeout> <shared> <deep>",
+// percent-encoded, "-" for none.
+fn encode_lock(lock: &DavLock) -> String {
+    let owner = lock.owner.as_ref().and_then(|o| {
+        let mut xml = Vec::new();
+        o.write(&mut xml).ok()?;
+        Some(xml)
+    });
+    let secs = |t: Option<Duration>| t.map(|t| t.as_secs().to_string());
+    let timeout_at = lock
+        .timeout_at
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok());
+    let (timeout_at, timeout) = (secs(timeout_at), secs(lock.timeout));
+    let path = lock.path.as_url_string();
+    let fields = [
+        Some(lock.token.as_bytes()),
+        Some(path.as_bytes()),
+        Some(lock.path.prefix().as_bytes()),
+        lock.principal.as_ref().map(|p| p.as_bytes()),
+        owner.as_deref(),
+        timeout_at.as_ref().map(|t| t.as_bytes()),
+        timeout.as_ref().map(|t| t.as_bytes()),
+        Some(if lock.shared { b"1".as_slice() } else { b"0" }),
+        Some(if lock.deep { b"1".as_slice() } else { b"0" }),
+    ];
+    let fields: Vec<_> = fields.into_iter().map(encode_field).collect();
+    fields.join(" ")
+}
+
+fn decode_lock(line: &str) -> Option<DavLock> {
+    let mut fields = line.split(' ').map(decode_field);
+    let mut string = || {
+        fields
+            .next()
+            .map(|f| f.map(String::from_utf8).transpose().ok())
+    };
+    let token = string()???;
+    let mut path = DavPath::new(&string()???).ok()?;
+    let prefix = string()???;
+    if !prefix.is_empty() {
+        path.add_prefix(&prefix);
+    }
+    let principal = string()??;
+    let owner = match string()?? {
+        Some(xml) => Some(Box::new(Element::parse(xml.as_bytes()).ok()?)),
+        None => None,
+    };
+    let mut secs = || -> Option<Option<Duration>> {
+        match string()?? {
+            Some(s) => Some(Some(Duration::from_secs(s.parse().ok()?))),
+            None => Some(None),
+        }
+    };
+    let timeout_at = secs()?.map(|d| SystemTime::UNIX_EPOCH + d);
+    let timeout = secs()?;
+    let shared = string()??? == "1";
+    let deep = string()??? == "1";
+    Some(DavLock {
+        token,
+        path: Box::new(path),
+        principal,
+        owner,
+        timeout_at,
+        timeout,
+        shared,
+        deep,
+    })
+}
+
+// "<token> <path> <prefix>", percent-encoded.
+fn encode_lock_null(path: &DavPath, token: &str) -> String {
+    let url = path.as_url_string();
+    let fields = [token.as_bytes(), url.as_bytes(), path.prefix().as_bytes()];
+    let fields: Vec<_> = fields.into_iter().map(|f| encode_field(Some(f))).collect();
+    fields.join(" ")
+}
+
+fn decode_lock_null(line: &str) -> Option<(DavPath, String)> {
+    let fields: Vec<_> = line.split(' ').collect();
+    let [token, path, prefix] = fields[..] else {
+        return None;
+    };
+    let string = |f: &str| String::from_utf8(decode_field(f)?).ok();
+    let mut path = DavPath::new(&string(path)?).ok()?;
+    let prefix = string(prefix)?;
+    if !prefix.is_empty() {
+        path.add_prefix(&prefix);
+    }
+    Some((path, string(token)?))
+}
+
+fn encode_field(field: Option<&[u8]>) -> String {
+    match field {
+        Some(f) => percent_encode(f, NON_ALPHANUMERIC).to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn decode_field(field: &str) -> Option<Vec<u8>> {
+    (field != "-").then(|| percent_decode_str(field).collect())
+}
+
 // check if there are any locks along the path.
 fn check_locks_to_path(
     tree: &Tree,
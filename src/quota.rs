@@ -0,0 +1,102 @@
+//
+// Byte limits per subtree (RFC 4331), see DavConfig::quota_limit.
+//
+// The usage of a subtree is not stored anywhere, it is calculated by
+// walking the subtree when a PUT, COPY or MKCOL needs to know it.
+//
+use std::collections::HashSet;
+
+use futures_util::{FutureExt, StreamExt, future::BoxFuture};
+use http::{Response, StatusCode};
+
+use crate::DavInner;
+use crate::body::Body;
+use crate::davpath::DavPath;
+use crate::fs::*;
+use crate::util::dav_xml_error_response;
+
+// is `path` equal to, or below, `subtree`.
+pub(crate) fn in_subtree(path: &DavPath, subtree: &DavPath) -> bool {
+    let path = path.as_bytes();
+    let subtree = subtree.as_bytes();
+    let subtree = subtree.strip_suffix(b"/").unwrap_or(subtree);
+    match path.strip_prefix(subtree) {
+        Some(rest) => rest.is_empty() || rest[0] == b'/',
+        None => false,
+    }
+}
+
+// The 507 response for a request that would exceed the quota.
+pub(crate) fn quota_exceeded() -> Response<Body> {
+    dav_xml_error_response(StatusCode::INSUFFICIENT_STORAGE, "<D:quota-not-exceeded/>")
+}
+
+impl<C: Clone + Send + Sync + 'static> DavInner<C> {
+    // Number of bytes that can still be stored at `path`,
+    // or None if it is not in a subtree with a limit.
+    pub(crate) async fn quota_room(&self, path: &DavPath) -> Option<u64> {
+        let mut room = None;
+        for (subtree, limit) in &self.quota_limits {
+            if !in_subtree(path, subtree) {
+                continue;
+            }
+            let used = self.usage(subtree).await;
+            let r = limit.saturating_sub(used);
+            room = Some(room.map_or(r, |room: u64| room.min(r)));
+        }
+        room
+    }
+
+    // Number of bytes stored at `path` (a file, or a collection and
+    // everything below it).
+    pub(crate) async fn usage(&self, path: &DavPath) -> u64 {
+        match self.fs.symlink_metadata(path, &self.credentials).await {
+            Ok(meta) if meta.is_dir() => {
+                let mut path = path.clone();
+                path.add_slash();
+                self.usage_dir(&path, &mut HashSet::new()).await
+            }
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => 0,
+        }
+    }
+
+    fn usage_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        seen: &'a mut HashSet<String>,
+    ) -> BoxFuture<'a, u64> {
+        async move {
+            // a collection with several bindings is only counted once.
+            if let Ok(id) = self.fs.resource_id(path, &self.credentials).await
+                && !seen.insert(id)
+            {
+                return 0;
+            }
+            let mut entries = match self
+                .fs
+                .read_dir(path, ReadDirMeta::DataSymlink, &self.credentials)
+                .await
+            {
+                Ok(entries) => entries,
+                Err(_) => return 0,
+            };
+            let mut used = 0;
+            while let Some(Ok(dirent)) = entries.next().await {
+                let Ok(meta) = dirent.metadata().await else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    let mut npath = path.clone();
+                    npath.push_segment(&dirent.name());
+                    npath.add_slash();
+                    used += self.usage_dir(&npath, seen).await;
+                } else if meta.is_file() {
+                    used += meta.len();
+                }
+            }
+            used
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,145 @@
+//! CalDAV scheduling (RFC 6638).
+//!
+//! Calendar users are the principals that can organize and attend
+//! events. Each has a calendar user address (like `mailto:alice@example.com`),
+//! a calendar home, and a scheduling inbox and outbox in that home.
+//! They are configured with [`DavConfig::calendar_user`](crate::DavConfig::calendar_user).
+//!
+//! When the organizer of an event stores it in their calendar home, the
+//! attendees get an iTIP (RFC 5546) REQUEST in their inbox, and a CANCEL
+//! when they are removed again. When an attendee changes their
+//! participation status, the organizer gets a REPLY, and the status is
+//! updated in the organizer's copy of the event. A POST of a VFREEBUSY
+//! request to the outbox returns the busy time of the attendees.
+//!
+//! Messages for addresses that are not calendar users of this server are
+//! handed to an [`ItipDelivery`], for example one that sends them by mail
+//! (iMIP, RFC 6047). [`MemDelivery`] keeps them in memory, for testing.
+//! Whether a REQUEST could be delivered is written to the SCHEDULE-STATUS
+//! parameter of the attendee, in the organizer's copy of the event.
+use std::fmt::Debug;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use dyn_clone::{DynClone, clone_trait_object};
+use futures_util::FutureExt;
+
+/// A calendar user (RFC 6638).
+#[derive(Debug, Clone)]
+pub struct CalendarUser {
+    pub(crate) address: String,
+    pub(crate) principal: String,
+    pub(crate) home: String,
+    pub(crate) inbox: String,
+    pub(crate) outbox: String,
+}
+
+impl CalendarUser {
+    /// Create a new calendar user.
+    ///
+    /// `address` is the calendar user address, used in ORGANIZER and
+    /// ATTENDEE properties (e.g. `mailto:alice@example.com`). `principal`
+    /// is the URL of the principal (e.g. `/principals/alice/`), as passed to
+    /// [`DavConfig::principal`](crate::DavConfig::principal). `home` is the
+    /// calendar home, a path relative to the
+    /// [`strip_prefix`](crate::DavConfig::strip_prefix) (e.g. `/calendars/alice/`).
+    ///
+    /// The inbox and outbox are `inbox/` and `outbox/` in the calendar home.
+    pub fn new(
+        address: impl Into<String>,
+        principal: impl Into<String>,
+        home: impl Into<String>,
+    ) -> CalendarUser {
+        let mut home = home.into();
+        if !home.ends_with('/') {
+            home.push('/');
+        }
+        CalendarUser {
+            address: address.into(),
+            principal: principal.into(),
+            inbox: format!("{home}inbox/"),
+            outbox: format!("{home}outbox/"),
+            home,
+        }
+    }
+
+    /// Use another path for the scheduling inbox.
+    pub fn inbox(self, path: impl Into<String>) -> CalendarUser {
+        let mut this = self;
+        this.inbox = path.into();
+        this
+    }
+
+    /// Use another path for the scheduling outbox.
+    pub fn outbox(self, path: impl Into<String>) -> CalendarUser {
+        let mut this = self;
+        this.outbox = path.into();
+        this
+    }
+
+    // calendar user addresses are compared case-insensitively.
+    pub(crate) fn has_address(&self, address: &str) -> bool {
+        self.address.eq_ignore_ascii_case(address.trim())
+    }
+}
+
+/// Future returned by [`ItipDelivery`] methods.
+pub type ItipFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Delivery of iTIP messages to calendar users outside this server.
+pub trait ItipDelivery: Debug + Send + Sync + DynClone {
+    /// Deliver `message`, an iCalendar object with a METHOD, from calendar
+    /// user address `from` to calendar user address `recipient`.
+    fn deliver<'a>(
+        &'a self,
+        from: &'a str,
+        recipient: &'a str,
+        message: &'a str,
+    ) -> ItipFuture<'a, io::Result<()>>;
+}
+
+clone_trait_object! {ItipDelivery}
+
+/// A message that was handed to [`MemDelivery`].
+#[derive(Debug, Clone)]
+pub struct ItipMessage {
+    pub from: String,
+    pub recipient: String,
+    pub message: String,
+}
+
+/// In-memory [`ItipDelivery`], that keeps the messages, for testing.
+///
+/// Clones share the same messages.
+#[derive(Debug, Clone, Default)]
+pub struct MemDelivery(Arc<Mutex<Vec<ItipMessage>>>);
+
+impl MemDelivery {
+    /// Create a new, empty, delivery.
+    pub fn new() -> Box<MemDelivery> {
+        Box::default()
+    }
+
+    /// The messages that were delivered so far.
+    pub fn messages(&self) -> Vec<ItipMessage> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl ItipDelivery for MemDelivery {
+    fn deliver<'a>(
+        &'a self,
+        from: &'a str,
+        recipient: &'a str,
+        message: &'a str,
+    ) -> ItipFuture<'a, io::Result<()>> {
+        self.0.lock().unwrap().push(ItipMessage {
+            from: from.to_string(),
+            recipient: recipient.to_string(),
+            message: message.to_string(),
+        });
+        std::future::ready(Ok(())).boxed()
+    }
+}
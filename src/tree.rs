@@ -1,5 +1,5 @@
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -18,11 +18,14 @@ pub const ROOT_ID: u64 = 1;
 
 #[derive(Debug)]
 /// Node itself. "data" contains user-modifiable data.
+///
+/// A node can be linked into more than one parent (or more than once
+/// into the same parent), it then has an entry in `parents` for each link.
 pub struct Node<K: Eq + Hash, D> {
     pub data: D,
     #[allow(dead_code)]
     id: u64,
-    parent_id: u64,
+    parents: Vec<u64>,
     children: HashMap<K, u64>,
 }
 
@@ -46,7 +49,7 @@ impl<K: Eq + Hash + Debug + Clone, D: Debug> Tree<K, D> {
         self.node_id += 1;
         let node = Node {
             id,
-            parent_id: parent,
+            parents: vec![parent],
             data,
             children: HashMap::new(),
         };
@@ -58,8 +61,11 @@ impl<K: Eq + Hash + Debug + Clone, D: Debug> Tree<K, D> {
     pub fn add_child(&mut self, parent: u64, key: K, data: D, overwrite: bool) -> FsResult<u64> {
         {
             let pnode = self.nodes.get(&parent).ok_or(FsError::NotFound)?;
-            if !overwrite && pnode.children.contains_key(&key) {
-                return Err(FsError::Exists);
+            if pnode.children.contains_key(&key) {
+                if !overwrite {
+                    return Err(FsError::Exists);
+                }
+                self.unlink(parent, &key)?;
             }
         }
         let id = self.new_node(parent, data);
@@ -69,6 +75,116 @@ impl<K: Eq + Hash + Debug + Clone, D: Debug> Tree<K, D> {
         Ok(id)
     }
 
+    /// Link an existing node into `parent` as well, under name `key`.
+    #[cfg(feature = "memfs")]
+    pub fn add_link(&mut self, parent: u64, key: K, id: u64) -> FsResult<()> {
+        if !self.nodes.contains_key(&id) {
+            return Err(FsError::NotFound);
+        }
+        let pnode = self.nodes.get_mut(&parent).ok_or(FsError::NotFound)?;
+        if pnode.children.contains_key(&key) {
+            return Err(FsError::Exists);
+        }
+        pnode.children.insert(key, id);
+        self.nodes.get_mut(&id).unwrap().parents.push(parent);
+        Ok(())
+    }
+
+    /// Remove the link `key` from `parent`. If that was the last link
+    /// of the node, the node is deleted, and so is everything below
+    /// it that cannot be reached from the root anymore.
+    pub fn unlink<Q>(&mut self, parent: u64, key: &Q) -> FsResult<()>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let pnode = self.nodes.get_mut(&parent).ok_or(FsError::NotFound)?;
+        let id = pnode.children.remove(key).ok_or(FsError::NotFound)?;
+        let node = self.nodes.get_mut(&id).unwrap();
+        if let Some(idx) = node.parents.iter().position(|&p| p == parent) {
+            node.parents.remove(idx);
+        }
+        if node.parents.is_empty() {
+            if node.children.is_empty() {
+                self.nodes.remove(&id);
+            } else {
+                self.collect_garbage();
+            }
+        }
+        Ok(())
+    }
+
+    // Remove all nodes that cannot be reached from the root.
+    fn collect_garbage(&mut self) {
+        let mut reachable = HashSet::new();
+        let mut todo = vec![ROOT_ID];
+        while let Some(id) = todo.pop() {
+            if reachable.insert(id)
+                && let Some(n) = self.nodes.get(&id)
+            {
+                todo.extend(n.children.values().copied());
+            }
+        }
+        self.nodes.retain(|id, _| reachable.contains(id));
+        for (id, n) in self.nodes.iter_mut() {
+            if *id != ROOT_ID {
+                n.parents.retain(|p| reachable.contains(p));
+            }
+        }
+    }
+
+    /// Number of links to this node.
+    #[cfg(feature = "memfs")]
+    pub fn link_count(&self, id: u64) -> FsResult<usize> {
+        let n = self.nodes.get(&id).ok_or(FsError::NotFound)?;
+        Ok(n.parents.len())
+    }
+
+    /// All paths (as a list of keys) that lead from the root to this node.
+    /// Only the first link of each parent is followed upwards.
+    #[cfg(feature = "memfs")]
+    pub fn paths(&self, id: u64) -> FsResult<Vec<Vec<K>>> {
+        let n = self.nodes.get(&id).ok_or(FsError::NotFound)?;
+        if id == ROOT_ID {
+            return Ok(vec![Vec::new()]);
+        }
+        let mut parents = n.parents.clone();
+        parents.sort_unstable();
+        parents.dedup();
+        let mut v = Vec::new();
+        for parent in parents {
+            let Some(ppath) = self.path_of(parent) else {
+                continue;
+            };
+            let pnode = self.nodes.get(&parent).unwrap();
+            for (k, _) in pnode.children.iter().filter(|(_, c)| **c == id) {
+                let mut path = ppath.clone();
+                path.push(k.clone());
+                v.push(path);
+            }
+        }
+        Ok(v)
+    }
+
+    // the path of a node, through its first link.
+    #[cfg(feature = "memfs")]
+    fn path_of(&self, mut id: u64) -> Option<Vec<K>> {
+        let mut seen = HashSet::new();
+        let mut path = Vec::new();
+        while id != ROOT_ID {
+            if !seen.insert(id) {
+                return None;
+            }
+            let parent = *self.nodes.get(&id)?.parents.first()?;
+            let pnode = self.nodes.get(&parent)?;
+            let (k, _) = pnode.children.iter().find(|(_, c)| **c == id)?;
+            path.push(k.clone());
+            id = parent;
+        }
+        path.reverse();
+        Some(path)
+    }
+
     /*
      * unused ...
     pub fn remove_child(&mut self, parent: u64, key: &K) -> FsResult<()> {
@@ -122,22 +238,15 @@ impl<K: Eq + Hash + Debug + Clone, D: Debug> Tree<K, D> {
         Ok(&mut n.data)
     }
 
+    // remove all links to this node.
     fn delete_node_from_parent(&mut self, id: u64) -> FsResult<()> {
-        let parent_id = self.nodes.get(&id).ok_or(FsError::NotFound)?.parent_id;
-        let key = {
-            let pnode = self.nodes.get(&parent_id).unwrap();
-            let mut key = None;
-            for (k, i) in &pnode.children {
-                if i == &id {
-                    key = Some((*k).clone());
-                    break;
-                }
+        let parents =
+            std::mem::take(&mut self.nodes.get_mut(&id).ok_or(FsError::NotFound)?.parents);
+        for parent_id in parents {
+            if let Some(pnode) = self.nodes.get_mut(&parent_id) {
+                pnode.children.retain(|_, i| *i != id);
             }
-            key
-        };
-        let key = key.unwrap();
-        let pnode = self.nodes.get_mut(&parent_id).unwrap();
-        pnode.children.remove(&key);
+        }
         Ok(())
     }
 
@@ -165,33 +274,37 @@ impl<K: Eq + Hash + Debug + Clone, D: Debug> Tree<K, D> {
         self.delete_node_from_parent(id)
     }
 
-    /// Move a node to a new position and new name in the tree.
+    /// Move the link `name` in `parent` to a new position and new name in the tree.
     /// If "overwrite" is true, will replace an existing
     /// node, but only if it doesn't have any children.
     #[cfg(feature = "memfs")]
     pub fn move_node(
         &mut self,
-        id: u64,
+        parent: u64,
+        name: &K,
         new_parent: u64,
         new_name: K,
         overwrite: bool,
     ) -> FsResult<()> {
-        let dest = {
+        let id = self.get_child(parent, name)?;
+        {
             let pnode = self.nodes.get(&new_parent).ok_or(FsError::NotFound)?;
             if let Some(cid) = pnode.children.get(&new_name) {
+                if *cid == id {
+                    // already there (another link to the same node).
+                    return self.unlink(parent, name);
+                }
                 let cnode = self.nodes.get(cid).unwrap();
                 if !overwrite || !cnode.children.is_empty() {
                     return Err(FsError::Exists);
                 }
-                Some(*cid)
-            } else {
-                None
+                self.unlink(new_parent, &new_name)?;
             }
-        };
-        self.delete_node_from_parent(id)?;
-        self.nodes.get_mut(&id).unwrap().parent_id = new_parent;
-        if let Some(dest) = dest {
-            self.nodes.remove(&dest);
+        }
+        self.nodes.get_mut(&parent).unwrap().children.remove(name);
+        let node = self.nodes.get_mut(&id).unwrap();
+        if let Some(idx) = node.parents.iter().position(|&p| p == parent) {
+            node.parents[idx] = new_parent;
         }
         let pnode = self.nodes.get_mut(&new_parent).unwrap();
         pnode.children.insert(new_name, id);
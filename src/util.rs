@@ -5,6 +5,7 @@ use bytes::Bytes;
 use chrono::{DateTime, SecondsFormat, Utc};
 use headers::Header;
 use http::method::InvalidMethod;
+use http::{Response, StatusCode};
 
 use crate::DavResult;
 use crate::body::Body;
@@ -30,6 +31,19 @@ pub enum DavMethod {
     Report = 0x2000,
     MkCalendar = 0x4000,
     MkAddressbook = 0x8000,
+    Acl = 0x10000,
+    VersionControl = 0x20000,
+    Checkout = 0x40000,
+    Checkin = 0x80000,
+    Uncheckout = 0x100000,
+    Bind = 0x200000,
+    Unbind = 0x400000,
+    Rebind = 0x800000,
+    MkRedirectRef = 0x1000000,
+    UpdateRedirectRef = 0x2000000,
+    Search = 0x4000000,
+    OrderPatch = 0x8000000,
+    Post = 0x10000000,
 }
 
 // translate method into our own enum that has webdav methods as well.
@@ -41,6 +55,7 @@ pub(crate) fn dav_method(m: &http::Method) -> DavResult<DavMethod> {
         http::Method::PATCH => DavMethod::Patch,
         http::Method::DELETE => DavMethod::Delete,
         http::Method::OPTIONS => DavMethod::Options,
+        http::Method::POST => DavMethod::Post,
         _ => match m.as_str() {
             "PROPFIND" => DavMethod::PropFind,
             "PROPPATCH" => DavMethod::PropPatch,
@@ -52,6 +67,18 @@ pub(crate) fn dav_method(m: &http::Method) -> DavResult<DavMethod> {
             "REPORT" => DavMethod::Report,
             "MKCALENDAR" => DavMethod::MkCalendar,
             "MKADDRESSBOOK" => DavMethod::MkAddressbook,
+            "ACL" => DavMethod::Acl,
+            "VERSION-CONTROL" => DavMethod::VersionControl,
+            "CHECKOUT" => DavMethod::Checkout,
+            "CHECKIN" => DavMethod::Checkin,
+            "UNCHECKOUT" => DavMethod::Uncheckout,
+            "BIND" => DavMethod::Bind,
+            "UNBIND" => DavMethod::Unbind,
+            "REBIND" => DavMethod::Rebind,
+            "MKREDIRECTREF" => DavMethod::MkRedirectRef,
+            "UPDATEREDIRECTREF" => DavMethod::UpdateRedirectRef,
+            "SEARCH" => DavMethod::Search,
+            "ORDERPATCH" => DavMethod::OrderPatch,
             _ => {
                 return Err(DavError::UnknownDavMethod);
             }
@@ -133,6 +160,19 @@ impl DavMethodSet {
                 "report" => DavMethod::Report as u32,
                 "mkcalendar" => DavMethod::MkCalendar as u32,
                 "mkaddressbook" => DavMethod::MkAddressbook as u32,
+                "acl" => DavMethod::Acl as u32,
+                "version-control" => DavMethod::VersionControl as u32,
+                "checkout" => DavMethod::Checkout as u32,
+                "checkin" => DavMethod::Checkin as u32,
+                "uncheckout" => DavMethod::Uncheckout as u32,
+                "bind" => DavMethod::Bind as u32,
+                "unbind" => DavMethod::Unbind as u32,
+                "rebind" => DavMethod::Rebind as u32,
+                "mkredirectref" => DavMethod::MkRedirectRef as u32,
+                "updateredirectref" => DavMethod::UpdateRedirectRef as u32,
+                "search" => DavMethod::Search as u32,
+                "orderpatch" => DavMethod::OrderPatch as u32,
+                "post" => DavMethod::Post as u32,
                 "http-ro" => Self::HTTP_RO.0,
                 "http-rw" => Self::HTTP_RW.0,
                 "webdav-ro" => Self::WEBDAV_RO.0,
@@ -159,6 +199,17 @@ pub(crate) fn dav_xml_error(body: &str) -> Body {
     Body::from(xml)
 }
 
+// A response with a <D:error> body, for failed preconditions.
+pub(crate) fn dav_xml_error_response(status: StatusCode, body: &str) -> Response<Body> {
+    let mut res = Response::new(dav_xml_error(body));
+    *res.status_mut() = status;
+    res.headers_mut().insert(
+        "content-type",
+        "application/xml; charset=utf-8".parse().unwrap(),
+    );
+    res
+}
+
 pub(crate) fn systemtime_to_httpdate(t: SystemTime) -> String {
     let d = headers::Date::from(t);
     let mut v = Vec::new();
@@ -166,6 +217,18 @@ pub(crate) fn systemtime_to_httpdate(t: SystemTime) -> String {
     v[0].to_str().unwrap().to_owned()
 }
 
+// The other way around. RFC 3339 dates are accepted as well.
+#[cfg(feature = "proppatch")]
+pub(crate) fn httpdate_to_systemtime(s: &str) -> Option<SystemTime> {
+    let s = s.trim();
+    if let Ok(value) = http::HeaderValue::from_str(s)
+        && let Ok(date) = headers::Date::decode(&mut std::iter::once(&value))
+    {
+        return Some(date.into());
+    }
+    DateTime::parse_from_rfc3339(s).ok().map(SystemTime::from)
+}
+
 pub(crate) fn systemtime_to_rfc3339_without_nanosecond(t: SystemTime) -> String {
     // 1996-12-19T16:39:57Z
     DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Secs, true)
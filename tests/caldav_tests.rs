@@ -1,6 +1,7 @@
 #[cfg(all(feature = "caldav", feature = "memfs"))]
 mod caldav_tests {
-    use dav_server::{DavHandler, body::Body, caldav::*, fakels::FakeLs};
+    use dav_server::schedule::{CalendarUser, MemDelivery};
+    use dav_server::{DavConfig, DavHandler, body::Body, caldav::*, fakels::FakeLs};
     use http::response::Response;
     use http::{Method, Request, StatusCode};
 
@@ -99,6 +100,48 @@ END:VCALENDAR"
         assert_eq!(resp.status(), StatusCode::CREATED);
     }
 
+    #[tokio::test]
+    async fn test_extended_mkcol_calendar() {
+        let server = setup_caldav_server();
+
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:mkcol xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:set><D:prop>
+    <D:resourcetype><D:collection/><C:calendar/></D:resourcetype>
+    <D:displayname>Work</D:displayname>
+  </D:prop></D:set>
+</D:mkcol>"#;
+        let req = Request::builder()
+            .method("MKCOL")
+            .uri("/calendars/work/")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/calendars/work/")
+            .header("Depth", "0")
+            .body(Body::from(
+                r#"<D:propfind xmlns:D="DAV:"><D:prop><D:displayname/><D:resourcetype/></D:prop></D:propfind>"#,
+            ))
+            .unwrap();
+        let body_str = resp_to_string(server.handle(req).await).await;
+        assert!(body_str.contains("<D:displayname>Work</D:displayname>"));
+        assert!(body_str.contains("<C:calendar"));
+
+        // a calendar outside of the calendar home.
+        let req = Request::builder()
+            .method("MKCOL")
+            .uri("/work/")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp_to_string(resp).await.contains("valid-resourcetype"));
+    }
+
     #[tokio::test]
     async fn test_mkcalendar_already_exists() {
         // First create a regular collection
@@ -326,6 +369,1210 @@ END:VCALENDAR"#;
         let resp = server.handle(req).await;
         assert_eq!(resp.status(), StatusCode::NO_CONTENT); // For an update, it should be NO_CONTENT
     }
+
+    #[tokio::test]
+    async fn test_calendar_put_preconditions() {
+        let server = setup_caldav_server2().await;
+        let uri = "/calendars/my-calendar/event.ics";
+
+        let event = |comp: &str, props: &str| {
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//Test//EN\r\n{props}\
+                 BEGIN:{comp}\r\nUID:uid-1\r\nDTSTART:20240101T120000Z\r\nEND:{comp}\r\n\
+                 END:VCALENDAR\r\n"
+            )
+        };
+        let no_uid = create_ics_data("", "No UID").replace("UID:\n", "");
+        let mixed = event("VEVENT", "").replace(
+            "END:VCALENDAR",
+            "BEGIN:VTODO\r\nUID:uid-1\r\nEND:VTODO\r\nEND:VCALENDAR",
+        );
+        let cases = [
+            ("not a calendar".to_string(), "valid-calendar-data"),
+            (no_uid, "valid-calendar-object-resource"),
+            (mixed, "valid-calendar-object-resource"),
+            (
+                event("VEVENT", "METHOD:REQUEST\r\n"),
+                "valid-calendar-object-resource",
+            ),
+            (event("VPOLL", ""), "supported-calendar-component"),
+        ];
+        for (ics_data, condition) in cases {
+            let resp = put_ics_data(&server, ics_data, uri).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{condition}");
+            let body = resp_to_string(resp).await;
+            assert!(body.contains(&format!("<C:{condition} ")), "{body}");
+        }
+
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header("Content-Length", "2000000")
+            .body(Body::from(event("VEVENT", "")))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp_to_string(resp).await.contains("C:max-resource-size"));
+
+        // nothing was stored.
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::NOT_FOUND);
+
+        let resp = put_ics_data(&server, create_ics_data("uid-1", "Event"), uri).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let ics_data = create_ics_data("uid-1", "Same event");
+        let resp = put_ics_data(&server, ics_data.clone(), uri).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        // the UID is already used by event.ics.
+        let other = "/calendars/my-calendar/other.ics";
+        let resp = put_ics_data(&server, ics_data, other).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("<C:no-uid-conflict "));
+        assert!(body.contains(&format!("<D:href>{uri}</D:href>")));
+
+        // and event.ics cannot get another one.
+        let resp = put_ics_data(&server, create_ics_data("uid-2", "Event"), uri).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp_to_string(resp).await.contains("<C:no-uid-conflict "));
+        let resp = put_ics_data(&server, create_ics_data("uid-2", "Event"), other).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        // outside of a calendar collection anything goes.
+        let resp = put_ics_data(&server, "not a calendar".to_string(), "/event.ics").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_put_supported_components() {
+        let server = setup_caldav_server();
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:mkcol xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:set><D:prop>
+    <D:resourcetype><D:collection/><C:calendar/></D:resourcetype>
+    <C:supported-calendar-component-set><C:comp name="VTODO"/></C:supported-calendar-component-set>
+  </D:prop></D:set>
+</D:mkcol>"#;
+        let req = Request::builder()
+            .method("MKCOL")
+            .uri("/calendars/tasks/")
+            .body(Body::from(body))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::CREATED);
+
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/calendars/tasks/")
+            .header("Depth", "0")
+            .body(Body::from(
+                r#"<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav"><D:prop><C:supported-calendar-component-set/></D:prop></D:propfind>"#,
+            ))
+            .unwrap();
+        let body = resp_to_string(server.handle(req).await).await;
+        assert!(body.contains(r#"name="VTODO""#));
+        assert!(!body.contains(r#"name="VEVENT""#));
+
+        let uri = "/calendars/tasks/item.ics";
+        let resp = put_ics_data(&server, create_ics_data("uid-1", "Event"), uri).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(
+            resp_to_string(resp)
+                .await
+                .contains("<C:supported-calendar-component ")
+        );
+        let todo = create_ics_data("uid-1", "Task").replace("VEVENT", "VTODO");
+        let resp = put_ics_data(&server, todo, uri).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_partial_update() {
+        let server = setup_caldav_server2().await;
+        let uri = "/calendars/my-calendar/event.ics";
+        let ics_data = create_ics_data("uid-1", "Event");
+        let resp = put_ics_data(&server, ics_data.clone(), uri).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let start = ics_data.find("SUMMARY:Event").unwrap() + "SUMMARY:".len();
+        let range = format!("{}-{}", start, start + 4);
+
+        // SabreDAV style PATCH.
+        let req = Request::builder()
+            .method(Method::PATCH)
+            .uri(uri)
+            .header("Content-Type", "application/x-sabredav-partialupdate")
+            .header("Content-Length", "5")
+            .header("X-Update-Range", format!("bytes={range}"))
+            .body(Body::from("Party"))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::NO_CONTENT);
+
+        // Apache style Content-Range.
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header("Content-Range", format!("bytes {range}/*"))
+            .body(Body::from("Feast"))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::NO_CONTENT);
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let body = resp_to_string(server.handle(req).await).await;
+        assert_eq!(body, ics_data.replace("SUMMARY:Event", "SUMMARY:Feast"));
+
+        // the object that a partial update results in is checked.
+        let req = Request::builder()
+            .method(Method::PATCH)
+            .uri(uri)
+            .header("Content-Type", "application/x-sabredav-partialupdate")
+            .header("Content-Length", "5")
+            .header("X-Update-Range", "bytes=0-4")
+            .body(Body::from("BOGUS"))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(
+            resp_to_string(resp)
+                .await
+                .contains("<C:valid-calendar-data ")
+        );
+        let uid = ics_data.find("UID:uid-1").unwrap() + "UID:uid-".len();
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header("Content-Range", format!("bytes {uid}-{uid}/*"))
+            .body(Body::from("2"))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp_to_string(resp).await.contains("<C:no-uid-conflict "));
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let body = resp_to_string(server.handle(req).await).await;
+        assert_eq!(body, ics_data.replace("SUMMARY:Event", "SUMMARY:Feast"));
+    }
+
+    #[tokio::test]
+    async fn test_calendar_put_conditional_before_preconditions() {
+        let server = DavHandler::builder()
+            .filesystem(dav_server::memfs::MemFs::new())
+            .locksystem(dav_server::memls::MemLs::new())
+            .build_handler();
+        let req = Request::builder()
+            .method("MKCALENDAR")
+            .uri("/calendars/my-calendar")
+            .body(Body::empty())
+            .unwrap();
+        server.handle(req).await;
+        let uri = "/calendars/my-calendar/event.ics";
+        let resp = put_ics_data(&server, create_ics_data("uid-1", "Event"), uri).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        // not a calendar object, but the If-Match fails first.
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header("If-Match", "\"no-such-etag\"")
+            .body(Body::from("not a calendar"))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+        let req = Request::builder()
+            .method("LOCK")
+            .uri(uri)
+            .body(Body::from(
+                r#"<D:lockinfo xmlns:D="DAV:"><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockinfo>"#,
+            ))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::OK);
+        let resp = put_ics_data(&server, "not a calendar".to_string(), uri).await;
+        assert_eq!(resp.status(), StatusCode::LOCKED);
+    }
+
+    // Run a calendar-query with this filter, and return the status and body.
+    async fn calendar_query(server: &DavHandler, filter: &str) -> (StatusCode, String) {
+        let report_body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:getetag/>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      {filter}
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#
+        );
+        let req = Request::builder()
+            .method("REPORT")
+            .uri("/calendars/my-calendar")
+            .header("Depth", "1")
+            .body(Body::from(report_body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        let status = resp.status();
+        (status, resp_to_string(resp).await)
+    }
+
+    #[tokio::test]
+    async fn test_calendar_query_time_range() {
+        let server = setup_caldav_server2().await;
+        let ics_data = create_ics_data("test-event-1", "Test Event");
+        put_ics_data(&server, ics_data, "/calendars/my-calendar/january.ics").await;
+        let ics_data =
+            create_ics_data("test-event-2", "Test Event").replace("20240101T1", "20240301T1");
+        put_ics_data(&server, ics_data, "/calendars/my-calendar/march.ics").await;
+
+        let (status, body) = calendar_query(
+            &server,
+            r#"<C:comp-filter name="VEVENT">
+                 <C:time-range start="20240201T000000Z" end="20240401T000000Z"/>
+               </C:comp-filter>"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(body.contains("march.ics"));
+        assert!(!body.contains("january.ics"));
+
+        // the event ends when the range starts, so it does not overlap.
+        let (_, body) = calendar_query(
+            &server,
+            r#"<C:comp-filter name="VEVENT">
+                 <C:time-range start="20240101T130000Z"/>
+               </C:comp-filter>"#,
+        )
+        .await;
+        assert!(body.contains("march.ics"));
+        assert!(!body.contains("january.ics"));
+
+        let (status, _) = calendar_query(
+            &server,
+            r#"<C:comp-filter name="VEVENT">
+                 <C:time-range start="tomorrow"/>
+               </C:comp-filter>"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_query_recurrence() {
+        let server = setup_caldav_server2().await;
+        // weekly on monday at 00:30 in Berlin, which is 23:30 UTC on sunday.
+        let ics_data = "BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Test//Test//EN
+BEGIN:VTIMEZONE
+TZID:Europe/Berlin
+BEGIN:DAYLIGHT
+TZOFFSETFROM:+0100
+TZOFFSETTO:+0200
+DTSTART:19700329T020000
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU
+END:DAYLIGHT
+BEGIN:STANDARD
+TZOFFSETFROM:+0200
+TZOFFSETTO:+0100
+DTSTART:19701025T030000
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU
+END:STANDARD
+END:VTIMEZONE
+BEGIN:VEVENT
+UID:weekly
+DTSTART;TZID=Europe/Berlin:20240101T003000
+DURATION:PT1H
+RRULE:FREQ=WEEKLY;COUNT=14
+EXDATE;TZID=Europe/Berlin:20240115T003000
+SUMMARY:Weekly
+END:VEVENT
+BEGIN:VEVENT
+UID:weekly
+RECURRENCE-ID;TZID=Europe/Berlin:20240122T003000
+DTSTART;TZID=Europe/Berlin:20240122T120000
+DURATION:PT1H
+SUMMARY:Moved
+END:VEVENT
+END:VCALENDAR
+";
+        let resp = put_ics_data(
+            &server,
+            ics_data.to_string(),
+            "/calendars/my-calendar/weekly.ics",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let query = |start: &str, end: &str| {
+            format!(
+                r#"<C:comp-filter name="VEVENT">
+                     <C:time-range start="{start}" end="{end}"/>
+                   </C:comp-filter>"#
+            )
+        };
+        let matches = async |start: &str, end: &str| {
+            let (_, body) = calendar_query(&server, &query(start, end)).await;
+            body.contains("weekly.ics")
+        };
+        // second instance, on sunday the 7th in UTC.
+        assert!(matches("20240107T230000Z", "20240107T234500Z").await);
+        assert!(!matches("20240108T003000Z", "20240108T013000Z").await);
+        // excluded.
+        assert!(!matches("20240114T000000Z", "20240115T000000Z").await);
+        // moved from midnight to noon.
+        assert!(!matches("20240121T000000Z", "20240122T000000Z").await);
+        assert!(matches("20240122T110000Z", "20240122T113000Z").await);
+        // the last instance is in summer time.
+        assert!(matches("20240331T220000Z", "20240331T223500Z").await);
+        assert!(!matches("20240331T233000Z", "20250101T000000Z").await);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_query_long_recurrence() {
+        let server = setup_caldav_server2().await;
+        let ics_data = create_ics_data("daily", "Daily")
+            .replace("20240101T120000Z", "19900101T090000Z")
+            .replace("20240101T130000Z", "19900101T100000Z")
+            .replace("SUMMARY", "RRULE:FREQ=DAILY\nSUMMARY");
+        let resp = put_ics_data(&server, ics_data, "/calendars/my-calendar/daily.ics").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let ics_data = create_ics_data("hourly", "Hourly")
+            .replace("20240101T130000Z", "20240101T123000Z")
+            .replace("SUMMARY", "RRULE:FREQ=HOURLY\nSUMMARY");
+        let resp = put_ics_data(&server, ics_data, "/calendars/my-calendar/hourly.ics").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let query = |start: &str, end: &str| {
+            format!(
+                r#"<C:comp-filter name="VEVENT">
+                     <C:time-range start="{start}" end="{end}"/>
+                   </C:comp-filter>"#
+            )
+        };
+        // both rules have many more instances before these ranges than
+        // are expanded for one request.
+        let (_, body) =
+            calendar_query(&server, &query("20240601T000000Z", "20240602T000000Z")).await;
+        assert!(body.contains("daily.ics"));
+        let (_, body) =
+            calendar_query(&server, &query("20260301T093000Z", "20260301T094500Z")).await;
+        assert!(body.contains("daily.ics"));
+        assert!(!body.contains("hourly.ics"));
+        let (_, body) =
+            calendar_query(&server, &query("20260305T101500Z", "20260305T104500Z")).await;
+        assert!(body.contains("hourly.ics"));
+        assert!(!body.contains("daily.ics"));
+    }
+
+    #[tokio::test]
+    async fn test_calendar_query_prop_filter() {
+        let server = setup_caldav_server2().await;
+        let ics_data = create_ics_data("test-event-1", "Team Meeting").replace(
+            "SUMMARY",
+            "ATTENDEE;PARTSTAT=ACCEPTED:mailto:a@example.com\nSUMMARY",
+        );
+        put_ics_data(&server, ics_data, "/calendars/my-calendar/meeting.ics").await;
+        let ics_data = create_ics_data("test-event-2", "Lunch");
+        put_ics_data(&server, ics_data, "/calendars/my-calendar/lunch.ics").await;
+
+        let query = async |filter: &str| {
+            let filter = format!(r#"<C:comp-filter name="VEVENT">{filter}</C:comp-filter>"#);
+            let (status, body) = calendar_query(&server, &filter).await;
+            assert_eq!(status, StatusCode::MULTI_STATUS);
+            (body.contains("meeting.ics"), body.contains("lunch.ics"))
+        };
+        let summary = |text: &str| {
+            format!(
+                r#"<C:prop-filter name="SUMMARY"><C:text-match {text}</C:text-match></C:prop-filter>"#
+            )
+        };
+        assert_eq!(query(&summary(">meeting")).await, (true, false));
+        assert_eq!(
+            query(&summary(r#"collation="i;octet">meeting"#)).await,
+            (false, false)
+        );
+        assert_eq!(
+            query(&summary(r#"negate-condition="yes">meeting"#)).await,
+            (false, true)
+        );
+        assert_eq!(
+            query(r#"<C:prop-filter name="ATTENDEE"><C:is-not-defined/></C:prop-filter>"#).await,
+            (false, true)
+        );
+        assert_eq!(
+            query(
+                r#"<C:prop-filter name="ATTENDEE">
+                     <C:param-filter name="PARTSTAT">
+                       <C:text-match>ACCEPTED</C:text-match>
+                     </C:param-filter>
+                   </C:prop-filter>"#
+            )
+            .await,
+            (true, false)
+        );
+        assert_eq!(
+            query(r#"<C:comp-filter name="VALARM"/>"#).await,
+            (false, false)
+        );
+
+        let (status, body) = calendar_query(
+            &server,
+            &summary(r#"collation="i;unicode-casemap">meeting"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body.contains("supported-collation"));
+    }
+
+    #[tokio::test]
+    async fn test_freebusy_query() {
+        let server = setup_caldav_server2().await;
+        let event = |uid: &str, start: &str, end: &str, extra: &str| {
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//Test//EN\r\n\
+                 BEGIN:VEVENT\r\nUID:{uid}\r\nDTSTART:{start}\r\nDTEND:{end}\r\n{extra}\
+                 END:VEVENT\r\nEND:VCALENDAR\r\n"
+            )
+        };
+        let events = [
+            event("busy", "20240102T090000Z", "20240102T100000Z", ""),
+            // overlaps with "busy", so they are merged.
+            event("overlap", "20240102T093000Z", "20240102T110000Z", ""),
+            event(
+                "tentative",
+                "20240102T140000Z",
+                "20240102T150000Z",
+                "STATUS:TENTATIVE\r\n",
+            ),
+            event(
+                "transparent",
+                "20240102T160000Z",
+                "20240102T170000Z",
+                "TRANSP:TRANSPARENT\r\n",
+            ),
+            event(
+                "cancelled",
+                "20240102T170000Z",
+                "20240102T180000Z",
+                "STATUS:CANCELLED\r\n",
+            ),
+            event(
+                "daily",
+                "20240101T080000Z",
+                "20240101T083000Z",
+                "RRULE:FREQ=DAILY\r\n",
+            ),
+            // outside of the range.
+            event("later", "20240105T090000Z", "20240105T100000Z", ""),
+        ];
+        for (n, ics_data) in events.into_iter().enumerate() {
+            let uri = format!("/calendars/my-calendar/event{n}.ics");
+            let resp = put_ics_data(&server, ics_data, &uri).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+        // available on weekdays from 8 to 18, in a period that starts on the 2nd.
+        let availability = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//Test//EN\r\n\
+             BEGIN:VAVAILABILITY\r\nUID:office-hours\r\nDTSTART:20240102T000000Z\r\n\
+             BEGIN:AVAILABLE\r\nUID:weekdays\r\n\
+             DTSTART:20240101T080000Z\r\nDTEND:20240101T180000Z\r\n\
+             RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r\n\
+             END:AVAILABLE\r\nEND:VAVAILABILITY\r\nEND:VCALENDAR\r\n";
+        let resp = put_ics_data(
+            &server,
+            availability.to_string(),
+            "/calendars/my-calendar/availability.ics",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let report_body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <C:time-range start="20240101T000000Z" end="20240103T000000Z"/>
+</C:free-busy-query>"#;
+        let req = Request::builder()
+            .method("REPORT")
+            .uri("/calendars/my-calendar")
+            .header("Depth", "1")
+            .body(Body::from(report_body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp_to_string(resp).await;
+        let periods: Vec<_> = body
+            .lines()
+            .filter_map(|l| l.strip_prefix("FREEBUSY;"))
+            .collect();
+        assert_eq!(
+            periods,
+            [
+                "FBTYPE=BUSY:20240101T080000Z/20240101T083000Z",
+                "FBTYPE=BUSY-UNAVAILABLE:20240102T000000Z/20240102T080000Z",
+                "FBTYPE=BUSY:20240102T080000Z/20240102T083000Z",
+                "FBTYPE=BUSY:20240102T090000Z/20240102T110000Z",
+                "FBTYPE=BUSY-TENTATIVE:20240102T140000Z/20240102T150000Z",
+                "FBTYPE=BUSY-UNAVAILABLE:20240102T180000Z/20240103T000000Z",
+            ]
+        );
+        assert!(body.contains("DTSTART:20240101T000000Z"));
+        assert!(body.contains("DTEND:20240103T000000Z"));
+    }
+
+    #[tokio::test]
+    async fn test_freebusy_query_long_recurrence() {
+        let server = setup_caldav_server2().await;
+        // busy for half an hour, every hour since 2024.
+        let ics_data = create_ics_data("hourly", "Hourly")
+            .replace("20240101T130000Z", "20240101T123000Z")
+            .replace("SUMMARY", "RRULE:FREQ=HOURLY\nSUMMARY");
+        let resp = put_ics_data(&server, ics_data, "/calendars/my-calendar/hourly.ics").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        // available from 8 to 18, every day since 1990.
+        let availability = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//Test//EN\r\n\
+             BEGIN:VAVAILABILITY\r\nUID:office-hours\r\n\
+             BEGIN:AVAILABLE\r\nUID:daily\r\n\
+             DTSTART:19900101T080000Z\r\nDTEND:19900101T180000Z\r\n\
+             RRULE:FREQ=DAILY\r\n\
+             END:AVAILABLE\r\nEND:VAVAILABILITY\r\nEND:VCALENDAR\r\n";
+        let resp = put_ics_data(
+            &server,
+            availability.to_string(),
+            "/calendars/my-calendar/availability.ics",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let report_body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <C:time-range start="20260305T100000Z" end="20260305T120000Z"/>
+</C:free-busy-query>"#;
+        let req = Request::builder()
+            .method("REPORT")
+            .uri("/calendars/my-calendar")
+            .header("Depth", "1")
+            .body(Body::from(report_body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp_to_string(resp).await;
+        let periods: Vec<_> = body
+            .lines()
+            .filter_map(|l| l.strip_prefix("FREEBUSY;"))
+            .collect();
+        assert_eq!(
+            periods,
+            [
+                "FBTYPE=BUSY:20260305T100000Z/20260305T103000Z",
+                "FBTYPE=BUSY:20260305T110000Z/20260305T113000Z",
+            ]
+        );
+    }
+
+    // Run a calendar-multiget for one href, with this calendar-data element.
+    async fn calendar_multiget(server: &DavHandler, href: &str, calendar_data: &str) -> String {
+        let report_body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-multiget xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    {calendar_data}
+  </D:prop>
+  <D:href>{href}</D:href>
+</C:calendar-multiget>"#
+        );
+        let req = Request::builder()
+            .method("REPORT")
+            .uri("/calendars/my-calendar")
+            .body(Body::from(report_body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        resp_to_string(resp).await
+    }
+
+    #[tokio::test]
+    async fn test_calendar_data_expand() {
+        let server = setup_caldav_server2().await;
+        let ics_data = "BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Test//Test//EN
+BEGIN:VTIMEZONE
+TZID:Fixed
+BEGIN:STANDARD
+TZOFFSETFROM:+0200
+TZOFFSETTO:+0200
+DTSTART:19700101T000000
+END:STANDARD
+END:VTIMEZONE
+BEGIN:VEVENT
+UID:daily
+DTSTART;TZID=Fixed:20240101T100000
+DTEND;TZID=Fixed:20240101T110000
+RRULE:FREQ=DAILY
+SUMMARY:Standup
+END:VEVENT
+BEGIN:VEVENT
+UID:daily
+RECURRENCE-ID;TZID=Fixed:20240103T100000
+DTSTART;TZID=Fixed:20240103T150000
+DTEND;TZID=Fixed:20240103T160000
+SUMMARY:Late standup
+END:VEVENT
+BEGIN:VEVENT
+UID:daily
+RECURRENCE-ID;TZID=Fixed:20240110T100000
+DTSTART;TZID=Fixed:20240110T150000
+DTEND;TZID=Fixed:20240110T160000
+SUMMARY:Much later
+END:VEVENT
+END:VCALENDAR
+";
+        let href = "/calendars/my-calendar/daily.ics";
+        let resp = put_ics_data(&server, ics_data.to_string(), href).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body = calendar_multiget(
+            &server,
+            href,
+            r#"<C:calendar-data>
+                 <C:expand start="20240102T000000Z" end="20240104T000000Z"/>
+               </C:calendar-data>"#,
+        )
+        .await;
+        assert!(!body.contains("RRULE"));
+        assert!(!body.contains("VTIMEZONE"));
+        assert!(!body.contains("TZID"));
+        assert!(body.contains("RECURRENCE-ID:20240102T080000Z"));
+        assert!(body.contains("DTSTART:20240102T080000Z"));
+        assert!(body.contains("DTEND:20240102T090000Z"));
+        assert!(body.contains("RECURRENCE-ID:20240103T080000Z"));
+        assert!(body.contains("DTSTART:20240103T130000Z"));
+        assert!(!body.contains("DTSTART:20240103T080000Z"));
+        assert!(!body.contains("20240101T080000Z"));
+        assert!(!body.contains("Much later"));
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 2);
+
+        let body = calendar_multiget(
+            &server,
+            href,
+            r#"<C:calendar-data>
+                 <C:limit-recurrence-set start="20240102T000000Z" end="20240104T000000Z"/>
+               </C:calendar-data>"#,
+        )
+        .await;
+        assert!(body.contains("RRULE:FREQ=DAILY"));
+        assert!(body.contains("Late standup"));
+        assert!(!body.contains("Much later"));
+
+        // a rule with many more instances before the range than are
+        // expanded for one request.
+        let href = "/calendars/my-calendar/since-1990.ics";
+        let ics_data = create_ics_data("since-1990", "Daily")
+            .replace("20240101T120000Z", "19900101T090000Z")
+            .replace("20240101T130000Z", "19900101T100000Z")
+            .replace("SUMMARY", "RRULE:FREQ=DAILY\nSUMMARY");
+        let resp = put_ics_data(&server, ics_data, href).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let body = calendar_multiget(
+            &server,
+            href,
+            r#"<C:calendar-data>
+                 <C:expand start="20260301T000000Z" end="20260303T000000Z"/>
+               </C:calendar-data>"#,
+        )
+        .await;
+        assert!(body.contains("RECURRENCE-ID:20260301T090000Z"));
+        assert!(body.contains("RECURRENCE-ID:20260302T090000Z"));
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 2);
+
+        // both a start and an end are needed.
+        let report_body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-multiget xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data><C:expand start="20240102T000000Z"/></C:calendar-data>
+  </D:prop>
+  <D:href>/calendars/my-calendar/daily.ics</D:href>
+</C:calendar-multiget>"#;
+        let req = Request::builder()
+            .method("REPORT")
+            .uri("/calendars/my-calendar")
+            .body(Body::from(report_body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_data_comp_selection() {
+        let server = setup_caldav_server2().await;
+        let href = "/calendars/my-calendar/event.ics";
+        let ics_data = create_ics_data("test-event-1", "Test Event");
+        put_ics_data(&server, ics_data, href).await;
+
+        let body = calendar_multiget(
+            &server,
+            href,
+            r#"<C:calendar-data>
+                 <C:comp name="VCALENDAR">
+                   <C:prop name="VERSION"/>
+                   <C:comp name="VEVENT">
+                     <C:prop name="SUMMARY"/>
+                     <C:prop name="UID"/>
+                     <C:prop name="DESCRIPTION" novalue="yes"/>
+                   </C:comp>
+                 </C:comp>
+               </C:calendar-data>"#,
+        )
+        .await;
+        assert!(body.contains("VERSION:2.0"));
+        assert!(!body.contains("PRODID"));
+        assert!(body.contains("SUMMARY:Test Event"));
+        assert!(body.contains("UID:test-event-1"));
+        assert!(body.contains("DESCRIPTION:\r"));
+        assert!(!body.contains("This is a test event"));
+        assert!(!body.contains("DTSTART"));
+
+        // without the selection, everything is there.
+        let body = calendar_multiget(&server, href, "<C:calendar-data/>").await;
+        assert!(body.contains("This is a test event"));
+        assert!(body.contains("DTSTART:20240101T120000Z"));
+    }
+
+    fn setup_scheduling_server(delivery: &MemDelivery) -> DavHandler {
+        DavHandler::builder()
+            .filesystem(dav_server::memfs::MemFs::new())
+            .locksystem(FakeLs::new())
+            .calendar_user(CalendarUser::new(
+                "mailto:alice@example.com",
+                "/principals/alice/",
+                "/calendars/alice/",
+            ))
+            .calendar_user(CalendarUser::new(
+                "mailto:bob@example.com",
+                "/principals/bob/",
+                "/calendars/bob/",
+            ))
+            .itip_delivery(Box::new(delivery.clone()))
+            .build_handler()
+    }
+
+    // a request made by one of the calendar users.
+    async fn request_as(
+        server: &DavHandler,
+        user: &str,
+        method: &str,
+        uri: &str,
+        body: String,
+    ) -> Response<Body> {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body))
+            .unwrap();
+        let config = DavConfig::new().principal(format!("/principals/{user}/"));
+        server.handle_with(config, req).await
+    }
+
+    // the calendar data of all objects in a collection.
+    async fn collection_data(server: &DavHandler, uri: &str) -> String {
+        let report_body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data/></D:prop>
+  <C:filter><C:comp-filter name="VCALENDAR"/></C:filter>
+</C:calendar-query>"#;
+        let req = Request::builder()
+            .method("REPORT")
+            .uri(uri)
+            .header("Depth", "1")
+            .body(Body::from(report_body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        resp_to_string(resp).await
+    }
+
+    fn meeting(attendees: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Test//Test//EN
+BEGIN:VEVENT
+UID:meeting-1
+DTSTAMP:20240101T000000Z
+DTSTART:20240115T100000Z
+DTEND:20240115T110000Z
+SUMMARY:Planning
+ORGANIZER:mailto:alice@example.com
+ATTENDEE;PARTSTAT=ACCEPTED:mailto:alice@example.com
+{attendees}BEGIN:VALARM
+ACTION:DISPLAY
+TRIGGER:-PT10M
+DESCRIPTION:Reminder
+END:VALARM
+END:VEVENT
+END:VCALENDAR
+"
+        )
+    }
+
+    async fn setup_calendar_homes(server: &DavHandler) {
+        for user in ["alice", "bob"] {
+            let home = format!("/calendars/{user}/");
+            let resp = request_as(server, user, "MKCOL", &home, String::new()).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+            let uri = format!("{home}calendar/");
+            let resp = request_as(server, user, "MKCALENDAR", &uri, String::new()).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schedule_request_reply_cancel() {
+        let delivery = MemDelivery::new();
+        let server = setup_scheduling_server(&delivery);
+        setup_calendar_homes(&server).await;
+
+        // the organizer invites a local and an external attendee.
+        let attendees = "ATTENDEE;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:bob@example.com\n\
+                         ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:carol@example.org\n";
+        let uri = "/calendars/alice/calendar/meeting.ics";
+        let resp = request_as(&server, "alice", "PUT", uri, meeting(attendees)).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let inbox = collection_data(&server, "/calendars/bob/inbox/").await;
+        assert_eq!(inbox.matches("METHOD:REQUEST").count(), 1);
+        assert!(inbox.contains("SUMMARY:Planning"));
+        assert!(!inbox.contains("VALARM"));
+
+        let messages = delivery.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].from, "mailto:alice@example.com");
+        assert_eq!(messages[0].recipient, "mailto:carol@example.org");
+        assert!(messages[0].message.contains("METHOD:REQUEST"));
+        assert!(!messages[0].message.contains("SCHEDULE-STATUS"));
+
+        // the organizer's copy has the status of each delivery.
+        let calendar = collection_data(&server, "/calendars/alice/calendar/").await;
+        let calendar = calendar.replace("\r\n ", "");
+        assert!(calendar.contains("SCHEDULE-STATUS=1.2:mailto:bob@example.com"));
+        assert!(calendar.contains("SCHEDULE-STATUS=1.1:mailto:carol@example.org"));
+
+        // storing it again unchanged sends nothing.
+        request_as(&server, "alice", "PUT", uri, meeting(attendees)).await;
+        assert_eq!(delivery.messages().len(), 1);
+
+        // the attendee accepts, and the organizer's copy is updated.
+        let accepted = meeting(&attendees.replacen("NEEDS-ACTION", "ACCEPTED", 1));
+        let bob_uri = "/calendars/bob/calendar/meeting.ics";
+        let resp = request_as(&server, "bob", "PUT", bob_uri, accepted).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let inbox = collection_data(&server, "/calendars/alice/inbox/").await;
+        assert!(inbox.contains("METHOD:REPLY"));
+        assert!(inbox.contains("ATTENDEE;RSVP=TRUE;PARTSTAT=ACCEPTED:mailto:bob@example.com"));
+        let calendar = collection_data(&server, "/calendars/alice/calendar/").await;
+        assert!(calendar.contains("RSVP=TRUE;PARTSTAT=ACCEPTED;SCHEDULE-STATUS=2.0:mailto:bob"));
+        assert!(calendar.contains("BEGIN:VALARM"));
+
+        // the attendee that is removed gets a cancellation.
+        let attendees = "ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:carol@example.org\n";
+        request_as(&server, "alice", "PUT", uri, meeting(attendees)).await;
+        let inbox = collection_data(&server, "/calendars/bob/inbox/").await;
+        assert_eq!(inbox.matches("METHOD:REQUEST").count(), 1);
+        assert_eq!(inbox.matches("METHOD:CANCEL").count(), 1);
+        assert!(inbox.contains("STATUS:CANCELLED"));
+        assert!(inbox.contains("SEQUENCE:1"));
+        assert_eq!(delivery.messages().len(), 2);
+
+        // and so does everyone when the event is removed.
+        let resp = request_as(&server, "alice", "DELETE", uri, String::new()).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let messages = delivery.messages();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[2].message.contains("METHOD:CANCEL"));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_status_undeliverable() {
+        // no way to reach attendees outside of this server.
+        let server = DavHandler::builder()
+            .filesystem(dav_server::memfs::MemFs::new())
+            .locksystem(FakeLs::new())
+            .calendar_user(CalendarUser::new(
+                "mailto:alice@example.com",
+                "/principals/alice/",
+                "/calendars/alice/",
+            ))
+            .build_handler();
+        let resp = request_as(
+            &server,
+            "alice",
+            "MKCOL",
+            "/calendars/alice/",
+            String::new(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let uri = "/calendars/alice/calendar/";
+        let resp = request_as(&server, "alice", "MKCALENDAR", uri, String::new()).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let attendees = "ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:carol@example.org\n";
+        let uri = "/calendars/alice/calendar/meeting.ics";
+        let resp = request_as(&server, "alice", "PUT", uri, meeting(attendees)).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        // the stored object is not what was sent anymore.
+        assert!(resp.headers().get("ETag").is_none());
+        let calendar = collection_data(&server, "/calendars/alice/calendar/").await;
+        assert!(calendar.contains("SCHEDULE-STATUS=5.3:mailto:carol@example.org"));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_attendee_delete_declines() {
+        let delivery = MemDelivery::new();
+        let server = setup_scheduling_server(&delivery);
+        setup_calendar_homes(&server).await;
+
+        let attendees = "ATTENDEE;PARTSTAT=ACCEPTED:mailto:bob@example.com\n";
+        let uri = "/calendars/alice/calendar/meeting.ics";
+        request_as(&server, "alice", "PUT", uri, meeting(attendees)).await;
+        let bob_uri = "/calendars/bob/calendar/meeting.ics";
+        request_as(&server, "bob", "PUT", bob_uri, meeting(attendees)).await;
+
+        let resp = request_as(&server, "bob", "DELETE", bob_uri, String::new()).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let calendar = collection_data(&server, "/calendars/alice/calendar/").await;
+        assert!(calendar.contains("PARTSTAT=DECLINED;SCHEDULE-STATUS=2.0:mailto:bob"));
+        assert!(delivery.messages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_properties() {
+        let delivery = MemDelivery::new();
+        let server = setup_scheduling_server(&delivery);
+        setup_calendar_homes(&server).await;
+
+        let propfind = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:resourcetype/>
+    <C:calendar-home-set/>
+    <C:calendar-user-address-set/>
+    <C:schedule-inbox-URL/>
+    <C:schedule-outbox-URL/>
+  </D:prop>
+</D:propfind>"#;
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/principals/bob/")
+            .header("Depth", "0")
+            .body(Body::from(propfind))
+            .unwrap();
+        let config = DavConfig::new().principal("/principals/bob/");
+        let resp = server.handle_with(config, req).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("<D:principal"));
+        assert!(body.contains("<D:href>/calendars/bob/</D:href>"));
+        assert!(body.contains("<D:href>mailto:bob@example.com</D:href>"));
+        assert!(body.contains("<D:href>/calendars/bob/inbox/</D:href>"));
+        assert!(body.contains("<D:href>/calendars/bob/outbox/</D:href>"));
+
+        // the inbox is a scheduling inbox, not a calendar.
+        let attendees = "ATTENDEE:mailto:bob@example.com\n";
+        let uri = "/calendars/alice/calendar/meeting.ics";
+        request_as(&server, "alice", "PUT", uri, meeting(attendees)).await;
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/calendars/bob/inbox/")
+            .header("Depth", "0")
+            .body(Body::from(propfind))
+            .unwrap();
+        let body = resp_to_string(server.handle(req).await).await;
+        assert!(body.contains("schedule-inbox"));
+        assert!(!body.contains("<C:calendar/>"));
+
+        // so nothing can be stored in it.
+        let uri = "/calendars/bob/inbox/event.ics";
+        let resp = request_as(&server, "bob", "PUT", uri, meeting("")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("C:calendar-collection-location-ok"));
+
+        let req = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/calendars/bob/outbox/")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        let dav = resp.headers().get("DAV").unwrap().to_str().unwrap();
+        assert!(dav.contains("calendar-auto-schedule"));
+        let allow = resp.headers().get("allow").unwrap().to_str().unwrap();
+        assert!(allow.contains("POST"));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_freebusy_post() {
+        let delivery = MemDelivery::new();
+        let server = setup_scheduling_server(&delivery);
+        setup_calendar_homes(&server).await;
+
+        let ics_data = create_ics_data("busy-1", "Dentist");
+        let uri = "/calendars/bob/calendar/dentist.ics";
+        let resp = request_as(&server, "bob", "PUT", uri, ics_data).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let request = |organizer: &str| {
+            format!(
+                "BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Test//Test//EN
+METHOD:REQUEST
+BEGIN:VFREEBUSY
+UID:fb-1
+DTSTAMP:20240101T000000Z
+DTSTART:20240101T000000Z
+DTEND:20240102T000000Z
+ORGANIZER:{organizer}
+ATTENDEE:mailto:bob@example.com
+ATTENDEE:mailto:nobody@example.com
+END:VFREEBUSY
+END:VCALENDAR
+"
+            )
+        };
+        let outbox = "/calendars/alice/outbox/";
+        let body = request("mailto:alice@example.com");
+        let resp = request_as(&server, "alice", "POST", outbox, body).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("schedule-response"));
+        assert!(body.contains("<D:href>mailto:bob@example.com</D:href>"));
+        assert!(body.contains("2.0;Success"));
+        assert!(body.contains("METHOD:REPLY"));
+        assert!(body.contains("FREEBUSY;FBTYPE=BUSY:20240101T120000Z/20240101T130000Z"));
+        assert!(body.contains("3.7;Invalid calendar user"));
+
+        // only the owner of the outbox, as the organizer.
+        let body = request("mailto:alice@example.com");
+        let resp = request_as(&server, "bob", "POST", outbox, body).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body = request("mailto:bob@example.com");
+        let resp = request_as(&server, "alice", "POST", outbox, body).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp_to_string(resp).await.contains("organizer-allowed"));
+
+        // POST is only for the outbox.
+        let body = request("mailto:alice@example.com");
+        let resp = request_as(&server, "alice", "POST", "/calendars/alice/", body).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_report_read_privilege() {
+        use dav_server::acl::{DavAce, DavAcePrincipal, DavPrivilege};
+        use dav_server::davpath::DavPath;
+        use dav_server::memacl::MemAcl;
+
+        // everyone can read, except bob the secret event. alice can do everything.
+        let acl = MemAcl::new();
+        acl.add_principal("/principals/alice/");
+        acl.add_principal("/principals/bob/");
+        acl.set_owner(&DavPath::new("/").unwrap(), "/principals/alice/");
+        acl.set_aces(
+            &DavPath::new("/").unwrap(),
+            vec![
+                DavAce::grant(DavAcePrincipal::All, DavPrivilege::Read),
+                DavAce::grant(DavAcePrincipal::Owner, DavPrivilege::All),
+            ],
+        );
+        acl.set_aces(
+            &DavPath::new("/calendars/cal/secret.ics").unwrap(),
+            vec![DavAce::deny(
+                DavAcePrincipal::Href("/principals/bob/".to_string()),
+                DavPrivilege::Read,
+            )],
+        );
+        let server = DavHandler::builder()
+            .filesystem(dav_server::memfs::MemFs::new())
+            .locksystem(FakeLs::new())
+            .acl(acl)
+            .build_handler();
+        let resp = request_as(
+            &server,
+            "alice",
+            "MKCALENDAR",
+            "/calendars/cal/",
+            String::new(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        for name in ["public", "secret"] {
+            let uri = format!("/calendars/cal/{name}.ics");
+            let ics_data = create_ics_data(name, &format!("The {name} event"));
+            let resp = request_as(&server, "alice", "PUT", &uri, ics_data).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+
+        let multiget = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-multiget xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data/></D:prop>
+  <D:href>/calendars/cal/public.ics</D:href>
+  <D:href>/calendars/cal/secret.ics</D:href>
+</C:calendar-multiget>"#;
+        let resp = request_as(
+            &server,
+            "bob",
+            "REPORT",
+            "/calendars/cal/",
+            multiget.to_string(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("The public event"));
+        assert!(!body.contains("The secret event"));
+        assert!(body.contains("HTTP/1.1 403 Forbidden"));
+
+        let query = r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop><C:calendar-data/></D:prop>
+  <C:filter><C:comp-filter name="VCALENDAR"/></C:filter>
+</C:calendar-query>"#;
+        let resp = request_as(
+            &server,
+            "bob",
+            "REPORT",
+            "/calendars/cal/",
+            query.to_string(),
+        )
+        .await;
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("The public event"));
+        assert!(!body.contains("secret"));
+
+        // the owner sees both.
+        let resp = request_as(
+            &server,
+            "alice",
+            "REPORT",
+            "/calendars/cal/",
+            query.to_string(),
+        )
+        .await;
+        assert!(resp_to_string(resp).await.contains("The secret event"));
+    }
 }
 
 #[cfg(all(not(feature = "caldav"), feature = "memfs"))]
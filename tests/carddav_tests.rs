@@ -322,6 +322,86 @@ END:VCARD"#;
         );
     }
 
+    #[tokio::test]
+    async fn test_addressbook_report_read_privilege() {
+        use dav_server::DavConfig;
+        use dav_server::acl::{DavAce, DavAcePrincipal, DavPrivilege};
+        use dav_server::davpath::DavPath;
+        use dav_server::memacl::MemAcl;
+
+        // everyone can read, except bob the secret contact. alice can do everything.
+        let acl = MemAcl::new();
+        acl.set_owner(&DavPath::new("/").unwrap(), "/principals/alice/");
+        acl.set_aces(
+            &DavPath::new("/").unwrap(),
+            vec![
+                DavAce::grant(DavAcePrincipal::All, DavPrivilege::Read),
+                DavAce::grant(DavAcePrincipal::Owner, DavPrivilege::All),
+            ],
+        );
+        acl.set_aces(
+            &DavPath::new("/addressbooks/book/secret.vcf").unwrap(),
+            vec![DavAce::deny(
+                DavAcePrincipal::Href("/principals/bob/".to_string()),
+                DavPrivilege::Read,
+            )],
+        );
+        let server = DavHandler::builder()
+            .filesystem(MemFs::new())
+            .locksystem(FakeLs::new())
+            .acl(acl)
+            .build_handler();
+        let request = |user: &str, method: &str, uri: &str, body: String| {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Depth", "1")
+                .body(Body::from(body))
+                .unwrap();
+            let config = DavConfig::new().principal(format!("/principals/{user}/"));
+            let server = server.clone();
+            async move { server.handle_with(config, req).await }
+        };
+
+        let resp = request(
+            "alice",
+            "MKADDRESSBOOK",
+            "/addressbooks/book/",
+            String::new(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        for name in ["public", "secret"] {
+            let vcard =
+                format!("BEGIN:VCARD\nVERSION:3.0\nUID:{name}\nFN:The {name} contact\nEND:VCARD");
+            let uri = format!("/addressbooks/book/{name}.vcf");
+            let resp = request("alice", "PUT", &uri, vcard).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+
+        let multiget = r#"<?xml version="1.0" encoding="utf-8" ?>
+<CARD:addressbook-multiget xmlns:D="DAV:" xmlns:CARD="urn:ietf:params:xml:ns:carddav">
+  <D:prop><CARD:address-data/></D:prop>
+  <D:href>/addressbooks/book/public.vcf</D:href>
+  <D:href>/addressbooks/book/secret.vcf</D:href>
+</CARD:addressbook-multiget>"#;
+        let resp = request("bob", "REPORT", "/addressbooks/book/", multiget.to_string()).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("The public contact"));
+        assert!(!body.contains("The secret contact"));
+        assert!(body.contains("HTTP/1.1 403 Forbidden"));
+
+        let query = r#"<?xml version="1.0" encoding="utf-8" ?>
+<CARD:addressbook-query xmlns:D="DAV:" xmlns:CARD="urn:ietf:params:xml:ns:carddav">
+  <D:prop><CARD:address-data/></D:prop>
+</CARD:addressbook-query>"#;
+        let resp = request("bob", "REPORT", "/addressbooks/book/", query.to_string()).await;
+        let body = resp_to_string(resp).await;
+        assert!(body.contains("The public contact"));
+        assert!(!body.contains("secret"));
+    }
+
     #[test]
     fn test_is_vcard_data() {
         let valid_vcard = b"BEGIN:VCARD\nVERSION:3.0\nFN:Test\nEND:VCARD\n";
@@ -157,4 +157,345 @@ mod dav_tests {
         let resp_text = resp_to_string(resp).await;
         assert!(!resp_text.contains("/.hidden_folder"));
     }
+
+    #[tokio::test]
+    async fn test_localfs_atomic_put() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = "/tmp/DAV_SERVER_TEST_ATOMIC";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let server = DavHandler::builder()
+            .filesystem(LocalFs::new(dir, false, false, false))
+            .atomic_put(true)
+            .build_handler();
+        let put = |headers: &[(&'static str, &'static str)], body: &'static str| {
+            let mut req = Request::builder().method("PUT").uri("/a.txt");
+            for (name, value) in headers {
+                req = req.header(*name, *value);
+            }
+            server.handle(req.body(Body::from(body)).unwrap())
+        };
+
+        assert_eq!(put(&[], "hello world").await.status(), StatusCode::CREATED);
+        let path = format!("{dir}/a.txt");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        // an incomplete body leaves the file alone.
+        let resp = put(&[("Content-Length", "20")], "short").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
+
+        let resp = put(&[("Content-Range", "bytes 6-10/11")], "there").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello there");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        // no temporary files are left behind.
+        for entry in std::fs::read_dir(dir).unwrap() {
+            assert!(
+                !entry
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with('.')
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_localfs_dead_props() {
+        use dav_server::localfs::SidecarPropStore;
+
+        let dir = "/tmp/DAV_SERVER_TEST_PROPS";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let server = DavHandler::builder()
+            .filesystem(
+                LocalFs::new(dir, false, false, false).with_prop_store(SidecarPropStore::new()),
+            )
+            .build_handler();
+        let request = |method: &str, uri: &str, headers: &[(&str, &str)], body: &str| {
+            let mut req = Request::builder().method(method).uri(uri);
+            for (name, value) in headers {
+                req = req.header(*name, *value);
+            }
+            server.handle(req.body(Body::from(body.to_string())).unwrap())
+        };
+        let propfind = |uri: &str, depth: &str| {
+            request(
+                "PROPFIND",
+                uri,
+                &[("Depth", depth)],
+                r#"<D:propfind xmlns:D="DAV:"><D:allprop/></D:propfind>"#,
+            )
+        };
+        let proppatch = r#"<D:propertyupdate xmlns:D="DAV:" xmlns:Z="urn:z"><D:set><D:prop><Z:color>blue</Z:color></D:prop></D:set></D:propertyupdate>"#;
+
+        let resp = request("PUT", "/a.txt", &[], "hello").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = request("MKCOL", "/d/", &[], "").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        for uri in ["/a.txt", "/d/"] {
+            let resp = request("PROPPATCH", uri, &[], proppatch).await;
+            assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        }
+        let resp = resp_to_string(propfind("/", "1").await).await;
+        assert_eq!(resp.matches(">blue</").count(), 2);
+        assert!(!resp.contains(".davprops"));
+        let resp = request("GET", "/.davprops", &[], "").await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // the properties follow the file.
+        let resp = request("MOVE", "/a.txt", &[("Destination", "/d/b.txt")], "").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = request("COPY", "/d/b.txt", &[("Destination", "/c.txt")], "").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        for uri in ["/d/b.txt", "/c.txt"] {
+            let resp = resp_to_string(propfind(uri, "0").await).await;
+            assert!(resp.contains(">blue</"), "{uri}: {resp}");
+        }
+
+        // and are removed with it.
+        let resp = request("DELETE", "/d/", &[], "").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = request("DELETE", "/c.txt", &[], "").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = request("PUT", "/c.txt", &[], "hello").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = resp_to_string(propfind("/c.txt", "0").await).await;
+        assert!(!resp.contains(">blue</"));
+        assert!(!std::path::Path::new(dir).join(".davprops").exists());
+    }
+
+    #[tokio::test]
+    async fn test_localfs_bind_overwrite() {
+        let dir = "/tmp/DAV_SERVER_TEST_BIND";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let server = DavHandler::builder()
+            .filesystem(LocalFs::new(dir, false, false, false))
+            .build_handler();
+        let request = |method: &str, uri: &str, body: String| {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from(body))
+                .unwrap();
+            server.handle(req)
+        };
+        let bind = |segment: &str, href: &str| {
+            format!(
+                r#"<D:bind xmlns:D="DAV:"><D:segment>{segment}</D:segment><D:href>{href}</D:href></D:bind>"#
+            )
+        };
+        for (method, uri, body) in [
+            ("MKCOL", "/src/", ""),
+            ("PUT", "/src/a.txt", "new"),
+            ("MKCOL", "/dst/", ""),
+            ("PUT", "/dst/keep.txt", "keep"),
+            ("PUT", "/b.txt", "old"),
+        ] {
+            let resp = request(method, uri, body.to_string()).await;
+            assert_eq!(resp.status(), StatusCode::CREATED, "{method} {uri}");
+        }
+
+        // directories cannot be bound: the target is left alone.
+        let resp = request("BIND", "/", bind("dst", "/src/")).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(resp_to_string(resp).await.contains("binding-allowed"));
+        let keep = std::fs::read_to_string(format!("{dir}/dst/keep.txt")).unwrap();
+        assert_eq!(keep, "keep");
+
+        // files can, and replace the target.
+        let resp = request("BIND", "/", bind("b.txt", "/src/a.txt")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            std::fs::read_to_string(format!("{dir}/b.txt")).unwrap(),
+            "new"
+        );
+
+        // no temporary bindings are left behind.
+        for entry in std::fs::read_dir(dir).unwrap() {
+            assert!(
+                !entry
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with('.')
+            );
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_localfs_failed_changes_not_recorded() {
+        use dav_server::davpath::DavPath;
+        use dav_server::fs::DavFileSystem;
+
+        let dir = "/tmp/DAV_SERVER_TEST_CHANGES";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let fs = LocalFs::new(dir, false, false, false);
+        let root = DavPath::new("/").unwrap();
+        let token = fs.sync_token(&root).await.unwrap();
+
+        let missing = DavPath::new("/missing.txt").unwrap();
+        let copy = DavPath::new("/copy.txt").unwrap();
+        assert!(fs.remove_file(&missing).await.is_err());
+        assert!(fs.unbind(&missing).await.is_err());
+        assert!(fs.copy(&missing, &copy).await.is_err());
+        assert!(fs.bind(&missing, &copy).await.is_err());
+        let changes = fs.sync_changes(&root, &token, true).await.unwrap();
+        assert!(changes.members.is_empty(), "{:?}", changes.members);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_localfs_set_liveprops() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dir = "/tmp/DAV_SERVER_TEST_LIVEPROPS";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let server = DavHandler::builder()
+            .filesystem(LocalFs::new(dir, false, false, false))
+            .build_handler();
+        let proppatch = |props: &str| {
+            let body = format!(
+                r#"<D:propertyupdate xmlns:D="DAV:" xmlns:A="http://apache.org/dav/props/" xmlns:Z="urn:schemas-microsoft-com:"><D:set><D:prop>{props}</D:prop></D:set></D:propertyupdate>"#
+            );
+            let req = Request::builder()
+                .method("PROPPATCH")
+                .uri("/a.txt")
+                .body(Body::from(body))
+                .unwrap();
+            server.handle(req)
+        };
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/a.txt")
+            .body(Body::from("hello"))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::CREATED);
+        let path = format!("{dir}/a.txt");
+
+        let resp = proppatch(
+            "<D:getlastmodified>Mon, 01 Jan 2001 00:00:00 GMT</D:getlastmodified>\
+             <Z:Win32LastAccessTime>Tue, 02 Jan 2001 00:00:00 GMT</Z:Win32LastAccessTime>\
+             <A:executable>T</A:executable>\
+             <Z:Win32FileAttributes>00000021</Z:Win32FileAttributes>",
+        )
+        .await;
+        let resp = resp_to_string(resp).await;
+        assert!(!resp.contains("HTTP/1.1 403"), "{resp}");
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(
+            meta.modified().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(978307200)
+        );
+        assert_eq!(
+            meta.accessed().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(978393600)
+        );
+        assert_eq!(meta.permissions().mode() & 0o777, 0o500);
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/a.txt")
+            .header("Depth", "0")
+            .body(Body::from(
+                r#"<D:propfind xmlns:D="DAV:"><D:prop><D:isreadonly/><Z:Win32FileAttributes xmlns:Z="urn:schemas-microsoft-com:"/></D:prop></D:propfind>"#,
+            ))
+            .unwrap();
+        let resp = resp_to_string(server.handle(req).await).await;
+        assert!(resp.contains(">1</D:isreadonly>"), "{resp}");
+        assert!(resp.contains(">00000021</Z:Win32FileAttributes>"));
+
+        let resp = proppatch(
+            "<A:executable>F</A:executable><Z:Win32FileAttributes>00000020</Z:Win32FileAttributes>",
+        )
+        .await;
+        assert!(!resp_to_string(resp).await.contains("HTTP/1.1 403"));
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+
+        // not a date.
+        let resp = proppatch("<D:getlastmodified>yesterday</D:getlastmodified>").await;
+        assert!(resp_to_string(resp).await.contains("HTTP/1.1 409"));
+    }
+
+    #[tokio::test]
+    async fn test_filels() {
+        use dav_server::davpath::DavPath;
+        use dav_server::filels::FileLs;
+        use dav_server::ls::DavLockSystem;
+        use std::time::Duration;
+
+        let dir = "/tmp/DAV_SERVER_TEST_FILELS";
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let file = format!("{dir}/locks");
+
+        let ls = FileLs::new(&file).unwrap();
+        let mut path = DavPath::new("/dav/dir/a%20file").unwrap();
+        path.set_prefix("/dav").unwrap();
+        let owner =
+            xmltree::Element::parse("<D:href xmlns:D=\"DAV:\">me</D:href>".as_bytes()).unwrap();
+        let timeout = Some(Duration::from_secs(600));
+        let lock = ls
+            .lock(&path, Some("alice"), Some(&owner), timeout, false, false)
+            .await
+            .unwrap();
+        let stale = DavPath::new("/stale").unwrap();
+        let expired = ls
+            .lock(&stale, None, None, Some(Duration::ZERO), false, true)
+            .await
+            .unwrap();
+        let other = DavPath::new("/other").unwrap();
+        let unlocked = ls
+            .lock(&other, None, None, None, true, false)
+            .await
+            .unwrap();
+        ls.unlock(&other, &unlocked.token).await.unwrap();
+
+        // a new instance has the same locks, except the expired one.
+        let ls = FileLs::new(&file).unwrap();
+        let locks = ls.discover(&path).await;
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0].token, lock.token);
+        assert_eq!(*locks[0].path, path);
+        assert_eq!(locks[0].path.prefix(), "/dav");
+        assert_eq!(locks[0].principal.as_deref(), Some("alice"));
+        assert_eq!(locks[0].owner.as_deref(), Some(&owner));
+        assert_eq!(locks[0].timeout, timeout);
+        assert!(!locks[0].shared && !locks[0].deep);
+        assert!(ls.discover(&stale).await.is_empty());
+        assert!(ls.discover(&other).await.is_empty());
+        assert!(ls.unlock(&stale, &expired.token).await.is_err());
+
+        assert!(
+            ls.check(&path, Some("alice"), false, false, &[])
+                .await
+                .is_err()
+        );
+        let tokens = [lock.token.clone()];
+        assert!(
+            ls.check(&path, Some("alice"), false, false, &tokens)
+                .await
+                .is_ok()
+        );
+
+        // deleting the parent removes the lock, also from the file.
+        let mut parent = DavPath::new("/dav/dir/").unwrap();
+        parent.set_prefix("/dav").unwrap();
+        ls.delete(&parent).await.unwrap();
+        let ls = FileLs::new(&file).unwrap();
+        assert!(ls.discover(&path).await.is_empty());
+        assert!(ls.check(&path, None, false, false, &[]).await.is_ok());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
@@ -0,0 +1,1637 @@
+#[cfg(feature = "memfs")]
+mod memfs_tests {
+    use dav_server::acl::{DavAce, DavAcePrincipal, DavPrivilege, DavPrivilegeSet};
+    use dav_server::davpath::DavPath;
+    use dav_server::memacl::MemAcl;
+    use dav_server::{DavConfig, DavHandler, body::Body, fakels::FakeLs, memfs::MemFs};
+    use http::{Request, StatusCode};
+
+    fn setup_dav_server() -> DavHandler {
+        DavHandler::builder()
+            .filesystem(MemFs::new())
+            .locksystem(FakeLs::new())
+            .build_handler()
+    }
+
+    async fn resp_to_string(mut resp: http::Response<Body>) -> String {
+        use futures_util::StreamExt;
+
+        let mut data = Vec::new();
+        let body = resp.body_mut();
+
+        while let Some(chunk) = body.next().await {
+            match chunk {
+                Ok(bytes) => data.extend_from_slice(&bytes),
+                Err(e) => panic!("Error reading body stream: {}", e),
+            }
+        }
+
+        String::from_utf8(data).unwrap_or_else(|_| "".to_string())
+    }
+
+    async fn request(server: &DavHandler, method: &str, uri: &str, body: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        server.handle(req).await.status()
+    }
+
+    async fn sync_collection(server: &DavHandler, uri: &str, token: &str) -> (StatusCode, String) {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:sync-collection xmlns:D="DAV:">
+  <D:sync-token>{token}</D:sync-token>
+  <D:sync-level>1</D:sync-level>
+  <D:prop><D:getetag/></D:prop>
+</D:sync-collection>"#
+        );
+        let req = Request::builder()
+            .method("REPORT")
+            .uri(uri)
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        let status = resp.status();
+        (status, resp_to_string(resp).await)
+    }
+
+    fn sync_token(text: &str) -> String {
+        let start = text.find("<D:sync-token>").unwrap() + "<D:sync-token>".len();
+        let end = text.find("</D:sync-token>").unwrap();
+        text[start..end].to_string()
+    }
+
+    #[tokio::test]
+    async fn test_sync_collection_initial() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/coll/", "").await;
+        request(&server, "PUT", "/coll/a.txt", "a").await;
+        request(&server, "PUT", "/coll/b.txt", "b").await;
+
+        let (status, text) = sync_collection(&server, "/coll/", "").await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(text.contains("<D:href>/coll/a.txt</D:href>"));
+        assert!(text.contains("<D:href>/coll/b.txt</D:href>"));
+        assert!(text.contains("<D:getetag>"));
+        assert!(!sync_token(&text).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_collection_changes() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/coll/", "").await;
+        request(&server, "PUT", "/coll/a.txt", "a").await;
+        request(&server, "PUT", "/coll/b.txt", "b").await;
+
+        let (_, text) = sync_collection(&server, "/coll/", "").await;
+        let token = sync_token(&text);
+
+        request(&server, "PUT", "/coll/a.txt", "changed").await;
+        request(&server, "DELETE", "/coll/b.txt", "").await;
+        request(&server, "PUT", "/coll/c.txt", "c").await;
+        request(&server, "PUT", "/other.txt", "other").await;
+
+        let (status, text) = sync_collection(&server, "/coll/", &token).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(text.contains("<D:href>/coll/a.txt</D:href><D:propstat>"));
+        assert!(text.contains("<D:href>/coll/b.txt</D:href><D:status>HTTP/1.1 404 Not Found"));
+        assert!(text.contains("<D:href>/coll/c.txt</D:href><D:propstat>"));
+        assert!(!text.contains("other.txt"));
+
+        // nothing changed since the last token.
+        let (status, text) = sync_collection(&server, "/coll/", &sync_token(&text)).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(!text.contains("<D:response>"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_collection_invalid_token() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/coll/", "").await;
+
+        let (status, text) = sync_collection(&server, "/coll/", "urn:bogus").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(text.contains("valid-sync-token"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_token_property() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/coll/", "").await;
+
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/coll/")
+            .header("Depth", "0")
+            .body(Body::from(
+                r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:"><D:prop><D:sync-token/></D:prop></D:propfind>"#,
+            ))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        let text = resp_to_string(resp).await;
+        assert!(text.contains("<D:sync-token>urn:dav-server:sync:"));
+    }
+
+    const ALICE: &str = "/principals/alice/";
+    const BOB: &str = "/principals/bob/";
+
+    // everyone can read, alice can do everything.
+    fn setup_acl_server() -> DavHandler {
+        let acl = MemAcl::new();
+        acl.add_principal(ALICE);
+        acl.add_principal(BOB);
+        acl.set_owner(&DavPath::new("/").unwrap(), ALICE);
+        acl.set_aces(
+            &DavPath::new("/").unwrap(),
+            vec![
+                DavAce::grant(
+                    DavAcePrincipal::All,
+                    DavPrivilegeSet::from(DavPrivilege::Read)
+                        | DavPrivilege::ReadCurrentUserPrivilegeSet.into(),
+                ),
+                DavAce::grant(DavAcePrincipal::Owner, DavPrivilege::All),
+            ],
+        );
+        DavHandler::builder()
+            .filesystem(MemFs::new())
+            .locksystem(FakeLs::new())
+            .acl(acl)
+            .build_handler()
+    }
+
+    async fn request_as(
+        server: &DavHandler,
+        principal: &str,
+        method: &str,
+        uri: &str,
+        body: &str,
+    ) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Depth", "1")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let config = DavConfig::new().principal(principal);
+        let resp = server.handle_with(config, req).await;
+        let status = resp.status();
+        (status, resp_to_string(resp).await)
+    }
+
+    #[tokio::test]
+    async fn test_acl_privileges_enforced() {
+        let server = setup_acl_server();
+
+        let (status, text) = request_as(&server, BOB, "PUT", "/bob.txt", "bob").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(text.contains("<D:need-privileges>"));
+        assert!(text.contains("<D:bind />") || text.contains("<D:bind/>"));
+
+        let (status, _) = request_as(&server, ALICE, "PUT", "/alice.txt", "alice").await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = request_as(&server, BOB, "GET", "/alice.txt", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = request_as(&server, BOB, "DELETE", "/alice.txt", "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = request_as(&server, BOB, "PROPPATCH", "/alice.txt", "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_acl_method() {
+        let server = setup_acl_server();
+        request_as(&server, ALICE, "MKCOL", "/private/", "").await;
+        request_as(&server, ALICE, "PUT", "/private/a.txt", "a").await;
+
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:acl xmlns:D="DAV:">
+  <D:ace>
+    <D:principal><D:href>{BOB}</D:href></D:principal>
+    <D:deny><D:privilege><D:read/></D:privilege></D:deny>
+  </D:ace>
+</D:acl>"#
+        );
+        let (status, _) = request_as(&server, BOB, "ACL", "/private/", &body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = request_as(&server, ALICE, "ACL", "/private/", &body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // denied on the collection, and inherited by its members.
+        let (status, _) = request_as(&server, BOB, "GET", "/private/a.txt", "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = request_as(&server, ALICE, "GET", "/private/a.txt", "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        // and left out of listings.
+        let (status, text) = request_as(&server, BOB, "PROPFIND", "/", "").await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(!text.contains("/private/"));
+
+        let propfind = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:"><D:prop><D:acl/><D:owner/></D:prop></D:propfind>"#;
+        let (_, text) = request_as(&server, ALICE, "PROPFIND", "/private/a.txt", propfind).await;
+        assert!(text.contains(&format!("<D:href>{BOB}</D:href>")));
+        assert!(text.contains("<D:inherited><D:href>/private/</D:href></D:inherited>"));
+        assert!(text.contains(&format!("<D:owner><D:href>{ALICE}</D:href></D:owner>")));
+
+        // invert is not supported.
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:acl xmlns:D="DAV:">
+  <D:ace>
+    <D:invert><D:principal><D:all/></D:principal></D:invert>
+    <D:grant><D:privilege><D:read/></D:privilege></D:grant>
+  </D:ace>
+</D:acl>"#;
+        let (status, text) = request_as(&server, ALICE, "ACL", "/private/", body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(text.contains("no-invert"));
+    }
+
+    #[tokio::test]
+    async fn test_acl_principals() {
+        let server = setup_acl_server();
+
+        let propfind = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:"><D:prop>
+  <D:resourcetype/><D:principal-URL/><D:current-user-privilege-set/><D:principal-collection-set/>
+</D:prop></D:propfind>"#;
+        let (status, text) = request_as(&server, BOB, "PROPFIND", "/principals/", propfind).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(text.contains("<D:href>/principals/</D:href>"));
+        assert!(text.contains(&format!(
+            "<D:principal-URL><D:href>{ALICE}</D:href></D:principal-URL>"
+        )));
+        assert!(text.contains(&format!(
+            "<D:principal-URL><D:href>{BOB}</D:href></D:principal-URL>"
+        )));
+        assert!(text.contains("<D:principal"));
+        assert!(text.contains(
+            "<D:principal-collection-set><D:href>/principals/</D:href></D:principal-collection-set>"
+        ));
+        assert!(text.contains("<D:privilege><D:read"));
+        assert!(!text.contains("<D:privilege><D:write"));
+
+        let (status, _) =
+            request_as(&server, BOB, "PROPFIND", "/principals/carol/", propfind).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn get(server: &DavHandler, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method("GET")
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        let status = resp.status();
+        (status, resp_to_string(resp).await)
+    }
+
+    async fn version_tree(server: &DavHandler, uri: &str) -> (StatusCode, String) {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:version-tree xmlns:D="DAV:">
+  <D:prop><D:version-name/><D:getcontentlength/><D:successor-set/></D:prop>
+</D:version-tree>"#;
+        let req = Request::builder()
+            .method("REPORT")
+            .uri(uri)
+            .header("Depth", "0")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        let status = resp.status();
+        (status, resp_to_string(resp).await)
+    }
+
+    #[tokio::test]
+    async fn test_version_control_checkout_checkin() {
+        let server = setup_dav_server();
+        request(&server, "PUT", "/doc.txt", "one").await;
+
+        // not under version control yet.
+        let status = request(&server, "CHECKOUT", "/doc.txt", "").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+        let status = request(&server, "VERSION-CONTROL", "/doc.txt", "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        // must be checked out before checkin.
+        let status = request(&server, "CHECKIN", "/doc.txt", "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let status = request(&server, "CHECKOUT", "/doc.txt", "").await;
+        assert_eq!(status, StatusCode::OK);
+        request(&server, "PUT", "/doc.txt", "two").await;
+        let req = Request::builder()
+            .method("CHECKIN")
+            .uri("/doc.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()["Location"], "/doc.txt?version=2");
+
+        // uncheckout restores the content of the latest version.
+        request(&server, "CHECKOUT", "/doc.txt", "").await;
+        request(&server, "PUT", "/doc.txt", "three").await;
+        let status = request(&server, "UNCHECKOUT", "/doc.txt", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(get(&server, "/doc.txt").await.1, "two");
+
+        assert_eq!(get(&server, "/doc.txt?version=1").await.1, "one");
+        assert_eq!(get(&server, "/doc.txt?version=2").await.1, "two");
+        let status = get(&server, "/doc.txt?version=3").await.0;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // versions are read-only.
+        let status = request(&server, "DELETE", "/doc.txt?version=1", "").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(get(&server, "/doc.txt").await.1, "two");
+
+        // a query that does not name a version is left alone.
+        let status = request(&server, "PUT", "/doc.txt?version=3", "four").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let status = request(&server, "PUT", "/other.txt?version=1", "other").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(get(&server, "/other.txt").await.1, "other");
+    }
+
+    #[tokio::test]
+    async fn test_version_tree_report() {
+        let server = setup_dav_server();
+        request(&server, "PUT", "/doc.txt", "one").await;
+
+        let (status, _) = version_tree(&server, "/doc.txt").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        request(&server, "VERSION-CONTROL", "/doc.txt", "").await;
+        // a PUT on a checked-in file creates a new version.
+        request(&server, "PUT", "/doc.txt", "second").await;
+
+        let (status, text) = version_tree(&server, "/doc.txt").await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(text.contains("<D:href>/doc.txt?version=1</D:href>"));
+        assert!(text.contains("<D:version-name>2</D:version-name>"));
+        assert!(text.contains("<D:getcontentlength>6</D:getcontentlength>"));
+        assert!(
+            text.contains("<D:successor-set><D:href>/doc.txt?version=2</D:href></D:successor-set>")
+        );
+
+        let propfind = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:"><D:prop><D:checked-in/><D:auto-version/></D:prop></D:propfind>"#;
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/doc.txt")
+            .header("Depth", "0")
+            .body(Body::from(propfind))
+            .unwrap();
+        let text = resp_to_string(server.handle(req).await).await;
+        assert!(text.contains("<D:checked-in><D:href>/doc.txt?version=2</D:href></D:checked-in>"));
+        assert!(text.contains("<D:checkout-checkin"));
+    }
+
+    #[tokio::test]
+    async fn test_autoversion_put() {
+        let server = DavHandler::builder()
+            .filesystem(MemFs::new())
+            .autoversion(true)
+            .build_handler();
+        request(&server, "PUT", "/doc.txt", "one").await;
+        request(&server, "PUT", "/doc.txt", "two").await;
+        request(&server, "PUT", "/doc.txt", "three").await;
+
+        assert_eq!(get(&server, "/doc.txt?version=1").await.1, "one");
+        assert_eq!(get(&server, "/doc.txt?version=2").await.1, "two");
+        assert_eq!(get(&server, "/doc.txt?version=3").await.1, "three");
+        assert_eq!(get(&server, "/doc.txt").await.1, "three");
+
+        // a copy starts without history.
+        let req = Request::builder()
+            .method("COPY")
+            .uri("/doc.txt")
+            .header("Destination", "/copy.txt")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::CREATED);
+        let status = get(&server, "/copy.txt?version=2").await.0;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    fn bind_body(method: &str, segment: &str, href: Option<&str>) -> String {
+        let href = href
+            .map(|h| format!("<D:href>{h}</D:href>"))
+            .unwrap_or_default();
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:{method} xmlns:D="DAV:"><D:segment>{segment}</D:segment>{href}</D:{method}>"#
+        )
+    }
+
+    async fn propfind(server: &DavHandler, uri: &str, depth: &str, props: &str) -> String {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:"><D:prop>{props}</D:prop></D:propfind>"#
+        );
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri(uri)
+            .header("Depth", depth)
+            .body(Body::from(body))
+            .unwrap();
+        resp_to_string(server.handle(req).await).await
+    }
+
+    #[tokio::test]
+    async fn test_bind_unbind() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/a/", "").await;
+        request(&server, "MKCOL", "/b/", "").await;
+        request(&server, "PUT", "/a/doc.txt", "one").await;
+
+        let body = bind_body("bind", "doc.txt", Some("/a/doc.txt"));
+        let status = request(&server, "BIND", "/b/", &body).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        // both bindings refer to the same resource.
+        request(&server, "PUT", "/b/doc.txt", "two").await;
+        assert_eq!(get(&server, "/a/doc.txt").await.1, "two");
+        let text = propfind(
+            &server,
+            "/a/doc.txt",
+            "0",
+            "<D:resource-id/><D:parent-set/>",
+        )
+        .await;
+        assert!(text.contains("<D:resource-id><D:href>urn:uuid:"));
+        assert!(text.contains("<D:parent><D:href>/a/</D:href><D:segment>doc.txt</D:segment>"));
+        assert!(text.contains("<D:parent><D:href>/b/</D:href><D:segment>doc.txt</D:segment>"));
+
+        // preconditions.
+        let body = bind_body("bind", "x", Some("/nothere"));
+        let status = request(&server, "BIND", "/b/", &body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let body = bind_body("bind", "x", Some("/a/doc.txt"));
+        let status = request(&server, "BIND", "/a/doc.txt", &body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // removing one binding leaves the other one.
+        let status = request(
+            &server,
+            "UNBIND",
+            "/a/",
+            &bind_body("unbind", "doc.txt", None),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(get(&server, "/a/doc.txt").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(get(&server, "/b/doc.txt").await.1, "two");
+        let status = request(
+            &server,
+            "UNBIND",
+            "/a/",
+            &bind_body("unbind", "doc.txt", None),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        // DELETE of a collection with another binding only removes that binding.
+        let body = bind_body("bind", "c", Some("/b/"));
+        assert_eq!(
+            request(&server, "BIND", "/a/", &body).await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            request(&server, "DELETE", "/b/", "").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(get(&server, "/a/c/doc.txt").await.1, "two");
+    }
+
+    #[tokio::test]
+    async fn test_rebind() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/a/", "").await;
+        request(&server, "PUT", "/doc.txt", "one").await;
+        request(&server, "PUT", "/a/doc.txt", "old").await;
+
+        let body = bind_body("rebind", "doc.txt", Some("/doc.txt"));
+        let req = Request::builder()
+            .method("REBIND")
+            .uri("/a/")
+            .header("Overwrite", "F")
+            .body(Body::from(body.clone()))
+            .unwrap();
+        assert_eq!(
+            server.handle(req).await.status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        assert_eq!(
+            request(&server, "REBIND", "/a/", &body).await,
+            StatusCode::OK
+        );
+        assert_eq!(get(&server, "/doc.txt").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(get(&server, "/a/doc.txt").await.1, "one");
+    }
+
+    #[tokio::test]
+    async fn test_bind_loop() {
+        let server = DavHandler::builder()
+            .filesystem(MemFs::new())
+            .allow_infinity_depth(true)
+            .build_handler();
+        request(&server, "MKCOL", "/a/", "").await;
+        request(&server, "PUT", "/a/doc.txt", "one").await;
+        let body = bind_body("bind", "loop", Some("/a/"));
+        assert_eq!(
+            request(&server, "BIND", "/a/", &body).await,
+            StatusCode::CREATED
+        );
+        assert_eq!(get(&server, "/a/loop/loop/doc.txt").await.1, "one");
+
+        // the loop is reported, not followed.
+        let text = propfind(&server, "/", "infinity", "<D:resourcetype/>").await;
+        assert!(text.contains(
+            "<D:href>/a/loop/</D:href><D:status>HTTP/1.1 508 Loop Detected</D:status>\
+             <D:error><D:loop-detected></D:loop-detected></D:error>"
+        ));
+        assert!(!text.contains("/a/loop/doc.txt"));
+
+        // a copy of the loop is a loop as well.
+        let req = Request::builder()
+            .method("COPY")
+            .uri("/a/")
+            .header("Destination", "/b/")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::CREATED);
+        assert_eq!(get(&server, "/b/loop/loop/doc.txt").await.1, "one");
+
+        // and it can be deleted.
+        assert_eq!(
+            request(&server, "DELETE", "/b/", "").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(get(&server, "/b/doc.txt").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(
+            request(&server, "DELETE", "/a/", "").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            get(&server, "/a/loop/doc.txt").await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    fn setup_redirectref_server() -> DavHandler {
+        DavHandler::builder()
+            .filesystem(MemFs::new())
+            .redirectrefs(true)
+            .build_handler()
+    }
+
+    fn mkredirectref_body(href: &str, lifetime: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:mkredirectref xmlns:D="DAV:"><D:reftarget><D:href>{href}</D:href></D:reftarget><D:redirect-lifetime><D:{lifetime}/></D:redirect-lifetime></D:mkredirectref>"#
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redirectref() {
+        let server = setup_redirectref_server();
+        request(&server, "MKCOL", "/a/", "").await;
+        request(&server, "PUT", "/a/doc.txt", "one").await;
+        let body = mkredirectref_body("/a/", "temporary");
+        assert_eq!(
+            request(&server, "MKREDIRECTREF", "/link", &body).await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            request(&server, "MKREDIRECTREF", "/link", &body).await,
+            StatusCode::CONFLICT
+        );
+
+        // GET of the reference, and of a path below it.
+        let req = Request::builder().uri("/link").body(Body::empty()).unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers()["location"], "/a/");
+        let req = Request::builder()
+            .uri("/link/doc.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(resp.headers()["location"], "/a/doc.txt");
+        assert_eq!(resp.headers()["redirect-ref"], "/link");
+        assert_eq!(
+            request(&server, "PROPFIND", "/link", "").await,
+            StatusCode::TEMPORARY_REDIRECT
+        );
+
+        // a member that is a redirect reference.
+        let text = propfind(&server, "/", "1", "<D:resourcetype/>").await;
+        assert!(text.contains(
+            "<D:href>/link</D:href><D:status>HTTP/1.1 302 Found</D:status>\
+             <D:location><D:href>/a/</D:href></D:location>"
+        ));
+
+        // the reference itself.
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:"><D:prop><D:resourcetype/><D:reftarget/><D:redirect-lifetime/></D:prop></D:propfind>"#;
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri("/link")
+            .header("Depth", "0")
+            .header("Apply-To-Redirect-Ref", "T")
+            .body(Body::from(body))
+            .unwrap();
+        let text = resp_to_string(server.handle(req).await).await;
+        assert!(text.contains("<D:resourcetype><D:redirectref></D:redirectref></D:resourcetype>"));
+        assert!(text.contains("<D:reftarget><D:href>/a/</D:href></D:reftarget>"));
+        assert!(text.contains("<D:temporary></D:temporary>"));
+    }
+
+    #[tokio::test]
+    async fn test_updateredirectref() {
+        let server = setup_redirectref_server();
+        request(&server, "PUT", "/doc.txt", "one").await;
+        let body = mkredirectref_body("doc.txt", "temporary");
+        assert_eq!(
+            request(&server, "MKREDIRECTREF", "/link", &body).await,
+            StatusCode::CREATED
+        );
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:updateredirectref xmlns:D="DAV:"><D:redirect-lifetime><D:permanent/></D:redirect-lifetime></D:updateredirectref>"#;
+        assert_eq!(
+            request(&server, "UPDATEREDIRECTREF", "/doc.txt", body).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            request(&server, "UPDATEREDIRECTREF", "/link", body).await,
+            StatusCode::OK
+        );
+        let req = Request::builder().uri("/link").body(Body::empty()).unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(resp.headers()["location"], "/doc.txt");
+
+        // DELETE removes the reference, not the target.
+        let req = Request::builder()
+            .method("DELETE")
+            .uri("/link")
+            .header("Apply-To-Redirect-Ref", "T")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(get(&server, "/link").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(get(&server, "/doc.txt").await.1, "one");
+    }
+
+    #[tokio::test]
+    async fn test_quota_limit() {
+        let server = DavHandler::builder()
+            .filesystem(MemFs::new())
+            .quota_limit("/limited/", 10)
+            .build_handler();
+        request(&server, "MKCOL", "/limited/", "").await;
+        request(&server, "MKCOL", "/other/", "").await;
+        assert_eq!(
+            request(&server, "PUT", "/limited/a.txt", "123456").await,
+            StatusCode::CREATED
+        );
+        // replacing a file only counts the difference.
+        assert_eq!(
+            request(&server, "PUT", "/limited/a.txt", "12345678").await,
+            StatusCode::NO_CONTENT
+        );
+
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/limited/b.txt")
+            .body(Body::from("12345"))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+        assert!(
+            resp_to_string(resp)
+                .await
+                .contains("<D:quota-not-exceeded/>")
+        );
+        assert_eq!(
+            get(&server, "/limited/b.txt").await.0,
+            StatusCode::NOT_FOUND
+        );
+        // an existing file is kept as it was.
+        assert_eq!(
+            request(&server, "PUT", "/limited/a.txt", "1234567890abc").await,
+            StatusCode::INSUFFICIENT_STORAGE
+        );
+        assert_eq!(
+            get(&server, "/limited/a.txt").await,
+            (StatusCode::OK, "12345678".to_string())
+        );
+        assert_eq!(
+            request(&server, "PUT", "/other/b.txt", "12345").await,
+            StatusCode::CREATED
+        );
+
+        // COPY into the subtree.
+        let req = Request::builder()
+            .method("COPY")
+            .uri("/other/b.txt")
+            .header("Destination", "/limited/b.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+
+        assert_eq!(
+            request(&server, "PUT", "/limited/c.txt", "12").await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            request(&server, "MKCOL", "/limited/dir/", "").await,
+            StatusCode::INSUFFICIENT_STORAGE
+        );
+    }
+
+    async fn search(server: &DavHandler, uri: &str, query: &str) -> (StatusCode, String) {
+        let body = format!(
+            r#"<D:searchrequest xmlns:D="DAV:"><D:basicsearch>{query}</D:basicsearch></D:searchrequest>"#
+        );
+        let req = Request::builder()
+            .method("SEARCH")
+            .uri(uri)
+            .header("Content-Type", "application/xml")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        let status = resp.status();
+        (status, resp_to_string(resp).await)
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let server = setup_dav_server();
+        request(&server, "MKCOL", "/docs/", "").await;
+        request(&server, "MKCOL", "/docs/sub/", "").await;
+        request(&server, "PUT", "/docs/a.txt", "1234").await;
+        request(&server, "PUT", "/docs/B.TXT", "123456789").await;
+        request(&server, "PUT", "/docs/sub/c.txt", "12345678901234").await;
+        request(&server, "PUT", "/docs/sub/d.html", "<html/>").await;
+
+        let req = Request::builder()
+            .method("OPTIONS")
+            .uri("/docs/")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.headers()["DASL"], "<DAV:basicsearch>");
+
+        // text files of more than 5 bytes, largest first.
+        let (status, text) = search(
+            &server,
+            "/",
+            r#"<D:select><D:prop><D:getcontentlength/></D:prop></D:select>
+            <D:from><D:scope><D:href>/docs/</D:href><D:depth>infinity</D:depth></D:scope></D:from>
+            <D:where><D:and>
+              <D:like><D:prop><D:displayname/></D:prop><D:literal>%.txt</D:literal></D:like>
+              <D:gt><D:prop><D:getcontentlength/></D:prop><D:literal>5</D:literal></D:gt>
+            </D:and></D:where>
+            <D:orderby><D:order><D:prop><D:getcontentlength/></D:prop><D:descending/></D:order></D:orderby>"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert!(!text.contains("/docs/a.txt"));
+        assert!(!text.contains("d.html"));
+        let c = text.find("/docs/sub/c.txt").unwrap();
+        let b = text.find("/docs/B.TXT").unwrap();
+        assert!(c < b);
+        assert!(text.contains("<D:getcontentlength>14</D:getcontentlength>"));
+
+        // case-sensitive, depth 1, and a limit.
+        let (_, text) = search(
+            &server,
+            "/docs/",
+            r#"<D:select><D:prop><D:getcontenttype/></D:prop></D:select>
+            <D:from><D:scope><D:href>.</D:href><D:depth>1</D:depth></D:scope></D:from>
+            <D:where><D:not><D:like caseless="no"><D:prop><D:displayname/></D:prop><D:literal>%.txt</D:literal></D:like></D:not></D:where>
+            <D:orderby><D:order><D:prop><D:displayname/></D:prop><D:ascending/></D:order></D:orderby>
+            <D:limit><D:nresults>2</D:nresults></D:limit>"#,
+        )
+        .await;
+        assert!(text.contains("/docs/B.TXT"));
+        assert!(text.contains("/docs/</D:href>"));
+        assert!(!text.contains("/docs/sub/"));
+        assert!(!text.contains("a.txt"));
+
+        // collections only.
+        let (_, text) = search(
+            &server,
+            "/",
+            r#"<D:select><D:allprop/></D:select>
+            <D:from><D:scope><D:href>/docs/</D:href></D:scope></D:from>
+            <D:where><D:is-collection/></D:where>"#,
+        )
+        .await;
+        assert!(text.contains("/docs/sub/"));
+        assert!(!text.contains(".txt"));
+
+        // the scope must be a collection.
+        let (status, text) = search(
+            &server,
+            "/",
+            r#"<D:select><D:allprop/></D:select>
+            <D:from><D:scope><D:href>/docs/a.txt</D:href></D:scope></D:from>"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(text.contains("<D:search-scope-valid/>"));
+    }
+
+    #[tokio::test]
+    async fn test_extended_mkcol() {
+        let server = setup_dav_server();
+        let body = r#"<D:mkcol xmlns:D="DAV:" xmlns:X="urn:x">
+            <D:set><D:prop>
+              <D:resourcetype><D:collection/></D:resourcetype>
+              <D:displayname>Photos</D:displayname>
+              <X:color>blue</X:color>
+            </D:prop></D:set>
+          </D:mkcol>"#;
+        assert_eq!(
+            request(&server, "MKCOL", "/photos/", body).await,
+            StatusCode::CREATED
+        );
+        let text = propfind(
+            &server,
+            "/photos/",
+            "0",
+            r#"<D:displayname/><X:color xmlns:X="urn:x"/>"#,
+        )
+        .await;
+        assert!(text.contains(">Photos</D:displayname>"));
+        assert!(text.contains(">blue</"));
+
+        // a protected property: nothing is created.
+        let body = r#"<D:mkcol xmlns:D="DAV:">
+            <D:set><D:prop>
+              <D:displayname>Music</D:displayname>
+              <D:getetag>x</D:getetag>
+            </D:prop></D:set>
+          </D:mkcol>"#;
+        let req = Request::builder()
+            .method("MKCOL")
+            .uri("/music/")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let text = resp_to_string(resp).await;
+        assert!(text.contains("mkcol-response"));
+        assert!(text.contains("HTTP/1.1 424 Failed Dependency"));
+        assert_eq!(get(&server, "/music/").await.0, StatusCode::NOT_FOUND);
+
+        // not a collection.
+        let body = r#"<D:mkcol xmlns:D="DAV:"><D:set><D:prop>
+            <D:resourcetype><D:principal/></D:resourcetype>
+          </D:prop></D:set></D:mkcol>"#;
+        assert_eq!(
+            request(&server, "MKCOL", "/music/", body).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            request(&server, "MKCOL", "/music/", "<D:foo xmlns:D=\"DAV:\"/>").await,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ordered_collection() {
+        let server = setup_dav_server();
+        let req = Request::builder()
+            .method("MKCOL")
+            .uri("/list/")
+            .header("Ordering-Type", "DAV:custom")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::CREATED);
+
+        let put = |path: &'static str, position: &'static str| {
+            let mut req = Request::builder().method("PUT").uri(path);
+            if !position.is_empty() {
+                req = req.header("Position", position);
+            }
+            server.handle(req.body(Body::from("x")).unwrap())
+        };
+        assert_eq!(put("/list/c", "").await.status(), StatusCode::CREATED);
+        assert_eq!(put("/list/a", "first").await.status(), StatusCode::CREATED);
+        assert_eq!(
+            put("/list/b", "after a").await.status(),
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            put("/list/d", "after zz").await.status(),
+            StatusCode::CONFLICT
+        );
+
+        let order = |text: String| {
+            ["/list/a", "/list/b", "/list/c"]
+                .map(|m| text.find(&format!("<D:href>{m}</D:href>")).unwrap())
+        };
+        let text = propfind(&server, "/list/", "1", "<D:ordering-type/>").await;
+        assert!(text.contains("<D:href>DAV:custom</D:href>"));
+        let idx = order(text);
+        assert!(idx[0] < idx[1] && idx[1] < idx[2]);
+
+        // move c to the front.
+        let body = r#"<D:orderpatch xmlns:D="DAV:">
+            <D:order-member>
+              <D:segment>c</D:segment>
+              <D:position><D:first/></D:position>
+            </D:order-member>
+          </D:orderpatch>"#;
+        assert_eq!(
+            request(&server, "ORDERPATCH", "/list/", body).await,
+            StatusCode::OK
+        );
+        let idx = order(propfind(&server, "/list/", "1", "<D:getetag/>").await);
+        assert!(idx[2] < idx[0] && idx[0] < idx[1]);
+
+        // a segment that is not a member: nothing changes.
+        let body = r#"<D:orderpatch xmlns:D="DAV:">
+            <D:order-member>
+              <D:segment>a</D:segment>
+              <D:position><D:last/></D:position>
+            </D:order-member>
+            <D:order-member>
+              <D:segment>b</D:segment>
+              <D:position><D:before><D:segment>zz</D:segment></D:before></D:position>
+            </D:order-member>
+          </D:orderpatch>"#;
+        let req = Request::builder()
+            .method("ORDERPATCH")
+            .uri("/list/")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        let text = resp_to_string(resp).await;
+        assert!(text.contains("HTTP/1.1 424 Failed Dependency"));
+        assert!(text.contains("segment-must-identify-member"));
+        let idx = order(propfind(&server, "/list/", "1", "<D:getetag/>").await);
+        assert!(idx[2] < idx[0] && idx[0] < idx[1]);
+
+        // a Position header on an unordered collection.
+        assert_eq!(
+            request(&server, "MKCOL", "/bag/", "").await,
+            StatusCode::CREATED
+        );
+        assert_eq!(put("/bag/a", "first").await.status(), StatusCode::CONFLICT);
+        let text = propfind(&server, "/bag/", "0", "<D:ordering-type/>").await;
+        assert!(text.contains("<D:href>DAV:unordered</D:href>"));
+    }
+
+    #[tokio::test]
+    async fn test_prefer() {
+        let server = setup_dav_server();
+        assert_eq!(
+            request(&server, "MKCOL", "/coll/", "").await,
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            request(&server, "PUT", "/coll/a.txt", "a").await,
+            StatusCode::CREATED
+        );
+
+        let propfind_prefer = |prefer: &'static str| {
+            let body = r#"<D:propfind xmlns:D="DAV:"><D:prop>
+                <D:getetag/><X:missing xmlns:X="urn:x"/>
+              </D:prop></D:propfind>"#;
+            let req = Request::builder()
+                .method("PROPFIND")
+                .uri("/coll/")
+                .header("Depth", "1")
+                .header("Prefer", prefer)
+                .body(Body::from(body))
+                .unwrap();
+            server.handle(req)
+        };
+
+        // without preferences, the missing property is a 404 propstat.
+        let resp = propfind_prefer("handling=lenient").await;
+        assert!(resp.headers().get("preference-applied").is_none());
+        let text = resp_to_string(resp).await;
+        assert!(text.contains("HTTP/1.1 404 Not Found"));
+        assert!(text.contains("<D:href>/coll/</D:href>"));
+
+        let resp = propfind_prefer("return=minimal, depth-noroot").await;
+        let applied = resp
+            .headers()
+            .get_all("preference-applied")
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(applied, vec!["return=minimal", "depth-noroot"]);
+        let text = resp_to_string(resp).await;
+        assert!(!text.contains("404 Not Found"));
+        assert!(!text.contains("<D:href>/coll/</D:href>"));
+        assert!(text.contains("<D:href>/coll/a.txt</D:href>"));
+
+        // a PROPPATCH that succeeds has no body.
+        let body = r#"<D:propertyupdate xmlns:D="DAV:" xmlns:X="urn:x">
+            <D:set><D:prop><X:color>red</X:color></D:prop></D:set>
+          </D:propertyupdate>"#;
+        let req = Request::builder()
+            .method("PROPPATCH")
+            .uri("/coll/a.txt")
+            .header("Prefer", "return=minimal")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["preference-applied"], "return=minimal");
+        assert_eq!(resp_to_string(resp).await, "");
+
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/coll/a.txt")
+            .header("Prefer", "return=minimal")
+            .body(Body::from("b"))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.headers()["preference-applied"], "return=minimal");
+    }
+
+    #[tokio::test]
+    async fn test_resumable_upload() {
+        let dir = std::env::temp_dir().join(format!("dav-uploads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let server = DavHandler::builder()
+            .filesystem(MemFs::new())
+            .locksystem(FakeLs::new())
+            .resumable_uploads(&dir)
+            .build_handler();
+
+        // create the upload, the client says there is more to come.
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/big.bin")
+            .header("Upload-Complete", "?0")
+            .body(Body::from("hello "))
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()["upload-offset"], "6");
+        let location = resp.headers()["location"].to_str().unwrap().to_string();
+        assert!(location.starts_with("/big.bin?upload="));
+        assert_eq!(get(&server, "/big.bin").await.0, StatusCode::NOT_FOUND);
+
+        let head = |uri: String| {
+            let req = Request::builder()
+                .method("HEAD")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            server.handle(req)
+        };
+        let resp = head(location.clone()).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.headers()["upload-offset"], "6");
+        assert_eq!(resp.headers()["upload-complete"], "?0");
+
+        // the upload only exists at the URL of its target, for whoever created it.
+        let other = location.replace("/big.bin", "/other.bin");
+        let req = Request::builder()
+            .method("PATCH")
+            .uri(other.clone())
+            .header("Content-Type", "application/partial-upload")
+            .header("Upload-Offset", "6")
+            .header("Upload-Complete", "?1")
+            .body(Body::from("world"))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&server, "/other.bin").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(head(other.clone()).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            request(&server, "DELETE", &other, "").await,
+            StatusCode::NOT_FOUND
+        );
+        let (status, _) = request_as(&server, BOB, "HEAD", &location, "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = request_as(&server, BOB, "DELETE", &location, "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            head(location.clone()).await.status(),
+            StatusCode::NO_CONTENT
+        );
+
+        let patch = |offset: &'static str, body: &'static str| {
+            let req = Request::builder()
+                .method("PATCH")
+                .uri(location.clone())
+                .header("Content-Type", "application/partial-upload")
+                .header("Upload-Offset", offset)
+                .header("Upload-Complete", "?1")
+                .body(Body::from(body))
+                .unwrap();
+            server.handle(req)
+        };
+        let resp = patch("3", "lo world").await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert_eq!(resp.headers()["upload-offset"], "6");
+
+        let resp = patch("6", "world").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(resp.headers()["upload-complete"], "?1");
+        assert_eq!(
+            get(&server, "/big.bin").await,
+            (StatusCode::OK, "hello world".to_string())
+        );
+        assert_eq!(head(location.clone()).await.status(), StatusCode::NOT_FOUND);
+
+        // an upload that is cancelled.
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/big.bin")
+            .header("Upload-Complete", "?0")
+            .header("Upload-Length", "100")
+            .body(Body::from("x"))
+            .unwrap();
+        let resp = server.handle(req).await;
+        let location = resp.headers()["location"].to_str().unwrap().to_string();
+        let resp = head(location.clone()).await;
+        assert_eq!(resp.headers()["upload-length"], "100");
+        assert_eq!(
+            request(&server, "DELETE", &location, "").await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(head(location).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&server, "/big.bin").await.1, "hello world");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload() {
+        let server = setup_dav_server();
+        for coll in [
+            "/uploads/",
+            "/uploads/alice/",
+            "/uploads/alice/1234/",
+            "/files/",
+        ] {
+            assert_eq!(
+                request(&server, "MKCOL", coll, "").await,
+                StatusCode::CREATED
+            );
+        }
+        for (chunk, data) in [("00002", "world"), ("00001", "hello ")] {
+            let uri = format!("/uploads/alice/1234/{chunk}");
+            assert_eq!(
+                request(&server, "PUT", &uri, data).await,
+                StatusCode::CREATED
+            );
+        }
+
+        let finish = |length: &'static str, checksum: &'static str| {
+            let req = Request::builder()
+                .method("MOVE")
+                .uri("/uploads/alice/1234/.file")
+                .header("Destination", "/files/big.bin")
+                .header("OC-Total-Length", length)
+                .header("OC-Checksum", checksum)
+                .body(Body::empty())
+                .unwrap();
+            server.handle(req)
+        };
+        let sha1 = "SHA1:2aae6c35c94fcfb415dbe95f408b9ce91ee846ed";
+
+        // the length and the checksum must match.
+        let resp = finish("12", sha1).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = finish("11", "MD5:00000000000000000000000000000000").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            get(&server, "/files/big.bin").await.0,
+            StatusCode::NOT_FOUND
+        );
+        let text = propfind(&server, "/uploads/alice/1234/", "1", "<D:getetag/>").await;
+        assert!(!text.contains(".file"));
+
+        let resp = finish("11", sha1).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert!(resp.headers().contains_key("oc-etag"));
+        assert_eq!(
+            get(&server, "/files/big.bin").await,
+            (StatusCode::OK, "hello world".to_string())
+        );
+        assert_eq!(
+            get(&server, "/uploads/alice/1234/").await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_atomic_put() {
+        let server = DavHandler::builder()
+            .filesystem(MemFs::new())
+            .locksystem(FakeLs::new())
+            .atomic_put(true)
+            .build_handler();
+        assert_eq!(
+            request(&server, "PUT", "/a.txt", "hello world").await,
+            StatusCode::CREATED
+        );
+        let body = r#"<D:propertyupdate xmlns:D="DAV:" xmlns:X="urn:x">
+            <D:set><D:prop><X:color>red</X:color></D:prop></D:set>
+          </D:propertyupdate>"#;
+        assert_eq!(
+            request(&server, "PROPPATCH", "/a.txt", body).await,
+            StatusCode::MULTI_STATUS
+        );
+
+        let put = |uri: &'static str, headers: &[(&'static str, &'static str)], body| {
+            let mut req = Request::builder().method("PUT").uri(uri);
+            for (name, value) in headers {
+                req = req.header(*name, *value);
+            }
+            server.handle(req.body(Body::from(body)).unwrap())
+        };
+
+        // an incomplete body leaves the file alone.
+        let resp = put("/a.txt", &[("Content-Length", "20")], "short").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            get(&server, "/a.txt").await,
+            (StatusCode::OK, "hello world".to_string())
+        );
+        let resp = put("/b.txt", &[("Content-Length", "20")], "short").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&server, "/b.txt").await.0, StatusCode::NOT_FOUND);
+
+        // partial PUT.
+        let resp = put("/a.txt", &[("Content-Range", "bytes 6-10/11")], "there").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            get(&server, "/a.txt").await,
+            (StatusCode::OK, "hello there".to_string())
+        );
+
+        // the dead properties are still there.
+        let text = propfind(&server, "/a.txt", "0", r#"<X:color xmlns:X="urn:x"/>"#).await;
+        assert!(text.contains(">red</"));
+    }
+
+    #[tokio::test]
+    async fn test_checksums() {
+        let server = setup_dav_server();
+        let put = |name: &'static str, value: &'static str| {
+            let req = Request::builder()
+                .method("PUT")
+                .uri("/a.txt")
+                .header(name, value)
+                .body(Body::from("hello world"))
+                .unwrap();
+            server.handle(req)
+        };
+        let checksums = r#"<oc:checksums xmlns:oc="http://owncloud.org/ns"/>"#;
+
+        // a mismatch does not leave a file behind.
+        let resp = put(
+            "OC-Checksum",
+            "SHA1:0000000000000000000000000000000000000000",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&server, "/a.txt").await.0, StatusCode::NOT_FOUND);
+
+        let resp = put(
+            "OC-Checksum",
+            "SHA1:2aae6c35c94fcfb415dbe95f408b9ce91ee846ed",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let text = propfind(&server, "/a.txt", "0", checksums).await;
+        assert!(
+            text.contains(
+                "<oc:checksum>SHA1:2aae6c35c94fcfb415dbe95f408b9ce91ee846ed</oc:checksum>"
+            )
+        );
+        let req = Request::builder()
+            .method("GET")
+            .uri("/a.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.headers()["digest"], "SHA=Kq5sNclPz7QV2+lfQIuc6R7oRu0=");
+
+        // and an existing file is not removed.
+        let resp = put(
+            "OC-Checksum",
+            "SHA1:0000000000000000000000000000000000000000",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&server, "/a.txt").await.0, StatusCode::OK);
+
+        let sha256 = "sha-256=:uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=:";
+        let resp = put("Content-Digest", "sha-256=:AAAA:").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = put("Content-Digest", sha256).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let req = Request::builder()
+            .method("HEAD")
+            .uri("/a.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.headers()["repr-digest"], sha256);
+
+        // writing without a checksum drops the stored one.
+        assert_eq!(
+            request(&server, "PUT", "/a.txt", "changed").await,
+            StatusCode::NO_CONTENT
+        );
+        let text = propfind(&server, "/a.txt", "0", checksums).await;
+        assert!(text.contains("404 Not Found"));
+    }
+
+    // MemFs, but it remembers what files were opened with.
+    #[derive(Clone)]
+    struct RecordingFs {
+        fs: Box<MemFs>,
+        opened: std::sync::Arc<std::sync::Mutex<Vec<dav_server::fs::OpenOptions>>>,
+    }
+
+    impl dav_server::fs::DavFileSystem for RecordingFs {
+        fn open<'a>(
+            &'a self,
+            path: &'a DavPath,
+            options: dav_server::fs::OpenOptions,
+        ) -> dav_server::fs::FsFuture<'a, Box<dyn dav_server::fs::DavFile>> {
+            self.opened.lock().unwrap().push(options.clone());
+            self.fs.open(path, options)
+        }
+
+        fn read_dir<'a>(
+            &'a self,
+            path: &'a DavPath,
+            meta: dav_server::fs::ReadDirMeta,
+        ) -> dav_server::fs::FsFuture<
+            'a,
+            dav_server::fs::FsStream<Box<dyn dav_server::fs::DavDirEntry>>,
+        > {
+            self.fs.read_dir(path, meta)
+        }
+
+        fn metadata<'a>(
+            &'a self,
+            path: &'a DavPath,
+        ) -> dav_server::fs::FsFuture<'a, Box<dyn dav_server::fs::DavMetaData>> {
+            self.fs.metadata(path)
+        }
+
+        fn symlink_metadata<'a>(
+            &'a self,
+            path: &'a DavPath,
+        ) -> dav_server::fs::FsFuture<'a, Box<dyn dav_server::fs::DavMetaData>> {
+            self.fs.symlink_metadata(path)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oc_checksum_passed_through() {
+        let fs = RecordingFs {
+            fs: MemFs::new(),
+            opened: Default::default(),
+        };
+        let opened = fs.opened.clone();
+        let server = DavHandler::builder()
+            .filesystem(Box::new(fs))
+            .build_handler();
+        let put = |range: Option<&'static str>, checksum: &'static str| {
+            let mut req = Request::builder()
+                .method("PUT")
+                .uri("/a.txt")
+                .header("OC-Checksum", checksum);
+            if let Some(range) = range {
+                req = req.header("Content-Range", range);
+            }
+            server.handle(req.body(Body::from("hello")).unwrap())
+        };
+        let last = || opened.lock().unwrap().pop().unwrap();
+
+        // the header goes to the filesystem as it was sent, also when
+        // the algorithm is unknown or only part of the file is written.
+        let resp = put(None, "CRC64:f6e4c7c2a7b51c87").await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let oo = last();
+        assert_eq!(oo.checksum.as_deref(), Some("CRC64:f6e4c7c2a7b51c87"));
+        assert_eq!(oo.checksums, None);
+
+        let sha1 = "sha1:AAF4C61DDCC5E8A2DABEDE0F3B482CD9AEA9434D";
+        let resp = put(None, sha1).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let oo = last();
+        assert_eq!(oo.checksum.as_deref(), Some(sha1));
+        assert_eq!(
+            oo.checksums.as_deref(),
+            Some("SHA1:aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d")
+        );
+
+        let resp = put(Some("bytes 5-9/10"), "CRC64:0f7e2ea9b1d6c8a4").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(last().checksum.as_deref(), Some("CRC64:0f7e2ea9b1d6c8a4"));
+    }
+
+    #[tokio::test]
+    async fn test_win32_props() {
+        let server = setup_dav_server();
+        let props = r#"<D:ishidden/><D:isreadonly/><Z:Win32FileAttributes xmlns:Z="urn:schemas-microsoft-com:"/><Z:Win32CreationTime xmlns:Z="urn:schemas-microsoft-com:"/>"#;
+
+        assert_eq!(
+            request(&server, "PUT", "/.hidden", "hello").await,
+            StatusCode::CREATED
+        );
+        let text = propfind(&server, "/.hidden", "0", props).await;
+        assert!(text.contains(">1</D:ishidden>"), "{text}");
+        assert!(text.contains(">0</D:isreadonly>"));
+        assert!(text.contains(">00000022</Z:Win32FileAttributes>"));
+        assert!(text.contains("GMT</Z:Win32CreationTime>"));
+        assert!(!text.contains("404 Not Found"));
+
+        assert_eq!(
+            request(&server, "MKCOL", "/dir", "").await,
+            StatusCode::CREATED
+        );
+        let text = propfind(&server, "/dir/", "0", props).await;
+        assert!(text.contains(">0</D:ishidden>"));
+        assert!(text.contains(">00000010</Z:Win32FileAttributes>"));
+    }
+
+    #[tokio::test]
+    async fn test_kvls_shared() {
+        use dav_server::kvls::{KvLs, MemKv};
+
+        // two servers, with the same storage and the same locks.
+        let fs = MemFs::new();
+        let kv = MemKv::new();
+        let server = |fs: Box<MemFs>| {
+            DavHandler::builder()
+                .filesystem(fs)
+                .locksystem(KvLs::new(kv.clone(), "locks"))
+                .build_handler()
+        };
+        let (a, b) = (server(fs.clone()), server(fs));
+
+        assert_eq!(
+            request(&a, "PUT", "/a.txt", "hello").await,
+            StatusCode::CREATED
+        );
+        let lockinfo = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:lockinfo xmlns:D="DAV:">
+  <D:lockscope><D:exclusive/></D:lockscope>
+  <D:locktype><D:write/></D:locktype>
+</D:lockinfo>"#;
+        let req = Request::builder()
+            .method("LOCK")
+            .uri("/a.txt")
+            .header("Timeout", "Second-600")
+            .body(Body::from(lockinfo))
+            .unwrap();
+        let resp = a.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let token = resp.headers()["Lock-Token"].to_str().unwrap().to_string();
+
+        // the lock is visible on the other server.
+        assert_eq!(
+            request(&b, "PUT", "/a.txt", "world").await,
+            StatusCode::LOCKED
+        );
+        let text = propfind(&b, "/a.txt", "0", "<D:lockdiscovery/>").await;
+        assert!(text.contains(token.trim_matches(['<', '>'])));
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/a.txt")
+            .header("If", format!("({token})"))
+            .body(Body::from("world"))
+            .unwrap();
+        assert_eq!(b.handle(req).await.status(), StatusCode::NO_CONTENT);
+
+        // and it can be unlocked there as well.
+        let req = Request::builder()
+            .method("UNLOCK")
+            .uri("/a.txt")
+            .header("Lock-Token", &token)
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(b.handle(req).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            request(&a, "PUT", "/a.txt", "again").await,
+            StatusCode::NO_CONTENT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lock_null() {
+        use dav_server::memls::MemLs;
+        use std::time::Duration;
+
+        let server = |lock_null: bool| {
+            DavHandler::builder()
+                .filesystem(MemFs::new())
+                .locksystem(MemLs::new())
+                .lock_null(lock_null)
+                .build_handler()
+        };
+        let lock = |server: &DavHandler, uri: &str, timeout: &str| {
+            let lockinfo = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:lockinfo xmlns:D="DAV:">
+  <D:lockscope><D:exclusive/></D:lockscope>
+  <D:locktype><D:write/></D:locktype>
+</D:lockinfo>"#;
+            let req = Request::builder()
+                .method("LOCK")
+                .uri(uri)
+                .header("Timeout", timeout)
+                .body(Body::from(lockinfo))
+                .unwrap();
+            let server = server.clone();
+            async move {
+                let resp = server.handle(req).await;
+                assert_eq!(resp.status(), StatusCode::CREATED);
+                resp.headers()["Lock-Token"].to_str().unwrap().to_string()
+            }
+        };
+        let unlock = |server: &DavHandler, uri: &str, token: &str| {
+            let req = Request::builder()
+                .method("UNLOCK")
+                .uri(uri)
+                .header("Lock-Token", token)
+                .body(Body::empty())
+                .unwrap();
+            let server = server.clone();
+            async move { server.handle(req).await.status() }
+        };
+
+        // by default, the empty resource that LOCK creates stays.
+        let plain = server(false);
+        let token = lock(&plain, "/a.txt", "Second-600").await;
+        assert_eq!(request(&plain, "GET", "/a.txt", "").await, StatusCode::OK);
+        assert_eq!(
+            unlock(&plain, "/a.txt", &token).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(request(&plain, "GET", "/a.txt", "").await, StatusCode::OK);
+
+        // a lock-null resource is removed if it was not written to.
+        let server = server(true);
+        let token = lock(&server, "/a.txt", "Second-600").await;
+        assert_eq!(
+            unlock(&server, "/a.txt", &token).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            request(&server, "GET", "/a.txt", "").await,
+            StatusCode::NOT_FOUND
+        );
+
+        // but not if it was.
+        let token = lock(&server, "/b.txt", "Second-600").await;
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/b.txt")
+            .header("If", format!("({token})"))
+            .body(Body::from("hello"))
+            .unwrap();
+        assert_eq!(server.handle(req).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            unlock(&server, "/b.txt", &token).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(request(&server, "GET", "/b.txt", "").await, StatusCode::OK);
+
+        // or when its lock expires, on the next lock discovery.
+        lock(&server, "/c.txt", "Second-1").await;
+        lock(&server, "/d.txt", "Second-600").await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(request(&server, "GET", "/c.txt", "").await, StatusCode::OK);
+        let text = propfind(&server, "/", "1", "<D:lockdiscovery/>").await;
+        assert!(!text.contains("/c.txt"));
+        assert!(text.contains("/d.txt"));
+        assert_eq!(
+            request(&server, "GET", "/c.txt", "").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(request(&server, "GET", "/d.txt", "").await, StatusCode::OK);
+    }
+}